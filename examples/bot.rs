@@ -1,23 +1,43 @@
-// 实现一个自动化交易机器人，监控 Raydium 池并执行代币交换。  
+// 实现一个自动化交易机器人，监控 Raydium 池并执行代币交换。
 
+use std::{sync::Arc, time::Duration};
+
+use serde_json::json;
 use solagent::{
-    agent_controller::AgentController,
-    tool_system::ToolRegistry,
-    solana_integration::SolanaRPC,
+    llm_integration::ProviderConfig,
+    workflow_engine::scheduler::{Schedule, ScheduleStore, Scheduler, TaskJob, Trigger},
+    SolAgent, SolAgentConfig,
 };
 
 #[tokio::main]
-async fn main() {
-    let rpc = SolanaRPC::new("https://api.devnet.solana.com");
-    let registry = ToolRegistry::new();
-    registry.register_tool("swap_tokens", SwapTokensTool::new(rpc));
-    let controller = AgentController::new(registry);
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let config = SolAgentConfig {
+        name: "Raydium Bot".to_string(),
+        instructions: "Swap 1 USDC when the Raydium pool price drops below 100.".to_string(),
+        model: ProviderConfig::from_env("grok3")?,
+        tools: vec!["get_pool_data".to_string(), "swap_tokens".to_string()],
+        wallet_address: None,
+        prompts_dir: None,
+        context: None,
+        memory_path: Some("bot.db".to_string()),
+    };
+    let solagent = SolAgent::new(config).await?;
+
+    // Poll the pool every minute; a poll still running when the next one is due is skipped,
+    // and the last poll time survives restarts
+    let store = Arc::new(ScheduleStore::open("bot.db")?);
+    let scheduler = Scheduler::new().with_store(store);
+    let job = TaskJob::new(
+        solagent.controller.clone(),
+        "Check the pool and swap if the price is below 100",
+        json!({ "pool_id": "raydium_pool", "amount": 1.0, "token": "USDC" }),
+    );
+    let trigger = Trigger::every(Duration::from_secs(60));
+    let schedule = Schedule::new("poll_raydium", trigger, Arc::new(job))
+        .with_jitter(Duration::from_secs(5));
+    scheduler.add(schedule)?;
 
-    loop {
-        let pool_data = controller.execute_task("get_pool_data", { pool_id: "raydium_pool" }).await;
-        if should_swap(&pool_data) {
-            controller.execute_task("swap_tokens", { amount: 1.0, token: "USDC" }).await;
-        }
-        tokio::time::sleep(Duration::from_secs(60)).await;
-    }
-}
\ No newline at end of file
+    tokio::signal::ctrl_c().await?;
+    scheduler.shutdown();
+    Ok(())
+}
@@ -1,14 +1,49 @@
+// Registers a multisig tool and runs it directly through the tool registry, without an LLM.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde_json::json;
 use solagent::{
-    tool_system::ToolRegistry,
-    solana_integration::SolanaRPC,
+    llm_integration::{LLMProvider, NoLlmProvider},
+    tool_system::{SolanaTool, ToolInput, ToolMetadata, ToolRegistry},
 };
 
+// Checks a multisig request; a real tool would build and send the create instruction
+struct CreateMultisigTool;
+
+#[async_trait]
+impl SolanaTool for CreateMultisigTool {
+    async fn execute(
+        &self,
+        input: ToolInput,
+        _llm: &dyn LLMProvider,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let signers = input.params["signers"].as_array().ok_or("Missing signers")?;
+        let threshold = input.params["threshold"].as_u64().ok_or("Missing threshold")?;
+        if threshold == 0 || threshold as usize > signers.len() {
+            return Err("Threshold must be between 1 and the number of signers".into());
+        }
+        Ok(format!("{}-of-{} multisig ready", threshold, signers.len()))
+    }
+}
+
 #[tokio::main]
-async fn main() {
-    let rpc = SolanaRPC::new("https://api.devnet.solana.com");
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let registry = ToolRegistry::new();
-    registry.register_tool("create_multisig", CreateMultisigTool::new(rpc));
+    let metadata = ToolMetadata {
+        name: "create_multisig".to_string(),
+        aliases: vec![],
+        version: "1.0".to_string(),
+        llm_type: "grok3".to_string(),
+        schema: json!({ "name": "create_multisig", "description": "Create a multisig account" }),
+        sends_transactions: true,
+    };
+    registry.register(metadata, Arc::new(CreateMultisigTool)).await;
 
-    let result = registry.execute("create_multisig", { signers: vec!["pubkey1", "pubkey2"], threshold: 2 }).await;
-    println!("Multisig Created: {}", result.unwrap());
+    let (_, tool) = registry.get("create_multisig").await.ok_or("Tool not registered")?;
+    let params = json!({ "signers": ["pubkey1", "pubkey2"], "threshold": 2 });
+    let result = tool.execute(ToolInput::new(params), &NoLlmProvider).await?;
+    println!("Multisig Created: {}", result);
+    Ok(())
 }
\ No newline at end of file
@@ -1,23 +1,88 @@
-// 用途：展示如何使用 SolAgent 实现链上数据的 RAG（检索增强生成），结合 LLM 查询 Solana 交易历史。  
+// 用途：展示如何使用 SolAgent 实现链上数据的 RAG（检索增强生成），结合 LLM 查询 Solana 交易历史。
 
+use std::{str::FromStr, sync::Arc};
+
+use serde_json::json;
 use solagent::{
-    tool_system::ToolRegistry,
-    memory_system::{ShortTermMemory, LongTermMemory},
-    llm_integration::LLMClient,
-    solana_integration::SolanaRPC,
+    agent_controller::AgentController,
+    llm_integration::ProviderConfig,
+    memory_system::{
+        long_term::Namespace,
+        vector::{
+            Document, HashEmbedding, MetadataFilter, OpenAICompatibleEmbedding, VectorMemory,
+            VectorQuery, VectorRecall,
+        },
+    },
+    SolAgent, SolAgentConfig,
 };
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
 
 #[tokio::main]
-async fn main() {
-    let rpc = SolanaRPC::new("https://api.devnet.solana.com");
-    let memory = LongTermMemory::new("solagent.db");
-    let llm = LLMClient::new("grok", "https://api.x.ai");
-    let registry = ToolRegistry::new();
-    registry.register_tool("get_transaction", GetTransactionTool::new(rpc));
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let wallet = std::env::var("WALLET_ADDRESS")?;
+    let config = SolAgentConfig {
+        name: "History Agent".to_string(),
+        instructions: "Answer questions about the wallet's transaction history.".to_string(),
+        model: ProviderConfig::from_env("grok3")?,
+        tools: vec!["get_balance".to_string()],
+        wallet_address: Some(wallet.clone()),
+        prompts_dir: None,
+        context: None,
+        memory_path: Some("solagent.db".to_string()),
+    };
+    let solagent = SolAgent::new(config).await?;
+
+    // OpenAI embeddings when a key is set, the offline hashing embedding otherwise
+    let memory = Arc::new(match std::env::var("OPENAI_API_KEY") {
+        Ok(api_key) => {
+            VectorMemory::open("solagent.db", Arc::new(OpenAICompatibleEmbedding::openai(api_key)))?
+        }
+        Err(_) => VectorMemory::open("solagent.db", Arc::new(HashEmbedding::default()))?,
+    });
+
+    // Index the wallet's recent transactions
+    let rpc = RpcClient::new("https://api.devnet.solana.com".to_string());
+    let signatures = rpc.get_signatures_for_address(&Pubkey::from_str(&wallet)?).await?;
+    let documents = signatures
+        .iter()
+        .map(|tx| {
+            let status = if tx.err.is_some() { "failed" } else { "succeeded" };
+            let memo = tx.memo.as_deref().unwrap_or("no memo");
+            let text =
+                format!("Transaction {} at slot {} {} ({})", tx.signature, tx.slot, status, memo);
+            Document::new(&tx.signature, &text).with_metadata(json!({
+                "wallet": wallet,
+                "slot": tx.slot,
+                "block_time": tx.block_time,
+            }))
+        })
+        .collect();
+    let namespace = Namespace::agent("rag");
+    memory.add_all(&namespace, documents).await?;
+
+    // Direct search: failed transactions of the last week
+    let week_ago = chrono::Utc::now().timestamp() - 7 * 24 * 3600;
+    let query = VectorQuery::new("failed transaction")
+        .with_top_k(3)
+        .with_filter(MetadataFilter::equals("wallet", wallet.clone()))
+        .with_filter(MetadataFilter::range("block_time", Some(week_ago as f64), None));
+    for result in memory.search(&namespace, &query).await? {
+        println!("{:.2} {}", result.score, result.entry.document.text);
+    }
 
-    let query = "Find my recent SOL transactions";
-    let context = memory.retrieve("transaction_history");
-    let prompt = format!("Query: {}\nContext: {}", query, context);
-    let response = llm.generate(&prompt).await.unwrap();
-    println!("RAG Response: {}", response);
-}
\ No newline at end of file
+    // Tasks about the wallet get its most related transactions in their prompt
+    let recall = VectorRecall::new(memory, namespace).with_top_k(10).with_input_filter("wallet");
+    let controller = AgentController::new(
+        solagent.tool_registry.clone(),
+        solagent.sessions.clone(),
+        solagent.memory_long.clone(),
+        solagent.llm_client.clone(),
+    )
+    .with_recall(recall);
+    let input = json!({ "wallet": wallet });
+    let task = "Find my recent SOL transactions";
+    let transcript = controller.execute_task(&wallet, task, input).await?;
+    println!("RAG Response: {:?}", transcript.final_answer);
+    Ok(())
+}
@@ -1,6 +1,5 @@
-use async_std::sync::Arc;
 use serde_json::json;
-use solagent::{SolAgent, SolAgentConfig};
+use solagent::{llm_integration::ProviderConfig, SolAgent, SolAgentConfig};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -8,19 +7,23 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let config = SolAgentConfig {
         name: "Solana Agent".to_string(),
         instructions: "Execute Solana-related tasks with tools and LLM.".to_string(),
-        model: "grok3".to_string(),
+        model: ProviderConfig::from_env("grok3")?,
         tools: vec!["stake_sol".to_string(), "get_balance".to_string()],
+        wallet_address: None,
+        prompts_dir: None,
+        context: None,
+        memory_path: None,
     };
-    let solagent = SolAgent::new(config)?;
+    let solagent = SolAgent::new(config).await?;
 
     // Execute staking task
     let input = json!({ "amount": 10.0, "validator": "validator_pubkey" });
-    let result = solagent.execute_task("stake_sol", input).await?;
-    println!("Stake Result: {}", result);
+    let result = solagent.execute_task("staker", "stake_sol", input.clone()).await?;
+    println!("Stake Result: {:?}", result.final_answer);
 
     // Test with alias
-    let result = solagent.execute_task("stake", input).await?;
-    println!("Stake Result (using alias): {}", result);
+    let result = solagent.execute_task("staker", "stake", input).await?;
+    println!("Stake Result (using alias): {:?}", result.final_answer);
 
     Ok(())
 }
\ No newline at end of file
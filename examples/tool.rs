@@ -1,7 +1,8 @@
-use async_std::sync::Arc;
+use std::sync::Arc;
+
 use serde_json::json;
 use solagent::{
-    llm_integration::{Grok3Provider, LLMClient, OpenAIProvider, GeminiProvider, LLMProvider},
+    llm_integration::{LLMProvider, ProviderConfig},
     tool_system::{ToolInput, ToolMetadata, SolanaTool},
     SolAgent, SolAgentConfig,
 };
@@ -21,10 +22,7 @@ impl SolanaTool for BalanceTool {
             .ok_or("Missing pubkey")?;
         let prompt = format!("Get the balance for Solana address: {}", pubkey);
         let response = llm.call(&prompt, vec![]).await?;
-        Ok(response["choices"][0]["message"]["content"]
-            .as_str()
-            .unwrap_or("No response")
-            .to_string())
+        Ok(response.text().unwrap_or("No response").to_string())
     }
 }
 
@@ -34,10 +32,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let config = SolAgentConfig {
         name: "Solana Agent".to_string(),
         instructions: "Execute Solana-related tasks with tools and LLM.".to_string(),
-        model: "grok3".to_string(),
+        model: ProviderConfig::from_env("grok3")?,
         tools: vec!["get_balance".to_string()],
+        wallet_address: None,
+        prompts_dir: None,
+        context: None,
+        memory_path: None,
     };
-    let solagent = SolAgent::new(config)?;
+    let solagent = SolAgent::new(config).await?;
 
     // Register balance tool for Grok 3
     let grok3_tool_metadata = ToolMetadata {
@@ -55,6 +57,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 }
             }
         }),
+        sends_transactions: false,
     };
     solagent
         .tool_registry
@@ -63,8 +66,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Execute task with Grok 3
     let input = json!({ "pubkey": "abc123" });
-    let result = solagent.execute_task("get_balance", input).await?;
-    println!("Grok 3 Balance Result: {}", result);
+    let result = solagent.execute_task("example", "get_balance", input).await?;
+    println!("Grok 3 Balance Result: {:?}", result.final_answer);
 
     Ok(())
 }
\ No newline at end of file
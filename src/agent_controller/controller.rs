@@ -1,94 +1,409 @@
-use async_std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
+
+use futures::StreamExt;
 use serde_json::Value;
+use tokio::sync::mpsc::UnboundedSender;
 
 use crate::{
-    llm_integration::LLMClient,
-    memory_system::{LongTermMemory, ShortTermMemory},
-    tool_system::{ToolInput, ToolMetadata, ToolRegistry, SolanaTool},
+    agent_controller::{
+        session::{SessionEntry, SessionManager},
+        transcript::{
+            ExecutionBudget, StopReason, TaskError, TaskEvent, TaskTranscript, TranscriptStep,
+        },
+    },
+    llm_integration::{
+        context::{transcript_text, ContextConfig, ContextManager, TokenEstimator},
+        message::{ChatMessage, ToolCall},
+        prompt::{PromptConfig, TASK_TEMPLATE},
+        response::LlmResponse,
+        stream::StreamEvent,
+        usage::{BudgetExceeded, UsageMeter},
+        LLMClient, LLMProvider,
+    },
+    memory_system::{vector::VectorRecall, LongTermMemory},
+    tool_system::{ToolInput, ToolMetadata, ToolRegistry},
 };
 
 // Agent Controller structure
 pub struct AgentController {
     tool_registry: Arc<ToolRegistry>,
-    memory_short: Arc<ShortTermMemory>,
+    sessions: Arc<SessionManager>,
     memory_long: Arc<LongTermMemory>,
     llm_client: Arc<LLMClient>,
+    prompts: Arc<PromptConfig>,
+    context: ContextManager,
+    budget: ExecutionBudget,
+    recall: Option<VectorRecall>,
 }
 
+// Number of recorded actions shown in task prompts
+const RECENT_ACTIONS: usize = 10;
+
+// Instructions for folding dropped turns into the running summary
+const SUMMARY_INSTRUCTIONS: &str = "Summarize the conversation below between a Solana agent and \
+its tools. Keep every fact needed to finish the task: amounts, addresses, transaction \
+signatures, tool results and errors. Reply with the summary only.";
+
 impl AgentController {
     pub fn new(
         tool_registry: Arc<ToolRegistry>,
-        memory_short: Arc<ShortTermMemory>,
+        sessions: Arc<SessionManager>,
         memory_long: Arc<LongTermMemory>,
         llm_client: Arc<LLMClient>,
     ) -> Self {
         AgentController {
             tool_registry,
-            memory_short,
+            sessions,
             memory_long,
             llm_client,
+            prompts: Arc::new(PromptConfig::new()),
+            context: ContextManager::new(ContextConfig::default()),
+            budget: ExecutionBudget::default(),
+            recall: None,
         }
     }
 
-    // Executes a task by selecting a tool and calling the appropriate LLM
+    // Sets the prompt templates used to build task prompts
+    pub fn with_prompts(mut self, prompts: Arc<PromptConfig>) -> Self {
+        self.prompts = prompts;
+        self
+    }
+
+    // Sets the context window budget for task conversations
+    pub fn with_context(mut self, config: ContextConfig) -> Self {
+        self.context = ContextManager::new(config);
+        self
+    }
+
+    // Sets the limits applied to the tool-calling loop
+    pub fn with_budget(mut self, budget: ExecutionBudget) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    // Adds the documents in vector memory most related to each task to its prompt
+    pub fn with_recall(mut self, recall: VectorRecall) -> Self {
+        self.recall = Some(recall);
+        self
+    }
+
+    pub fn sessions(&self) -> &Arc<SessionManager> {
+        &self.sessions
+    }
+
+    pub fn memory_long(&self) -> &Arc<LongTermMemory> {
+        &self.memory_long
+    }
+
+    // Executes a task in a session by letting the LLM call tools until it gives a final
+    // answer or the execution budget runs out. The session is started if it doesn't exist,
+    // and tasks of the same session run one at a time.
     pub async fn execute_task(
         &self,
+        session_id: &str,
         task: &str,
         input: Value,
-    ) -> Result<String, Box<dyn std::error::Error>> {
-        // Retrieve tool and metadata
-        let (metadata, tool) = self
-            .tool_registry
-            .get(task)
-            .await
-            .ok_or(format!("Tool '{}' not found", task))?;
+    ) -> Result<TaskTranscript, Box<dyn std::error::Error>> {
+        self.run_task(session_id, task, input, None).await
+    }
+
+    // Same as `execute_task`, but streams model output and tool progress to `events`
+    pub async fn execute_task_streaming(
+        &self,
+        session_id: &str,
+        task: &str,
+        input: Value,
+        events: UnboundedSender<TaskEvent>,
+    ) -> Result<TaskTranscript, Box<dyn std::error::Error>> {
+        self.run_task(session_id, task, input, Some(&events)).await
+    }
+
+    async fn run_task(
+        &self,
+        session_id: &str,
+        task: &str,
+        input: Value,
+        events: Option<&UnboundedSender<TaskEvent>>,
+    ) -> Result<TaskTranscript, Box<dyn std::error::Error>> {
+        let session = self.sessions.open(session_id).await?;
+        let _turn = session.take_turn().await;
 
-        // Get LLM provider
-        let llm_provider = self
+        // A task naming a tool runs on that tool's provider, anything else on the default one
+        let provider_name = match self.tool_registry.get(task).await {
+            Some((metadata, _)) => metadata.llm_type,
+            None => self.llm_client.default_provider().await.ok_or("No LLM provider registered")?,
+        };
+        let mut llm_provider = self
             .llm_client
-            .providers
-            .read()
+            .provider(&provider_name)
             .await
-            .get(&metadata.llm_type)
-            .ok_or(format!("LLM provider '{}' not found", metadata.llm_type))?
-            .clone();
-
-        // Construct prompt with task and context
-        let context = self.memory_short.context.read().await;
-        let prompt = format!(
-            "Execute task: {}\nInput: {}\nContext: {:?}",
-            task, input, context
-        );
-
-        // Call LLM
-        let llm_response = llm_provider.call(&prompt, vec![metadata.clone()]).await?;
-
-        // Parse LLM response
-        let tool_call = parse_llm_response(&llm_response, &metadata.llm_type)?;
-        let tool_input = ToolInput {
-            params: tool_call.get("arguments").cloned().unwrap_or(input),
-        };
+            .ok_or(format!("LLM provider '{}' not found", provider_name))?;
 
-        // Execute tool
-        let result = tool.execute(tool_input, llm_provider.as_ref()).await?;
+        // The client offers the provider every tool, and a fallback the tools whose schema
+        // targets it
+        let tools: Vec<ToolMetadata> = self.tool_registry.list().await;
 
-        // Store result in memory
-        self.memory_short
-            .context
-            .write()
-            .await
-            .insert(task.to_string(), result.clone());
+        // Construct prompt from the task template, with as much memory as the budget allows
+        let estimator = TokenEstimator::for_model(llm_provider.model_name());
+        // Shared memory being unreachable should not stop the task
+        let entries = session.memory().entries().await.unwrap_or_else(|e| {
+            tracing::warn!("Could not read short-term memory: {}", e);
+            vec![]
+        });
+        let mut memory_lines = entries
+            .iter()
+            .map(|(key, value)| format!("- {}: {}", key, value))
+            .collect::<Vec<_>>();
+        // The conversation so far, most recent last
+        memory_lines.extend(session.history().await.iter().map(|entry| entry.line()));
+        // Recent on-chain actions, so the model knows what was already done
+        if let Some(action_log) = self.tool_registry.action_log() {
+            match action_log.recent(RECENT_ACTIONS).await {
+                Ok(actions) => memory_lines
+                    .extend(actions.iter().map(|a| format!("- action: {}", a.summary()))),
+                Err(e) => tracing::warn!("Could not read the action log: {}", e),
+            }
+        }
+        // Recalled documents go last, best last, since the budget keeps the last lines first
+        if let Some(recall) = &self.recall {
+            match recall.recall(task, &input).await {
+                Ok(recalled) => memory_lines.extend(
+                    recalled.iter().rev().map(|r| format!("- recalled: {}", r.entry.document.text)),
+                ),
+                Err(e) => tracing::warn!("Could not search vector memory: {}", e),
+            }
+        }
+        let memory = self.context.fit_memory(&memory_lines, &estimator);
+        let variables = HashMap::from([
+            ("task".to_string(), task.to_string()),
+            ("input".to_string(), input.to_string()),
+            ("memory".to_string(), memory),
+        ]);
+        // Keyed by session, so a conversation stays on one A/B variant from task to task
+        let prompt = self.prompts.render(TASK_TEMPLATE, session_id, &variables)?;
+        let mut messages = prompt.messages;
+
+        let mut transcript = TaskTranscript::new(task, &provider_name);
+        transcript.prompt_version = Some(format!("{}@{}", prompt.name, prompt.version));
+        transcript.stop_reason = StopReason::MaxIterations;
+        let task_usage =
+            Arc::new(UsageMeter::new(self.budget.usage_budget()).within(session.usage().clone()));
 
-        Ok(result)
+        'turns: for iteration in 0..self.budget.max_iterations {
+            // Stop before a call the task or session budget can no longer pay for
+            if let Err(exceeded) = task_usage.check() {
+                transcript.stop_reason = budget_stop_reason(&exceeded);
+                break;
+            }
+
+            self.fit_context(&provider_name, &estimator, &mut messages, &task_usage).await;
+            let turn =
+                self.model_turn(&provider_name, &messages, &tools, &task_usage, iteration, events);
+            let (response, answered) = match turn.await {
+                Ok(answer) => answer,
+                Err(err) => match err.downcast_ref::<BudgetExceeded>() {
+                    // Refused as the prompt alone would overrun a budget
+                    Some(exceeded) => {
+                        transcript.stop_reason = budget_stop_reason(exceeded);
+                        break;
+                    }
+                    None => {
+                        transcript.usage = task_usage.totals();
+                        return Err(Box::new(TaskError { error: err.to_string(), transcript }));
+                    }
+                },
+            };
+            // A fallback provider answered, so it also serves the tools it called
+            if answered != transcript.provider {
+                if let Some(provider) = self.llm_client.provider(&answered).await {
+                    llm_provider = provider;
+                }
+                transcript.provider = answered;
+            }
+            let text = response.text().map(String::from);
+            let tool_calls = response.tool_calls().to_vec();
+            transcript.steps.push(TranscriptStep::Llm {
+                iteration,
+                text: text.clone(),
+                tool_calls: tool_calls.clone(),
+            });
+
+            // No tool calls means the model has answered
+            if tool_calls.is_empty() {
+                transcript.final_answer = text;
+                transcript.stop_reason = StopReason::FinalAnswer;
+                break;
+            }
+
+            messages.push(ChatMessage::assistant(text.unwrap_or_default(), tool_calls.clone()));
+            for call in tool_calls {
+                if transcript.tool_calls() >= self.budget.max_tool_calls {
+                    transcript.stop_reason = StopReason::MaxToolCalls;
+                    break 'turns;
+                }
+                if let Some(events) = events {
+                    let _ = events.send(TaskEvent::ToolStarted { iteration, call: call.clone() });
+                }
+                let output = self.run_tool(&call, llm_provider.as_ref()).await;
+                if let Some(events) = events {
+                    let _ = events.send(TaskEvent::ToolFinished {
+                        iteration,
+                        call_id: call.id.clone(),
+                        output: output.clone(),
+                    });
+                }
+                let content = match &output {
+                    Ok(out) => out.clone(),
+                    Err(err) => format!("Error: {}", err),
+                };
+                messages.push(ChatMessage::tool(&call, content));
+                transcript.steps.push(TranscriptStep::Tool {
+                    iteration,
+                    call_id: call.id,
+                    tool: call.name,
+                    arguments: call.arguments,
+                    output,
+                });
+            }
+        }
+        transcript.usage = task_usage.totals();
+
+        // Store the exchange in the session's history and result in its memory
+        let mut entries = vec![SessionEntry::user(&format!("{}\nInput: {}", task, input))];
+        for step in &transcript.steps {
+            if let TranscriptStep::Tool { tool, output, .. } = step {
+                let content = match output {
+                    Ok(out) => out.clone(),
+                    Err(err) => format!("Error: {}", err),
+                };
+                entries.push(SessionEntry::tool(tool, &content));
+            }
+        }
+        if let Some(answer) = &transcript.final_answer {
+            entries.push(SessionEntry::assistant(answer));
+        }
+        for entry in entries {
+            if let Err(e) = session.push(entry).await {
+                tracing::warn!(session = session_id, "Could not write session history: {}", e);
+            }
+        }
+        if let Some(result) =
+            transcript.final_answer.clone().or(transcript.last_tool_output().map(String::from))
+        {
+            if let Err(e) = session.remember(task, &result).await {
+                tracing::warn!(session = session_id, "Could not write short-term memory: {}", e);
+            }
+        }
+
+        Ok(transcript)
+    }
+
+    // Drops the oldest turns once the conversation outgrows the context budget, folding them
+    // into a summary when enabled. A failed summary falls back to dropping.
+    async fn fit_context(
+        &self,
+        provider_name: &str,
+        estimator: &TokenEstimator,
+        messages: &mut Vec<ChatMessage>,
+        task_usage: &Arc<UsageMeter>,
+    ) {
+        let dropped = self.context.compact(messages, estimator);
+        if dropped.is_empty() || !self.context.config.summarize {
+            return;
+        }
+        let mut text = transcript_text(&dropped);
+        if let Some(previous) = self.context.current_summary(messages) {
+            text = format!("{}\n{}", previous, text);
+        }
+        let request = vec![ChatMessage::system(SUMMARY_INSTRUCTIONS), ChatMessage::user(text)];
+        match self.llm_client.chat_metered(provider_name, &request, vec![], Some(task_usage)).await
+        {
+            Ok((response, _)) => {
+                let summary = response.text().unwrap_or_default().to_string();
+                self.context.insert_summary(messages, summary);
+            }
+            Err(err) => tracing::warn!("Context summary failed, dropping old turns: {}", err),
+        }
+    }
+
+    // Asks the model for its next turn, streaming it to `events` if given. Returns the response
+    // with the name of the provider that answered.
+    async fn model_turn(
+        &self,
+        provider_name: &str,
+        messages: &[ChatMessage],
+        tools: &[ToolMetadata],
+        task_usage: &Arc<UsageMeter>,
+        iteration: usize,
+        events: Option<&UnboundedSender<TaskEvent>>,
+    ) -> Result<(LlmResponse, String), Box<dyn std::error::Error>> {
+        match events {
+            Some(events) => {
+                self.stream_turn(provider_name, messages, tools, task_usage, iteration, events)
+                    .await
+            }
+            None => {
+                self.llm_client
+                    .chat_metered(provider_name, messages, tools.to_vec(), Some(task_usage))
+                    .await
+            }
+        }
+    }
+
+    // Streams one model turn, forwarding deltas and returning the assembled response
+    async fn stream_turn(
+        &self,
+        provider_name: &str,
+        messages: &[ChatMessage],
+        tools: &[ToolMetadata],
+        task_usage: &Arc<UsageMeter>,
+        iteration: usize,
+        events: &UnboundedSender<TaskEvent>,
+    ) -> Result<(LlmResponse, String), Box<dyn std::error::Error>> {
+        let (mut stream, answered) = self
+            .llm_client
+            .chat_stream_metered(provider_name, messages, tools.to_vec(), Some(task_usage))
+            .await?;
+        // Send errors only mean the receiver stopped listening, so they are ignored
+        while let Some(event) = stream.next().await {
+            match event.map_err(|e| e.to_string())? {
+                StreamEvent::TextDelta(text) => {
+                    let _ = events.send(TaskEvent::TextDelta { iteration, text });
+                }
+                StreamEvent::ToolCallDelta { index, name, arguments_delta, .. } => {
+                    let _ = events.send(TaskEvent::ToolCallDelta {
+                        iteration,
+                        index,
+                        name,
+                        arguments_delta,
+                    });
+                }
+                StreamEvent::Done(response) => return Ok((response, answered)),
+            }
+        }
+        Err("LLM stream ended without a final response".into())
+    }
+
+    // Runs a single tool call; failures are reported back to the model rather than aborting
+    async fn run_tool(
+        &self,
+        call: &ToolCall,
+        llm: &dyn LLMProvider,
+    ) -> Result<String, String> {
+        let (_, tool) = self
+            .tool_registry
+            .get(&call.name)
+            .await
+            .ok_or(format!("Tool '{}' not found", call.name))?;
+        tool.execute(ToolInput::new(call.arguments.clone()), llm)
+            .await
+            .map_err(|e| e.to_string())
     }
 }
 
-// Helper function to parse LLM response
-fn parse_llm_response(response: &Value, llm_type: &str) -> Result<Value, Box<dyn std::error::Error>> {
-    match llm_type {
-        "grok3" => Ok(response["choices"][0]["message"]["tool_calls"][0].clone()),
-        "gemini" => Ok(response["candidates"][0]["content"]["parts"][0]["functionCall"].clone()),
-        "openai" => Ok(response["choices"][0]["message"]["tool_calls"][0].clone()),
-        _ => Err("Unsupported LLM type".into()),
+fn budget_stop_reason(exceeded: &BudgetExceeded) -> StopReason {
+    match exceeded {
+        BudgetExceeded::Tokens { .. } => StopReason::TokenBudget,
+        BudgetExceeded::Cost { .. } => StopReason::CostBudget,
     }
-}
\ No newline at end of file
+}
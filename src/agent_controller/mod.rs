@@ -1 +1,5 @@
-pub mod controller;
\ No newline at end of file
+pub mod controller;
+pub mod transcript;
+pub mod session;
+
+pub use controller::AgentController;
\ No newline at end of file
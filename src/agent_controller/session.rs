@@ -0,0 +1,507 @@
+//! Conversation sessions: each user talks to the agent in their own session, with its own
+//! history and short-term memory
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, Weak,
+    },
+    time::Duration,
+};
+
+use rand::Rng;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::{MutexGuard, RwLock},
+    task::JoinHandle,
+};
+
+use crate::{
+    llm_integration::usage::{UsageBudget, UsageMeter},
+    memory_system::{long_term::MemoryError, ShortTermMemory},
+    util::{lock_db, now},
+};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryRole {
+    User,
+    Assistant,
+    Tool,
+}
+
+// Message or tool result in a session's history, with its time in Unix milliseconds
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SessionEntry {
+    pub role: EntryRole,
+    // Tool that produced a tool result
+    #[serde(default)]
+    pub tool: Option<String>,
+    pub content: String,
+    pub timestamp: u64,
+}
+
+impl SessionEntry {
+    pub fn user(content: &str) -> Self {
+        Self::new(EntryRole::User, None, content)
+    }
+
+    pub fn assistant(content: &str) -> Self {
+        Self::new(EntryRole::Assistant, None, content)
+    }
+
+    pub fn tool(tool: &str, content: &str) -> Self {
+        Self::new(EntryRole::Tool, Some(tool.to_string()), content)
+    }
+
+    fn new(role: EntryRole, tool: Option<String>, content: &str) -> Self {
+        SessionEntry { role, tool, content: content.to_string(), timestamp: now() }
+    }
+
+    // One line description for prompts
+    pub fn line(&self) -> String {
+        let role = match self.role {
+            EntryRole::User => "user",
+            EntryRole::Assistant => "assistant",
+            EntryRole::Tool => "tool",
+        };
+        match &self.tool {
+            Some(tool) => format!("- {} {}: {}", role, tool, self.content),
+            None => format!("- {}: {}", role, self.content),
+        }
+    }
+}
+
+// One user's conversation with the agent
+pub struct Session {
+    id: String,
+    created_at: u64,
+    last_active: AtomicU64,
+    history: RwLock<Vec<SessionEntry>>,
+    max_history: usize,
+    memory: ShortTermMemory,
+    // Model usage of the session's tasks while it is live
+    usage: Arc<UsageMeter>,
+    store: Option<Arc<SessionStore>>,
+    // Held while a task runs, so tasks of one session take turns
+    turn: tokio::sync::Mutex<()>,
+}
+
+impl Session {
+    fn new(
+        id: &str,
+        max_history: usize,
+        memory: ShortTermMemory,
+        budget: UsageBudget,
+        store: Option<Arc<SessionStore>>,
+    ) -> Self {
+        let now = now();
+        Session {
+            id: id.to_string(),
+            created_at: now,
+            last_active: AtomicU64::new(now),
+            history: RwLock::new(vec![]),
+            max_history,
+            memory,
+            usage: Arc::new(UsageMeter::new(budget)),
+            store,
+            turn: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    // Picks up a persisted session where it was left
+    async fn resume(mut self, state: SessionState) -> Result<Self, MemoryError> {
+        self.created_at = state.created_at;
+        self.last_active = AtomicU64::new(state.last_active);
+        for (key, value) in &state.memory {
+            self.memory.insert(key, value).await?;
+        }
+        self.history = RwLock::new(state.history);
+        Ok(self)
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn created_at(&self) -> u64 {
+        self.created_at
+    }
+
+    // Time of the last task or history entry, in Unix milliseconds
+    pub fn last_active(&self) -> u64 {
+        self.last_active.load(Ordering::Relaxed)
+    }
+
+    // Working context of the session's recent task results
+    pub fn memory(&self) -> &ShortTermMemory {
+        &self.memory
+    }
+
+    // Meter every task of the session charges, limited by the manager's session budget
+    pub fn usage(&self) -> &Arc<UsageMeter> {
+        &self.usage
+    }
+
+    // Messages and tool results, oldest first
+    pub async fn history(&self) -> Vec<SessionEntry> {
+        self.history.read().await.clone()
+    }
+
+    // Appends to the history, forgetting the oldest entries past the session's limit
+    pub async fn push(&self, entry: SessionEntry) -> Result<(), MemoryError> {
+        if let Some(store) = &self.store {
+            store.append(&self.id, &entry, self.max_history)?;
+        }
+        let mut history = self.history.write().await;
+        history.push(entry);
+        let excess = history.len().saturating_sub(self.max_history);
+        history.drain(..excess);
+        drop(history);
+        self.touch()
+    }
+
+    // Keeps a value in the session's short-term memory
+    pub async fn remember(&self, key: &str, value: &str) -> Result<(), MemoryError> {
+        if let Some(store) = &self.store {
+            store.save_memory(&self.id, key, value)?;
+        }
+        self.memory.insert(key, value).await?;
+        self.touch()
+    }
+
+    // Waits for the session's running task, if any, to finish
+    pub async fn take_turn(&self) -> MutexGuard<'_, ()> {
+        self.turn.lock().await
+    }
+
+    fn touch(&self) -> Result<(), MemoryError> {
+        let now = now();
+        self.last_active.store(now, Ordering::Relaxed);
+        if let Some(store) = &self.store {
+            store.save_session(&self.id, self.created_at, now)?;
+        }
+        Ok(())
+    }
+
+    fn idle_for(&self, now: u64) -> Duration {
+        Duration::from_millis(now.saturating_sub(self.last_active()))
+    }
+}
+
+// Creates the short-term memory of a session from its id
+pub type MemoryFactory = Arc<dyn Fn(&str) -> ShortTermMemory + Send + Sync>;
+
+// Live sessions by id. Sessions idle for longer than the idle timeout are forgotten, and with
+// a store, sessions outlive restarts.
+pub struct SessionManager {
+    sessions: Mutex<HashMap<String, Arc<Session>>>,
+    idle_timeout: Duration,
+    max_history: usize,
+    memory: MemoryFactory,
+    usage_budget: UsageBudget,
+    store: Option<Arc<SessionStore>>,
+}
+
+impl SessionManager {
+    pub fn new() -> Self {
+        SessionManager {
+            sessions: Mutex::new(HashMap::new()),
+            idle_timeout: Duration::from_secs(30 * 60),
+            max_history: 100,
+            memory: Arc::new(|_| ShortTermMemory::new()),
+            usage_budget: UsageBudget::default(),
+            store: None,
+        }
+    }
+
+    // Gives each session the memory `memory` creates for it, instead of memory kept in this
+    // process. Replicas sharing a store can share session memory too, e.g. in Redis with
+    // `Arc::new(move |id| {
+    //     ShortTermMemory::redis(pool.clone(), &Namespace::session(id), ttl).with_prefix(prefix)
+    // })`.
+    pub fn with_memory(mut self, memory: MemoryFactory) -> Self {
+        self.memory = memory;
+        self
+    }
+
+    // Limits model usage per session, across all of its tasks
+    pub fn with_usage_budget(mut self, budget: UsageBudget) -> Self {
+        self.usage_budget = budget;
+        self
+    }
+
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    // Number of history entries kept per session
+    pub fn with_max_history(mut self, max_history: usize) -> Self {
+        self.max_history = max_history.max(1);
+        self
+    }
+
+    pub fn with_store(mut self, store: Arc<SessionStore>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    // Random id for a new session
+    pub fn new_id() -> String {
+        format!("{:032x}", rand::thread_rng().gen::<u128>())
+    }
+
+    // The session with this id, resumed from the store or started if there is none or it
+    // has been idle for too long
+    pub async fn open(&self, id: &str) -> Result<Arc<Session>, MemoryError> {
+        let now = now();
+        if let Some(session) = self.get(id) {
+            if session.idle_for(now) <= self.idle_timeout {
+                return Ok(session);
+            }
+            self.close(id).await?;
+        }
+
+        let stored = match &self.store {
+            Some(store) => store.load(id)?,
+            None => None,
+        };
+        let idle_timeout = self.idle_timeout.as_millis() as u64;
+        let memory = (self.memory)(id);
+        let budget = self.usage_budget.clone();
+        let session = Session::new(id, self.max_history, memory, budget, self.store.clone());
+        let session = match stored {
+            Some(state) if now.saturating_sub(state.last_active) <= idle_timeout => {
+                session.resume(state).await?
+            }
+            stale => {
+                if stale.is_some() {
+                    self.close(id).await?;
+                }
+                session.touch()?;
+                session
+            }
+        };
+
+        // Another task may have opened the session meanwhile; the first one wins
+        let mut sessions = self.sessions.lock().unwrap();
+        Ok(sessions.entry(id.to_string()).or_insert_with(|| Arc::new(session)).clone())
+    }
+
+    // The live session with this id
+    pub fn get(&self, id: &str) -> Option<Arc<Session>> {
+        self.sessions.lock().unwrap().get(id).cloned()
+    }
+
+    // Ids of the live sessions
+    pub fn ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self.sessions.lock().unwrap().keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+
+    // Ends a session, deleting it from the store and clearing its memory; returns whether it
+    // existed
+    pub async fn close(&self, id: &str) -> Result<bool, MemoryError> {
+        let live = self.sessions.lock().unwrap().remove(id);
+        // Memory shared with other replicas outlives the session object, so it is cleared
+        // even when the session is not live here
+        match &live {
+            Some(session) => session.memory().clear().await?,
+            None => (self.memory)(id).clear().await?,
+        }
+        let stored = match &self.store {
+            Some(store) => store.delete(id)?,
+            None => false,
+        };
+        Ok(live.is_some() || stored)
+    }
+
+    // Ends every session idle for longer than the idle timeout, returning how many there were
+    pub async fn expire_idle(&self) -> Result<usize, MemoryError> {
+        let now = now();
+        let mut expired: Vec<String> = self
+            .sessions
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, session)| session.idle_for(now) > self.idle_timeout)
+            .map(|(id, _)| id.clone())
+            .collect();
+        if let Some(store) = &self.store {
+            let before = now.saturating_sub(self.idle_timeout.as_millis() as u64);
+            for id in store.idle_since(before)? {
+                if !expired.contains(&id) && self.get(&id).is_none() {
+                    expired.push(id);
+                }
+            }
+        }
+        for id in &expired {
+            self.close(id).await?;
+        }
+        Ok(expired.len())
+    }
+
+    // Expires idle sessions every `period` in the background, until the manager is dropped;
+    // needs a Tokio runtime
+    pub fn sweep_every(self: &Arc<Self>, period: Duration) -> JoinHandle<()> {
+        let manager: Weak<Self> = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(period);
+            ticks.tick().await;
+            loop {
+                ticks.tick().await;
+                let Some(manager) = manager.upgrade() else { break };
+                if let Err(e) = manager.expire_idle().await {
+                    tracing::warn!("Could not expire idle sessions: {}", e);
+                }
+            }
+        })
+    }
+}
+
+impl Default for SessionManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Persisted session, as read back from the store
+pub struct SessionState {
+    pub created_at: u64,
+    pub last_active: u64,
+    pub history: Vec<SessionEntry>,
+    pub memory: Vec<(String, String)>,
+}
+
+// SQLite store of sessions, their history and short-term memory
+pub struct SessionStore {
+    db: Mutex<Connection>,
+}
+
+impl SessionStore {
+    pub fn open(path: &str) -> rusqlite::Result<Self> {
+        Self::init(Connection::open(path)?)
+    }
+
+    pub fn in_memory() -> rusqlite::Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(db: Connection) -> rusqlite::Result<Self> {
+        db.busy_timeout(Duration::from_secs(5))?;
+        db.execute_batch(
+            "CREATE TABLE IF NOT EXISTS sessions (
+                id TEXT PRIMARY KEY,
+                created_at INTEGER NOT NULL,
+                last_active INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS session_entries (
+                seq INTEGER PRIMARY KEY AUTOINCREMENT,
+                session_id TEXT NOT NULL,
+                entry TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS session_entries_session
+                ON session_entries (session_id, seq);
+            CREATE TABLE IF NOT EXISTS session_memory (
+                session_id TEXT NOT NULL,
+                key TEXT NOT NULL,
+                value TEXT NOT NULL,
+                PRIMARY KEY (session_id, key)
+            );",
+        )?;
+        Ok(SessionStore { db: Mutex::new(db) })
+    }
+
+    pub fn save_session(
+        &self,
+        id: &str,
+        created_at: u64,
+        last_active: u64,
+    ) -> rusqlite::Result<()> {
+        lock_db(&self.db)?.execute(
+            "INSERT INTO sessions (id, created_at, last_active) VALUES (?1, ?2, ?3)
+             ON CONFLICT(id) DO UPDATE SET last_active = ?3",
+            params![id, created_at, last_active],
+        )?;
+        Ok(())
+    }
+
+    // Appends a history entry, keeping the last `keep` entries of the session
+    pub fn append(&self, id: &str, entry: &SessionEntry, keep: usize) -> rusqlite::Result<()> {
+        let entry = serde_json::to_string(entry)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        let db = lock_db(&self.db)?;
+        db.execute(
+            "INSERT INTO session_entries (session_id, entry) VALUES (?1, ?2)",
+            params![id, entry],
+        )?;
+        db.execute(
+            "DELETE FROM session_entries WHERE session_id = ?1 AND seq NOT IN (
+                 SELECT seq FROM session_entries WHERE session_id = ?1
+                 ORDER BY seq DESC LIMIT ?2
+             )",
+            params![id, keep as i64],
+        )?;
+        Ok(())
+    }
+
+    pub fn save_memory(&self, id: &str, key: &str, value: &str) -> rusqlite::Result<()> {
+        lock_db(&self.db)?.execute(
+            "INSERT OR REPLACE INTO session_memory (session_id, key, value) VALUES (?1, ?2, ?3)",
+            params![id, key, value],
+        )?;
+        Ok(())
+    }
+
+    pub fn load(&self, id: &str) -> rusqlite::Result<Option<SessionState>> {
+        let db = lock_db(&self.db)?;
+        let Some((created_at, last_active)) = db
+            .query_row(
+                "SELECT created_at, last_active FROM sessions WHERE id = ?1",
+                [id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?
+        else {
+            return Ok(None);
+        };
+
+        let mut statement =
+            db.prepare("SELECT entry FROM session_entries WHERE session_id = ?1 ORDER BY seq")?;
+        let history = statement
+            .query_map([id], |row| row.get::<_, String>(0))?
+            .filter_map(|entry| entry.map(|e| serde_json::from_str(&e).ok()).transpose())
+            .collect::<rusqlite::Result<_>>()?;
+        let mut statement = db.prepare(
+            "SELECT key, value FROM session_memory WHERE session_id = ?1 ORDER BY key",
+        )?;
+        let memory = statement
+            .query_map([id], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(Some(SessionState { created_at, last_active, history, memory }))
+    }
+
+    // Deletes a session with its history and memory; returns whether it existed
+    pub fn delete(&self, id: &str) -> rusqlite::Result<bool> {
+        let db = lock_db(&self.db)?;
+        let deleted = db.execute("DELETE FROM sessions WHERE id = ?1", [id])?;
+        db.execute("DELETE FROM session_entries WHERE session_id = ?1", [id])?;
+        db.execute("DELETE FROM session_memory WHERE session_id = ?1", [id])?;
+        Ok(deleted > 0)
+    }
+
+    // Ids of the sessions last active before `before`, in Unix milliseconds
+    pub fn idle_since(&self, before: u64) -> rusqlite::Result<Vec<String>> {
+        let db = lock_db(&self.db)?;
+        let mut statement = db.prepare("SELECT id FROM sessions WHERE last_active < ?1")?;
+        let ids = statement
+            .query_map([before], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        Ok(ids)
+    }
+}
+
@@ -0,0 +1,133 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::llm_integration::{
+    message::ToolCall,
+    usage::{UsageBudget, UsageTotals},
+};
+
+// Limits applied to the tool-calling loop of a single task
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ExecutionBudget {
+    pub max_iterations: usize,
+    pub max_tool_calls: usize,
+    pub max_total_tokens: Option<u64>,
+    pub max_cost_usd: Option<f64>,
+}
+
+impl Default for ExecutionBudget {
+    fn default() -> Self {
+        ExecutionBudget {
+            max_iterations: 8,
+            max_tool_calls: 16,
+            max_total_tokens: None,
+            max_cost_usd: None,
+        }
+    }
+}
+
+impl ExecutionBudget {
+    // Usage limits enforced by the LLM client for one task
+    pub fn usage_budget(&self) -> UsageBudget {
+        UsageBudget { max_total_tokens: self.max_total_tokens, max_cost_usd: self.max_cost_usd }
+    }
+}
+
+// Why the tool-calling loop ended
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StopReason {
+    FinalAnswer,
+    MaxIterations,
+    MaxToolCalls,
+    TokenBudget,
+    CostBudget,
+}
+
+// Single step recorded while executing a task
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TranscriptStep {
+    // Model turn, with any text and the tool calls it requested
+    Llm { iteration: usize, text: Option<String>, tool_calls: Vec<ToolCall> },
+    // Execution of one requested tool call
+    Tool {
+        iteration: usize,
+        call_id: String,
+        tool: String,
+        arguments: Value,
+        output: Result<String, String>,
+    },
+}
+
+// Structured record of every step taken for a task
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TaskTranscript {
+    pub task: String,
+    // Provider that answered last; a fallback when the requested one kept failing
+    pub provider: String,
+    // Prompt template used, as `name@version`
+    pub prompt_version: Option<String>,
+    pub steps: Vec<TranscriptStep>,
+    pub final_answer: Option<String>,
+    pub stop_reason: StopReason,
+    pub usage: UsageTotals,
+}
+
+impl TaskTranscript {
+    pub fn new(task: &str, provider: &str) -> Self {
+        TaskTranscript {
+            task: task.to_string(),
+            provider: provider.to_string(),
+            prompt_version: None,
+            steps: vec![],
+            final_answer: None,
+            stop_reason: StopReason::FinalAnswer,
+            usage: UsageTotals::default(),
+        }
+    }
+
+    // Number of tool calls executed so far
+    pub fn tool_calls(&self) -> usize {
+        self.steps.iter().filter(|s| matches!(s, TranscriptStep::Tool { .. })).count()
+    }
+
+    // Output of the last successful tool call, if any
+    pub fn last_tool_output(&self) -> Option<&str> {
+        self.steps.iter().rev().find_map(|s| match s {
+            TranscriptStep::Tool { output: Ok(out), .. } => Some(out.as_str()),
+            _ => None,
+        })
+    }
+}
+
+// Task that failed part way through, with the steps it took before the error
+#[derive(Debug)]
+pub struct TaskError {
+    pub error: String,
+    pub transcript: TaskTranscript,
+}
+
+impl fmt::Display for TaskError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let steps = self.transcript.steps.len();
+        write!(f, "Task '{}' failed after {} steps: {}", self.transcript.task, steps, self.error)
+    }
+}
+
+impl std::error::Error for TaskError {}
+
+// Live progress event emitted while a task runs in streaming mode
+#[derive(Clone, Debug)]
+pub enum TaskEvent {
+    // Partial answer text from the model
+    TextDelta { iteration: usize, text: String },
+    // Partial tool call as the model writes it
+    ToolCallDelta { iteration: usize, index: usize, name: Option<String>, arguments_delta: String },
+    // A tool call is about to run
+    ToolStarted { iteration: usize, call: ToolCall },
+    // A tool call finished
+    ToolFinished { iteration: usize, call_id: String, output: Result<String, String> },
+}
@@ -1,51 +1,85 @@
-/// SolAgent Framework: A Rust AI agent for Solana, integrating blockchain tools and LLMs.
-/// 
-/// # Example
-/// ```
-/// use solagent::{SolAgent, SolAgentConfig};
-/// use serde_json::json;
-/// 
-/// #[tokio::main]
-/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
-///     let config = SolAgentConfig {
-///         name: "Solana Agent".to_string(),
-///         instructions: "Execute Solana tasks.".to_string(),
-///         model: "grok3".to_string(),
-///         tools: vec!["stake_sol".to_string()],
-///     };
-///     let solagent = SolAgent::new(config)?;
-///     let input = json!({ "amount": 10.0, "validator": "validator_pubkey" });
-///     let result = solagent.execute_task("stake_sol", input).await?;
-///     println!("Result: {}", result);
-///     Ok(())
-/// }
-/// ```
-/// 
+//! SolAgent Framework: A Rust AI agent for Solana, integrating blockchain tools and LLMs.
+//!
+//! # Example
+//! ```no_run
+//! use solagent::{llm_integration::ProviderConfig, SolAgent, SolAgentConfig};
+//! use serde_json::json;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let config = SolAgentConfig {
+//!         name: "Solana Agent".to_string(),
+//!         instructions: "Execute Solana tasks.".to_string(),
+//!         model: ProviderConfig::from_env("grok3")?,
+//!         tools: vec!["stake_sol".to_string()],
+//!         wallet_address: None,
+//!         prompts_dir: None,
+//!         context: None,
+//!         memory_path: None,
+//!     };
+//!     let solagent = SolAgent::new(config).await?;
+//!     let input = json!({ "amount": 10.0, "validator": "validator_pubkey" });
+//!     let transcript = solagent.execute_task("alice", "stake_sol", input).await?;
+//!     println!("Result: {:?}", transcript.final_answer);
+//!     Ok(())
+//! }
+//! ```
 
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
-use agent_controller::AgentController;
-use llm_integration::{Grok3Provider, LLMClient, LLMProvider};
-use memory_system::{LongTermMemory, ShortTermMemory};
+pub mod agent_controller;
+pub mod llm_integration;
+pub mod memory_system;
+pub mod observability;
+pub mod planning_reasoning;
+pub mod security_permission;
+pub mod solana_integration;
+#[path = "system_tools/tools.rs"]
+pub mod tool_system;
+pub mod user_interface;
+mod util;
+pub mod workflow_engine;
+
+use agent_controller::{
+    session::{SessionManager, SessionStore},
+    transcript::{TaskEvent, TaskTranscript},
+    AgentController,
+};
+use llm_integration::{
+    context::ContextConfig, prompt::PromptConfig, LLMClient, ProviderConfig,
+};
+use memory_system::{actions::ActionLog, long_term::Namespace, LongTermMemory};
 use observability::{Logger, Monitoring};
 use security_permission::{ABAC, RBAC};
-use solana_integration::{IndexerClient, SolanaRPC};
+use solana_integration::{rpc::cluster_name, IndexerClient, SolanaRPC};
 use tool_system::ToolRegistry;
 use user_interface::{ApiServer, CliConfig, WebConfig};
 use workflow_engine::WorkflowEngine;
 
+// How often sessions idle for longer than their timeout are ended
+const SESSION_SWEEP_PERIOD: Duration = Duration::from_secs(60);
+
 // Configuration for SolAgent initialization
 pub struct SolAgentConfig {
     pub name: String,
     pub instructions: String,
-    pub model: String,
+    pub model: ProviderConfig,
     pub tools: Vec<String>,
+    // Address of the wallet the agent acts for, shown to the model
+    pub wallet_address: Option<String>,
+    // Directory of `.toml`/`.json` prompt templates overriding the built-in ones
+    pub prompts_dir: Option<String>,
+    // Context window budget for task conversations; defaults apply when unset
+    pub context: Option<ContextConfig>,
+    // SQLite file for long-term memory and conversation sessions; both are kept in memory
+    // only when unset
+    pub memory_path: Option<String>,
 }
 
 // Main SolAgent struct, orchestrating all framework components
 pub struct SolAgent {
     pub tool_registry: Arc<ToolRegistry>,
-    pub memory_short: Arc<ShortTermMemory>,
+    pub sessions: Arc<SessionManager>,
     pub memory_long: Arc<LongTermMemory>,
     pub controller: Arc<AgentController>,
     pub workflow: Arc<WorkflowEngine>,
@@ -64,54 +98,75 @@ pub struct SolAgent {
 
 impl SolAgent {
     // Creates a new SolAgent instance with provided configuration
-    pub fn new(config: SolAgentConfig) -> Result<Self, Box<dyn std::error::Error>> {
+    pub async fn new(config: SolAgentConfig) -> Result<Self, Box<dyn std::error::Error>> {
         let rpc_url = std::env::var("SOLANA_RPC_URL").unwrap_or("https://api.devnet.solana.com".to_string());
         let rpc = Arc::new(SolanaRPC::new());
         let indexer = Arc::new(IndexerClient::new());
-        let tool_registry = Arc::new(ToolRegistry::new());
+        let memory_long = Arc::new(match &config.memory_path {
+            Some(path) => LongTermMemory::open(path)?,
+            None => LongTermMemory::in_memory()?,
+        });
+        let mut sessions = SessionManager::new();
+        if let Some(path) = &config.memory_path {
+            sessions = sessions.with_store(Arc::new(SessionStore::open(path)?));
+        }
+        let sessions = Arc::new(sessions);
+        sessions.sweep_every(SESSION_SWEEP_PERIOD);
+
+        // Every transaction sent through a tool is recorded in the agent's long-term memory
+        let mut action_log =
+            ActionLog::new(memory_long.clone(), Namespace::agent(&config.name), &rpc_url);
+        if let Some(wallet_address) = &config.wallet_address {
+            action_log = action_log.with_wallet(wallet_address)?;
+        }
+        let tool_registry = Arc::new(ToolRegistry::new().with_action_log(Arc::new(action_log)));
         
         // Register specified tools
         tool_registry.register_tools(&config.tools, &rpc_url).await;
 
-        let memory_short = Arc::new(ShortTermMemory::new());
-        let memory_long = Arc::new(LongTermMemory::new());
-        let rbac = Arc::new(RBAC::new());
-        let abac = Arc::new(ABAC::new());
+        let rbac = Arc::new(RBAC::new().await?);
+        let abac = Arc::new(ABAC::new().await?);
         let logger = Arc::new(Logger::new());
         let monitoring = Arc::new(Monitoring::new());
         let llm_client = Arc::new(LLMClient::new());
 
-        // Initialize LLM provider based on config.model
-        match config.model.as_str() {
-            "grok3" => {
-                let api_key = std::env::var("GROK3_API_KEY")?;
-                llm_client.register_provider("grok3", Arc::new(Grok3Provider::new(api_key))).await;
-            }
-            "gemini" => {
-                let api_key = std::env::var("GEMINI_API_KEY")?;
-                llm_client.register_provider("gemini", Arc::new(GeminiProvider::new(api_key))).await;
-            }
-            "openai" => {
-                let api_key = std::env::var("OPENAI_API_KEY")?;
-                llm_client.register_provider("openai", Arc::new(OpenAIProvider::new(api_key))).await;
-            }
-            _ => return Err("Unsupported LLM model".into()),
+        // Initialize LLM provider from config.model
+        llm_client.register_provider(config.model.name(), config.model.build()).await;
+
+        // Prompt templates, with agent-wide variables
+        let mut prompts = PromptConfig::new();
+        if let Some(dir) = &config.prompts_dir {
+            prompts.load_dir(std::path::Path::new(dir))?;
+        }
+        prompts.set_variable("instructions", &config.instructions);
+        prompts.set_variable("cluster", cluster_name(&rpc_url));
+        if let Some(wallet_address) = &config.wallet_address {
+            prompts.set_variable("wallet_address", wallet_address);
         }
 
-        let controller = Arc::new(AgentController::new(
-            tool_registry.clone(),
-            memory_short.clone(),
-            memory_long.clone(),
-            llm_client.clone(),
-        ));
-        let workflow = Arc::new(WorkflowEngine::new());
+        let controller = Arc::new(
+            AgentController::new(
+                tool_registry.clone(),
+                sessions.clone(),
+                memory_long.clone(),
+                llm_client.clone(),
+            )
+            .with_prompts(Arc::new(prompts))
+            .with_context(config.context.clone().unwrap_or_default()),
+        );
+        let workflow = Arc::new(WorkflowEngine::new(tool_registry.clone()));
         let cli = Arc::new(CliConfig::new());
-        let api = Arc::new(ApiServer::new());
+        // The API only starts with a bearer token, which callers present as `api`
+        let mut api = ApiServer::new(controller.clone());
+        if let Ok(token) = std::env::var("SOLAGENT_API_TOKEN") {
+            api = api.with_token("api", &token);
+        }
+        let api = Arc::new(api);
         let web = Arc::new(WebConfig::new());
 
         Ok(SolAgent {
             tool_registry,
-            memory_short,
+            sessions,
             memory_long,
             controller,
             workflow,
@@ -129,12 +184,25 @@ impl SolAgent {
         })
     }
 
-    // Executes a task using the AgentController
+    // Executes a task in a conversation session using the AgentController, returning every
+    // step taken
     pub async fn execute_task(
         &self,
+        session_id: &str,
+        task: &str,
+        input: serde_json::Value,
+    ) -> Result<TaskTranscript, Box<dyn std::error::Error>> {
+        self.controller.execute_task(session_id, task, input).await
+    }
+
+    // Executes a task, streaming partial model output and tool progress to `events`
+    pub async fn execute_task_streaming(
+        &self,
+        session_id: &str,
         task: &str,
         input: serde_json::Value,
-    ) -> Result<String, Box<dyn std::error::Error>> {
-        self.controller.execute_task(task, input).await
+        events: tokio::sync::mpsc::UnboundedSender<TaskEvent>,
+    ) -> Result<TaskTranscript, Box<dyn std::error::Error>> {
+        self.controller.execute_task_streaming(session_id, task, input, events).await
     }
 }
\ No newline at end of file
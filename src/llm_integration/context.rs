@@ -0,0 +1,301 @@
+use serde::{Deserialize, Serialize};
+
+use crate::llm_integration::message::{ChatMessage, Role};
+
+// `name` marking the message that holds the summary of dropped turns
+pub const SUMMARY_MESSAGE_NAME: &str = "context_summary";
+
+// `name` marking the user message that states the task, as rendered from a prompt template
+pub const TASK_MESSAGE_NAME: &str = "task";
+
+// Approximate token counter. Exact counts need the provider's tokenizer, so this estimates
+// from character counts with a per-model ratio.
+#[derive(Clone, Copy, Debug)]
+pub struct TokenEstimator {
+    pub chars_per_token: f64,
+    pub per_message_overhead: usize,
+}
+
+impl TokenEstimator {
+    pub fn for_model(model: &str) -> Self {
+        let chars_per_token = match model {
+            m if m.starts_with("gpt") || m.starts_with("o1") || m.starts_with("o3") => 4.0,
+            m if m.starts_with("grok") => 4.0,
+            m if m.starts_with("gemini") => 4.0,
+            m if m.contains("llama") || m.contains("mistral") || m.contains("qwen") => 3.5,
+            // Unknown models: err on the side of more tokens
+            _ => 3.0,
+        };
+        TokenEstimator { chars_per_token, per_message_overhead: 4 }
+    }
+
+    pub fn estimate_text(&self, text: &str) -> usize {
+        (text.chars().count() as f64 / self.chars_per_token).ceil() as usize
+    }
+
+    pub fn estimate_message(&self, message: &ChatMessage) -> usize {
+        let calls: usize = message
+            .tool_calls
+            .iter()
+            .map(|c| self.estimate_text(&c.name) + self.estimate_text(&c.arguments.to_string()))
+            .sum();
+        self.per_message_overhead + self.estimate_text(&message.content) + calls
+    }
+
+    pub fn estimate(&self, messages: &[ChatMessage]) -> usize {
+        messages.iter().map(|m| self.estimate_message(m)).sum()
+    }
+}
+
+// Context window limits
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ContextConfig {
+    // Token budget for the messages sent on each call
+    pub max_tokens: usize,
+    // Token budget for the memory section of the task prompt
+    pub max_memory_tokens: usize,
+    // Summarize dropped turns instead of discarding them
+    pub summarize: bool,
+    // Tokens kept free for the summary when summarizing
+    pub summary_tokens: usize,
+}
+
+impl Default for ContextConfig {
+    fn default() -> Self {
+        ContextConfig {
+            max_tokens: 16_000,
+            max_memory_tokens: 2_000,
+            summarize: true,
+            summary_tokens: 500,
+        }
+    }
+}
+
+// Keeps a rolling message history within the context budget.
+//
+// System messages (instructions and any earlier summary), the task message and the latest
+// assistant turn with its tool results are always kept; older turns, including few-shot
+// examples, are dropped oldest first.
+pub struct ContextManager {
+    pub config: ContextConfig,
+}
+
+impl ContextManager {
+    pub fn new(config: ContextConfig) -> Self {
+        ContextManager { config }
+    }
+
+    // Budget to compact to, leaving room for a summary if one will be added
+    fn target(&self) -> usize {
+        if self.config.summarize {
+            self.config.max_tokens.saturating_sub(self.config.summary_tokens)
+        } else {
+            self.config.max_tokens
+        }
+    }
+
+    // Drops the oldest unpinned turns until `messages` fits, returning the dropped messages
+    pub fn compact(
+        &self,
+        messages: &mut Vec<ChatMessage>,
+        estimator: &TokenEstimator,
+    ) -> Vec<ChatMessage> {
+        if estimator.estimate(messages) <= self.config.max_tokens {
+            return vec![];
+        }
+        let target = self.target();
+        let pinned = pinned(messages);
+        let mut total = estimator.estimate(messages);
+        let mut dropped = vec![false; messages.len()];
+        let mut i = 0;
+        while total > target && i < messages.len() {
+            if pinned[i] {
+                i += 1;
+                continue;
+            }
+            // An assistant turn and its tool results are dropped together, as providers reject
+            // tool results without the call that produced them
+            let end = turn_end(messages, i);
+            if (i..end).any(|j| pinned[j]) {
+                i = end;
+                continue;
+            }
+            for j in i..end {
+                dropped[j] = true;
+                total -= estimator.estimate_message(&messages[j]);
+            }
+            i = end;
+        }
+
+        let mut kept = Vec::with_capacity(messages.len());
+        let mut removed = vec![];
+        for (message, dropped) in messages.drain(..).zip(dropped) {
+            if dropped {
+                removed.push(message);
+            } else {
+                kept.push(message);
+            }
+        }
+        *messages = kept;
+        removed
+    }
+
+    // Replaces any earlier summary with `summary`, placed after the system instructions
+    pub fn insert_summary(&self, messages: &mut Vec<ChatMessage>, summary: String) {
+        messages.retain(|m| m.name.as_deref() != Some(SUMMARY_MESSAGE_NAME));
+        let position = messages.iter().take_while(|m| m.role == Role::System).count();
+        let mut message =
+            ChatMessage::system(format!("Summary of earlier conversation:\n{}", summary));
+        message.name = Some(SUMMARY_MESSAGE_NAME.to_string());
+        messages.insert(position, message);
+    }
+
+    // Existing summary text, to be folded into the next summary
+    pub fn current_summary<'a>(&self, messages: &'a [ChatMessage]) -> Option<&'a str> {
+        messages
+            .iter()
+            .find(|m| m.name.as_deref() == Some(SUMMARY_MESSAGE_NAME))
+            .map(|m| m.content.as_str())
+    }
+
+    // Keeps memory lines, most recent last, until the memory budget is reached
+    pub fn fit_memory(&self, lines: &[String], estimator: &TokenEstimator) -> String {
+        let mut used = 0;
+        let mut kept = vec![];
+        for line in lines.iter().rev() {
+            used += estimator.estimate_text(line) + 1;
+            if used > self.config.max_memory_tokens {
+                break;
+            }
+            kept.push(line.as_str());
+        }
+        kept.reverse();
+        kept.join("\n")
+    }
+}
+
+// Marks messages that must never be dropped
+fn pinned(messages: &[ChatMessage]) -> Vec<bool> {
+    let mut pinned: Vec<bool> = messages.iter().map(|m| m.role == Role::System).collect();
+    // The task is the marked message; conversations not rendered from a template start with it
+    let task = messages
+        .iter()
+        .rposition(|m| m.role == Role::User && m.name.as_deref() == Some(TASK_MESSAGE_NAME))
+        .or_else(|| messages.iter().position(|m| m.role == Role::User));
+    if let Some(task) = task {
+        pinned[task] = true;
+    }
+    // Latest assistant turn and everything after it, i.e. the most recent tool results
+    if let Some(last_turn) = messages.iter().rposition(|m| m.role == Role::Assistant) {
+        for p in &mut pinned[last_turn..] {
+            *p = true;
+        }
+    }
+    pinned
+}
+
+// End (exclusive) of the turn starting at `start`
+fn turn_end(messages: &[ChatMessage], start: usize) -> usize {
+    let mut end = start + 1;
+    if messages[start].role == Role::Assistant {
+        while end < messages.len() && messages[end].role == Role::Tool {
+            end += 1;
+        }
+    }
+    end
+}
+
+// Renders dropped messages as plain text for summarization
+pub fn transcript_text(messages: &[ChatMessage]) -> String {
+    messages
+        .iter()
+        .map(|m| {
+            let calls = m
+                .tool_calls
+                .iter()
+                .map(|c| format!(" [call {}({})]", c.name, c.arguments))
+                .collect::<String>();
+            format!("{:?}: {}{}", m.role, m.content, calls)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm_integration::message::ToolCall;
+
+    fn call(id: &str) -> ToolCall {
+        ToolCall {
+            id: id.to_string(),
+            name: "get_balance".to_string(),
+            arguments: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_compact_keeps_pinned_messages() {
+        let estimator = TokenEstimator { chars_per_token: 1.0, per_message_overhead: 0 };
+        let manager = ContextManager::new(ContextConfig {
+            max_tokens: 30,
+            max_memory_tokens: 0,
+            summarize: false,
+            summary_tokens: 0,
+        });
+        let mut messages = vec![
+            ChatMessage::system("sys"),
+            ChatMessage::user("task"),
+            ChatMessage::assistant("", vec![call("1")]),
+            ChatMessage::tool(&call("1"), "x".repeat(20)),
+            ChatMessage::assistant("", vec![call("2")]),
+            ChatMessage::tool(&call("2"), "latest"),
+        ];
+
+        let dropped = manager.compact(&mut messages, &estimator);
+
+        assert_eq!(dropped.len(), 2);
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[1].content, "task");
+        assert_eq!(messages[3].content, "latest");
+    }
+
+    #[test]
+    fn test_compact_keeps_the_task_after_examples() {
+        let estimator = TokenEstimator { chars_per_token: 1.0, per_message_overhead: 0 };
+        let manager = ContextManager::new(ContextConfig {
+            max_tokens: 40,
+            max_memory_tokens: 0,
+            summarize: false,
+            summary_tokens: 0,
+        });
+        let mut task = ChatMessage::user("task");
+        task.name = Some(TASK_MESSAGE_NAME.to_string());
+        let mut messages = vec![
+            ChatMessage::system("sys"),
+            ChatMessage::user("example question"),
+            ChatMessage::assistant("example answer", vec![]),
+            task,
+            ChatMessage::assistant("", vec![call("1")]),
+            ChatMessage::tool(&call("1"), "x".repeat(20)),
+            ChatMessage::assistant("", vec![call("2")]),
+            ChatMessage::tool(&call("2"), "latest"),
+        ];
+
+        manager.compact(&mut messages, &estimator);
+
+        let contents: Vec<&str> = messages.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, vec!["sys", "task", "", "latest"]);
+    }
+
+    #[test]
+    fn test_insert_summary_replaces_previous() {
+        let manager = ContextManager::new(ContextConfig::default());
+        let mut messages = vec![ChatMessage::system("sys"), ChatMessage::user("task")];
+        manager.insert_summary(&mut messages, "first".to_string());
+        manager.insert_summary(&mut messages, "second".to_string());
+
+        assert_eq!(messages.len(), 3);
+        assert!(manager.current_summary(&messages).unwrap().ends_with("second"));
+    }
+}
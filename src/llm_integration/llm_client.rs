@@ -1,57 +1,128 @@
+use std::sync::Arc;
+
 use async_trait::async_trait;
+use futures::StreamExt;
 use reqwest::Client;
+use schemars::JsonSchema;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::Value;
 
-use crate::tool_system::ToolMetadata;
+use crate::{
+    llm_integration::{
+        context::TokenEstimator,
+        message::{to_gemini_contents, ChatMessage, Role, ToolCall},
+        openai_compatible::{
+            http_client, OpenAICompatibleConfig, OpenAICompatibleProvider, REDACTED,
+        },
+        response::{FinishReason, LlmResponse, Usage},
+        retry::{send_checked, RetryPolicy},
+        stream::{sse_event_stream, stream_from_response, LlmStream, StreamAccumulator, StreamEvent},
+        structured::{OutputSchema, StructuredOutputError},
+        usage::{BudgetExceeded, ModelPrice, PriceTable, UsageBudget, UsageMeter, UsageTotals},
+    },
+    tool_system::ToolMetadata,
+};
 
 // LLM provider trait for pluggable LLM APIs
 #[async_trait]
 pub trait LLMProvider: Send + Sync {
+    // Model served by this provider, used to look up prices
+    fn model_name(&self) -> &str {
+        ""
+    }
+
+    // Single-prompt convenience wrapper around `chat`
     async fn call(
         &self,
         prompt: &str,
         tools: Vec<ToolMetadata>,
-    ) -> Result<Value, Box<dyn std::error::Error>>;
+    ) -> Result<LlmResponse, Box<dyn std::error::Error>> {
+        self.chat(&[ChatMessage::user(prompt)], tools).await
+    }
+
+    // Sends a full conversation, including earlier tool calls and results
+    async fn chat(
+        &self,
+        messages: &[ChatMessage],
+        tools: Vec<ToolMetadata>,
+    ) -> Result<LlmResponse, Box<dyn std::error::Error>>;
+
+    // Streams the response as text and tool-call deltas.
+    // Providers without native streaming emit the complete response as a single delta.
+    async fn chat_stream(
+        &self,
+        messages: &[ChatMessage],
+        tools: Vec<ToolMetadata>,
+    ) -> Result<LlmStream, Box<dyn std::error::Error>> {
+        let response = self.chat(messages, tools).await?;
+        Ok(stream_from_response(response))
+    }
+
+    // `chat` with the completion capped at `max_tokens`, so one call cannot overrun a usage
+    // budget. Providers without such a cap answer as `chat` does.
+    async fn chat_limited(
+        &self,
+        messages: &[ChatMessage],
+        tools: Vec<ToolMetadata>,
+        _max_tokens: u32,
+    ) -> Result<LlmResponse, Box<dyn std::error::Error>> {
+        self.chat(messages, tools).await
+    }
+
+    // Streaming counterpart of `chat_limited`
+    async fn chat_stream_limited(
+        &self,
+        messages: &[ChatMessage],
+        tools: Vec<ToolMetadata>,
+        _max_tokens: u32,
+    ) -> Result<LlmStream, Box<dyn std::error::Error>> {
+        self.chat_stream(messages, tools).await
+    }
+
+    // Asks for a JSON answer matching `schema`. The client already instructs the model and
+    // validates the answer; providers with a native JSON mode should also enable it here.
+    async fn chat_json(
+        &self,
+        messages: &[ChatMessage],
+        _schema: &OutputSchema,
+    ) -> Result<LlmResponse, Box<dyn std::error::Error>> {
+        self.chat(messages, vec![]).await
+    }
 }
 
-// Grok 3 provider
-pub struct Grok3Provider {
-    client: Client,
-    api_key: String,
-    endpoint: String,
+// Provider for tools run without an LLM, e.g. by rules and workflows. Every request fails.
+pub struct NoLlmProvider;
+
+#[async_trait]
+impl LLMProvider for NoLlmProvider {
+    async fn chat(
+        &self,
+        _messages: &[ChatMessage],
+        _tools: Vec<ToolMetadata>,
+    ) -> Result<LlmResponse, Box<dyn std::error::Error>> {
+        Err("No LLM provider is configured".into())
+    }
 }
 
+// Grok 3 provider, now served by `OpenAICompatibleProvider`; `new` is kept for existing code
+#[deprecated(note = "use OpenAICompatibleProvider::grok3")]
+pub struct Grok3Provider;
+
+#[allow(deprecated, clippy::new_ret_no_self)]
 impl Grok3Provider {
-    pub fn new(api_key: String) -> Self {
-        Grok3Provider {
-            client: Client::new(),
-            api_key,
-            endpoint: "https://api.x.ai/v1".to_string(),
-        }
+    pub fn new(api_key: String) -> OpenAICompatibleProvider {
+        OpenAICompatibleProvider::grok3(api_key)
     }
 }
 
-#[async_trait]
-impl LLMProvider for Grok3Provider {
-    async fn call(
-        &self,
-        prompt: &str,
-        tools: Vec<ToolMetadata>,
-    ) -> Result<Value, Box<dyn std::error::Error>> {
-        let response = self
-            .client
-            .post(&format!("{}/chat/completions", self.endpoint))
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .json(&serde_json::json!({
-                "model": "grok-3",
-                "messages": [{"role": "user", "content": prompt}],
-                "tools": tools.iter().map(|t| &t.schema).collect::<Vec<_>>(),
-            }))
-            .send()
-            .await?
-            .json()
-            .await?;
-        Ok(response)
+// OpenAI provider, now served by `OpenAICompatibleProvider`; `new` is kept for existing code
+#[deprecated(note = "use OpenAICompatibleProvider::openai")]
+pub struct OpenAIProvider;
+
+#[allow(deprecated, clippy::new_ret_no_self)]
+impl OpenAIProvider {
+    pub fn new(api_key: String) -> OpenAICompatibleProvider {
+        OpenAICompatibleProvider::openai(api_key)
     }
 }
 
@@ -60,97 +131,344 @@ pub struct GeminiProvider {
     client: Client,
     api_key: String,
     endpoint: String,
+    model: String,
 }
 
 impl GeminiProvider {
     pub fn new(api_key: String) -> Self {
         GeminiProvider {
-            client: Client::new(),
+            client: http_client(None),
             api_key,
             endpoint: "https://generativelanguage.googleapis.com/v1".to_string(),
+            model: "gemini-1.5-pro".to_string(),
         }
     }
+
+    pub fn with_model(mut self, model: &str) -> Self {
+        self.model = model.to_string();
+        self
+    }
+
+    // Sets the request timeout, including reading the response body; `DEFAULT_TIMEOUT_SECS`
+    // when unset
+    pub fn with_timeout_secs(mut self, timeout_secs: u64) -> Self {
+        self.client = http_client(Some(timeout_secs));
+        self
+    }
 }
 
 #[async_trait]
 impl LLMProvider for GeminiProvider {
-    async fn call(
+    fn model_name(&self) -> &str {
+        &self.model
+    }
+
+    async fn chat(
         &self,
-        prompt: &str,
+        messages: &[ChatMessage],
         tools: Vec<ToolMetadata>,
-    ) -> Result<Value, Box<dyn std::error::Error>> {
-        let response = self
+    ) -> Result<LlmResponse, Box<dyn std::error::Error>> {
+        self.generate(gemini_body(messages, &tools)).await
+    }
+
+    async fn chat_limited(
+        &self,
+        messages: &[ChatMessage],
+        tools: Vec<ToolMetadata>,
+        max_tokens: u32,
+    ) -> Result<LlmResponse, Box<dyn std::error::Error>> {
+        let mut body = gemini_body(messages, &tools);
+        body["generationConfig"] = serde_json::json!({"maxOutputTokens": max_tokens});
+        self.generate(body).await
+    }
+
+    async fn chat_json(
+        &self,
+        messages: &[ChatMessage],
+        _schema: &OutputSchema,
+    ) -> Result<LlmResponse, Box<dyn std::error::Error>> {
+        // Gemini's responseSchema only accepts an OpenAPI subset, so only JSON mode is enabled
+        // and the schema itself travels in the instructions
+        let mut body = gemini_body(messages, &[]);
+        body["generationConfig"] = serde_json::json!({"responseMimeType": "application/json"});
+        self.generate(body).await
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[ChatMessage],
+        tools: Vec<ToolMetadata>,
+    ) -> Result<LlmStream, Box<dyn std::error::Error>> {
+        self.stream_generate(gemini_body(messages, &tools)).await
+    }
+
+    async fn chat_stream_limited(
+        &self,
+        messages: &[ChatMessage],
+        tools: Vec<ToolMetadata>,
+        max_tokens: u32,
+    ) -> Result<LlmStream, Box<dyn std::error::Error>> {
+        let mut body = gemini_body(messages, &tools);
+        body["generationConfig"] = serde_json::json!({"maxOutputTokens": max_tokens});
+        self.stream_generate(body).await
+    }
+}
+
+impl GeminiProvider {
+    async fn generate(&self, body: Value) -> Result<LlmResponse, Box<dyn std::error::Error>> {
+        let request = self
             .client
-            .post(&format!("{}/models/gemini-1.5-pro:generateContent", self.endpoint))
+            .post(format!("{}/models/{}:generateContent", self.endpoint, self.model))
             .query(&[("key", &self.api_key)])
-            .json(&serde_json::json!({
-                "contents": [{"parts": [{"text": prompt}]}],
-                "tools": [{"functionDeclarations": tools.iter().map(|t| &t.schema).collect::<Vec<_>>()}],
-            }))
-            .send()
-            .await?
-            .json()
-            .await?;
-        Ok(response)
+            .json(&body);
+        let response = send_checked(request).await?;
+        let response = response.json().await?;
+        decode_gemini_response(&response)
+    }
+
+    async fn stream_generate(&self, body: Value) -> Result<LlmStream, Box<dyn std::error::Error>> {
+        let request = self
+            .client
+            .post(format!("{}/models/{}:streamGenerateContent", self.endpoint, self.model))
+            .query(&[("key", self.api_key.as_str()), ("alt", "sse")])
+            .json(&body);
+        let response = send_checked(request).await?;
+        Ok(sse_event_stream(response, StreamAccumulator::push_gemini_chunk))
     }
 }
 
-// OpenAI provider
-pub struct OpenAIProvider {
-    client: Client,
-    api_key: String,
-    endpoint: String,
+// Builds a Gemini generateContent request body
+fn gemini_body(messages: &[ChatMessage], tools: &[ToolMetadata]) -> Value {
+    let (system, contents) = to_gemini_contents(messages);
+    let mut body = serde_json::json!({
+        "contents": contents,
+        "tools": [{"functionDeclarations": tools.iter().map(|t| &t.schema).collect::<Vec<_>>()}],
+    });
+    if let Some(system) = system {
+        body["systemInstruction"] = system;
+    }
+    body
 }
 
-impl OpenAIProvider {
-    pub fn new(api_key: String) -> Self {
-        OpenAIProvider {
-            client: Client::new(),
-            api_key,
-            endpoint: "https://api.openai.com/v1".to_string(),
+// Decodes a Gemini generateContent response
+fn decode_gemini_response(response: &Value) -> Result<LlmResponse, Box<dyn std::error::Error>> {
+    if let Some(error) = response["error"]["message"].as_str() {
+        return Err(format!("Gemini error: {}", error).into());
+    }
+    let candidate = &response["candidates"][0];
+    let parts = candidate["content"]["parts"].as_array().cloned().unwrap_or_default();
+    let text = parts.iter().filter_map(|p| p["text"].as_str()).collect::<String>();
+    let tool_calls: Vec<ToolCall> = parts
+        .iter()
+        .filter(|p| p["functionCall"].is_object())
+        .enumerate()
+        .map(|(i, p)| ToolCall {
+            // Gemini does not assign call ids
+            id: format!("call_{}", i),
+            name: p["functionCall"]["name"].as_str().unwrap_or_default().to_string(),
+            arguments: p["functionCall"]["args"].clone(),
+        })
+        .collect();
+    let finish_reason = match candidate["finishReason"].as_str() {
+        _ if !tool_calls.is_empty() => FinishReason::ToolCalls,
+        Some("STOP") | None => FinishReason::Stop,
+        Some("MAX_TOKENS") => FinishReason::Length,
+        Some("SAFETY") | Some("RECITATION") => FinishReason::ContentFilter,
+        Some(other) => FinishReason::Other(other.to_string()),
+    };
+    let usage = &response["usageMetadata"];
+    let usage = Usage {
+        prompt_tokens: usage["promptTokenCount"].as_u64().unwrap_or(0),
+        completion_tokens: usage["candidatesTokenCount"].as_u64().unwrap_or(0),
+        total_tokens: usage["totalTokenCount"].as_u64().unwrap_or(0),
+    };
+    Ok(LlmResponse::new((!text.is_empty()).then_some(text), tool_calls, finish_reason, usage))
+}
+
+// LLM provider configuration, used to build and register a provider by name. Its `Debug`
+// output hides API keys.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ProviderConfig {
+    OpenAICompatible { name: String, config: OpenAICompatibleConfig },
+    Gemini {
+        name: String,
+        model: String,
+        api_key: String,
+        #[serde(default)]
+        timeout_secs: Option<u64>,
+    },
+}
+
+impl ProviderConfig {
+    // Preset for the built-in "grok3", "gemini" and "openai" names, reading API keys from the
+    // environment
+    pub fn from_env(name: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let config = match name {
+            "grok3" => OpenAICompatibleConfig::grok3(std::env::var("GROK3_API_KEY")?),
+            "openai" => OpenAICompatibleConfig::openai(std::env::var("OPENAI_API_KEY")?),
+            "gemini" => {
+                return Ok(ProviderConfig::Gemini {
+                    name: name.to_string(),
+                    model: "gemini-1.5-pro".to_string(),
+                    api_key: std::env::var("GEMINI_API_KEY")?,
+                    timeout_secs: None,
+                })
+            }
+            _ => return Err(format!("Unsupported LLM model '{}'", name).into()),
+        };
+        Ok(ProviderConfig::OpenAICompatible { name: name.to_string(), config })
+    }
+
+    // Name the provider is registered under; tools target it through `ToolMetadata::llm_type`
+    pub fn name(&self) -> &str {
+        match self {
+            ProviderConfig::OpenAICompatible { name, .. } | ProviderConfig::Gemini { name, .. } => {
+                name
+            }
+        }
+    }
+
+    pub fn build(&self) -> Arc<dyn LLMProvider> {
+        match self {
+            ProviderConfig::OpenAICompatible { config, .. } => {
+                Arc::new(OpenAICompatibleProvider::new(config.clone()))
+            }
+            ProviderConfig::Gemini { model, api_key, timeout_secs, .. } => {
+                let mut provider = GeminiProvider::new(api_key.clone()).with_model(model);
+                if let Some(timeout_secs) = timeout_secs {
+                    provider = provider.with_timeout_secs(*timeout_secs);
+                }
+                Arc::new(provider)
+            }
         }
     }
 }
 
-#[async_trait]
-impl LLMProvider for OpenAIProvider {
-    async fn call(
-        &self,
-        prompt: &str,
-        tools: Vec<ToolMetadata>,
-    ) -> Result<Value, Box<dyn std::error::Error>> {
-        let response = self
-            .client
-            .post(&format!("{}/chat/completions", self.endpoint))
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .json(&serde_json::json!({
-                "model": "gpt-4o",
-                "messages": [{"role": "user", "content": prompt}],
-                "tools": tools.iter().map(|t| &t.schema).collect::<Vec<_>>(),
-            }))
-            .send()
-            .await?
-            .json()
-            .await?;
-        Ok(response)
+impl std::fmt::Debug for ProviderConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProviderConfig::OpenAICompatible { name, config } => f
+                .debug_struct("OpenAICompatible")
+                .field("name", name)
+                .field("config", config)
+                .finish(),
+            ProviderConfig::Gemini { name, model, timeout_secs, .. } => f
+                .debug_struct("Gemini")
+                .field("name", name)
+                .field("model", model)
+                .field("api_key", &REDACTED)
+                .field("timeout_secs", timeout_secs)
+                .finish(),
+        }
+    }
+}
+
+// Provider a call was sent to: its registered name and its price table key
+#[derive(Clone)]
+struct Answered {
+    name: String,
+    model: String,
+}
+
+// Tools offered to the provider registered as `answered` when `requested` was asked for. The
+// requested provider gets every tool; a fallback only gets those whose `llm_type` names it,
+// as the others were written for another provider.
+fn tools_for(tools: &[ToolMetadata], requested: &str, answered: &str) -> Vec<ToolMetadata> {
+    if answered == requested {
+        return tools.to_vec();
     }
+    let (offered, dropped): (Vec<_>, Vec<_>) =
+        tools.iter().cloned().partition(|t| t.llm_type == answered);
+    if !dropped.is_empty() {
+        let names: Vec<&str> = dropped.iter().map(|t| t.name.as_str()).collect();
+        tracing::warn!(
+            provider = answered,
+            "Tools not offered to the fallback provider, as their llm_type names another: {}",
+            names.join(", ")
+        );
+    }
+    offered
 }
 
-// LLM client managing multiple providers
+// LLM client managing multiple providers, with retries, an ordered fallback chain and
+// usage accounting
 pub struct LLMClient {
     providers: tokio::sync::RwLock<std::collections::HashMap<String, Arc<dyn LLMProvider>>>,
+    default_provider: tokio::sync::RwLock<Option<String>>,
+    retry_policy: tokio::sync::RwLock<RetryPolicy>,
+    fallback_chain: tokio::sync::RwLock<Vec<String>>,
+    price_table: tokio::sync::RwLock<PriceTable>,
+    usage: Arc<UsageMeter>,
+}
+
+impl Default for LLMClient {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl LLMClient {
     pub fn new() -> Self {
         LLMClient {
             providers: tokio::sync::RwLock::new(std::collections::HashMap::new()),
+            default_provider: tokio::sync::RwLock::new(None),
+            retry_policy: tokio::sync::RwLock::new(RetryPolicy::default()),
+            fallback_chain: tokio::sync::RwLock::new(vec![]),
+            price_table: tokio::sync::RwLock::new(PriceTable::default()),
+            usage: Arc::new(UsageMeter::new(UsageBudget::default())),
         }
     }
 
-    // Registers an LLM provider
+    // Registers an LLM provider; the first one registered becomes the default
     pub async fn register_provider(&self, name: &str, provider: Arc<dyn LLMProvider>) {
         self.providers.write().await.insert(name.to_string(), provider);
+        self.default_provider.write().await.get_or_insert_with(|| name.to_string());
+    }
+
+    // Overrides the provider used when a task does not name one
+    pub async fn set_default_provider(&self, name: &str) {
+        *self.default_provider.write().await = Some(name.to_string());
+    }
+
+    // Name of the default provider, if any is registered
+    pub async fn default_provider(&self) -> Option<String> {
+        self.default_provider.read().await.clone()
+    }
+
+    // Looks up a registered provider by name
+    pub async fn provider(&self, name: &str) -> Option<Arc<dyn LLMProvider>> {
+        self.providers.read().await.get(name).cloned()
+    }
+
+    pub async fn set_retry_policy(&self, policy: RetryPolicy) {
+        *self.retry_policy.write().await = policy;
+    }
+
+    // Sets the providers tried, in order, after the requested one fails,
+    // e.g. `["grok3", "openai", "ollama"]`
+    pub async fn set_fallback_chain(&self, chain: Vec<String>) {
+        *self.fallback_chain.write().await = chain;
+    }
+
+    pub async fn set_price_table(&self, price_table: PriceTable) {
+        *self.price_table.write().await = price_table;
+    }
+
+    // Sets the price of a model, or of a provider whose model name is unknown
+    pub async fn set_price(&self, model: &str, price: ModelPrice) {
+        self.price_table.write().await.set(model, price);
+    }
+
+    // Limits usage across all calls made through this client; sessions and tasks have their
+    // own budgets on the meters passed to the metered calls
+    pub fn set_budget(&self, budget: UsageBudget) {
+        self.usage.set_budget(budget);
+    }
+
+    // Usage and cost of all calls made through this client
+    pub fn usage(&self) -> UsageTotals {
+        self.usage.totals()
     }
 
     // Calls an LLM provider
@@ -159,14 +477,304 @@ impl LLMClient {
         provider_name: &str,
         prompt: &str,
         tools: Vec<ToolMetadata>,
+    ) -> Result<LlmResponse, Box<dyn std::error::Error>> {
+        self.chat(provider_name, &[ChatMessage::user(prompt)], tools).await
+    }
+
+    // Sends a conversation, retrying transient failures and falling back along the chain when
+    // they persist. The requested provider is offered every tool, a fallback only the tools
+    // whose `llm_type` names it; the others are dropped with a warning.
+    pub async fn chat(
+        &self,
+        provider_name: &str,
+        messages: &[ChatMessage],
+        tools: Vec<ToolMetadata>,
+    ) -> Result<LlmResponse, Box<dyn std::error::Error>> {
+        let (response, _) = self.chat_metered(provider_name, messages, tools, None).await?;
+        Ok(response)
+    }
+
+    // Like `chat`, also charging usage to `task_usage` (and the meters it is within), and
+    // returning the name of the provider that answered. The call is refused when its
+    // estimated prompt would overrun a budget, and otherwise its completion is capped at what
+    // the budgets have left.
+    pub async fn chat_metered(
+        &self,
+        provider_name: &str,
+        messages: &[ChatMessage],
+        tools: Vec<ToolMetadata>,
+        task_usage: Option<&Arc<UsageMeter>>,
+    ) -> Result<(LlmResponse, String), Box<dyn std::error::Error>> {
+        let prices = self.price_table.read().await.clone();
+        let (response, answered) = self
+            .with_fallback(provider_name, |provider, answered| {
+                let tools = tools_for(&tools, provider_name, &answered.name);
+                let model = &answered.model;
+                let limit = self.completion_limit(&prices, model, messages, &tools, task_usage);
+                async move {
+                    match limit? {
+                        Some(max_tokens) => {
+                            provider.chat_limited(messages, tools, max_tokens).await
+                        }
+                        None => provider.chat(messages, tools).await,
+                    }
+                }
+            })
+            .await?;
+        self.record_usage(&answered.model, &response, task_usage).await;
+        Ok((response, answered.name))
+    }
+
+    // Asks for a JSON answer matching `schema`, re-asking with the validation errors until the
+    // answer validates or `schema.max_retries` re-asks are used up
+    pub async fn chat_json(
+        &self,
+        provider_name: &str,
+        messages: &[ChatMessage],
+        schema: &OutputSchema,
+    ) -> Result<Value, Box<dyn std::error::Error>> {
+        self.chat_json_metered(provider_name, messages, schema, None).await
+    }
+
+    // Like `chat_json`, deserializing the answer into `T` using its generated schema
+    pub async fn chat_typed<T: DeserializeOwned + JsonSchema>(
+        &self,
+        provider_name: &str,
+        messages: &[ChatMessage],
+    ) -> Result<T, Box<dyn std::error::Error>> {
+        let value = self.chat_json(provider_name, messages, &OutputSchema::for_type::<T>()).await?;
+        Ok(serde_json::from_value(value)?)
+    }
+
+    // Metered counterpart of `chat_json`; every attempt is charged. Attempts are refused when
+    // over budget but not capped, as a cut-off answer could not be parsed.
+    pub async fn chat_json_metered(
+        &self,
+        provider_name: &str,
+        messages: &[ChatMessage],
+        schema: &OutputSchema,
+        task_usage: Option<&Arc<UsageMeter>>,
     ) -> Result<Value, Box<dyn std::error::Error>> {
-        let provider = self
-            .providers
-            .read()
-            .await
-            .get(provider_name)
-            .ok_or("Provider not found")?
-            .clone();
-        provider.call(prompt, tools).await
-    }
-}
\ No newline at end of file
+        let mut messages = messages.to_vec();
+        let position = messages.iter().take_while(|m| m.role == Role::System).count();
+        messages.insert(position, ChatMessage::system(schema.instructions()));
+
+        let mut errors = vec![];
+        let mut last_output = String::new();
+        let prices = self.price_table.read().await.clone();
+        for _ in 0..=schema.max_retries {
+            let conversation = &messages;
+            let (response, answered) = self
+                .with_fallback(provider_name, |provider, answered| {
+                    let model = &answered.model;
+                    let limit =
+                        self.completion_limit(&prices, model, conversation, &[], task_usage);
+                    async move {
+                        limit?;
+                        provider.chat_json(conversation, schema).await
+                    }
+                })
+                .await?;
+            self.record_usage(&answered.model, &response, task_usage).await;
+
+            last_output = response.text().unwrap_or_default().to_string();
+            match schema.parse(&last_output) {
+                Ok(value) => return Ok(value),
+                Err(violations) => {
+                    messages.push(ChatMessage::assistant(last_output.clone(), vec![]));
+                    messages.push(ChatMessage::user(schema.repair_prompt(&violations)));
+                    errors = violations;
+                }
+            }
+        }
+        Err(Box::new(StructuredOutputError {
+            schema: schema.name.clone(),
+            attempts: schema.max_retries + 1,
+            errors,
+            last_output,
+        }))
+    }
+
+    // Streaming counterpart of `chat`; retries and fallbacks apply until the stream is opened
+    pub async fn chat_stream(
+        &self,
+        provider_name: &str,
+        messages: &[ChatMessage],
+        tools: Vec<ToolMetadata>,
+    ) -> Result<LlmStream, Box<dyn std::error::Error>> {
+        let (stream, _) = self.chat_stream_metered(provider_name, messages, tools, None).await?;
+        Ok(stream)
+    }
+
+    // Streaming counterpart of `chat_metered`; usage is recorded when the stream completes
+    pub async fn chat_stream_metered(
+        &self,
+        provider_name: &str,
+        messages: &[ChatMessage],
+        tools: Vec<ToolMetadata>,
+        task_usage: Option<&Arc<UsageMeter>>,
+    ) -> Result<(LlmStream, String), Box<dyn std::error::Error>> {
+        let prices = self.price_table.read().await.clone();
+        let (stream, answered) = self
+            .with_fallback(provider_name, |provider, answered| {
+                let tools = tools_for(&tools, provider_name, &answered.name);
+                let model = &answered.model;
+                let limit = self.completion_limit(&prices, model, messages, &tools, task_usage);
+                async move {
+                    match limit? {
+                        Some(max_tokens) => {
+                            provider.chat_stream_limited(messages, tools, max_tokens).await
+                        }
+                        None => provider.chat_stream(messages, tools).await,
+                    }
+                }
+            })
+            .await?;
+        let usage = self.usage.clone();
+        let task_usage = task_usage.cloned();
+        let model = answered.model;
+        let stream: LlmStream = Box::pin(stream.inspect(move |event| {
+            if let Ok(StreamEvent::Done(response)) = event {
+                let cost = prices.cost(&model, &response.usage());
+                usage.record(&response.usage(), cost);
+                if let Some(task_usage) = &task_usage {
+                    task_usage.record(&response.usage(), cost);
+                }
+            }
+        }));
+        Ok((stream, answered.name))
+    }
+
+    // Charges a response to the client and, if given, to the task
+    async fn record_usage(
+        &self,
+        model: &str,
+        response: &LlmResponse,
+        task_usage: Option<&Arc<UsageMeter>>,
+    ) {
+        let cost = self.price_table.read().await.cost(model, &response.usage());
+        self.usage.record(&response.usage(), cost);
+        if let Some(task_usage) = task_usage {
+            task_usage.record(&response.usage(), cost);
+        }
+    }
+
+    // Completion tokens the client's and the task's budgets leave for sending `messages` and
+    // `tools` to `model`, or `None` when unlimited. Fails when the estimated prompt alone is
+    // over a budget.
+    fn completion_limit(
+        &self,
+        prices: &PriceTable,
+        model: &str,
+        messages: &[ChatMessage],
+        tools: &[ToolMetadata],
+        task_usage: Option<&Arc<UsageMeter>>,
+    ) -> Result<Option<u32>, BudgetExceeded> {
+        let estimator = TokenEstimator::for_model(model);
+        let prompt_tokens = (estimator.estimate(messages)
+            + tools.iter().map(|t| estimator.estimate_text(&t.schema.to_string())).sum::<usize>())
+            as u64;
+        let price = prices.get(model);
+        let mut limit = self.usage.limit_call(prompt_tokens, price)?;
+        if let Some(task_usage) = task_usage {
+            if let Some(task_limit) = task_usage.limit_call(prompt_tokens, price)? {
+                limit = Some(limit.map_or(task_limit, |limit| limit.min(task_limit)));
+            }
+        }
+        Ok(limit.map(|limit| limit.min(u32::MAX as u64) as u32))
+    }
+
+    // Runs `op` against the requested provider, then each fallback until one succeeds. Only
+    // errors worth retrying move on to the next provider; a request one provider rejects is
+    // returned as is. The provider that answered is returned with the result.
+    async fn with_fallback<T, F, Fut>(
+        &self,
+        provider_name: &str,
+        op: F,
+    ) -> Result<(T, Answered), Box<dyn std::error::Error>>
+    where
+        F: Fn(Arc<dyn LLMProvider>, Answered) -> Fut,
+        Fut: std::future::Future<Output = Result<T, Box<dyn std::error::Error>>>,
+    {
+        let policy = self.retry_policy.read().await.clone();
+        let mut last_error = format!("LLM provider '{}' not found", provider_name);
+        for (i, name) in self.candidates(provider_name).await.into_iter().enumerate() {
+            let Some(provider) = self.provider(&name).await else { continue };
+            let answered = Answered {
+                model: match provider.model_name() {
+                    "" => name.clone(),
+                    model => model.to_string(),
+                },
+                name: name.clone(),
+            };
+            match self.with_retries(&name, || op(provider.clone(), answered.clone())).await {
+                Ok(value) => {
+                    if i > 0 {
+                        tracing::info!(
+                            requested = provider_name,
+                            provider = %name,
+                            "LLM fallback provider succeeded"
+                        );
+                    }
+                    return Ok((value, answered));
+                }
+                // Budgets apply whichever provider answers
+                Err(err) if err.is::<BudgetExceeded>() => return Err(err),
+                Err(err) if !policy.is_retryable(err.as_ref()) => return Err(err),
+                Err(err) => {
+                    tracing::warn!(
+                        provider = %name,
+                        error = %err,
+                        "LLM provider failed, trying next fallback"
+                    );
+                    last_error = err.to_string();
+                }
+            }
+        }
+        Err(last_error.into())
+    }
+
+    // Requested provider first, then the rest of the fallback chain
+    async fn candidates(&self, provider_name: &str) -> Vec<String> {
+        let mut candidates = vec![provider_name.to_string()];
+        for name in self.fallback_chain.read().await.iter() {
+            if !candidates.contains(name) {
+                candidates.push(name.clone());
+            }
+        }
+        candidates
+    }
+
+    // Runs `op`, retrying retryable errors with exponential backoff
+    async fn with_retries<T, F, Fut>(
+        &self,
+        provider_name: &str,
+        mut op: F,
+    ) -> Result<T, Box<dyn std::error::Error>>
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = Result<T, Box<dyn std::error::Error>>>,
+    {
+        let policy = self.retry_policy.read().await.clone();
+        let mut attempt = 0;
+        loop {
+            let delay = match op().await {
+                Ok(value) => return Ok(value),
+                Err(err) if attempt < policy.max_retries && policy.is_retryable(err.as_ref()) => {
+                    let delay = policy.delay(attempt, err.as_ref());
+                    tracing::warn!(
+                        provider = provider_name,
+                        attempt = attempt + 1,
+                        delay_ms = delay.as_millis() as u64,
+                        error = %err,
+                        "Retrying LLM call"
+                    );
+                    delay
+                }
+                Err(err) => return Err(err),
+            };
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
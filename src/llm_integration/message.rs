@@ -0,0 +1,144 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+// Role of a chat message author
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    System,
+    User,
+    Assistant,
+    Tool,
+}
+
+// Tool call requested by the model
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ToolCall {
+    pub id: String,
+    pub name: String,
+    pub arguments: Value,
+}
+
+impl ToolCall {
+    // Builds a call from arguments received as a JSON-encoded string; an empty string means
+    // no arguments. Fails on anything that is not JSON, rather than running the tool with
+    // arguments the model did not send.
+    pub fn from_json_arguments(id: &str, name: &str, arguments: &str) -> Result<Self, String> {
+        let arguments = match arguments.trim() {
+            "" => Value::Object(Default::default()),
+            json => serde_json::from_str(json).map_err(|e| {
+                format!("Tool call '{}' has unparsable arguments ({}): {}", name, e, json)
+            })?,
+        };
+        Ok(ToolCall { id: id.to_string(), name: name.to_string(), arguments })
+    }
+}
+
+// Single message in a chat conversation
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ChatMessage {
+    pub role: Role,
+    pub content: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+impl ChatMessage {
+    pub fn system(content: impl Into<String>) -> Self {
+        Self::new(Role::System, content)
+    }
+
+    pub fn user(content: impl Into<String>) -> Self {
+        Self::new(Role::User, content)
+    }
+
+    // Assistant turn, optionally carrying tool calls
+    pub fn assistant(content: impl Into<String>, tool_calls: Vec<ToolCall>) -> Self {
+        ChatMessage { tool_calls, ..Self::new(Role::Assistant, content) }
+    }
+
+    // Result of a tool call, fed back to the model
+    pub fn tool(call: &ToolCall, content: impl Into<String>) -> Self {
+        ChatMessage {
+            tool_call_id: Some(call.id.clone()),
+            name: Some(call.name.clone()),
+            ..Self::new(Role::Tool, content)
+        }
+    }
+
+    fn new(role: Role, content: impl Into<String>) -> Self {
+        ChatMessage {
+            role,
+            content: content.into(),
+            tool_calls: vec![],
+            tool_call_id: None,
+            name: None,
+        }
+    }
+}
+
+// Encodes messages in the OpenAI chat completions format
+pub fn to_openai_messages(messages: &[ChatMessage]) -> Vec<Value> {
+    messages
+        .iter()
+        .map(|m| match m.role {
+            Role::Tool => serde_json::json!({
+                "role": "tool",
+                "tool_call_id": m.tool_call_id,
+                "content": m.content,
+            }),
+            Role::Assistant if !m.tool_calls.is_empty() => serde_json::json!({
+                "role": "assistant",
+                "content": m.content,
+                "tool_calls": m.tool_calls.iter().map(|c| serde_json::json!({
+                    "id": c.id,
+                    "type": "function",
+                    "function": {"name": c.name, "arguments": c.arguments.to_string()},
+                })).collect::<Vec<_>>(),
+            }),
+            role => serde_json::json!({"role": role, "content": m.content}),
+        })
+        .collect()
+}
+
+// Encodes messages in the Gemini generateContent format.
+// System messages are returned separately as Gemini takes them as `systemInstruction`.
+pub fn to_gemini_contents(messages: &[ChatMessage]) -> (Option<Value>, Vec<Value>) {
+    let system = messages
+        .iter()
+        .filter(|m| m.role == Role::System)
+        .map(|m| serde_json::json!({"text": m.content}))
+        .collect::<Vec<_>>();
+    let contents = messages
+        .iter()
+        .filter(|m| m.role != Role::System)
+        .map(|m| match m.role {
+            Role::Tool => serde_json::json!({
+                "role": "function",
+                "parts": [{"functionResponse": {
+                    "name": m.name,
+                    "response": {"content": m.content},
+                }}],
+            }),
+            Role::Assistant => {
+                let mut parts = vec![];
+                if !m.content.is_empty() {
+                    parts.push(serde_json::json!({"text": m.content}));
+                }
+                for call in &m.tool_calls {
+                    parts.push(serde_json::json!({
+                        "functionCall": {"name": call.name, "args": call.arguments},
+                    }));
+                }
+                serde_json::json!({"role": "model", "parts": parts})
+            }
+            _ => serde_json::json!({"role": "user", "parts": [{"text": m.content}]}),
+        })
+        .collect();
+    let system = (!system.is_empty()).then(|| serde_json::json!({"parts": system}));
+    (system, contents)
+}
@@ -0,0 +1,181 @@
+use std::{collections::VecDeque, sync::Mutex};
+
+use async_trait::async_trait;
+use serde_json::Value;
+
+use crate::{
+    llm_integration::{
+        message::{ChatMessage, ToolCall},
+        response::{FinishReason, LlmResponse, Usage},
+        stream::{stream_from_response, LlmStream},
+        LLMProvider,
+    },
+    tool_system::ToolMetadata,
+};
+
+// Request received by the mock provider
+#[derive(Clone, Debug)]
+pub struct MockRequest {
+    pub messages: Vec<ChatMessage>,
+    pub tools: Vec<ToolMetadata>,
+    // Completion cap the caller asked for, if any
+    pub max_tokens: Option<u32>,
+}
+
+impl MockRequest {
+    // Content of the last message, i.e. the prompt or tool result the model is answering
+    pub fn prompt(&self) -> &str {
+        self.messages.last().map(|m| m.content.as_str()).unwrap_or_default()
+    }
+
+    // Names of the tools offered to the model
+    pub fn tool_names(&self) -> Vec<&str> {
+        self.tools.iter().map(|t| t.name.as_str()).collect()
+    }
+}
+
+// Deterministic LLM provider replaying scripted responses, for offline tests.
+//
+// Each call is answered by the first `when` rule whose pattern appears in the last message,
+// then by the next scripted response, then by the fallback. Every request is recorded.
+pub struct MockProvider {
+    rules: Vec<(String, LlmResponse)>,
+    script: Mutex<VecDeque<LlmResponse>>,
+    fallback: Option<LlmResponse>,
+    requests: Mutex<Vec<MockRequest>>,
+    next_call_id: Mutex<usize>,
+}
+
+impl Default for MockProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockProvider {
+    pub fn new() -> Self {
+        MockProvider {
+            rules: vec![],
+            script: Mutex::new(VecDeque::new()),
+            fallback: None,
+            requests: Mutex::new(vec![]),
+            next_call_id: Mutex::new(0),
+        }
+    }
+
+    // Queues a plain text answer
+    pub fn text(self, text: &str) -> Self {
+        self.respond(Self::text_response(text))
+    }
+
+    // Queues a turn calling a single tool
+    pub fn tool_call(self, name: &str, arguments: Value) -> Self {
+        self.tool_calls(vec![(name, arguments)])
+    }
+
+    // Queues a turn calling several tools at once
+    pub fn tool_calls(self, calls: Vec<(&str, Value)>) -> Self {
+        let response = self.tool_calls_response(calls);
+        self.respond(response)
+    }
+
+    // Queues an arbitrary response
+    pub fn respond(self, response: LlmResponse) -> Self {
+        self.script.lock().unwrap().push_back(response);
+        self
+    }
+
+    // Answers with `response` whenever the last message contains `pattern`
+    pub fn when(mut self, pattern: &str, response: LlmResponse) -> Self {
+        self.rules.push((pattern.to_string(), response));
+        self
+    }
+
+    // Answers with `text` once the script is exhausted
+    pub fn fallback_text(mut self, text: &str) -> Self {
+        self.fallback = Some(Self::text_response(text));
+        self
+    }
+
+    pub fn text_response(text: &str) -> LlmResponse {
+        LlmResponse::new(Some(text.to_string()), vec![], FinishReason::Stop, Usage::default())
+    }
+
+    pub fn tool_calls_response(&self, calls: Vec<(&str, Value)>) -> LlmResponse {
+        let mut next_call_id = self.next_call_id.lock().unwrap();
+        let tool_calls = calls
+            .into_iter()
+            .map(|(name, arguments)| {
+                *next_call_id += 1;
+                ToolCall {
+                    id: format!("mock_call_{}", next_call_id),
+                    name: name.to_string(),
+                    arguments,
+                }
+            })
+            .collect();
+        LlmResponse::new(None, tool_calls, FinishReason::ToolCalls, Usage::default())
+    }
+
+    // All requests received so far, in order
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    // Last message of every request received so far
+    pub fn prompts(&self) -> Vec<String> {
+        self.requests().iter().map(|r| r.prompt().to_string()).collect()
+    }
+
+    pub fn call_count(&self) -> usize {
+        self.requests.lock().unwrap().len()
+    }
+
+    fn answer(&self, request: MockRequest) -> Result<LlmResponse, Box<dyn std::error::Error>> {
+        let prompt = request.prompt().to_string();
+        self.requests.lock().unwrap().push(request);
+
+        if let Some((_, response)) = self.rules.iter().find(|(pattern, _)| prompt.contains(pattern))
+        {
+            return Ok(response.clone());
+        }
+        if let Some(response) = self.script.lock().unwrap().pop_front() {
+            return Ok(response);
+        }
+        self.fallback.clone().ok_or_else(|| "MockProvider script exhausted".into())
+    }
+}
+
+#[async_trait]
+impl LLMProvider for MockProvider {
+    fn model_name(&self) -> &str {
+        "mock"
+    }
+
+    async fn chat(
+        &self,
+        messages: &[ChatMessage],
+        tools: Vec<ToolMetadata>,
+    ) -> Result<LlmResponse, Box<dyn std::error::Error>> {
+        self.answer(MockRequest { messages: messages.to_vec(), tools, max_tokens: None })
+    }
+
+    async fn chat_limited(
+        &self,
+        messages: &[ChatMessage],
+        tools: Vec<ToolMetadata>,
+        max_tokens: u32,
+    ) -> Result<LlmResponse, Box<dyn std::error::Error>> {
+        let max_tokens = Some(max_tokens);
+        self.answer(MockRequest { messages: messages.to_vec(), tools, max_tokens })
+    }
+
+    async fn chat_stream_limited(
+        &self,
+        messages: &[ChatMessage],
+        tools: Vec<ToolMetadata>,
+        max_tokens: u32,
+    ) -> Result<LlmStream, Box<dyn std::error::Error>> {
+        Ok(stream_from_response(self.chat_limited(messages, tools, max_tokens).await?))
+    }
+}
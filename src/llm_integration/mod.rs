@@ -1,2 +1,13 @@
 pub mod prompt;
-pub mod llm_client;
\ No newline at end of file
+pub mod context;
+pub mod llm_client;
+pub mod message;
+pub mod response;
+pub mod stream;
+pub mod openai_compatible;
+pub mod mock;
+pub mod retry;
+pub mod usage;
+pub mod structured;
+
+pub use llm_client::{LLMClient, LLMProvider, NoLlmProvider, ProviderConfig};
\ No newline at end of file
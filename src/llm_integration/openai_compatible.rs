@@ -0,0 +1,382 @@
+use std::{collections::HashMap, fmt, time::Duration};
+
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+
+use crate::{
+    llm_integration::{
+        message::{to_openai_messages, ChatMessage, ToolCall},
+        response::{FinishReason, LlmResponse, Usage},
+        retry::send_checked,
+        stream::{sse_event_stream, LlmStream, StreamAccumulator},
+        structured::OutputSchema,
+        LLMProvider,
+    },
+    tool_system::ToolMetadata,
+};
+
+// Request timeout of providers not configured with one. It covers a whole streamed answer, so
+// it is generous; a hung connection still fails and can be retried or fall back.
+pub const DEFAULT_TIMEOUT_SECS: u64 = 300;
+
+// Configuration for any OpenAI-compatible chat completions endpoint
+// (OpenAI, Grok, vLLM, llama.cpp server, OpenRouter, ...). Its `Debug` output hides the API
+// key and credential headers, so configs can be logged.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct OpenAICompatibleConfig {
+    // Endpoint without a trailing slash; one given in a config file is dropped
+    #[serde(deserialize_with = "trimmed_url")]
+    pub base_url: String,
+    pub model: String,
+    #[serde(default)]
+    pub api_key: Option<String>,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    #[serde(default)]
+    pub max_tokens: Option<u32>,
+    // "auto", "none", "required" or a specific function object
+    #[serde(default)]
+    pub tool_choice: Option<Value>,
+    // Request timeout, including reading the response body; `DEFAULT_TIMEOUT_SECS` when unset
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+impl OpenAICompatibleConfig {
+    pub fn new(base_url: &str, model: &str) -> Self {
+        OpenAICompatibleConfig {
+            base_url: trim_url(base_url),
+            model: model.to_string(),
+            api_key: None,
+            headers: HashMap::new(),
+            temperature: None,
+            max_tokens: None,
+            tool_choice: None,
+            timeout_secs: None,
+        }
+    }
+
+    // Grok 3 on the xAI API
+    pub fn grok3(api_key: String) -> Self {
+        Self::new("https://api.x.ai/v1", "grok-3").with_api_key(api_key)
+    }
+
+    // GPT-4o on the OpenAI API
+    pub fn openai(api_key: String) -> Self {
+        Self::new("https://api.openai.com/v1", "gpt-4o").with_api_key(api_key)
+    }
+
+    pub fn with_api_key(mut self, api_key: String) -> Self {
+        self.api_key = Some(api_key);
+        self
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    pub fn with_tool_choice(mut self, tool_choice: Value) -> Self {
+        self.tool_choice = Some(tool_choice);
+        self
+    }
+
+    pub fn with_timeout_secs(mut self, timeout_secs: u64) -> Self {
+        self.timeout_secs = Some(timeout_secs);
+        self
+    }
+}
+
+impl fmt::Debug for OpenAICompatibleConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let headers: HashMap<&str, &str> = self
+            .headers
+            .iter()
+            .map(|(name, value)| match is_credential(name) {
+                true => (name.as_str(), REDACTED),
+                false => (name.as_str(), value.as_str()),
+            })
+            .collect();
+        f.debug_struct("OpenAICompatibleConfig")
+            .field("base_url", &self.base_url)
+            .field("model", &self.model)
+            .field("api_key", &self.api_key.as_ref().map(|_| REDACTED))
+            .field("headers", &headers)
+            .field("temperature", &self.temperature)
+            .field("max_tokens", &self.max_tokens)
+            .field("tool_choice", &self.tool_choice)
+            .field("timeout_secs", &self.timeout_secs)
+            .finish()
+    }
+}
+
+// Shown in place of secrets in `Debug` output
+pub(crate) const REDACTED: &str = "<redacted>";
+
+// Headers carrying credentials, e.g. `Authorization` or `x-api-key`
+fn is_credential(header: &str) -> bool {
+    let header = header.to_ascii_lowercase();
+    ["auth", "key", "token", "secret", "cookie"].iter().any(|word| header.contains(word))
+}
+
+fn trim_url(url: &str) -> String {
+    url.trim().trim_end_matches('/').to_string()
+}
+
+fn trimmed_url<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    Ok(trim_url(&String::deserialize(deserializer)?))
+}
+
+// Provider for OpenAI-compatible chat completions APIs
+pub struct OpenAICompatibleProvider {
+    client: Client,
+    config: OpenAICompatibleConfig,
+}
+
+impl OpenAICompatibleProvider {
+    pub fn new(config: OpenAICompatibleConfig) -> Self {
+        OpenAICompatibleProvider { client: http_client(config.timeout_secs), config }
+    }
+
+    pub fn grok3(api_key: String) -> Self {
+        Self::new(OpenAICompatibleConfig::grok3(api_key))
+    }
+
+    pub fn openai(api_key: String) -> Self {
+        Self::new(OpenAICompatibleConfig::openai(api_key))
+    }
+
+    pub fn config(&self) -> &OpenAICompatibleConfig {
+        &self.config
+    }
+
+    // Builds a chat completions request
+    fn request(
+        &self,
+        messages: &[ChatMessage],
+        tools: &[ToolMetadata],
+        response_format: Option<Value>,
+        stream: bool,
+        max_tokens: Option<u32>,
+    ) -> reqwest::RequestBuilder {
+        let mut body = serde_json::json!({
+            "model": self.config.model,
+            "messages": to_openai_messages(messages),
+        });
+        // Some local servers reject an empty tool list
+        if !tools.is_empty() {
+            body["tools"] = tools.iter().map(|t| t.schema.clone()).collect();
+            if let Some(tool_choice) = &self.config.tool_choice {
+                body["tool_choice"] = tool_choice.clone();
+            }
+        }
+        if let Some(temperature) = self.config.temperature {
+            body["temperature"] = temperature.into();
+        }
+        // The tighter of the configured cap and the caller's
+        let max_tokens = match (self.config.max_tokens, max_tokens) {
+            (Some(configured), Some(requested)) => Some(configured.min(requested)),
+            (configured, requested) => configured.or(requested),
+        };
+        if let Some(max_tokens) = max_tokens {
+            body["max_tokens"] = max_tokens.into();
+        }
+        if let Some(response_format) = response_format {
+            body["response_format"] = response_format;
+        }
+        if stream {
+            body["stream"] = Value::Bool(true);
+            body["stream_options"] = serde_json::json!({"include_usage": true});
+        }
+
+        let mut request =
+            self.client.post(format!("{}/chat/completions", self.config.base_url)).json(&body);
+        if let Some(api_key) = &self.config.api_key {
+            request = request.header("Authorization", format!("Bearer {}", api_key));
+        }
+        for (name, value) in &self.config.headers {
+            request = request.header(name, value);
+        }
+        request
+    }
+
+    async fn complete(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<LlmResponse, Box<dyn std::error::Error>> {
+        let response = send_checked(request).await?;
+        let response = response.json().await?;
+        decode_openai_response(&response)
+    }
+}
+
+#[async_trait]
+impl LLMProvider for OpenAICompatibleProvider {
+    fn model_name(&self) -> &str {
+        &self.config.model
+    }
+
+    async fn chat(
+        &self,
+        messages: &[ChatMessage],
+        tools: Vec<ToolMetadata>,
+    ) -> Result<LlmResponse, Box<dyn std::error::Error>> {
+        self.complete(self.request(messages, &tools, None, false, None)).await
+    }
+
+    async fn chat_limited(
+        &self,
+        messages: &[ChatMessage],
+        tools: Vec<ToolMetadata>,
+        max_tokens: u32,
+    ) -> Result<LlmResponse, Box<dyn std::error::Error>> {
+        self.complete(self.request(messages, &tools, None, false, Some(max_tokens))).await
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[ChatMessage],
+        tools: Vec<ToolMetadata>,
+    ) -> Result<LlmStream, Box<dyn std::error::Error>> {
+        let response = send_checked(self.request(messages, &tools, None, true, None)).await?;
+        Ok(sse_event_stream(response, StreamAccumulator::push_openai_chunk))
+    }
+
+    async fn chat_stream_limited(
+        &self,
+        messages: &[ChatMessage],
+        tools: Vec<ToolMetadata>,
+        max_tokens: u32,
+    ) -> Result<LlmStream, Box<dyn std::error::Error>> {
+        let request = self.request(messages, &tools, None, true, Some(max_tokens));
+        let response = send_checked(request).await?;
+        Ok(sse_event_stream(response, StreamAccumulator::push_openai_chunk))
+    }
+
+    async fn chat_json(
+        &self,
+        messages: &[ChatMessage],
+        schema: &OutputSchema,
+    ) -> Result<LlmResponse, Box<dyn std::error::Error>> {
+        let response_format = serde_json::json!({
+            "type": "json_schema",
+            "json_schema": {"name": schema.api_name(), "schema": schema.schema},
+        });
+        self.complete(self.request(messages, &[], Some(response_format), false, None)).await
+    }
+}
+
+// Builds an HTTP client with an optional request timeout
+pub(crate) fn http_client(timeout_secs: Option<u64>) -> Client {
+    Client::builder()
+        .connect_timeout(Duration::from_secs(10))
+        .timeout(Duration::from_secs(timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS)))
+        .build()
+        .unwrap_or_default()
+}
+
+// Decodes a chat completions response
+fn decode_openai_response(response: &Value) -> Result<LlmResponse, Box<dyn std::error::Error>> {
+    if let Some(error) = response["error"]["message"].as_str() {
+        return Err(format!("LLM error: {}", error).into());
+    }
+    let choice = response["choices"]
+        .as_array()
+        .and_then(|choices| choices.first())
+        .ok_or("LLM response has no choices")?;
+    let message = &choice["message"];
+    // Arguments arrive as a JSON-encoded string
+    let tool_calls = message["tool_calls"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|call| {
+            ToolCall::from_json_arguments(
+                call["id"].as_str().unwrap_or_default(),
+                call["function"]["name"].as_str().unwrap_or_default(),
+                call["function"]["arguments"].as_str().unwrap_or_default(),
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let finish_reason = match choice["finish_reason"].as_str() {
+        Some("stop") | None => FinishReason::Stop,
+        Some("tool_calls") | Some("function_call") => FinishReason::ToolCalls,
+        Some("length") => FinishReason::Length,
+        Some("content_filter") => FinishReason::ContentFilter,
+        Some(other) => FinishReason::Other(other.to_string()),
+    };
+    let usage = &response["usage"];
+    let usage = Usage {
+        prompt_tokens: usage["prompt_tokens"].as_u64().unwrap_or(0),
+        completion_tokens: usage["completion_tokens"].as_u64().unwrap_or(0),
+        total_tokens: usage["total_tokens"].as_u64().unwrap_or(0),
+    };
+    let text = message["content"].as_str().map(String::from);
+    Ok(LlmResponse::new(text, tool_calls, finish_reason, usage))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    // Response calling tools with (id, name, JSON-encoded arguments)
+    fn tool_calls_response(calls: &[(&str, &str, &str)]) -> Value {
+        let calls: Vec<Value> = calls
+            .iter()
+            .map(|(id, name, arguments)| {
+                json!({"id": id, "function": {"name": name, "arguments": arguments}})
+            })
+            .collect();
+        json!({
+            "choices": [{
+                "message": {"content": null, "tool_calls": calls},
+                "finish_reason": "tool_calls"
+            }],
+            "usage": {"prompt_tokens": 12, "completion_tokens": 8, "total_tokens": 20}
+        })
+    }
+
+    #[test]
+    fn test_decodes_tool_calls_and_usage() {
+        let response = tool_calls_response(&[
+            ("call_1", "get_balance", r#"{"pubkey":"abc"}"#),
+            ("call_2", "get_slot", ""),
+        ]);
+        let decoded = decode_openai_response(&response).unwrap();
+        assert_eq!(decoded.finish_reason(), &FinishReason::ToolCalls);
+        assert_eq!(decoded.usage().total_tokens, 20);
+        assert_eq!(decoded.tool_calls()[0].arguments, json!({"pubkey": "abc"}));
+        assert_eq!(decoded.tool_calls()[1].arguments, json!({}));
+    }
+
+    #[test]
+    fn test_unparsable_arguments_are_an_error() {
+        let response = tool_calls_response(&[("call_1", "swap", r#"{"amount": 1"#)]);
+        let err = decode_openai_response(&response).unwrap_err();
+        assert!(err.to_string().contains("'swap' has unparsable arguments"));
+    }
+
+    #[test]
+    fn test_missing_choices_are_an_error() {
+        assert!(decode_openai_response(&json!({"choices": []})).is_err());
+        assert!(decode_openai_response(&json!({"id": "chatcmpl-1"})).is_err());
+        let err = decode_openai_response(&json!({"error": {"message": "bad key"}})).unwrap_err();
+        assert_eq!(err.to_string(), "LLM error: bad key");
+    }
+}
@@ -1,12 +1,322 @@
-// Prompt configuration structure
+use std::{collections::HashMap, fmt, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    llm_integration::{context::TASK_MESSAGE_NAME, message::ChatMessage},
+    util::fnv1a,
+};
+
+// Name of the built-in template used by the agent controller
+pub const TASK_TEMPLATE: &str = "task";
+
+// Names of the built-in templates used by the LLM planner
+pub const PLAN_TEMPLATE: &str = "plan";
+pub const REPLAN_TEMPLATE: &str = "replan";
+
+// System section shared by the planning templates
+const PLAN_SYSTEM: &str = "{{instructions}}\n\nYou break Solana goals into steps that call the \
+    tools listed below. Give each step a unique `id`, the `tool` to call, its `arguments`, the \
+    ids of the steps it `depends_on` and its `expected_outcome`. Steps that do not depend on \
+    each other may run in parallel. To use the output of a step you depend on, write \
+    `${steps.<id>.output}`, or `${steps.<id>.output.<field>}` for a field of a JSON output.\n\n\
+    Tools:\n{{tools}}";
+
+// Error raised while loading or rendering prompts
+#[derive(Debug)]
+pub enum PromptError {
+    NotFound(String),
+    MissingVariable { template: String, variable: String },
+    Syntax { template: String, message: String },
+    Load { path: String, message: String },
+}
+
+impl fmt::Display for PromptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PromptError::NotFound(name) => write!(f, "Prompt template '{}' not found", name),
+            PromptError::MissingVariable { template, variable } => {
+                write!(f, "Prompt template '{}' uses undefined variable '{}'", template, variable)
+            }
+            PromptError::Syntax { template, message } => {
+                write!(f, "Prompt template '{}': {}", template, message)
+            }
+            PromptError::Load { path, message } => {
+                write!(f, "Failed to load prompt template '{}': {}", path, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PromptError {}
+
+// Example exchange shown to the model before the real request
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FewShotExample {
+    pub user: String,
+    pub assistant: String,
+}
+
+// Versioned prompt template with system, few-shot and user sections.
+// Sections may reference variables as `{{name}}`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PromptTemplate {
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub system: String,
+    #[serde(default)]
+    pub examples: Vec<FewShotExample>,
+    pub user: String,
+}
+
+impl PromptTemplate {
+    // Loads a template from a `.toml` or `.json` file
+    pub fn load(path: &Path) -> Result<Self, PromptError> {
+        let load_error =
+            |message: String| PromptError::Load { path: path.display().to_string(), message };
+        let source = std::fs::read_to_string(path).map_err(|e| load_error(e.to_string()))?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => toml::from_str(&source).map_err(|e| load_error(e.to_string())),
+            Some("json") => serde_json::from_str(&source).map_err(|e| load_error(e.to_string())),
+            _ => Err(load_error("expected a .toml or .json file".to_string())),
+        }
+    }
+
+    // Renders the template into chat messages
+    pub fn render(
+        &self,
+        variables: &HashMap<String, String>,
+    ) -> Result<RenderedPrompt, PromptError> {
+        let mut messages = vec![];
+        let system = interpolate(&self.name, &self.system, variables)?;
+        if !system.trim().is_empty() {
+            messages.push(ChatMessage::system(system));
+        }
+        for example in &self.examples {
+            messages.push(ChatMessage::user(interpolate(&self.name, &example.user, variables)?));
+            messages.push(ChatMessage::assistant(
+                interpolate(&self.name, &example.assistant, variables)?,
+                vec![],
+            ));
+        }
+        let mut task = ChatMessage::user(interpolate(&self.name, &self.user, variables)?);
+        task.name = Some(TASK_MESSAGE_NAME.to_string());
+        messages.push(task);
+        Ok(RenderedPrompt { name: self.name.clone(), version: self.version.clone(), messages })
+    }
+}
+
+// Rendered prompt, tagged with the template version that produced it
+#[derive(Clone, Debug)]
+pub struct RenderedPrompt {
+    pub name: String,
+    pub version: String,
+    pub messages: Vec<ChatMessage>,
+}
+
+// Replaces `{{name}}` placeholders with their values
+fn interpolate(
+    template_name: &str,
+    source: &str,
+    variables: &HashMap<String, String>,
+) -> Result<String, PromptError> {
+    let mut out = String::with_capacity(source.len());
+    let mut rest = source;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let end = rest[start..].find("}}").ok_or_else(|| PromptError::Syntax {
+            template: template_name.to_string(),
+            message: "unclosed '{{'".to_string(),
+        })?;
+        let variable = rest[start + 2..start + end].trim();
+        let value = variables.get(variable).ok_or_else(|| PromptError::MissingVariable {
+            template: template_name.to_string(),
+            variable: variable.to_string(),
+        })?;
+        out.push_str(value);
+        rest = &rest[start + end + 2..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+// Prompt configuration: named, versioned templates, global variables and A/B variants
 pub struct PromptConfig {
-    template: String,
+    templates: HashMap<String, Vec<PromptTemplate>>,
+    // Weighted versions to split traffic between, per template name
+    variants: HashMap<String, Vec<(String, u32)>>,
+    variables: HashMap<String, String>,
+}
+
+impl Default for PromptConfig {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl PromptConfig {
     pub fn new() -> Self {
-        PromptConfig {
-            template: String::new(),
+        let mut config = PromptConfig {
+            templates: HashMap::new(),
+            variants: HashMap::new(),
+            variables: HashMap::new(),
+        };
+        config.register(PromptTemplate {
+            name: TASK_TEMPLATE.to_string(),
+            version: "v1".to_string(),
+            system: "{{instructions}}\n\nWallet: {{wallet_address}}\nCluster: {{cluster}}"
+                .to_string(),
+            examples: vec![],
+            user: "Execute task: {{task}}\nInput: {{input}}\nRelevant memory:\n{{memory}}"
+                .to_string(),
+        });
+        config.register(PromptTemplate {
+            name: PLAN_TEMPLATE.to_string(),
+            version: "v1".to_string(),
+            system: PLAN_SYSTEM.to_string(),
+            examples: vec![],
+            user: "Goal: {{goal}}".to_string(),
+        });
+        config.register(PromptTemplate {
+            name: REPLAN_TEMPLATE.to_string(),
+            version: "v1".to_string(),
+            system: PLAN_SYSTEM.to_string(),
+            examples: vec![],
+            user: "Goal: {{goal}}\n\nThe plan failed partway.\n\nCompleted steps and their \
+                   outputs:\n{{completed}}\n\nFailed steps:\n{{failed}}\n\nSteps not yet run:\n\
+                   {{remaining}}\n\nPropose new steps replacing the failed and remaining ones. \
+                   They may depend on completed steps and use their outputs, but must not reuse \
+                   their ids. Explain in `reason` what went wrong and what you changed."
+                .to_string(),
+        });
+        config.set_variable("instructions", "");
+        config.set_variable("wallet_address", "unknown");
+        config.set_variable("cluster", "unknown");
+        config
+    }
+
+    // Adds a template version; the latest registered version is used unless variants are set
+    pub fn register(&mut self, template: PromptTemplate) {
+        let versions = self.templates.entry(template.name.clone()).or_default();
+        versions.retain(|t| t.version != template.version);
+        versions.push(template);
+    }
+
+    // Loads every `.toml` and `.json` template in a directory
+    pub fn load_dir(&mut self, dir: &Path) -> Result<(), PromptError> {
+        let entries = std::fs::read_dir(dir).map_err(|e| PromptError::Load {
+            path: dir.display().to_string(),
+            message: e.to_string(),
+        })?;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if matches!(path.extension().and_then(|e| e.to_str()), Some("toml") | Some("json")) {
+                self.register(PromptTemplate::load(&path)?);
+            }
+        }
+        Ok(())
+    }
+
+    // Sets a variable available to every template, e.g. `instructions` or `cluster`
+    pub fn set_variable(&mut self, name: &str, value: &str) {
+        self.variables.insert(name.to_string(), value.to_string());
+    }
+
+    // Splits traffic for `name` between versions by weight, e.g. `[("v1", 90), ("v2", 10)]`
+    pub fn set_variants(&mut self, name: &str, variants: Vec<(&str, u32)>) {
+        let variants = variants.into_iter().map(|(v, w)| (v.to_string(), w)).collect();
+        self.variants.insert(name.to_string(), variants);
+    }
+
+    // Picks the template version for `name`. The same `key` (e.g. a session id) always gets
+    // the same A/B variant.
+    pub fn select(&self, name: &str, key: &str) -> Result<&PromptTemplate, PromptError> {
+        let versions =
+            self.templates.get(name).ok_or_else(|| PromptError::NotFound(name.to_string()))?;
+        let find = |version: &str| versions.iter().find(|t| t.version == version);
+        if let Some(variants) = self.variants.get(name) {
+            let total: u32 = variants.iter().map(|(_, w)| w).sum();
+            if total > 0 {
+                let mut point = (fnv1a(key.as_bytes()) % total as u64) as u32;
+                for (version, weight) in variants {
+                    if point < *weight {
+                        return find(version).ok_or_else(|| {
+                            PromptError::NotFound(format!("{}@{}", name, version))
+                        });
+                    }
+                    point -= weight;
+                }
+            }
+        }
+        versions.last().ok_or_else(|| PromptError::NotFound(name.to_string()))
+    }
+
+    // Renders `name` with the global variables overlaid by `variables`
+    pub fn render(
+        &self,
+        name: &str,
+        key: &str,
+        variables: &HashMap<String, String>,
+    ) -> Result<RenderedPrompt, PromptError> {
+        let mut all = self.variables.clone();
+        all.extend(variables.iter().map(|(k, v)| (k.clone(), v.clone())));
+        self.select(name, key)?.render(&all)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_interpolate_variables() {
+        let variables = vars(&[("amount", "1"), ("to", "abc")]);
+        let out = interpolate("t", "Send {{ amount }} SOL to {{to}}.", &variables);
+        assert_eq!(out.unwrap(), "Send 1 SOL to abc.");
+    }
+
+    #[test]
+    fn test_interpolate_missing_variable() {
+        let err = interpolate("t", "Hello {{name}}", &HashMap::new()).unwrap_err();
+        assert!(matches!(err, PromptError::MissingVariable { variable, .. } if variable == "name"));
+    }
+
+    #[test]
+    fn test_render_sections() {
+        let template = PromptTemplate {
+            name: "swap".to_string(),
+            version: "v2".to_string(),
+            system: "{{instructions}}".to_string(),
+            examples: vec![FewShotExample { user: "q".to_string(), assistant: "a".to_string() }],
+            user: "{{task}}".to_string(),
+        };
+        let variables = vars(&[("instructions", "Be safe"), ("task", "swap")]);
+        let rendered = template.render(&variables).unwrap();
+        assert_eq!(rendered.version, "v2");
+        assert_eq!(rendered.messages.len(), 4);
+        assert_eq!(rendered.messages[0], ChatMessage::system("Be safe"));
+        let mut task = ChatMessage::user("swap");
+        task.name = Some(TASK_MESSAGE_NAME.to_string());
+        assert_eq!(rendered.messages[3], task);
+        assert_eq!(rendered.messages[1].name, None);
+    }
+
+    #[test]
+    fn test_variant_selection_is_stable() {
+        let mut config = PromptConfig::new();
+        let mut v2 = config.select(TASK_TEMPLATE, "").unwrap().clone();
+        v2.version = "v2".to_string();
+        config.register(v2);
+        config.set_variants(TASK_TEMPLATE, vec![("v1", 50), ("v2", 50)]);
+
+        let first = config.select(TASK_TEMPLATE, "session-42").unwrap().version.clone();
+        for _ in 0..10 {
+            assert_eq!(config.select(TASK_TEMPLATE, "session-42").unwrap().version, first);
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+
+use crate::llm_integration::message::ToolCall;
+
+// Why the model stopped generating
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FinishReason {
+    Stop,
+    ToolCalls,
+    Length,
+    ContentFilter,
+    Other(String),
+}
+
+// Token usage reported by the provider
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Usage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub total_tokens: u64,
+}
+
+// Provider-independent LLM response
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LlmResponse {
+    // Plain text answer
+    Text { text: String, finish_reason: FinishReason, usage: Usage },
+    // One or more tool calls, optionally with accompanying text
+    ToolCalls {
+        text: Option<String>,
+        tool_calls: Vec<ToolCall>,
+        finish_reason: FinishReason,
+        usage: Usage,
+    },
+}
+
+impl LlmResponse {
+    // Builds a response from decoded parts, choosing the variant by whether tools were called
+    pub fn new(
+        text: Option<String>,
+        tool_calls: Vec<ToolCall>,
+        finish_reason: FinishReason,
+        usage: Usage,
+    ) -> Self {
+        if tool_calls.is_empty() {
+            LlmResponse::Text { text: text.unwrap_or_default(), finish_reason, usage }
+        } else {
+            LlmResponse::ToolCalls { text, tool_calls, finish_reason, usage }
+        }
+    }
+
+    pub fn text(&self) -> Option<&str> {
+        match self {
+            LlmResponse::Text { text, .. } => Some(text),
+            LlmResponse::ToolCalls { text, .. } => text.as_deref(),
+        }
+    }
+
+    pub fn tool_calls(&self) -> &[ToolCall] {
+        match self {
+            LlmResponse::Text { .. } => &[],
+            LlmResponse::ToolCalls { tool_calls, .. } => tool_calls,
+        }
+    }
+
+    pub fn finish_reason(&self) -> &FinishReason {
+        match self {
+            LlmResponse::Text { finish_reason, .. }
+            | LlmResponse::ToolCalls { finish_reason, .. } => finish_reason,
+        }
+    }
+
+    pub fn usage(&self) -> Usage {
+        match self {
+            LlmResponse::Text { usage, .. } | LlmResponse::ToolCalls { usage, .. } => *usage,
+        }
+    }
+}
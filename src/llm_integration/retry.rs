@@ -0,0 +1,122 @@
+use std::{fmt, time::Duration};
+
+use reqwest::header::RETRY_AFTER;
+use serde::{Deserialize, Serialize};
+
+// Non-success HTTP response from an LLM API
+#[derive(Debug)]
+pub struct HttpStatusError {
+    pub status: u16,
+    pub retry_after: Option<Duration>,
+    pub body: String,
+}
+
+impl fmt::Display for HttpStatusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "LLM API returned HTTP {}: {}", self.status, self.body)
+    }
+}
+
+impl std::error::Error for HttpStatusError {}
+
+// Sends a request, turning non-success statuses into `HttpStatusError`
+pub async fn send_checked(
+    request: reqwest::RequestBuilder,
+) -> Result<reqwest::Response, Box<dyn std::error::Error>> {
+    let response = request.send().await?;
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    // Only the delta-seconds form of Retry-After is honored
+    let retry_after = response
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .map(Duration::from_secs);
+    let body = response.text().await.unwrap_or_default();
+    Err(Box::new(HttpStatusError { status: status.as_u16(), retry_after, body }))
+}
+
+// Exponential backoff policy for transient LLM API failures
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy { max_retries: 3, initial_backoff_ms: 500, max_backoff_ms: 30_000 }
+    }
+}
+
+impl RetryPolicy {
+    // Never retries
+    pub fn none() -> Self {
+        RetryPolicy { max_retries: 0, ..Self::default() }
+    }
+
+    // Rate limits, server errors, timeouts and connection failures are worth retrying
+    pub fn is_retryable(&self, err: &(dyn std::error::Error + 'static)) -> bool {
+        if let Some(err) = err.downcast_ref::<HttpStatusError>() {
+            return err.status == 429 || err.status >= 500;
+        }
+        if let Some(err) = err.downcast_ref::<reqwest::Error>() {
+            return err.is_timeout() || err.is_connect();
+        }
+        false
+    }
+
+    // Delay before retry number `attempt` (0-based), preferring the server's Retry-After
+    pub fn delay(&self, attempt: u32, err: &(dyn std::error::Error + 'static)) -> Duration {
+        let max = Duration::from_millis(self.max_backoff_ms);
+        if let Some(retry_after) = err.downcast_ref::<HttpStatusError>().and_then(|e| e.retry_after)
+        {
+            return retry_after.min(max);
+        }
+        let backoff = self.initial_backoff_ms.saturating_mul(1u64 << attempt.min(20));
+        Duration::from_millis(backoff).min(max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(status: u16, retry_after: Option<Duration>) -> HttpStatusError {
+        HttpStatusError { status, retry_after, body: String::new() }
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_the_maximum() {
+        let policy =
+            RetryPolicy { max_retries: 10, initial_backoff_ms: 500, max_backoff_ms: 3_000 };
+        let err = status(503, None);
+        let delays: Vec<u64> =
+            (0..5).map(|attempt| policy.delay(attempt, &err).as_millis() as u64).collect();
+        assert_eq!(delays, vec![500, 1_000, 2_000, 3_000, 3_000]);
+    }
+
+    #[test]
+    fn test_retry_after_is_preferred_but_capped() {
+        let policy = RetryPolicy { max_backoff_ms: 10_000, ..RetryPolicy::default() };
+        let err = status(429, Some(Duration::from_secs(2)));
+        assert_eq!(policy.delay(3, &err), Duration::from_secs(2));
+        let err = status(429, Some(Duration::from_secs(60)));
+        assert_eq!(policy.delay(0, &err), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_only_transient_failures_are_retryable() {
+        let policy = RetryPolicy::default();
+        assert!(policy.is_retryable(&status(429, None)));
+        assert!(policy.is_retryable(&status(502, None)));
+        assert!(!policy.is_retryable(&status(400, None)));
+        assert!(!policy.is_retryable(&status(401, None)));
+        let err: Box<dyn std::error::Error> = "LLM error: invalid request".into();
+        assert!(!policy.is_retryable(err.as_ref()));
+    }
+}
@@ -0,0 +1,368 @@
+use std::{collections::VecDeque, pin::Pin};
+
+use futures::{stream, Stream, StreamExt};
+use serde_json::Value;
+
+use crate::llm_integration::{
+    message::ToolCall,
+    response::{FinishReason, LlmResponse, Usage},
+};
+
+pub type StreamError = Box<dyn std::error::Error + Send + Sync>;
+
+// Boxed stream of incremental LLM output
+pub type LlmStream = Pin<Box<dyn Stream<Item = Result<StreamEvent, StreamError>> + Send>>;
+
+// Incremental piece of a streamed LLM response
+#[derive(Clone, Debug, PartialEq)]
+pub enum StreamEvent {
+    // New text appended to the answer
+    TextDelta(String),
+    // New fragment of a tool call; `id` and `name` are only set on the first fragment
+    ToolCallDelta {
+        index: usize,
+        id: Option<String>,
+        name: Option<String>,
+        arguments_delta: String,
+    },
+    // Stream finished; carries the fully assembled response
+    Done(LlmResponse),
+}
+
+// Wraps an already complete response as a stream, for providers without streaming support
+pub fn stream_from_response(response: LlmResponse) -> LlmStream {
+    let mut events = vec![];
+    if let Some(text) = response.text().filter(|t| !t.is_empty()) {
+        events.push(Ok(StreamEvent::TextDelta(text.to_string())));
+    }
+    for (index, call) in response.tool_calls().iter().enumerate() {
+        events.push(Ok(StreamEvent::ToolCallDelta {
+            index,
+            id: Some(call.id.clone()),
+            name: Some(call.name.clone()),
+            arguments_delta: call.arguments.to_string(),
+        }));
+    }
+    events.push(Ok(StreamEvent::Done(response)));
+    Box::pin(stream::iter(events))
+}
+
+// Collects stream deltas into a complete response
+#[derive(Default)]
+pub struct StreamAccumulator {
+    text: String,
+    // (id, name, raw JSON arguments) per tool call index
+    tool_calls: Vec<(String, String, String)>,
+    finish_reason: Option<FinishReason>,
+    usage: Usage,
+}
+
+impl StreamAccumulator {
+    // Applies an OpenAI-style `chat.completion.chunk`
+    pub fn push_openai_chunk(&mut self, chunk: &Value) -> Vec<StreamEvent> {
+        let mut events = vec![];
+        if chunk["usage"].is_object() {
+            self.usage = Usage {
+                prompt_tokens: chunk["usage"]["prompt_tokens"].as_u64().unwrap_or(0),
+                completion_tokens: chunk["usage"]["completion_tokens"].as_u64().unwrap_or(0),
+                total_tokens: chunk["usage"]["total_tokens"].as_u64().unwrap_or(0),
+            };
+        }
+        let choice = &chunk["choices"][0];
+        if let Some(text) = choice["delta"]["content"].as_str().filter(|t| !t.is_empty()) {
+            self.text.push_str(text);
+            events.push(StreamEvent::TextDelta(text.to_string()));
+        }
+        for call in choice["delta"]["tool_calls"].as_array().into_iter().flatten() {
+            let index = call["index"].as_u64().unwrap_or(0) as usize;
+            let id = call["id"].as_str().map(String::from);
+            let name = call["function"]["name"].as_str().map(String::from);
+            let arguments_delta = call["function"]["arguments"].as_str().unwrap_or_default();
+            self.push_tool_call_delta(index, id.as_deref(), name.as_deref(), arguments_delta);
+            events.push(StreamEvent::ToolCallDelta {
+                index,
+                id,
+                name,
+                arguments_delta: arguments_delta.to_string(),
+            });
+        }
+        self.finish_reason = match choice["finish_reason"].as_str() {
+            Some("stop") => Some(FinishReason::Stop),
+            Some("tool_calls") => Some(FinishReason::ToolCalls),
+            Some("length") => Some(FinishReason::Length),
+            Some("content_filter") => Some(FinishReason::ContentFilter),
+            Some(other) => Some(FinishReason::Other(other.to_string())),
+            None => self.finish_reason.take(),
+        };
+        events
+    }
+
+    // Applies a Gemini `streamGenerateContent` chunk; function calls arrive whole
+    pub fn push_gemini_chunk(&mut self, chunk: &Value) -> Vec<StreamEvent> {
+        let mut events = vec![];
+        let candidate = &chunk["candidates"][0];
+        for part in candidate["content"]["parts"].as_array().into_iter().flatten() {
+            if let Some(text) = part["text"].as_str() {
+                self.text.push_str(text);
+                events.push(StreamEvent::TextDelta(text.to_string()));
+            } else if part["functionCall"].is_object() {
+                let index = self.tool_calls.len();
+                let id = format!("call_{}", index);
+                let name = part["functionCall"]["name"].as_str().unwrap_or_default();
+                let arguments = part["functionCall"]["args"].to_string();
+                self.push_tool_call_delta(index, Some(&id), Some(name), &arguments);
+                events.push(StreamEvent::ToolCallDelta {
+                    index,
+                    id: Some(id),
+                    name: Some(name.to_string()),
+                    arguments_delta: arguments,
+                });
+            }
+        }
+        if let Some(reason) = candidate["finishReason"].as_str() {
+            self.finish_reason = Some(match reason {
+                "STOP" => FinishReason::Stop,
+                "MAX_TOKENS" => FinishReason::Length,
+                "SAFETY" | "RECITATION" => FinishReason::ContentFilter,
+                other => FinishReason::Other(other.to_string()),
+            });
+        }
+        if chunk["usageMetadata"].is_object() {
+            let usage = &chunk["usageMetadata"];
+            self.usage = Usage {
+                prompt_tokens: usage["promptTokenCount"].as_u64().unwrap_or(0),
+                completion_tokens: usage["candidatesTokenCount"].as_u64().unwrap_or(0),
+                total_tokens: usage["totalTokenCount"].as_u64().unwrap_or(0),
+            };
+        }
+        events
+    }
+
+    fn push_tool_call_delta(
+        &mut self,
+        index: usize,
+        id: Option<&str>,
+        name: Option<&str>,
+        arguments_delta: &str,
+    ) {
+        if self.tool_calls.len() <= index {
+            self.tool_calls.resize(index + 1, Default::default());
+        }
+        let (call_id, call_name, arguments) = &mut self.tool_calls[index];
+        if let Some(id) = id {
+            *call_id = id.to_string();
+        }
+        if let Some(name) = name {
+            call_name.push_str(name);
+        }
+        arguments.push_str(arguments_delta);
+    }
+
+    // Assembles the final response; fails when a tool call's arguments are not valid JSON
+    pub fn finish(self) -> Result<LlmResponse, StreamError> {
+        let tool_calls = self
+            .tool_calls
+            .iter()
+            .map(|(id, name, arguments)| ToolCall::from_json_arguments(id, name, arguments))
+            .collect::<Result<Vec<_>, _>>()?;
+        let finish_reason = match self.finish_reason {
+            _ if !tool_calls.is_empty() => FinishReason::ToolCalls,
+            Some(reason) => reason,
+            None => FinishReason::Stop,
+        };
+        let text = (!self.text.is_empty()).then_some(self.text);
+        Ok(LlmResponse::new(text, tool_calls, finish_reason, self.usage))
+    }
+}
+
+// Splits a server-sent events body into its `data:` payloads as the body arrives. Chunks
+// may end anywhere, even inside a line or a UTF-8 character, so bytes are buffered until
+// their line is complete.
+#[derive(Default)]
+struct SseParser {
+    buffer: Vec<u8>,
+}
+
+impl SseParser {
+    // Payloads of the `data:` lines completed by `bytes`
+    fn push(&mut self, bytes: &[u8]) -> Vec<String> {
+        self.buffer.extend_from_slice(bytes);
+        let mut payloads = vec![];
+        while let Some(end) = self.buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line[..end]);
+            if let Some(data) = line.trim_end_matches('\r').strip_prefix("data:") {
+                payloads.push(data.trim().to_string());
+            }
+        }
+        payloads
+    }
+}
+
+// `data:` payloads of an SSE response, in order
+fn sse_data(response: reqwest::Response) -> impl Stream<Item = Result<String, StreamError>> + Send {
+    let state = (response.bytes_stream().boxed(), SseParser::default(), VecDeque::new());
+    stream::unfold(state, |(mut body, mut parser, mut pending)| async move {
+        loop {
+            if let Some(data) = pending.pop_front() {
+                return Some((Ok(data), (body, parser, pending)));
+            }
+            match body.next().await {
+                Some(Ok(bytes)) => pending.extend(parser.push(&bytes)),
+                Some(Err(err)) => return Some((Err(err.into()), (body, parser, pending))),
+                None => return None,
+            }
+        }
+    })
+}
+
+// Turns an SSE response into stream events using a provider-specific chunk decoder
+pub fn sse_event_stream(
+    response: reqwest::Response,
+    decode: fn(&mut StreamAccumulator, &Value) -> Vec<StreamEvent>,
+) -> LlmStream {
+    event_stream(sse_data(response), decode)
+}
+
+// Decodes `data:` payloads into stream events, up to `[DONE]` or the end of the body
+fn event_stream(
+    data: impl Stream<Item = Result<String, StreamError>> + Send + 'static,
+    decode: fn(&mut StreamAccumulator, &Value) -> Vec<StreamEvent>,
+) -> LlmStream {
+    let state = (data.boxed(), StreamAccumulator::default(), VecDeque::new(), false);
+    Box::pin(stream::unfold(state, move |(mut data, mut acc, mut pending, mut done)| async move {
+        loop {
+            if let Some(event) = pending.pop_front() {
+                return Some((Ok(event), (data, acc, pending, done)));
+            }
+            if done {
+                return None;
+            }
+            match data.next().await {
+                Some(Ok(payload)) if payload != "[DONE]" => {
+                    match serde_json::from_str::<Value>(&payload) {
+                        Ok(chunk) => pending.extend(decode(&mut acc, &chunk)),
+                        Err(err) => return Some((Err(err.into()), (data, acc, pending, true))),
+                    }
+                }
+                Some(Err(err)) => return Some((Err(err), (data, acc, pending, true))),
+                // `[DONE]` or end of body
+                _ => match std::mem::take(&mut acc).finish() {
+                    Ok(response) => {
+                        pending.push_back(StreamEvent::Done(response));
+                        done = true;
+                    }
+                    Err(err) => return Some((Err(err), (data, acc, pending, true))),
+                },
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    // OpenAI-style chunk carrying a text delta
+    fn text_chunk(text: &str) -> Value {
+        json!({"choices": [{"delta": {"content": text}}]})
+    }
+
+    // OpenAI-style chunk carrying a fragment of tool call `index`
+    fn tool_call_chunk(index: usize, name: Option<&str>, arguments: &str) -> Value {
+        let mut call = json!({"index": index, "function": {"arguments": arguments}});
+        if let Some(name) = name {
+            call["id"] = json!(format!("call_{}", index));
+            call["function"]["name"] = json!(name);
+        }
+        json!({"choices": [{"delta": {"tool_calls": [call]}}]})
+    }
+
+    #[test]
+    fn test_sse_lines_split_across_chunks() {
+        let mut parser = SseParser::default();
+        assert!(parser.push(b"data: {\"a\"").is_empty());
+        assert_eq!(parser.push(b":1}\r\n\n: keep-alive\ndata: [DO"), vec![r#"{"a":1}"#]);
+        assert_eq!(parser.push(b"NE]\n"), vec!["[DONE]"]);
+
+        // A multi-byte character split between chunks survives
+        let line = "data: ◎ SOL\n".as_bytes();
+        assert!(parser.push(&line[..8]).is_empty());
+        assert_eq!(parser.push(&line[8..]), vec!["◎ SOL"]);
+    }
+
+    #[test]
+    fn test_interleaved_tool_call_deltas() {
+        let mut acc = StreamAccumulator::default();
+        acc.push_openai_chunk(&text_chunk("Checking "));
+        acc.push_openai_chunk(&tool_call_chunk(0, Some("get_balance"), "{\"pub"));
+        acc.push_openai_chunk(&tool_call_chunk(1, Some("get_price"), "{\"token\":"));
+        acc.push_openai_chunk(&tool_call_chunk(0, None, "key\":\"abc\"}"));
+        let events = acc.push_openai_chunk(&tool_call_chunk(1, None, "\"SOL\"}"));
+        assert_eq!(
+            events,
+            vec![StreamEvent::ToolCallDelta {
+                index: 1,
+                id: None,
+                name: None,
+                arguments_delta: "\"SOL\"}".to_string(),
+            }]
+        );
+        acc.push_openai_chunk(&json!({
+            "choices": [{"delta": {}, "finish_reason": "tool_calls"}],
+            "usage": {"prompt_tokens": 10, "completion_tokens": 5, "total_tokens": 15}
+        }));
+
+        let response = acc.finish().unwrap();
+        assert_eq!(response.text(), Some("Checking "));
+        assert_eq!(response.finish_reason(), &FinishReason::ToolCalls);
+        assert_eq!(response.usage().total_tokens, 15);
+        let calls = response.tool_calls();
+        assert_eq!((calls[0].id.as_str(), calls[0].name.as_str()), ("call_0", "get_balance"));
+        assert_eq!(calls[0].arguments, json!({"pubkey": "abc"}));
+        assert_eq!((calls[1].id.as_str(), calls[1].name.as_str()), ("call_1", "get_price"));
+        assert_eq!(calls[1].arguments, json!({"token": "SOL"}));
+    }
+
+    #[tokio::test]
+    async fn test_stream_ends_at_done() {
+        let payloads = vec![
+            text_chunk("Hello").to_string(),
+            text_chunk(", world").to_string(),
+            "[DONE]".to_string(),
+            text_chunk("ignored").to_string(),
+        ];
+        let data = stream::iter(payloads.into_iter().map(Ok));
+        let events: Vec<StreamEvent> = event_stream(data, StreamAccumulator::push_openai_chunk)
+            .map(|event| event.unwrap())
+            .collect()
+            .await;
+
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[1], StreamEvent::TextDelta(", world".to_string()));
+        match &events[2] {
+            StreamEvent::Done(response) => assert_eq!(response.text(), Some("Hello, world")),
+            other => panic!("expected the final response, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stream_without_done_still_finishes() {
+        let data = stream::iter(vec![Ok(text_chunk("Partial").to_string())]);
+        let events: Vec<_> =
+            event_stream(data, StreamAccumulator::push_openai_chunk).collect().await;
+        assert!(matches!(events.last(), Some(Ok(StreamEvent::Done(_)))));
+    }
+
+    #[test]
+    fn test_truncated_tool_call_arguments_fail_the_stream() {
+        let mut acc = StreamAccumulator::default();
+        acc.push_openai_chunk(&json!({"choices": [{"delta": {"tool_calls": [
+            {"index": 0, "id": "call_1", "function": {"name": "swap", "arguments": "{\"amount\""}}
+        ]}, "finish_reason": "length"}]}));
+        let err = acc.finish().unwrap_err();
+        assert!(err.to_string().contains("'swap' has unparsable arguments"));
+    }
+}
@@ -0,0 +1,2 @@
+// Structured output is shared with solagent-core through the solagent-structured crate
+pub use solagent_structured::{extract_json, json_candidates, OutputSchema, StructuredOutputError};
@@ -0,0 +1,227 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::llm_integration::response::Usage;
+
+// Price of a model in USD per million tokens
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct ModelPrice {
+    pub prompt_per_million: f64,
+    pub completion_per_million: f64,
+}
+
+// Per-model price table; models without an entry are treated as free
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct PriceTable {
+    pub prices: HashMap<String, ModelPrice>,
+}
+
+impl PriceTable {
+    pub fn set(&mut self, model: &str, price: ModelPrice) {
+        self.prices.insert(model.to_string(), price);
+    }
+
+    pub fn get(&self, model: &str) -> Option<ModelPrice> {
+        self.prices.get(model).copied()
+    }
+
+    // Cost in USD of `usage` on `model`
+    pub fn cost(&self, model: &str, usage: &Usage) -> f64 {
+        self.prices.get(model).map_or(0.0, |price| {
+            (usage.prompt_tokens as f64 * price.prompt_per_million
+                + usage.completion_tokens as f64 * price.completion_per_million)
+                / 1_000_000.0
+        })
+    }
+}
+
+// Accumulated usage and cost
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct UsageTotals {
+    pub calls: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub total_tokens: u64,
+    pub cost_usd: f64,
+}
+
+impl UsageTotals {
+    pub fn add(&mut self, usage: &Usage, cost_usd: f64) {
+        self.calls += 1;
+        self.prompt_tokens += usage.prompt_tokens;
+        self.completion_tokens += usage.completion_tokens;
+        self.total_tokens += usage.total_tokens;
+        self.cost_usd += cost_usd;
+    }
+}
+
+// Hard limits on accumulated usage
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct UsageBudget {
+    pub max_total_tokens: Option<u64>,
+    pub max_cost_usd: Option<f64>,
+}
+
+// Returned when a call is refused because a budget is spent
+#[derive(Debug, Clone, PartialEq)]
+pub enum BudgetExceeded {
+    Tokens { used: u64, limit: u64 },
+    Cost { used_usd: f64, limit_usd: f64 },
+}
+
+impl fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BudgetExceeded::Tokens { used, limit } => {
+                write!(f, "Token budget exceeded: {} of {} tokens used", used, limit)
+            }
+            BudgetExceeded::Cost { used_usd, limit_usd } => {
+                write!(f, "Cost budget exceeded: ${:.4} of ${:.4} spent", used_usd, limit_usd)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BudgetExceeded {}
+
+// Thread-safe usage accumulator with an optional budget, kept per task or per session. A
+// meter created `within` another also charges and is limited by that one, e.g. a task within
+// its session.
+pub struct UsageMeter {
+    totals: Mutex<UsageTotals>,
+    budget: Mutex<UsageBudget>,
+    parent: Option<Arc<UsageMeter>>,
+}
+
+impl UsageMeter {
+    pub fn new(budget: UsageBudget) -> Self {
+        UsageMeter {
+            totals: Mutex::new(UsageTotals::default()),
+            budget: Mutex::new(budget),
+            parent: None,
+        }
+    }
+
+    pub fn within(mut self, parent: Arc<UsageMeter>) -> Self {
+        self.parent = Some(parent);
+        self
+    }
+
+    pub fn set_budget(&self, budget: UsageBudget) {
+        *self.budget.lock().unwrap() = budget;
+    }
+
+    pub fn record(&self, usage: &Usage, cost_usd: f64) {
+        self.totals.lock().unwrap().add(usage, cost_usd);
+        if let Some(parent) = &self.parent {
+            parent.record(usage, cost_usd);
+        }
+    }
+
+    pub fn totals(&self) -> UsageTotals {
+        self.totals.lock().unwrap().clone()
+    }
+
+    // Fails once the budget is spent, so the next call can be refused before it is made
+    pub fn check(&self) -> Result<(), BudgetExceeded> {
+        self.limit_call(0, None).map(|_| ())
+    }
+
+    // Completion tokens left for a call sending about `prompt_tokens` at `price`, or `None`
+    // when nothing limits them. Fails when the prompt alone would overrun the budget.
+    pub fn limit_call(
+        &self,
+        prompt_tokens: u64,
+        price: Option<ModelPrice>,
+    ) -> Result<Option<u64>, BudgetExceeded> {
+        let totals = self.totals();
+        let budget = self.budget.lock().unwrap().clone();
+        let mut limit = None;
+        if let Some(max_total_tokens) = budget.max_total_tokens {
+            let used = totals.total_tokens + prompt_tokens;
+            if used >= max_total_tokens {
+                return Err(BudgetExceeded::Tokens { used, limit: max_total_tokens });
+            }
+            limit = Some(max_total_tokens - used);
+        }
+        if let Some(limit_usd) = budget.max_cost_usd {
+            let price = price.unwrap_or_default();
+            let used_usd =
+                totals.cost_usd + prompt_tokens as f64 * price.prompt_per_million / 1_000_000.0;
+            let affordable = (price.completion_per_million > 0.0).then(|| {
+                ((limit_usd - used_usd) * 1_000_000.0 / price.completion_per_million) as u64
+            });
+            if used_usd >= limit_usd || affordable == Some(0) {
+                return Err(BudgetExceeded::Cost { used_usd, limit_usd });
+            }
+            limit = tighter(limit, affordable);
+        }
+        match &self.parent {
+            Some(parent) => Ok(tighter(limit, parent.limit_call(prompt_tokens, price)?)),
+            None => Ok(limit),
+        }
+    }
+}
+
+fn tighter(a: Option<u64>, b: Option<u64>) -> Option<u64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(prompt_tokens: u64, completion_tokens: u64) -> Usage {
+        Usage { prompt_tokens, completion_tokens, total_tokens: prompt_tokens + completion_tokens }
+    }
+
+    fn max_tokens(max_total_tokens: u64) -> UsageBudget {
+        UsageBudget { max_total_tokens: Some(max_total_tokens), max_cost_usd: None }
+    }
+
+    #[test]
+    fn test_limit_call_leaves_the_rest_of_the_budget_for_the_completion() {
+        let meter = UsageMeter::new(max_tokens(1_000));
+        meter.record(&usage(300, 100), 0.0);
+
+        assert_eq!(meter.limit_call(200, None), Ok(Some(400)));
+        assert_eq!(
+            meter.limit_call(600, None),
+            Err(BudgetExceeded::Tokens { used: 1_000, limit: 1_000 })
+        );
+    }
+
+    #[test]
+    fn test_limit_call_prices_the_prompt_and_completion() {
+        let budget = UsageBudget { max_total_tokens: None, max_cost_usd: Some(1.0) };
+        let meter = UsageMeter::new(budget);
+        let price = ModelPrice { prompt_per_million: 1_000.0, completion_per_million: 2_000.0 };
+
+        // 500 prompt tokens cost $0.50, leaving $0.50 for 250 completion tokens
+        assert_eq!(meter.limit_call(500, Some(price)), Ok(Some(250)));
+        assert!(matches!(meter.limit_call(1_000, Some(price)), Err(BudgetExceeded::Cost { .. })));
+        // Without a price only the cost so far counts
+        assert_eq!(meter.limit_call(1_000, None), Ok(None));
+    }
+
+    #[test]
+    fn test_meters_within_a_parent_share_its_budget() {
+        let session = Arc::new(UsageMeter::new(max_tokens(500)));
+        let first = UsageMeter::new(UsageBudget::default()).within(session.clone());
+        first.record(&usage(200, 100), 0.0);
+        let second = UsageMeter::new(max_tokens(1_000)).within(session.clone());
+
+        assert_eq!(session.totals().total_tokens, 300);
+        assert_eq!(second.limit_call(100, None), Ok(Some(100)));
+        second.record(&usage(150, 50), 0.0);
+        assert_eq!(second.check(), Err(BudgetExceeded::Tokens { used: 500, limit: 500 }));
+    }
+}
\ No newline at end of file
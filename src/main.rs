@@ -1,4 +1,6 @@
-use solagent::{SolAgent, SolAgentConfig};
+use solagent::{
+    llm_integration::ProviderConfig, user_interface::cli::CliHandler, SolAgent, SolAgentConfig,
+};
 use std::env;
 
 #[tokio::main]
@@ -10,14 +12,18 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let config = SolAgentConfig {
         name: "Solana Agent".to_string(),
         instructions: "Execute Solana-related tasks with tools and LLM.".to_string(),
-        model: "grok3".to_string(),
+        model: ProviderConfig::from_env("grok3")?,
         tools: vec!["get_balance".to_string(), "stake_sol".to_string()],
+        wallet_address: env::var("SOLANA_WALLET_ADDRESS").ok(),
+        prompts_dir: env::var("SOLAGENT_PROMPTS_DIR").ok(),
+        context: None,
+        memory_path: env::var("SOLAGENT_MEMORY_PATH").ok(),
     };
-    let solagent = SolAgent::new(config)?;
+    let solagent = SolAgent::new(config).await?;
 
     // Check if running in CLI mode
     if env::args().len() > 1 {
-        solagent.cli.handle_args().await?;
+        solagent.cli.handle_args(&solagent.controller).await?;
     } else {
         // Start API server
         solagent.api.run().await?;
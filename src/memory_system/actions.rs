@@ -0,0 +1,197 @@
+use std::{
+    collections::BTreeMap,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_request::TokenAccountsFilter};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    memory_system::long_term::{LongTermMemory, MemoryError, MemoryRecord, Namespace},
+    solana_integration::rpc::cluster_name,
+    util::now,
+};
+
+const SPL_TOKEN_PROGRAM: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+// Longest a tool call waits for the balances to record with it
+const BALANCES_TIMEOUT: Duration = Duration::from_secs(3);
+// Actions are stored under `action:<timestamp>:<sequence>`, so listing keeps them in order
+const KEY_PREFIX: &str = "action:";
+
+// On-chain action taken through a tool, as recorded once the call returned
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ActionRecord {
+    pub tool: String,
+    pub arguments: Value,
+    // Signatures of the transactions the tool reported sending, the first identifying the action
+    pub signatures: Vec<String>,
+    pub cluster: String,
+    // Unix milliseconds
+    pub timestamp: u64,
+    pub outcome: ActionOutcome,
+    // Wallet balances right after the call, as `{"sol": <lamports>, "tokens": {<mint>: <raw>}}`;
+    // missing when they could not be read in time
+    pub balances: Option<Value>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ActionOutcome {
+    Succeeded { output: String },
+    // The tool failed after sending a transaction, which may still have landed
+    Failed { error: String },
+}
+
+impl ActionRecord {
+    pub fn succeeded(&self) -> bool {
+        matches!(self.outcome, ActionOutcome::Succeeded { .. })
+    }
+
+    // One line description for prompts
+    pub fn summary(&self) -> String {
+        let time = chrono::DateTime::from_timestamp_millis(self.timestamp as i64)
+            .map(|time| time.format("%Y-%m-%d %H:%M UTC").to_string())
+            .unwrap_or_default();
+        let status = if self.succeeded() { "succeeded" } else { "failed" };
+        format!(
+            "{} {} {} on {} {} ({})",
+            time,
+            self.tool,
+            self.arguments,
+            self.cluster,
+            status,
+            self.signatures.join(", ")
+        )
+    }
+}
+
+// History of the on-chain actions an agent took, kept in long-term memory. Calls of tools
+// marked `sends_transactions` are actions when the tool reports a transaction through
+// `ToolInput::submitted`; other calls are not recorded.
+pub struct ActionLog {
+    memory: Arc<LongTermMemory>,
+    namespace: Namespace,
+    rpc_url: String,
+    wallet: Option<Pubkey>,
+    // Orders actions recorded within the same millisecond
+    sequence: AtomicU64,
+}
+
+impl ActionLog {
+    // Actions sent to the cluster at `rpc_url`
+    pub fn new(memory: Arc<LongTermMemory>, namespace: Namespace, rpc_url: &str) -> Self {
+        ActionLog {
+            memory,
+            namespace,
+            rpc_url: rpc_url.to_string(),
+            wallet: None,
+            sequence: AtomicU64::new(0),
+        }
+    }
+
+    // Also records the wallet's balances after each action
+    pub fn with_wallet(mut self, wallet: &str) -> Result<Self, String> {
+        let wallet = Pubkey::from_str(wallet).map_err(|e| format!("Invalid wallet: {}", e))?;
+        self.wallet = Some(wallet);
+        Ok(self)
+    }
+
+    // Records a tool call that sent the transactions `signatures`, returning the record; calls
+    // that sent none are not actions
+    pub async fn record(
+        &self,
+        tool: &str,
+        arguments: &Value,
+        signatures: Vec<String>,
+        result: Result<&str, &str>,
+    ) -> Result<Option<ActionRecord>, MemoryError> {
+        if signatures.is_empty() {
+            return Ok(None);
+        }
+        // Balances are best effort, so a slow RPC node does not hold up the tool call
+        let balances = match &self.wallet {
+            Some(wallet) => {
+                match tokio::time::timeout(BALANCES_TIMEOUT, self.balances(wallet)).await {
+                    Ok(Ok(balances)) => Some(balances),
+                    Ok(Err(e)) => {
+                        tracing::warn!(tool, "Could not read balances after action: {}", e);
+                        None
+                    }
+                    Err(_) => {
+                        tracing::warn!(tool, "Timed out reading balances after action");
+                        None
+                    }
+                }
+            }
+            None => None,
+        };
+        let record = ActionRecord {
+            tool: tool.to_string(),
+            arguments: arguments.clone(),
+            signatures,
+            cluster: cluster_name(&self.rpc_url).to_string(),
+            timestamp: now(),
+            outcome: match result {
+                Ok(output) => ActionOutcome::Succeeded { output: output.to_string() },
+                Err(error) => ActionOutcome::Failed { error: error.to_string() },
+            },
+            balances,
+        };
+        let sequence = self.sequence.fetch_add(1, Ordering::Relaxed);
+        let key = format!("{}{:013}:{:010}", KEY_PREFIX, record.timestamp, sequence);
+        let value =
+            serde_json::to_string(&record).map_err(|e| MemoryError::Backend(e.to_string()))?;
+        self.memory.store(&self.namespace, &key, &value).await?;
+        Ok(Some(record))
+    }
+
+    // Actions taken between two Unix millisecond times, inclusive, oldest first
+    pub async fn between(&self, from: u64, to: u64) -> Result<Vec<ActionRecord>, MemoryError> {
+        let records = self.all().await?;
+        Ok(records.into_iter().filter(|r| r.timestamp >= from && r.timestamp <= to).collect())
+    }
+
+    // The last `limit` actions, oldest first
+    pub async fn recent(&self, limit: usize) -> Result<Vec<ActionRecord>, MemoryError> {
+        let records = self.memory.list_last(&self.namespace, KEY_PREFIX, limit).await?;
+        Ok(parse(&records))
+    }
+
+    async fn all(&self) -> Result<Vec<ActionRecord>, MemoryError> {
+        Ok(parse(&self.memory.list(&self.namespace, KEY_PREFIX).await?))
+    }
+
+    // SOL balance in lamports and raw balance of each token mint held
+    async fn balances(&self, wallet: &Pubkey) -> Result<Value, String> {
+        let client = RpcClient::new(self.rpc_url.clone());
+        let lamports = client.get_balance(wallet).await.map_err(|e| e.to_string())?;
+        let program = Pubkey::from_str(SPL_TOKEN_PROGRAM).map_err(|e| e.to_string())?;
+        let accounts = client
+            .get_token_accounts_by_owner(wallet, TokenAccountsFilter::ProgramId(program))
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let mut tokens: BTreeMap<String, u64> = BTreeMap::new();
+        for keyed in accounts {
+            let account = serde_json::to_value(&keyed.account).unwrap_or_default();
+            let info = &account["data"]["parsed"]["info"];
+            let amount: Option<u64> =
+                info["tokenAmount"]["amount"].as_str().and_then(|a| a.parse().ok());
+            if let (Some(mint), Some(amount)) = (info["mint"].as_str(), amount) {
+                *tokens.entry(mint.to_string()).or_default() += amount;
+            }
+        }
+        Ok(json!({ "sol": lamports, "tokens": tokens }))
+    }
+}
+
+fn parse(records: &[MemoryRecord]) -> Vec<ActionRecord> {
+    records.iter().filter_map(|record| serde_json::from_str(&record.value).ok()).collect()
+}
@@ -1,34 +1,369 @@
+use std::{fmt, sync::Mutex, time::Duration};
+
 use async_trait::async_trait;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::util::now;
+
+// Stored value with its timestamps, in Unix milliseconds
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MemoryRecord {
+    pub namespace: String,
+    pub key: String,
+    pub value: String,
+    pub created_at: u64,
+    pub updated_at: u64,
+    pub expires_at: Option<u64>,
+}
+
+#[derive(Debug)]
+pub enum MemoryError {
+    Open { path: String, error: String },
+    Backend(String),
+}
+
+impl fmt::Display for MemoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MemoryError::Open { path, error } => {
+                write!(f, "Could not open memory database '{}': {}", path, error)
+            }
+            MemoryError::Backend(error) => write!(f, "Memory backend error: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for MemoryError {}
+
+impl From<rusqlite::Error> for MemoryError {
+    fn from(error: rusqlite::Error) -> Self {
+        MemoryError::Backend(error.to_string())
+    }
+}
+
+// Scope memories belong to, so agents and the users they serve don't read each other's keys
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Namespace(String);
+
+impl Namespace {
+    pub fn agent(agent: &str) -> Self {
+        Namespace(format!("agent:{}", agent))
+    }
+
+    pub fn user(agent: &str, user: &str) -> Self {
+        Namespace(format!("agent:{}/user:{}", agent, user))
+    }
+
+    pub fn session(session_id: &str) -> Self {
+        Namespace(format!("session:{}", session_id))
+    }
 
-// Memory backend trait for pluggable storage
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Namespace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+// Memory backend trait for pluggable storage. Expired records are never returned.
 #[async_trait]
-pub trait MemoryBackend {
-    async fn store(&self, key: &str, value: &str);
-    async fn retrieve(&self, key: &str) -> Option<String>;
+pub trait MemoryBackend: Send + Sync {
+    // Inserts or replaces a value, keeping its creation time when replacing
+    async fn store(
+        &self,
+        namespace: &str,
+        key: &str,
+        value: &str,
+        ttl: Option<Duration>,
+    ) -> Result<(), MemoryError>;
+    async fn retrieve(
+        &self,
+        namespace: &str,
+        key: &str,
+    ) -> Result<Option<MemoryRecord>, MemoryError>;
+    // Records whose key starts with `prefix`, ordered by key
+    async fn list(&self, namespace: &str, prefix: &str) -> Result<Vec<MemoryRecord>, MemoryError>;
+    // The last `limit` records `list` would return. Backends that can should read only those.
+    async fn list_last(
+        &self,
+        namespace: &str,
+        prefix: &str,
+        limit: usize,
+    ) -> Result<Vec<MemoryRecord>, MemoryError> {
+        let mut records = self.list(namespace, prefix).await?;
+        records.drain(..records.len().saturating_sub(limit));
+        Ok(records)
+    }
+    async fn delete(&self, namespace: &str, key: &str) -> Result<bool, MemoryError>;
+    // Deletes the records whose key starts with `prefix`, returning how many there were
+    async fn delete_prefix(&self, namespace: &str, prefix: &str) -> Result<usize, MemoryError>;
+    // Removes expired records from storage, returning how many there were
+    async fn purge_expired(&self) -> Result<usize, MemoryError>;
 }
 
 // Long-term memory structure
 pub struct LongTermMemory {
-    backend: Box<dyn MemoryBackend + Send + Sync>,
+    backend: Box<dyn MemoryBackend>,
 }
 
 impl LongTermMemory {
-    pub fn new() -> Self {
-        LongTermMemory {
-            backend: Box::new(SqliteBackend::new()),
-        }
+    // Memory kept in a SQLite database file, created if missing
+    pub fn open(path: &str) -> Result<Self, MemoryError> {
+        Ok(Self::with_backend(Box::new(SqliteBackend::open(path)?)))
+    }
+
+    // Memory kept in an in-process SQLite database, lost on exit
+    pub fn in_memory() -> Result<Self, MemoryError> {
+        Ok(Self::with_backend(Box::new(SqliteBackend::in_memory()?)))
+    }
+
+    pub fn with_backend(backend: Box<dyn MemoryBackend>) -> Self {
+        LongTermMemory { backend }
+    }
+
+    pub async fn store(
+        &self,
+        namespace: &Namespace,
+        key: &str,
+        value: &str,
+    ) -> Result<(), MemoryError> {
+        self.backend.store(namespace.as_str(), key, value, None).await
+    }
+
+    // Stores a value that is forgotten once `ttl` has passed
+    pub async fn store_with_ttl(
+        &self,
+        namespace: &Namespace,
+        key: &str,
+        value: &str,
+        ttl: Duration,
+    ) -> Result<(), MemoryError> {
+        self.backend.store(namespace.as_str(), key, value, Some(ttl)).await
+    }
+
+    pub async fn retrieve(
+        &self,
+        namespace: &Namespace,
+        key: &str,
+    ) -> Result<Option<String>, MemoryError> {
+        let record = self.backend.retrieve(namespace.as_str(), key).await?;
+        Ok(record.map(|record| record.value))
+    }
+
+    pub async fn record(
+        &self,
+        namespace: &Namespace,
+        key: &str,
+    ) -> Result<Option<MemoryRecord>, MemoryError> {
+        self.backend.retrieve(namespace.as_str(), key).await
+    }
+
+    pub async fn list(
+        &self,
+        namespace: &Namespace,
+        prefix: &str,
+    ) -> Result<Vec<MemoryRecord>, MemoryError> {
+        self.backend.list(namespace.as_str(), prefix).await
+    }
+
+    // The last `limit` records whose key starts with `prefix`, ordered by key
+    pub async fn list_last(
+        &self,
+        namespace: &Namespace,
+        prefix: &str,
+        limit: usize,
+    ) -> Result<Vec<MemoryRecord>, MemoryError> {
+        self.backend.list_last(namespace.as_str(), prefix, limit).await
+    }
+
+    pub async fn delete(&self, namespace: &Namespace, key: &str) -> Result<bool, MemoryError> {
+        self.backend.delete(namespace.as_str(), key).await
+    }
+
+    pub async fn delete_prefix(
+        &self,
+        namespace: &Namespace,
+        prefix: &str,
+    ) -> Result<usize, MemoryError> {
+        self.backend.delete_prefix(namespace.as_str(), prefix).await
+    }
+
+    pub async fn purge_expired(&self) -> Result<usize, MemoryError> {
+        self.backend.purge_expired().await
     }
 }
 
+// Schema changes, applied in order; the database's `user_version` counts those applied
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE memories (
+        namespace TEXT NOT NULL,
+        key TEXT NOT NULL,
+        value TEXT NOT NULL,
+        created_at INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL,
+        expires_at INTEGER,
+        PRIMARY KEY (namespace, key)
+    );
+    CREATE INDEX memories_expires_at ON memories (expires_at);",
+];
+
 // SQLite backend for long-term memory
 pub struct SqliteBackend {
-    db: rusqlite::Connection,
+    db: Mutex<Connection>,
 }
 
 impl SqliteBackend {
-    pub fn new() -> Self {
-        SqliteBackend {
-            db: rusqlite::Connection::open("solagent.db").unwrap(),
-        }
+    pub fn open(path: &str) -> Result<Self, MemoryError> {
+        let open_error = |e: rusqlite::Error| MemoryError::Open {
+            path: path.to_string(),
+            error: e.to_string(),
+        };
+        let db = Connection::open(path).map_err(open_error)?;
+        Self::init(db).map_err(open_error)
     }
-}
\ No newline at end of file
+
+    pub fn in_memory() -> Result<Self, MemoryError> {
+        let db = Connection::open_in_memory()?;
+        Ok(Self::init(db)?)
+    }
+
+    fn init(mut db: Connection) -> rusqlite::Result<Self> {
+        db.busy_timeout(Duration::from_secs(5))?;
+        migrate(&mut db)?;
+        Ok(SqliteBackend { db: Mutex::new(db) })
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, Connection>, MemoryError> {
+        self.db.lock().map_err(|_| MemoryError::Backend("database lock poisoned".to_string()))
+    }
+}
+
+// Brings the schema up to date, returning the version it is at
+pub fn migrate(db: &mut Connection) -> rusqlite::Result<usize> {
+    let version: usize = db.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let transaction = db.transaction()?;
+        transaction.execute_batch(migration)?;
+        transaction.pragma_update(None, "user_version", i + 1)?;
+        transaction.commit()?;
+    }
+    Ok(MIGRATIONS.len().max(version))
+}
+
+const COLUMNS: &str = "namespace, key, value, created_at, updated_at, expires_at";
+
+fn read_record(row: &rusqlite::Row<'_>) -> rusqlite::Result<MemoryRecord> {
+    Ok(MemoryRecord {
+        namespace: row.get(0)?,
+        key: row.get(1)?,
+        value: row.get(2)?,
+        created_at: row.get(3)?,
+        updated_at: row.get(4)?,
+        expires_at: row.get(5)?,
+    })
+}
+
+#[async_trait]
+impl MemoryBackend for SqliteBackend {
+    async fn store(
+        &self,
+        namespace: &str,
+        key: &str,
+        value: &str,
+        ttl: Option<Duration>,
+    ) -> Result<(), MemoryError> {
+        let now = now();
+        let expires_at = ttl.map(|ttl| now + ttl.as_millis() as u64);
+        self.lock()?.execute(
+            "INSERT INTO memories (namespace, key, value, created_at, updated_at, expires_at)
+             VALUES (?1, ?2, ?3, ?4, ?4, ?5)
+             ON CONFLICT(namespace, key) DO UPDATE SET
+                 value = ?3,
+                 created_at = CASE WHEN expires_at <= ?4 THEN ?4 ELSE created_at END,
+                 updated_at = ?4,
+                 expires_at = ?5",
+            params![namespace, key, value, now, expires_at],
+        )?;
+        Ok(())
+    }
+
+    async fn retrieve(
+        &self,
+        namespace: &str,
+        key: &str,
+    ) -> Result<Option<MemoryRecord>, MemoryError> {
+        let record = self
+            .lock()?
+            .query_row(
+                &format!(
+                    "SELECT {} FROM memories WHERE namespace = ?1 AND key = ?2
+                     AND (expires_at IS NULL OR expires_at > ?3)",
+                    COLUMNS
+                ),
+                params![namespace, key, now()],
+                read_record,
+            )
+            .optional()?;
+        Ok(record)
+    }
+
+    async fn list(&self, namespace: &str, prefix: &str) -> Result<Vec<MemoryRecord>, MemoryError> {
+        let db = self.lock()?;
+        let mut statement = db.prepare(&format!(
+            "SELECT {} FROM memories WHERE namespace = ?1 AND substr(key, 1, length(?2)) = ?2
+             AND (expires_at IS NULL OR expires_at > ?3) ORDER BY key",
+            COLUMNS
+        ))?;
+        let records = statement
+            .query_map(params![namespace, prefix, now()], read_record)?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(records)
+    }
+
+    async fn list_last(
+        &self,
+        namespace: &str,
+        prefix: &str,
+        limit: usize,
+    ) -> Result<Vec<MemoryRecord>, MemoryError> {
+        let db = self.lock()?;
+        let mut statement = db.prepare(&format!(
+            "SELECT {} FROM memories WHERE namespace = ?1 AND substr(key, 1, length(?2)) = ?2
+             AND (expires_at IS NULL OR expires_at > ?3) ORDER BY key DESC LIMIT ?4",
+            COLUMNS
+        ))?;
+        let mut records = statement
+            .query_map(params![namespace, prefix, now(), limit as i64], read_record)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        records.reverse();
+        Ok(records)
+    }
+
+    async fn delete(&self, namespace: &str, key: &str) -> Result<bool, MemoryError> {
+        let deleted = self.lock()?.execute(
+            "DELETE FROM memories WHERE namespace = ?1 AND key = ?2",
+            params![namespace, key],
+        )?;
+        Ok(deleted > 0)
+    }
+
+    async fn delete_prefix(&self, namespace: &str, prefix: &str) -> Result<usize, MemoryError> {
+        let deleted = self.lock()?.execute(
+            "DELETE FROM memories WHERE namespace = ?1 AND substr(key, 1, length(?2)) = ?2",
+            params![namespace, prefix],
+        )?;
+        Ok(deleted)
+    }
+
+    async fn purge_expired(&self) -> Result<usize, MemoryError> {
+        let purged =
+            self.lock()?.execute("DELETE FROM memories WHERE expires_at <= ?1", params![now()])?;
+        Ok(purged)
+    }
+}
+
@@ -1,2 +1,8 @@
 pub mod short_term;
-pub mod long_term;
\ No newline at end of file
+pub mod long_term;
+pub mod redis_backend;
+pub mod vector;
+pub mod actions;
+
+pub use long_term::LongTermMemory;
+pub use short_term::ShortTermMemory;
\ No newline at end of file
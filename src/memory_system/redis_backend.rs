@@ -0,0 +1,246 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use deadpool_redis::{Config, Connection, Pool, PoolConfig, Runtime};
+use redis::AsyncCommands;
+
+use crate::{
+    memory_system::long_term::{MemoryBackend, MemoryError, MemoryRecord},
+    util::now,
+};
+
+// Prefix of every key written, so agents can share a Redis database with other applications
+pub const DEFAULT_PREFIX: &str = "solagent";
+
+// Pool of Redis connections shared by memory backends, e.g.
+// `redis_pool("redis://127.0.0.1/", 16)`
+pub fn redis_pool(url: &str, max_size: usize) -> Result<Pool, MemoryError> {
+    let mut config = Config::from_url(url);
+    config.pool = Some(PoolConfig::new(max_size.max(1)));
+    config.create_pool(Some(Runtime::Tokio1)).map_err(|e| MemoryError::Open {
+        path: url.to_string(),
+        error: e.to_string(),
+    })
+}
+
+pub(crate) async fn connection(pool: &Pool) -> Result<Connection, MemoryError> {
+    pool.get().await.map_err(|e| MemoryError::Backend(e.to_string()))
+}
+
+pub(crate) fn redis_error(error: redis::RedisError) -> MemoryError {
+    MemoryError::Backend(error.to_string())
+}
+
+// Long-term memory in Redis, shared by every agent replica using the same database. Records
+// are JSON strings under `<prefix>:{<namespace>}:<key>`, expiring through Redis TTLs. Each
+// namespace keeps its keys in a sorted set for prefix listing; the braces keep a namespace's
+// keys in one slot under Redis Cluster, so each transaction only touches that slot. The set
+// of namespaces, used to purge the indexes, lives in its own slot and is updated separately.
+pub struct RedisBackend {
+    pool: Pool,
+    prefix: String,
+}
+
+impl RedisBackend {
+    pub fn new(pool: Pool) -> Self {
+        RedisBackend { pool, prefix: DEFAULT_PREFIX.to_string() }
+    }
+
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    fn record_key(&self, namespace: &str, key: &str) -> String {
+        format!("{}:{{{}}}:{}", self.prefix, namespace, key)
+    }
+
+    fn index_key(&self, namespace: &str) -> String {
+        format!("{}:{{{}}}:__keys", self.prefix, namespace)
+    }
+
+    fn namespaces_key(&self) -> String {
+        format!("{}:__namespaces", self.prefix)
+    }
+
+    // Indexed keys of a namespace starting with `prefix`
+    async fn keys(
+        &self,
+        db: &mut Connection,
+        namespace: &str,
+        prefix: &str,
+    ) -> Result<Vec<String>, MemoryError> {
+        let (min, max) = lex_range(prefix);
+        db.zrangebylex(self.index_key(namespace), min, max).await.map_err(redis_error)
+    }
+
+    // Records stored under `keys` of a namespace, in the same order
+    async fn records(
+        &self,
+        db: &mut Connection,
+        namespace: &str,
+        keys: Vec<String>,
+    ) -> Result<Vec<MemoryRecord>, MemoryError> {
+        if keys.is_empty() {
+            return Ok(vec![]);
+        }
+        let record_keys: Vec<String> =
+            keys.iter().map(|key| self.record_key(namespace, key)).collect();
+        let values: Vec<Option<String>> = redis::cmd("MGET")
+            .arg(&record_keys)
+            .query_async(db)
+            .await
+            .map_err(redis_error)?;
+
+        let mut records = vec![];
+        let mut expired = vec![];
+        for (key, value) in keys.into_iter().zip(values) {
+            match value.and_then(|json| serde_json::from_str(&json).ok()) {
+                Some(record) => records.push(record),
+                None => expired.push(key),
+            }
+        }
+        // Keys Redis expired are dropped from the index as they are found
+        if !expired.is_empty() {
+            db.zrem::<_, _, ()>(self.index_key(namespace), expired).await.map_err(redis_error)?;
+        }
+        Ok(records)
+    }
+}
+
+// Bounds of the index entries starting with `prefix`, for ZRANGEBYLEX
+fn lex_range(prefix: &str) -> (String, String) {
+    if prefix.is_empty() {
+        ("-".to_string(), "+".to_string())
+    } else {
+        // Every key starting with `prefix` sorts before `prefix` and the highest character
+        (format!("[{}", prefix), format!("[{}\u{10ffff}", prefix))
+    }
+}
+
+#[async_trait]
+impl MemoryBackend for RedisBackend {
+    async fn store(
+        &self,
+        namespace: &str,
+        key: &str,
+        value: &str,
+        ttl: Option<Duration>,
+    ) -> Result<(), MemoryError> {
+        let mut db = connection(&self.pool).await?;
+        let record_key = self.record_key(namespace, key);
+        let now = now();
+        let existing: Option<String> = db.get(&record_key).await.map_err(redis_error)?;
+        let created_at = existing
+            .and_then(|json| serde_json::from_str::<MemoryRecord>(&json).ok())
+            .map(|record| record.created_at)
+            .unwrap_or(now);
+        let record = MemoryRecord {
+            namespace: namespace.to_string(),
+            key: key.to_string(),
+            value: value.to_string(),
+            created_at,
+            updated_at: now,
+            expires_at: ttl.map(|ttl| now + ttl.as_millis() as u64),
+        };
+        let json =
+            serde_json::to_string(&record).map_err(|e| MemoryError::Backend(e.to_string()))?;
+
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        match ttl {
+            Some(ttl) => pipe.pset_ex(&record_key, json, ttl.as_millis().max(1) as u64),
+            None => pipe.set(&record_key, json),
+        };
+        pipe.zadd(self.index_key(namespace), key, 0);
+        pipe.query_async::<_, ()>(&mut db).await.map_err(redis_error)?;
+        db.sadd(self.namespaces_key(), namespace).await.map_err(redis_error)
+    }
+
+    async fn retrieve(
+        &self,
+        namespace: &str,
+        key: &str,
+    ) -> Result<Option<MemoryRecord>, MemoryError> {
+        let mut db = connection(&self.pool).await?;
+        let json: Option<String> =
+            db.get(self.record_key(namespace, key)).await.map_err(redis_error)?;
+        Ok(json.and_then(|json| serde_json::from_str(&json).ok()))
+    }
+
+    async fn list(&self, namespace: &str, prefix: &str) -> Result<Vec<MemoryRecord>, MemoryError> {
+        let mut db = connection(&self.pool).await?;
+        let keys = self.keys(&mut db, namespace, prefix).await?;
+        self.records(&mut db, namespace, keys).await
+    }
+
+    // Reads only the last `limit` indexed keys; keys Redis already expired count towards it
+    async fn list_last(
+        &self,
+        namespace: &str,
+        prefix: &str,
+        limit: usize,
+    ) -> Result<Vec<MemoryRecord>, MemoryError> {
+        let mut db = connection(&self.pool).await?;
+        let (min, max) = lex_range(prefix);
+        let mut keys: Vec<String> = db
+            .zrevrangebylex_limit(self.index_key(namespace), max, min, 0, limit as isize)
+            .await
+            .map_err(redis_error)?;
+        keys.reverse();
+        self.records(&mut db, namespace, keys).await
+    }
+
+    async fn delete(&self, namespace: &str, key: &str) -> Result<bool, MemoryError> {
+        let mut db = connection(&self.pool).await?;
+        let (deleted, _): (usize, usize) = redis::pipe()
+            .atomic()
+            .del(self.record_key(namespace, key))
+            .zrem(self.index_key(namespace), key)
+            .query_async(&mut db)
+            .await
+            .map_err(redis_error)?;
+        Ok(deleted > 0)
+    }
+
+    async fn delete_prefix(&self, namespace: &str, prefix: &str) -> Result<usize, MemoryError> {
+        let mut db = connection(&self.pool).await?;
+        let keys = self.keys(&mut db, namespace, prefix).await?;
+        if keys.is_empty() {
+            return Ok(0);
+        }
+        let record_keys: Vec<String> =
+            keys.iter().map(|key| self.record_key(namespace, key)).collect();
+        let (deleted, _): (usize, usize) = redis::pipe()
+            .atomic()
+            .del(record_keys)
+            .zrem(self.index_key(namespace), keys)
+            .query_async(&mut db)
+            .await
+            .map_err(redis_error)?;
+        Ok(deleted)
+    }
+
+    // Redis drops expired records by itself; this only cleans their keys out of the indexes
+    async fn purge_expired(&self) -> Result<usize, MemoryError> {
+        let mut db = connection(&self.pool).await?;
+        let namespaces: Vec<String> =
+            db.smembers(self.namespaces_key()).await.map_err(redis_error)?;
+        let mut purged = 0;
+        for namespace in namespaces {
+            let keys = self.keys(&mut db, &namespace, "").await?;
+            for key in keys {
+                let exists: bool =
+                    db.exists(self.record_key(&namespace, &key)).await.map_err(redis_error)?;
+                if !exists {
+                    db.zrem::<_, _, ()>(self.index_key(&namespace), &key)
+                        .await
+                        .map_err(redis_error)?;
+                    purged += 1;
+                }
+            }
+        }
+        Ok(purged)
+    }
+}
+
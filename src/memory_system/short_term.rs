@@ -1,14 +1,123 @@
+use std::{collections::HashMap, time::Duration};
+
+use deadpool_redis::Pool;
+use redis::AsyncCommands;
 use tokio::sync::RwLock;
 
-// Short-term memory structure
+use crate::memory_system::{
+    long_term::{MemoryError, Namespace},
+    redis_backend::{connection, redis_error, DEFAULT_PREFIX},
+};
+
+// Short-term memory structure: the working context of recent task results
 pub struct ShortTermMemory {
-    pub context: RwLock<std::collections::HashMap<String, String>>,
+    store: Store,
+}
+
+enum Store {
+    Local(RwLock<HashMap<String, String>>),
+    // Hash under `<prefix>:{<namespace>}:short_term`, shared by agent replicas and expiring
+    // `ttl` after its last write
+    Redis { pool: Pool, namespace: String, key: String, ttl: Option<Duration> },
+}
+
+fn hash_key(prefix: &str, namespace: &str) -> String {
+    format!("{}:{{{}}}:short_term", prefix, namespace)
 }
 
 impl ShortTermMemory {
+    // Memory kept in this process
     pub fn new() -> Self {
-        ShortTermMemory {
-            context: RwLock::new(std::collections::HashMap::new()),
+        ShortTermMemory { store: Store::Local(RwLock::new(HashMap::new())) }
+    }
+
+    // Memory kept in Redis, so every replica serving `namespace` sees the same context
+    pub fn redis(pool: Pool, namespace: &Namespace, ttl: Option<Duration>) -> Self {
+        let namespace = namespace.to_string();
+        let key = hash_key(DEFAULT_PREFIX, &namespace);
+        ShortTermMemory { store: Store::Redis { pool, namespace, key, ttl } }
+    }
+
+    // Key prefix of memory kept in Redis, as with `RedisBackend::with_prefix`
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        if let Store::Redis { namespace, key, .. } = &mut self.store {
+            *key = hash_key(prefix, namespace);
+        }
+        self
+    }
+
+    pub async fn insert(&self, key: &str, value: &str) -> Result<(), MemoryError> {
+        match &self.store {
+            Store::Local(context) => {
+                context.write().await.insert(key.to_string(), value.to_string());
+                Ok(())
+            }
+            Store::Redis { pool, key: hash, ttl, .. } => {
+                let mut db = connection(pool).await?;
+                let mut pipe = redis::pipe();
+                pipe.atomic().hset(hash, key, value);
+                if let Some(ttl) = ttl {
+                    pipe.pexpire(hash, ttl.as_millis().max(1) as i64);
+                }
+                pipe.query_async::<_, ()>(&mut db).await.map_err(redis_error)
+            }
+        }
+    }
+
+    pub async fn get(&self, key: &str) -> Result<Option<String>, MemoryError> {
+        match &self.store {
+            Store::Local(context) => Ok(context.read().await.get(key).cloned()),
+            Store::Redis { pool, key: hash, .. } => {
+                let mut db = connection(pool).await?;
+                db.hget(hash, key).await.map_err(redis_error)
+            }
+        }
+    }
+
+    // Every entry, ordered by key
+    pub async fn entries(&self) -> Result<Vec<(String, String)>, MemoryError> {
+        let mut entries: Vec<(String, String)> = match &self.store {
+            Store::Local(context) => {
+                context.read().await.iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+            }
+            Store::Redis { pool, key: hash, .. } => {
+                let mut db = connection(pool).await?;
+                let entries: HashMap<String, String> =
+                    db.hgetall(hash).await.map_err(redis_error)?;
+                entries.into_iter().collect()
+            }
+        };
+        entries.sort();
+        Ok(entries)
+    }
+
+    pub async fn remove(&self, key: &str) -> Result<bool, MemoryError> {
+        match &self.store {
+            Store::Local(context) => Ok(context.write().await.remove(key).is_some()),
+            Store::Redis { pool, key: hash, .. } => {
+                let mut db = connection(pool).await?;
+                let removed: usize = db.hdel(hash, key).await.map_err(redis_error)?;
+                Ok(removed > 0)
+            }
         }
     }
-}
\ No newline at end of file
+
+    pub async fn clear(&self) -> Result<(), MemoryError> {
+        match &self.store {
+            Store::Local(context) => {
+                context.write().await.clear();
+                Ok(())
+            }
+            Store::Redis { pool, key: hash, .. } => {
+                let mut db = connection(pool).await?;
+                db.del::<_, ()>(hash).await.map_err(redis_error)
+            }
+        }
+    }
+}
+
+impl Default for ShortTermMemory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,530 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use reqwest::Client;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    llm_integration::{openai_compatible::http_client, retry::send_checked},
+    memory_system::long_term::{MemoryError, Namespace},
+    util::{fnv1a, now},
+};
+
+// Turns texts into vectors whose cosine similarity reflects how related the texts are
+#[async_trait]
+pub trait EmbeddingProvider: Send + Sync {
+    // Identifies the model; vectors are only ever compared with vectors of the same model
+    fn model(&self) -> &str;
+    // One vector per text, in order
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, MemoryError>;
+}
+
+// Local embedding hashing words and character trigrams into a fixed number of dimensions.
+// It needs no model or network and always gives the same vector for the same text, which
+// suits tests and offline agents, but it only sees shared vocabulary, not meaning.
+pub struct HashEmbedding {
+    dimensions: usize,
+    model: String,
+}
+
+impl HashEmbedding {
+    pub fn new(dimensions: usize) -> Self {
+        let dimensions = dimensions.max(1);
+        HashEmbedding { dimensions, model: format!("hash-{}", dimensions) }
+    }
+
+    fn embed_text(&self, text: &str) -> Vec<f32> {
+        let mut vector = vec![0.0; self.dimensions];
+        let mut add = |feature: &str, weight: f32| {
+            let hash = fnv1a(feature.as_bytes());
+            let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+            vector[(hash % self.dimensions as u64) as usize] += sign * weight;
+        };
+        let text = text.to_lowercase();
+        for word in text.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()) {
+            add(word, 1.0);
+            // Trigrams let spelling variants and inflections share part of their features
+            let chars: Vec<char> = format!("^{}$", word).chars().collect();
+            for trigram in chars.windows(3) {
+                add(&trigram.iter().collect::<String>(), 0.5);
+            }
+        }
+        normalize(&mut vector);
+        vector
+    }
+}
+
+impl Default for HashEmbedding {
+    fn default() -> Self {
+        Self::new(256)
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for HashEmbedding {
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, MemoryError> {
+        Ok(texts.iter().map(|text| self.embed_text(text)).collect())
+    }
+}
+
+// Embeddings from any OpenAI-compatible `/embeddings` endpoint
+// (OpenAI, vLLM, llama.cpp server, Ollama, ...)
+pub struct OpenAICompatibleEmbedding {
+    client: Client,
+    base_url: String,
+    model: String,
+    api_key: Option<String>,
+}
+
+impl OpenAICompatibleEmbedding {
+    pub fn new(base_url: &str, model: &str) -> Self {
+        OpenAICompatibleEmbedding {
+            client: http_client(Some(60)),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            model: model.to_string(),
+            api_key: None,
+        }
+    }
+
+    // text-embedding-3-small on the OpenAI API
+    pub fn openai(api_key: String) -> Self {
+        Self::new("https://api.openai.com/v1", "text-embedding-3-small").with_api_key(api_key)
+    }
+
+    pub fn with_api_key(mut self, api_key: String) -> Self {
+        self.api_key = Some(api_key);
+        self
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for OpenAICompatibleEmbedding {
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, MemoryError> {
+        if texts.is_empty() {
+            return Ok(vec![]);
+        }
+        let body = serde_json::json!({ "model": self.model, "input": texts });
+        let mut request = self.client.post(format!("{}/embeddings", self.base_url)).json(&body);
+        if let Some(api_key) = &self.api_key {
+            request = request.header("Authorization", format!("Bearer {}", api_key));
+        }
+        let embedding_error = |e: Box<dyn std::error::Error>| {
+            MemoryError::Backend(format!("Embedding request failed: {}", e))
+        };
+        let response = send_checked(request).await.map_err(embedding_error)?;
+        let response: Value = response.json().await.map_err(|e| embedding_error(Box::new(e)))?;
+
+        // The API may return the vectors out of order; `index` says which text each belongs to
+        let mut vectors = vec![None; texts.len()];
+        for item in response["data"].as_array().into_iter().flatten() {
+            let index = item["index"].as_u64().unwrap_or_default() as usize;
+            let vector: Option<Vec<f32>> = item["embedding"]
+                .as_array()
+                .map(|values| values.iter().filter_map(|v| v.as_f64()).map(|v| v as f32).collect());
+            if let (Some(slot), Some(mut vector)) = (vectors.get_mut(index), vector) {
+                normalize(&mut vector);
+                *slot = Some(vector);
+            }
+        }
+        vectors
+            .into_iter()
+            .enumerate()
+            .map(|(i, vector)| {
+                vector.ok_or_else(|| {
+                    MemoryError::Backend(format!("Embedding response has no vector for text {}", i))
+                })
+            })
+            .collect()
+    }
+}
+
+// Text to remember, with metadata that searches can filter on, e.g.
+// `{"wallet": "...", "block_time": 1735689600, "kind": "transfer"}`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Document {
+    pub id: String,
+    pub text: String,
+    #[serde(default)]
+    pub metadata: Value,
+}
+
+impl Document {
+    pub fn new(id: &str, text: &str) -> Self {
+        Document { id: id.to_string(), text: text.to_string(), metadata: Value::Null }
+    }
+
+    pub fn with_metadata(mut self, metadata: Value) -> Self {
+        self.metadata = metadata;
+        self
+    }
+}
+
+// Stored document, with its creation time in Unix milliseconds
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct VectorEntry {
+    pub namespace: String,
+    pub document: Document,
+    pub created_at: u64,
+}
+
+// Search result; the score is the cosine similarity to the query, from -1 to 1
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ScoredEntry {
+    pub entry: VectorEntry,
+    pub score: f32,
+}
+
+// Condition on a top-level metadata field; documents without the field never match
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MetadataFilter {
+    Equals { field: String, value: Value },
+    // Numeric field within the bounds, inclusive; dates are compared as Unix timestamps
+    Range { field: String, min: Option<f64>, max: Option<f64> },
+}
+
+impl MetadataFilter {
+    pub fn equals(field: &str, value: impl Into<Value>) -> Self {
+        MetadataFilter::Equals { field: field.to_string(), value: value.into() }
+    }
+
+    pub fn range(field: &str, min: Option<f64>, max: Option<f64>) -> Self {
+        MetadataFilter::Range { field: field.to_string(), min, max }
+    }
+
+    pub fn matches(&self, metadata: &Value) -> bool {
+        match self {
+            MetadataFilter::Equals { field, value } => metadata.get(field) == Some(value),
+            MetadataFilter::Range { field, min, max } => {
+                match metadata.get(field).and_then(Value::as_f64) {
+                    Some(x) => min.map_or(true, |min| x >= min) && max.map_or(true, |max| x <= max),
+                    None => false,
+                }
+            }
+        }
+    }
+}
+
+// Similarity search for the `top_k` documents closest to `text`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct VectorQuery {
+    pub text: String,
+    pub top_k: usize,
+    #[serde(default)]
+    pub filters: Vec<MetadataFilter>,
+    // Results scoring below this are dropped
+    #[serde(default)]
+    pub min_score: Option<f32>,
+}
+
+impl VectorQuery {
+    pub fn new(text: &str) -> Self {
+        VectorQuery { text: text.to_string(), top_k: 5, filters: vec![], min_score: None }
+    }
+
+    pub fn with_top_k(mut self, top_k: usize) -> Self {
+        self.top_k = top_k;
+        self
+    }
+
+    pub fn with_filter(mut self, filter: MetadataFilter) -> Self {
+        self.filters.push(filter);
+        self
+    }
+
+    pub fn with_min_score(mut self, min_score: f32) -> Self {
+        self.min_score = Some(min_score);
+        self
+    }
+}
+
+// Vector store trait for pluggable indexes. Embeddings are unit length, so the dot product
+// of two of them is their cosine similarity.
+#[async_trait]
+pub trait VectorStore: Send + Sync {
+    // Inserts or replaces a document, keeping its creation time when replacing
+    async fn upsert(
+        &self,
+        namespace: &str,
+        model: &str,
+        document: &Document,
+        embedding: &[f32],
+    ) -> Result<(), MemoryError>;
+    // Highest scoring documents embedded by `model` that pass every filter, best first
+    async fn search(
+        &self,
+        namespace: &str,
+        model: &str,
+        embedding: &[f32],
+        query: &VectorQuery,
+    ) -> Result<Vec<ScoredEntry>, MemoryError>;
+    async fn delete(&self, namespace: &str, id: &str) -> Result<bool, MemoryError>;
+}
+
+// Semantic memory: documents are found by what they are about rather than by key
+pub struct VectorMemory {
+    embedder: Arc<dyn EmbeddingProvider>,
+    store: Box<dyn VectorStore>,
+}
+
+impl VectorMemory {
+    // Memory kept in a SQLite database file, created if missing
+    pub fn open(path: &str, embedder: Arc<dyn EmbeddingProvider>) -> Result<Self, MemoryError> {
+        Ok(Self::with_store(Box::new(SqliteVectorStore::open(path)?), embedder))
+    }
+
+    // Memory kept in an in-process SQLite database, lost on exit
+    pub fn in_memory(embedder: Arc<dyn EmbeddingProvider>) -> Result<Self, MemoryError> {
+        Ok(Self::with_store(Box::new(SqliteVectorStore::in_memory()?), embedder))
+    }
+
+    pub fn with_store(store: Box<dyn VectorStore>, embedder: Arc<dyn EmbeddingProvider>) -> Self {
+        VectorMemory { embedder, store }
+    }
+
+    pub async fn add(&self, namespace: &Namespace, document: Document) -> Result<(), MemoryError> {
+        self.add_all(namespace, vec![document]).await
+    }
+
+    // Embeds the documents in one request and stores them
+    pub async fn add_all(
+        &self,
+        namespace: &Namespace,
+        documents: Vec<Document>,
+    ) -> Result<(), MemoryError> {
+        let texts: Vec<String> = documents.iter().map(|d| d.text.clone()).collect();
+        let embeddings = self.embedder.embed(&texts).await?;
+        for (document, embedding) in documents.iter().zip(embeddings) {
+            self.store
+                .upsert(namespace.as_str(), self.embedder.model(), document, &embedding)
+                .await?;
+        }
+        Ok(())
+    }
+
+    pub async fn search(
+        &self,
+        namespace: &Namespace,
+        query: &VectorQuery,
+    ) -> Result<Vec<ScoredEntry>, MemoryError> {
+        if query.top_k == 0 {
+            return Ok(vec![]);
+        }
+        let texts = std::slice::from_ref(&query.text);
+        let embedding = self.embedder.embed(texts).await?.pop().unwrap_or_default();
+        self.store.search(namespace.as_str(), self.embedder.model(), &embedding, query).await
+    }
+
+    pub async fn delete(&self, namespace: &Namespace, id: &str) -> Result<bool, MemoryError> {
+        self.store.delete(namespace.as_str(), id).await
+    }
+}
+
+// What an agent recalls from vector memory for each task prompt
+pub struct VectorRecall {
+    memory: Arc<VectorMemory>,
+    namespace: Namespace,
+    top_k: usize,
+    min_score: Option<f32>,
+    input_filters: Vec<String>,
+}
+
+impl VectorRecall {
+    pub fn new(memory: Arc<VectorMemory>, namespace: Namespace) -> Self {
+        VectorRecall { memory, namespace, top_k: 5, min_score: None, input_filters: vec![] }
+    }
+
+    pub fn with_top_k(mut self, top_k: usize) -> Self {
+        self.top_k = top_k;
+        self
+    }
+
+    pub fn with_min_score(mut self, min_score: f32) -> Self {
+        self.min_score = Some(min_score);
+        self
+    }
+
+    // When the task input has `field`, only documents with the same `field` metadata are
+    // recalled, e.g. "wallet" to keep to the wallet the task is about
+    pub fn with_input_filter(mut self, field: &str) -> Self {
+        self.input_filters.push(field.to_string());
+        self
+    }
+
+    // Documents most related to a task and its input, best first
+    pub async fn recall(&self, task: &str, input: &Value) -> Result<Vec<ScoredEntry>, MemoryError> {
+        let mut query = VectorQuery::new(&format!("{}\n{}", task, input)).with_top_k(self.top_k);
+        query.min_score = self.min_score;
+        for field in &self.input_filters {
+            if let Some(value) = input.get(field) {
+                query = query.with_filter(MetadataFilter::equals(field, value.clone()));
+            }
+        }
+        self.memory.search(&self.namespace, &query).await
+    }
+}
+
+// SQLite vector store scanning every vector of the namespace on search. That stays fast up to
+// tens of thousands of documents per namespace, which covers an agent's own history.
+pub struct SqliteVectorStore {
+    db: Mutex<Connection>,
+}
+
+impl SqliteVectorStore {
+    pub fn open(path: &str) -> Result<Self, MemoryError> {
+        let open_error = |e: rusqlite::Error| MemoryError::Open {
+            path: path.to_string(),
+            error: e.to_string(),
+        };
+        let db = Connection::open(path).map_err(open_error)?;
+        Self::init(db).map_err(open_error)
+    }
+
+    pub fn in_memory() -> Result<Self, MemoryError> {
+        let db = Connection::open_in_memory()?;
+        Ok(Self::init(db)?)
+    }
+
+    // The table is created directly rather than through long-term memory's migrations, whose
+    // `user_version` it would otherwise share when both use the same database file
+    fn init(db: Connection) -> rusqlite::Result<Self> {
+        db.busy_timeout(Duration::from_secs(5))?;
+        db.execute_batch(
+            "CREATE TABLE IF NOT EXISTS vectors (
+                namespace TEXT NOT NULL,
+                id TEXT NOT NULL,
+                model TEXT NOT NULL,
+                text TEXT NOT NULL,
+                metadata TEXT NOT NULL,
+                embedding BLOB NOT NULL,
+                created_at INTEGER NOT NULL,
+                PRIMARY KEY (namespace, id)
+            );",
+        )?;
+        Ok(SqliteVectorStore { db: Mutex::new(db) })
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, Connection>, MemoryError> {
+        self.db.lock().map_err(|_| MemoryError::Backend("database lock poisoned".to_string()))
+    }
+}
+
+#[async_trait]
+impl VectorStore for SqliteVectorStore {
+    async fn upsert(
+        &self,
+        namespace: &str,
+        model: &str,
+        document: &Document,
+        embedding: &[f32],
+    ) -> Result<(), MemoryError> {
+        let bytes: Vec<u8> = embedding.iter().flat_map(|x| x.to_le_bytes()).collect();
+        self.lock()?.execute(
+            "INSERT INTO vectors (namespace, id, model, text, metadata, embedding, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+             ON CONFLICT(namespace, id) DO UPDATE SET
+                 model = ?3, text = ?4, metadata = ?5, embedding = ?6",
+            params![
+                namespace,
+                document.id,
+                model,
+                document.text,
+                document.metadata.to_string(),
+                bytes,
+                now()
+            ],
+        )?;
+        Ok(())
+    }
+
+    async fn search(
+        &self,
+        namespace: &str,
+        model: &str,
+        embedding: &[f32],
+        query: &VectorQuery,
+    ) -> Result<Vec<ScoredEntry>, MemoryError> {
+        let db = self.lock()?;
+        let mut statement = db.prepare(
+            "SELECT id, text, metadata, embedding, created_at FROM vectors
+             WHERE namespace = ?1 AND model = ?2",
+        )?;
+        let mut rows = statement.query(params![namespace, model])?;
+        let mut results = vec![];
+        while let Some(row) = rows.next()? {
+            let metadata: String = row.get(2)?;
+            let metadata: Value = serde_json::from_str(&metadata).unwrap_or_default();
+            if !query.filters.iter().all(|filter| filter.matches(&metadata)) {
+                continue;
+            }
+            let bytes: Vec<u8> = row.get(3)?;
+            let score = dot(embedding, &bytes);
+            if query.min_score.is_some_and(|min_score| score < min_score) {
+                continue;
+            }
+            let document = Document { id: row.get(0)?, text: row.get(1)?, metadata };
+            let entry =
+                VectorEntry { namespace: namespace.to_string(), document, created_at: row.get(4)? };
+            results.push(ScoredEntry { entry, score });
+        }
+        results.sort_by(|a, b| b.score.total_cmp(&a.score));
+        results.truncate(query.top_k);
+        Ok(results)
+    }
+
+    async fn delete(&self, namespace: &str, id: &str) -> Result<bool, MemoryError> {
+        let deleted = self.lock()?.execute(
+            "DELETE FROM vectors WHERE namespace = ?1 AND id = ?2",
+            params![namespace, id],
+        )?;
+        Ok(deleted > 0)
+    }
+}
+
+// Dot product of a vector with one stored as little-endian `f32`s
+fn dot(vector: &[f32], bytes: &[u8]) -> f32 {
+    vector
+        .iter()
+        .zip(bytes.chunks_exact(4))
+        .map(|(x, chunk)| x * f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .sum()
+}
+
+fn normalize(vector: &mut [f32]) {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= norm);
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_hash_embedding_is_deterministic_and_unit_length() {
+        let embedding = HashEmbedding::new(64);
+        let texts = vec!["Swapped 10 SOL for USDC".to_string(), "".to_string()];
+        let first = embedding.embed(&texts).await.unwrap();
+        let second = HashEmbedding::new(64).embed(&texts).await.unwrap();
+        assert_eq!(first, second);
+        let norm: f32 = first[0].iter().map(|x| x * x).sum();
+        assert!((norm - 1.0).abs() < 1e-5);
+        assert!(first[1].iter().all(|x| *x == 0.0));
+    }
+}
@@ -1,14 +1,24 @@
-use tracing::Subscriber;
+use tracing::{subscriber::NoSubscriber, Subscriber};
 
 // Logger configuration structure
 pub struct Logger {
     subscriber: Box<dyn Subscriber + Send + Sync>,
 }
 
+impl Default for Logger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Logger {
     pub fn new() -> Self {
         Logger {
-            subscriber: Box::new(tracing::subscriber::default()),
+            subscriber: Box::new(NoSubscriber::default()),
         }
     }
+
+    pub fn subscriber(&self) -> &(dyn Subscriber + Send + Sync) {
+        self.subscriber.as_ref()
+    }
 }
\ No newline at end of file
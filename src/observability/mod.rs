@@ -1,2 +1,5 @@
 pub mod logging;
-pub mod monitoring;
\ No newline at end of file
+pub mod monitoring;
+
+pub use logging::Logger;
+pub use monitoring::Monitoring;
\ No newline at end of file
@@ -3,10 +3,20 @@ pub struct Monitoring {
     metrics_endpoint: String,
 }
 
+impl Default for Monitoring {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Monitoring {
     pub fn new() -> Self {
         Monitoring {
             metrics_endpoint: String::from("http://127.0.0.1:9090"),
         }
     }
+
+    pub fn metrics_endpoint(&self) -> &str {
+        &self.metrics_endpoint
+    }
 }
\ No newline at end of file
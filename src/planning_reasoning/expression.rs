@@ -0,0 +1,476 @@
+//! Condition expression language for rules, e.g. `price("SOL") < 120 && balance("USDC") > 500`
+
+use std::fmt;
+
+use serde_json::Value;
+
+// Error raised while parsing an expression, with the character offset it occurred at
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOp {
+    Not,
+    Neg,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+// Parsed expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Literal(Value),
+    // Dotted path into the agent state, e.g. `position.size`
+    Variable(String),
+    Call { name: String, args: Vec<Expr> },
+    Unary { op: UnaryOp, expr: Box<Expr> },
+    Binary { op: BinaryOp, left: Box<Expr>, right: Box<Expr> },
+}
+
+// Variables and functions available to an expression
+pub trait Scope {
+    fn variable(&self, path: &str) -> Option<Value>;
+    fn call(&self, name: &str, args: &[Value]) -> Result<Value, String>;
+}
+
+impl Expr {
+    pub fn parse(source: &str) -> Result<Expr, ParseError> {
+        let mut parser = Parser { tokens: tokenize(source)?, index: 0, end: source.len() };
+        let expr = parser.or()?;
+        match parser.tokens.get(parser.index) {
+            Some((position, token)) => Err(ParseError {
+                position: *position,
+                message: format!("unexpected {}", token),
+            }),
+            None => Ok(expr),
+        }
+    }
+
+    // Function calls in the expression, innermost first
+    pub fn calls(&self) -> Vec<(&str, &[Expr])> {
+        let mut calls = vec![];
+        self.visit_calls(&mut calls);
+        calls
+    }
+
+    // Variables read by the expression, in order of appearance
+    pub fn variables(&self) -> Vec<&str> {
+        let mut variables = vec![];
+        self.visit_variables(&mut variables);
+        variables
+    }
+
+    fn visit_variables<'a>(&'a self, variables: &mut Vec<&'a str>) {
+        match self {
+            Expr::Variable(path) => variables.push(path),
+            Expr::Call { args, .. } => args.iter().for_each(|a| a.visit_variables(variables)),
+            Expr::Unary { expr, .. } => expr.visit_variables(variables),
+            Expr::Binary { left, right, .. } => {
+                left.visit_variables(variables);
+                right.visit_variables(variables);
+            }
+            Expr::Literal(_) => {}
+        }
+    }
+
+    fn visit_calls<'a>(&'a self, calls: &mut Vec<(&'a str, &'a [Expr])>) {
+        match self {
+            Expr::Call { name, args } => {
+                args.iter().for_each(|a| a.visit_calls(calls));
+                calls.push((name, args));
+            }
+            Expr::Unary { expr, .. } => expr.visit_calls(calls),
+            Expr::Binary { left, right, .. } => {
+                left.visit_calls(calls);
+                right.visit_calls(calls);
+            }
+            Expr::Literal(_) | Expr::Variable(_) => {}
+        }
+    }
+
+    pub fn eval(&self, scope: &dyn Scope) -> Result<Value, String> {
+        match self {
+            Expr::Literal(value) => Ok(value.clone()),
+            Expr::Variable(path) => {
+                scope.variable(path).ok_or(format!("unknown variable '{}'", path))
+            }
+            Expr::Call { name, args } => {
+                let args = args.iter().map(|a| a.eval(scope)).collect::<Result<Vec<_>, _>>()?;
+                scope.call(name, &args)
+            }
+            Expr::Unary { op: UnaryOp::Not, expr } => {
+                Ok(Value::Bool(!as_bool(&expr.eval(scope)?)?))
+            }
+            Expr::Unary { op: UnaryOp::Neg, expr } => number(-as_number(&expr.eval(scope)?)?),
+            // Logical operators short-circuit
+            Expr::Binary { op: BinaryOp::And, left, right } => {
+                Ok(Value::Bool(as_bool(&left.eval(scope)?)? && as_bool(&right.eval(scope)?)?))
+            }
+            Expr::Binary { op: BinaryOp::Or, left, right } => {
+                Ok(Value::Bool(as_bool(&left.eval(scope)?)? || as_bool(&right.eval(scope)?)?))
+            }
+            Expr::Binary { op, left, right } => binary(*op, left.eval(scope)?, right.eval(scope)?),
+        }
+    }
+}
+
+fn binary(op: BinaryOp, left: Value, right: Value) -> Result<Value, String> {
+    match op {
+        BinaryOp::Eq => Ok(Value::Bool(equal(&left, &right))),
+        BinaryOp::Ne => Ok(Value::Bool(!equal(&left, &right))),
+        BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => {
+            let ordering = match (&left, &right) {
+                (Value::String(l), Value::String(r)) => l.cmp(r),
+                _ => as_number(&left)?
+                    .partial_cmp(&as_number(&right)?)
+                    .ok_or("cannot compare NaN")?,
+            };
+            Ok(Value::Bool(match op {
+                BinaryOp::Lt => ordering.is_lt(),
+                BinaryOp::Le => ordering.is_le(),
+                BinaryOp::Gt => ordering.is_gt(),
+                _ => ordering.is_ge(),
+            }))
+        }
+        BinaryOp::Add => match (&left, &right) {
+            (Value::String(l), Value::String(r)) => Ok(Value::String(format!("{}{}", l, r))),
+            _ => number(as_number(&left)? + as_number(&right)?),
+        },
+        BinaryOp::Sub => number(as_number(&left)? - as_number(&right)?),
+        BinaryOp::Mul => number(as_number(&left)? * as_number(&right)?),
+        BinaryOp::Div => {
+            let divisor = as_number(&right)?;
+            if divisor == 0.0 {
+                return Err("division by zero".to_string());
+            }
+            number(as_number(&left)? / divisor)
+        }
+        BinaryOp::And | BinaryOp::Or => unreachable!("logical operators are evaluated lazily"),
+    }
+}
+
+// Numbers compare by value, so `1 == 1.0`
+fn equal(left: &Value, right: &Value) -> bool {
+    match (left.as_f64(), right.as_f64()) {
+        (Some(l), Some(r)) => l == r,
+        _ => left == right,
+    }
+}
+
+fn number(n: f64) -> Result<Value, String> {
+    serde_json::Number::from_f64(n).map(Value::Number).ok_or(format!("{} is not a number", n))
+}
+
+pub fn as_number(value: &Value) -> Result<f64, String> {
+    value.as_f64().ok_or(format!("expected a number, got {}", value))
+}
+
+pub fn as_bool(value: &Value) -> Result<bool, String> {
+    value.as_bool().ok_or(format!("expected a boolean, got {}", value))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Str(String),
+    Ident(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+    Comma,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "number {}", n),
+            Token::Str(s) => write!(f, "string \"{}\"", s),
+            Token::Ident(name) => write!(f, "'{}'", name),
+            Token::Op(op) => write!(f, "'{}'", op),
+            Token::LParen => write!(f, "'('"),
+            Token::RParen => write!(f, "')'"),
+            Token::Comma => write!(f, "','"),
+        }
+    }
+}
+
+const OPERATORS: [&str; 13] =
+    ["&&", "||", "==", "!=", "<=", ">=", "<", ">", "!", "+", "-", "*", "/"];
+
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>, ParseError> {
+    let mut tokens = vec![];
+    let chars: Vec<(usize, char)> = source.char_indices().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let (position, c) = chars[i];
+        let error = |message: &str| ParseError { position, message: message.to_string() };
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit()
+            || (c == '.' && chars.get(i + 1).is_some_and(|(_, n)| n.is_ascii_digit()))
+        {
+            let start = i;
+            while i < chars.len() && (chars[i].1.is_ascii_digit() || chars[i].1 == '.') {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().map(|(_, c)| c).collect();
+            let n = text.parse().map_err(|_| error("invalid number"))?;
+            tokens.push((position, Token::Number(n)));
+        } else if c == '"' || c == '\'' {
+            let mut text = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err(error("unterminated string")),
+                    Some((_, '\\')) => {
+                        text.extend(chars.get(i + 1).map(|(_, c)| c));
+                        i += 2;
+                    }
+                    Some((_, q)) if *q == c => break,
+                    Some((_, ch)) => {
+                        text.push(*ch);
+                        i += 1;
+                    }
+                }
+            }
+            i += 1;
+            tokens.push((position, Token::Str(text)));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].1.is_alphanumeric() || "_.".contains(chars[i].1)) {
+                i += 1;
+            }
+            tokens.push((position, Token::Ident(chars[start..i].iter().map(|(_, c)| c).collect())));
+        } else if c == '(' {
+            tokens.push((position, Token::LParen));
+            i += 1;
+        } else if c == ')' {
+            tokens.push((position, Token::RParen));
+            i += 1;
+        } else if c == ',' {
+            tokens.push((position, Token::Comma));
+            i += 1;
+        } else {
+            let rest = &source[position..];
+            let op = OPERATORS
+                .iter()
+                .find(|op| rest.starts_with(*op))
+                .ok_or_else(|| error(&format!("unexpected character '{}'", c)))?;
+            tokens.push((position, Token::Op(op)));
+            i += op.len();
+        }
+    }
+    Ok(tokens)
+}
+
+// Recursive descent parser, one method per precedence level
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    index: usize,
+    end: usize,
+}
+
+impl Parser {
+    fn peek_op(&self, ops: &[&'static str]) -> Option<&'static str> {
+        match self.tokens.get(self.index) {
+            Some((_, Token::Op(op))) if ops.contains(op) => Some(op),
+            _ => None,
+        }
+    }
+
+    fn next_is(&self, token: &Token) -> bool {
+        self.tokens.get(self.index).is_some_and(|(_, t)| t == token)
+    }
+
+    fn error(&self, message: String) -> ParseError {
+        let position = self.tokens.get(self.index).map_or(self.end, |(p, _)| *p);
+        ParseError { position, message }
+    }
+
+    fn binary_level(
+        &mut self,
+        ops: &[&'static str],
+        next: fn(&mut Self) -> Result<Expr, ParseError>,
+    ) -> Result<Expr, ParseError> {
+        let mut left = next(self)?;
+        while let Some(op) = self.peek_op(ops) {
+            self.index += 1;
+            let right = next(self)?;
+            let op = match op {
+                "||" => BinaryOp::Or,
+                "&&" => BinaryOp::And,
+                "==" => BinaryOp::Eq,
+                "!=" => BinaryOp::Ne,
+                "<" => BinaryOp::Lt,
+                "<=" => BinaryOp::Le,
+                ">" => BinaryOp::Gt,
+                ">=" => BinaryOp::Ge,
+                "+" => BinaryOp::Add,
+                "-" => BinaryOp::Sub,
+                "*" => BinaryOp::Mul,
+                _ => BinaryOp::Div,
+            };
+            left = Expr::Binary { op, left: Box::new(left), right: Box::new(right) };
+        }
+        Ok(left)
+    }
+
+    fn or(&mut self) -> Result<Expr, ParseError> {
+        self.binary_level(&["||"], Self::and)
+    }
+
+    fn and(&mut self) -> Result<Expr, ParseError> {
+        self.binary_level(&["&&"], Self::equality)
+    }
+
+    fn equality(&mut self) -> Result<Expr, ParseError> {
+        self.binary_level(&["==", "!="], Self::comparison)
+    }
+
+    fn comparison(&mut self) -> Result<Expr, ParseError> {
+        self.binary_level(&["<", "<=", ">", ">="], Self::additive)
+    }
+
+    fn additive(&mut self) -> Result<Expr, ParseError> {
+        self.binary_level(&["+", "-"], Self::multiplicative)
+    }
+
+    fn multiplicative(&mut self) -> Result<Expr, ParseError> {
+        self.binary_level(&["*", "/"], Self::unary)
+    }
+
+    fn unary(&mut self) -> Result<Expr, ParseError> {
+        let op = match self.peek_op(&["!", "-"]) {
+            Some("!") => UnaryOp::Not,
+            Some(_) => UnaryOp::Neg,
+            None => return self.primary(),
+        };
+        self.index += 1;
+        Ok(Expr::Unary { op, expr: Box::new(self.unary()?) })
+    }
+
+    fn primary(&mut self) -> Result<Expr, ParseError> {
+        let Some((_, token)) = self.tokens.get(self.index).cloned() else {
+            return Err(self.error("unexpected end of expression".to_string()));
+        };
+        self.index += 1;
+        match token {
+            Token::Number(n) => Ok(Expr::Literal(serde_json::json!(n))),
+            Token::Str(s) => Ok(Expr::Literal(Value::String(s))),
+            Token::Ident(name) if name == "true" => Ok(Expr::Literal(Value::Bool(true))),
+            Token::Ident(name) if name == "false" => Ok(Expr::Literal(Value::Bool(false))),
+            Token::Ident(name) if name == "null" => Ok(Expr::Literal(Value::Null)),
+            Token::Ident(name) if self.next_is(&Token::LParen) => {
+                self.index += 1;
+                let mut args = vec![];
+                if !self.next_is(&Token::RParen) {
+                    loop {
+                        args.push(self.or()?);
+                        if !self.next_is(&Token::Comma) {
+                            break;
+                        }
+                        self.index += 1;
+                    }
+                }
+                self.expect(Token::RParen)?;
+                Ok(Expr::Call { name, args })
+            }
+            Token::Ident(name) => Ok(Expr::Variable(name)),
+            Token::LParen => {
+                let expr = self.or()?;
+                self.expect(Token::RParen)?;
+                Ok(expr)
+            }
+            token => {
+                self.index -= 1;
+                Err(self.error(format!("unexpected {}", token)))
+            }
+        }
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), ParseError> {
+        match self.tokens.get(self.index) {
+            Some((_, token)) if *token == expected => {
+                self.index += 1;
+                Ok(())
+            }
+            _ => Err(self.error(format!("expected {}", expected))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use serde_json::json;
+
+    struct TestScope(HashMap<String, Value>);
+
+    impl Scope for TestScope {
+        fn variable(&self, path: &str) -> Option<Value> {
+            self.0.get(path).cloned()
+        }
+
+        fn call(&self, name: &str, args: &[Value]) -> Result<Value, String> {
+            match (name, args) {
+                ("price", [Value::String(mint)]) if mint == "SOL" => Ok(json!(118.5)),
+                ("balance", [Value::String(mint)]) if mint == "USDC" => Ok(json!(750)),
+                _ => Err(format!("unknown function '{}'", name)),
+            }
+        }
+    }
+
+    fn eval(source: &str) -> Result<Value, String> {
+        let scope = TestScope(HashMap::from([("position.size".to_string(), json!(2))]));
+        Expr::parse(source).map_err(|e| e.to_string())?.eval(&scope)
+    }
+
+    #[test]
+    fn test_eval_conditions() {
+        assert_eq!(eval(r#"price("SOL") < 120 && balance("USDC") > 500"#), Ok(json!(true)));
+        assert_eq!(eval(r#"!(price("SOL") >= 100) || position.size * 2 == 4"#), Ok(json!(true)));
+        assert_eq!(eval("1 + 2 * 3 - -1"), Ok(json!(8.0)));
+        assert_eq!(eval("'a' + \"b\" == 'ab'"), Ok(json!(true)));
+    }
+
+    #[test]
+    fn test_parse_errors_have_positions() {
+        let err = Expr::parse("price(\"SOL\" < 120").unwrap_err();
+        assert_eq!(err, ParseError { position: 17, message: "expected ')'".to_string() });
+        assert_eq!(Expr::parse("1 +").unwrap_err().position, 3);
+    }
+
+    #[test]
+    fn test_calls_are_collected() {
+        let expr = Expr::parse(r#"max(price("SOL"), 1) > 0"#).unwrap();
+        let names: Vec<&str> = expr.calls().iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, vec!["price", "max"]);
+    }
+}
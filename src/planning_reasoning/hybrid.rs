@@ -0,0 +1,121 @@
+//! Hybrid decision mode: deterministic rules first, the LLM planner for everything else
+
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::planning_reasoning::{
+    llm_planner::{LLMPlanner, PlanExecution},
+    rule_engine::{RuleEngine, RuleOutcome, RuleState, RuleStatus},
+};
+
+// Where a decision came from
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DecisionSource {
+    Rule { rule_id: String },
+    // The planner decided, either because no rule matched or because `escalated_by` handed
+    // the decision over
+    Llm { escalated_by: Option<String> },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DecisionOutcome {
+    // Action taken by the deciding rule
+    Action(RuleOutcome),
+    // Plan made and run by the LLM planner
+    Plan(PlanExecution),
+    // Nothing done: the deciding rule could not be evaluated or is cooling down, so neither a
+    // lower priority rule nor the planner may act in its place
+    Stopped { reason: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Decision {
+    pub goal: String,
+    pub source: DecisionSource,
+    pub outcome: DecisionOutcome,
+    // Every rule as evaluated for this decision, highest priority first
+    pub rules: Vec<RuleOutcome>,
+}
+
+// Planner consulting the rule engine before the LLM
+pub struct HybridPlanner {
+    rules: Arc<RuleEngine>,
+    planner: Arc<LLMPlanner>,
+}
+
+impl HybridPlanner {
+    pub fn new(rules: Arc<RuleEngine>, planner: Arc<LLMPlanner>) -> Self {
+        HybridPlanner { rules, planner }
+    }
+
+    // Fires the matching rule with the highest priority. The LLM planner only plans and runs
+    // the goal when no rule matches or the matching rule escalates. A rule above it that failed
+    // to evaluate or is cooling down stops the decision, as that rule may own the situation.
+    pub async fn decide(
+        &self,
+        goal: &str,
+        state: &RuleState,
+    ) -> Result<Decision, Box<dyn std::error::Error>> {
+        let mut rules = self.rules.dry_run(state).await;
+        let decision = |rule_id: &str, outcome, rules| Decision {
+            goal: goal.to_string(),
+            source: DecisionSource::Rule { rule_id: rule_id.to_string() },
+            outcome,
+            rules,
+        };
+
+        let mut escalation = None;
+        let deciding = rules.iter().position(|o| o.status != RuleStatus::NotMatched);
+        if let Some(index) = deciding {
+            let rule_id = rules[index].rule_id.clone();
+            if let Some(reason) = stop_reason(&rules[index]) {
+                return Ok(decision(&rule_id, DecisionOutcome::Stopped { reason }, rules));
+            }
+
+            // Another caller may have fired the rule since the dry run
+            let fired = self.rules.fire(rules[index].clone()).await;
+            rules[index] = fired.clone();
+            if let Some(reason) = stop_reason(&fired) {
+                return Ok(decision(&rule_id, DecisionOutcome::Stopped { reason }, rules));
+            }
+            match &fired.status {
+                RuleStatus::Escalated { reason } => escalation = Some((rule_id, reason.clone())),
+                _ => return Ok(decision(&rule_id, DecisionOutcome::Action(fired), rules)),
+            }
+        }
+
+        let planned_goal = match &escalation {
+            Some((rule_id, reason)) if !reason.is_empty() => {
+                format!("{}\n\nEscalated by rule '{}': {}", goal, rule_id, reason)
+            }
+            _ => goal.to_string(),
+        };
+        let plan = self.planner.plan(&planned_goal).await?;
+        let execution = self.planner.execute(&plan).await?;
+        Ok(Decision {
+            goal: goal.to_string(),
+            source: DecisionSource::Llm { escalated_by: escalation.map(|(rule_id, _)| rule_id) },
+            outcome: DecisionOutcome::Plan(execution),
+            rules,
+        })
+    }
+}
+
+// Why a rule that failed to evaluate or is cooling down stops a decision
+fn stop_reason(outcome: &RuleOutcome) -> Option<String> {
+    let rule_id = &outcome.rule_id;
+    match &outcome.status {
+        RuleStatus::Error { message } => {
+            tracing::warn!(rule = %rule_id, "Rule evaluation failed: {}", message);
+            Some(format!("rule '{}' could not be evaluated: {}", rule_id, message))
+        }
+        RuleStatus::CoolingDown { remaining_secs } => Some(format!(
+            "rule '{}' matched but is cooling down for {}s",
+            rule_id, remaining_secs
+        )),
+        _ => None,
+    }
+}
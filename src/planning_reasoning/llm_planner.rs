@@ -1,14 +1,552 @@
-///! LLM-based planner for task decomposition
+//! LLM-based planner for task decomposition
+
+use std::{collections::HashMap, fmt, sync::Arc};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    llm_integration::{
+        message::ChatMessage,
+        prompt::{PromptConfig, PLAN_TEMPLATE, REPLAN_TEMPLATE},
+        structured::OutputSchema,
+        LLMClient, LLMProvider,
+    },
+    planning_reasoning::template,
+    tool_system::{ToolInput, ToolRegistry},
+};
+
+// Single step of a plan
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+pub struct PlanStep {
+    pub id: String,
+    pub tool: String,
+    // Tool arguments; strings may reference earlier outputs as `${steps.<id>.output}`
+    #[serde(default)]
+    pub arguments: Value,
+    // Steps that must finish first; steps with no dependency between them may run in parallel
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    #[serde(default)]
+    pub expected_outcome: String,
+}
+
+// Plan for reaching a goal, serializable so it can be reviewed and edited before execution
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+pub struct Plan {
+    #[serde(default)]
+    pub goal: String,
+    pub steps: Vec<PlanStep>,
+}
+
+impl Plan {
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    pub fn step(&self, id: &str) -> Option<&PlanStep> {
+        self.steps.iter().find(|s| s.id == id)
+    }
+
+    pub fn step_mut(&mut self, id: &str) -> Option<&mut PlanStep> {
+        self.steps.iter_mut().find(|s| s.id == id)
+    }
+
+    // Checks the plan against the registry: known tools, well-formed dependencies, and
+    // arguments matching each tool's schema. Returns every problem found.
+    pub async fn validate(&self, registry: &ToolRegistry) -> Result<(), InvalidPlan> {
+        self.validate_after(registry, &[]).await
+    }
+
+    // Like `validate`, for steps that continue after the `completed` steps and may depend on them
+    pub async fn validate_after(
+        &self,
+        registry: &ToolRegistry,
+        completed: &[String],
+    ) -> Result<(), InvalidPlan> {
+        if self.steps.is_empty() {
+            return Err(InvalidPlan {
+                issues: vec![PlanIssue { step: None, message: "plan has no steps".to_string() }],
+            });
+        }
+        let mut issues = vec![];
+        let mut issue = |step: &PlanStep, message: String| {
+            issues.push(PlanIssue { step: Some(step.id.clone()), message })
+        };
+
+        for (index, step) in self.steps.iter().enumerate() {
+            let earlier = &self.steps[..index];
+            if step.id.trim().is_empty() {
+                issue(step, "step id is empty".to_string());
+            } else if earlier.iter().any(|s| s.id == step.id) || completed.contains(&step.id) {
+                issue(step, "duplicate step id".to_string());
+            }
+            // Dependencies must come earlier in the list, which also rules out cycles
+            for dependency in &step.depends_on {
+                if !earlier.iter().any(|s| &s.id == dependency) && !completed.contains(dependency)
+                {
+                    let message =
+                        format!("depends on '{}', which is not an earlier step", dependency);
+                    issue(step, message);
+                }
+            }
+            for referenced in template::referenced(&step.arguments, "steps") {
+                if !step.depends_on.contains(&referenced) {
+                    let message =
+                        format!("uses the output of '{}' without depending on it", referenced);
+                    issue(step, message);
+                }
+            }
+
+            let Some((metadata, _)) = registry.get(&step.tool).await else {
+                issue(step, format!("unknown tool '{}'", step.tool));
+                continue;
+            };
+            if let Some(parameters) = metadata.parameters() {
+                for message in check_arguments(parameters, &step.arguments) {
+                    issue(step, message);
+                }
+            }
+        }
+
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(InvalidPlan { issues })
+        }
+    }
+
+    // Steps grouped into waves: every step of a wave only depends on earlier waves
+    pub fn waves(&self) -> Vec<Vec<&PlanStep>> {
+        let mut wave_of: HashMap<&str, usize> = HashMap::new();
+        let mut waves: Vec<Vec<&PlanStep>> = vec![];
+        for step in &self.steps {
+            let wave = step
+                .depends_on
+                .iter()
+                .filter_map(|d| wave_of.get(d.as_str()))
+                .map(|w| w + 1)
+                .max()
+                .unwrap_or(0);
+            wave_of.insert(&step.id, wave);
+            if waves.len() <= wave {
+                waves.resize(wave + 1, vec![]);
+            }
+            waves[wave].push(step);
+        }
+        waves
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Plan: {}", self.goal)?;
+        for (index, step) in self.steps.iter().enumerate() {
+            write!(f, "{}. [{}] {} {}", index + 1, step.id, step.tool, step.arguments)?;
+            if !step.depends_on.is_empty() {
+                write!(f, " (after {})", step.depends_on.join(", "))?;
+            }
+            if !step.expected_outcome.is_empty() {
+                write!(f, " -> {}", step.expected_outcome)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+// Checks arguments against a tool's JSON Schema. Templated values are only checked for
+// presence, as their type is known once the referenced output exists.
+fn check_arguments(parameters: &Value, arguments: &Value) -> Vec<String> {
+    let mut messages = vec![];
+    let Some(arguments) = arguments.as_object() else {
+        return vec!["arguments must be an object".to_string()];
+    };
+    let properties = parameters["properties"].as_object();
+    for required in parameters["required"].as_array().into_iter().flatten() {
+        if let Some(name) = required.as_str() {
+            if !arguments.contains_key(name) {
+                messages.push(format!("missing required argument '{}'", name));
+            }
+        }
+    }
+    for (name, value) in arguments {
+        let Some(schema) = properties.and_then(|p| p.get(name)) else {
+            if properties.is_some() {
+                messages.push(format!("unknown argument '{}'", name));
+            }
+            continue;
+        };
+        if template::is_templated(value) {
+            continue;
+        }
+        if let Err(errors) = OutputSchema::new(name, schema.clone()).validate(value) {
+            messages.extend(errors.into_iter().map(|e| format!("argument '{}': {}", name, e)));
+        }
+    }
+    messages
+}
+
+// Steps proposed by the model, with its reason when revising a failed plan
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct PlanDraft {
+    #[serde(default)]
+    pub reason: String,
+    pub steps: Vec<PlanStep>,
+}
+
+// Problem found while validating a plan
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlanIssue {
+    pub step: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for PlanIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.step {
+            Some(step) => write!(f, "step '{}': {}", step, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+// Returned when a plan fails validation
+#[derive(Debug)]
+pub struct InvalidPlan {
+    pub issues: Vec<PlanIssue>,
+}
+
+impl fmt::Display for InvalidPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let issues: Vec<String> = self.issues.iter().map(|i| i.to_string()).collect();
+        write!(f, "Invalid plan: {}", issues.join("; "))
+    }
+}
+
+impl std::error::Error for InvalidPlan {}
+
+// Outcome of a single executed step
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StepResult {
+    pub step_id: String,
+    pub tool: String,
+    // Arguments after templates were resolved
+    pub arguments: Value,
+    pub output: Result<String, String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum PlanStatus {
+    Completed,
+    Failed { step_id: String, error: String },
+}
+
+// Record of a replan after a failed step
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ReplanRecord {
+    pub failed_step: String,
+    pub error: String,
+    // The model's explanation of what went wrong and what it changed
+    pub reason: String,
+    // Steps that were dropped, i.e. the failed ones and those not yet run
+    pub replaced: Vec<String>,
+    pub new_steps: Vec<PlanStep>,
+}
+
+// Execution trace of a plan
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PlanExecution {
+    // Plan as finally executed: the completed steps followed by the latest revision
+    pub plan: Plan,
+    pub results: Vec<StepResult>,
+    pub status: PlanStatus,
+    #[serde(default)]
+    pub replans: Vec<ReplanRecord>,
+}
+
+impl PlanExecution {
+    // Ids of the steps that succeeded
+    pub fn completed(&self) -> Vec<String> {
+        self.results.iter().filter(|r| r.output.is_ok()).map(|r| r.step_id.clone()).collect()
+    }
+
+    // Outputs of the steps that succeeded, by step id
+    pub fn outputs(&self) -> HashMap<String, String> {
+        self.results
+            .iter()
+            .filter_map(|r| r.output.as_ref().ok().map(|o| (r.step_id.clone(), o.clone())))
+            .collect()
+    }
+}
 
 // LLM Planner structure
 pub struct LLMPlanner {
-    model: String,
+    llm_client: Arc<LLMClient>,
+    tool_registry: Arc<ToolRegistry>,
+    prompts: Arc<PromptConfig>,
+    // Provider used for planning; the client's default when unset
+    provider: Option<String>,
+    // Times the model is re-asked after producing an invalid plan
+    max_retries: u32,
+    // Times a failed plan may be revised during one execution
+    max_replans: u32,
 }
 
 impl LLMPlanner {
-    pub fn new() -> Self {
+    pub fn new(llm_client: Arc<LLMClient>, tool_registry: Arc<ToolRegistry>) -> Self {
         LLMPlanner {
-            model: String::from("grok3"),
+            llm_client,
+            tool_registry,
+            prompts: Arc::new(PromptConfig::new()),
+            provider: None,
+            max_retries: 2,
+            max_replans: 0,
         }
     }
-}
\ No newline at end of file
+
+    pub fn with_provider(mut self, provider: &str) -> Self {
+        self.provider = Some(provider.to_string());
+        self
+    }
+
+    pub fn with_prompts(mut self, prompts: Arc<PromptConfig>) -> Self {
+        self.prompts = prompts;
+        self
+    }
+
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    // Lets `execute` ask the model for a revised remainder when a step fails
+    pub fn with_max_replans(mut self, max_replans: u32) -> Self {
+        self.max_replans = max_replans;
+        self
+    }
+
+    async fn provider_name(&self) -> Result<String, Box<dyn std::error::Error>> {
+        match &self.provider {
+            Some(provider) => Ok(provider.clone()),
+            None => {
+                Ok(self.llm_client.default_provider().await.ok_or("No LLM provider registered")?)
+            }
+        }
+    }
+
+    // Tool catalogue shown to the model
+    async fn catalogue(&self) -> String {
+        let mut tools = self.tool_registry.list().await;
+        tools.sort_by(|a, b| a.name.cmp(&b.name));
+        tools
+            .iter()
+            .map(|t| {
+                let parameters = t.parameters().cloned().unwrap_or(Value::Null);
+                format!("- {}: {} Parameters: {}", t.name, t.description(), parameters)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    // Asks the model for a plan reaching `goal`, re-asking with the validation issues until
+    // the plan is valid or the retries are used up
+    pub async fn plan(&self, goal: &str) -> Result<Plan, Box<dyn std::error::Error>> {
+        let variables = HashMap::from([
+            ("goal".to_string(), goal.to_string()),
+            ("tools".to_string(), self.catalogue().await),
+        ]);
+        let messages = self.prompts.render(PLAN_TEMPLATE, goal, &variables)?.messages;
+        let draft = self.draft(messages, goal, &[]).await?;
+        Ok(Plan { goal: goal.to_string(), steps: draft.steps })
+    }
+
+    // Requests steps for `messages`, feeding validation issues back to the model
+    async fn draft(
+        &self,
+        mut messages: Vec<ChatMessage>,
+        goal: &str,
+        completed: &[String],
+    ) -> Result<PlanDraft, Box<dyn std::error::Error>> {
+        let provider = self.provider_name().await?;
+        let schema = OutputSchema::for_type::<PlanDraft>();
+        let mut last_error = None;
+        for _ in 0..=self.max_retries {
+            let value = self.llm_client.chat_json(&provider, &messages, &schema).await?;
+            let draft: PlanDraft = serde_json::from_value(value)?;
+            let plan = Plan { goal: goal.to_string(), steps: draft.steps.clone() };
+            match plan.validate_after(&self.tool_registry, completed).await {
+                Ok(()) => return Ok(draft),
+                Err(invalid) => {
+                    messages.push(ChatMessage::assistant(plan.to_json(), vec![]));
+                    messages.push(ChatMessage::user(format!(
+                        "{}\nReply again with the corrected plan.",
+                        invalid
+                    )));
+                    last_error = Some(invalid);
+                }
+            }
+        }
+        Err(Box::new(last_error.unwrap_or(InvalidPlan { issues: vec![] })))
+    }
+
+    // Asks the model to replace the failed and not yet run steps of `execution`
+    async fn revise(
+        &self,
+        execution: &PlanExecution,
+        remaining: &[PlanStep],
+    ) -> Result<PlanDraft, Box<dyn std::error::Error>> {
+        let completed = execution.completed();
+        let describe = |lines: Vec<String>| {
+            if lines.is_empty() {
+                "(none)".to_string()
+            } else {
+                lines.join("\n")
+            }
+        };
+        let completed_lines: Vec<String> = execution
+            .results
+            .iter()
+            .filter_map(|r| {
+                let output = r.output.as_ref().ok()?;
+                Some(format!("- {} ({} {}): {}", r.step_id, r.tool, r.arguments, output))
+            })
+            .collect();
+        let failed_lines: Vec<String> = execution
+            .results
+            .iter()
+            .filter_map(|r| {
+                let error = r.output.as_ref().err()?;
+                Some(format!("- {} ({} {}): {}", r.step_id, r.tool, r.arguments, error))
+            })
+            .collect();
+        let remaining_lines: Vec<String> = remaining
+            .iter()
+            .filter(|s| !execution.results.iter().any(|r| r.step_id == s.id))
+            .map(|s| format!("- {} ({} {})", s.id, s.tool, s.arguments))
+            .collect();
+
+        let goal = execution.plan.goal.clone();
+        let variables = HashMap::from([
+            ("goal".to_string(), goal.clone()),
+            ("tools".to_string(), self.catalogue().await),
+            ("completed".to_string(), describe(completed_lines)),
+            ("failed".to_string(), describe(failed_lines)),
+            ("remaining".to_string(), describe(remaining_lines)),
+        ]);
+        let messages = self.prompts.render(REPLAN_TEMPLATE, &goal, &variables)?.messages;
+        self.draft(messages, &goal, &completed).await
+    }
+
+    // Validates the whole plan, then runs it wave by wave, running the steps of a wave
+    // concurrently. When a step fails, the failed and remaining steps are replaced by a
+    // revision from the model, up to `max_replans` times; otherwise execution stops there.
+    pub async fn execute(&self, plan: &Plan) -> Result<PlanExecution, Box<dyn std::error::Error>> {
+        plan.validate(&self.tool_registry).await?;
+        let mut execution = PlanExecution {
+            plan: plan.clone(),
+            results: vec![],
+            status: PlanStatus::Completed,
+            replans: vec![],
+        };
+        let mut remaining = plan.steps.clone();
+        loop {
+            self.run_steps(&mut execution, &remaining).await?;
+            let PlanStatus::Failed { step_id, error } = execution.status.clone() else { break };
+            if execution.replans.len() as u32 >= self.max_replans {
+                break;
+            }
+
+            let revision = match self.revise(&execution, &remaining).await {
+                Ok(revision) => revision,
+                Err(err) => {
+                    tracing::warn!(step = %step_id, error = %err, "Replanning failed");
+                    break;
+                }
+            };
+            let completed = execution.completed();
+            let replaced =
+                remaining.iter().filter(|s| !completed.contains(&s.id)).map(|s| s.id.clone());
+            execution.replans.push(ReplanRecord {
+                failed_step: step_id,
+                error,
+                reason: revision.reason,
+                replaced: replaced.collect(),
+                new_steps: revision.steps.clone(),
+            });
+            execution.plan.steps.retain(|s| completed.contains(&s.id));
+            execution.plan.steps.extend(revision.steps.clone());
+            remaining = revision.steps;
+        }
+        Ok(execution)
+    }
+
+    // Runs `steps` of `execution.plan`, appending to its results
+    async fn run_steps(
+        &self,
+        execution: &mut PlanExecution,
+        steps: &[PlanStep],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let provider_name = self.provider_name().await?;
+        let llm = self
+            .llm_client
+            .provider(&provider_name)
+            .await
+            .ok_or(format!("LLM provider '{}' not found", provider_name))?;
+        let pending = Plan { goal: execution.plan.goal.clone(), steps: steps.to_vec() };
+
+        for wave in pending.waves() {
+            let outputs = execution.outputs();
+            let runs = wave.iter().map(|step| {
+                let llm = llm.clone();
+                let outputs = &outputs;
+                async move {
+                    let arguments = template::resolve(&step.arguments, "steps", outputs);
+                    let output = match &arguments {
+                        Ok(arguments) => self.run_step(step, arguments.clone(), llm.as_ref()).await,
+                        Err(err) => Err(err.clone()),
+                    };
+                    StepResult {
+                        step_id: step.id.clone(),
+                        tool: step.tool.clone(),
+                        arguments: arguments.unwrap_or(Value::Null),
+                        output,
+                    }
+                }
+            });
+            let results = futures::future::join_all(runs).await;
+            let failed = results.iter().find_map(|r| {
+                r.output.as_ref().err().map(|e| PlanStatus::Failed {
+                    step_id: r.step_id.clone(),
+                    error: e.clone(),
+                })
+            });
+            execution.results.extend(results);
+            if let Some(failed) = failed {
+                execution.status = failed;
+                return Ok(());
+            }
+        }
+        execution.status = PlanStatus::Completed;
+        Ok(())
+    }
+
+    async fn run_step(
+        &self,
+        step: &PlanStep,
+        arguments: Value,
+        llm: &dyn LLMProvider,
+    ) -> Result<String, String> {
+        let (_, tool) = self
+            .tool_registry
+            .get(&step.tool)
+            .await
+            .ok_or(format!("Tool '{}' not found", step.tool))?;
+        tool.execute(ToolInput::new(arguments), llm).await.map_err(|e| e.to_string())
+    }
+}
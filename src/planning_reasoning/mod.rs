@@ -1,2 +1,5 @@
 pub mod llm_planner;
-pub mod rule_engine;
\ No newline at end of file
+pub mod rule_engine;
+pub mod template;
+pub mod expression;
+pub mod hybrid;
\ No newline at end of file
@@ -1,19 +1,416 @@
-///! Rule-based engine for decision-making
+//! Rule-based engine for decision-making
 
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
-// Rule Engine structure
-pub struct RuleEngine {
-    rules: Vec<Rule>,
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    llm_integration::{LLMProvider, NoLlmProvider},
+    planning_reasoning::{
+        expression::{as_number, Expr, ParseError, Scope},
+        template,
+    },
+    tool_system::{ToolInput, ToolRegistry},
+};
+
+// Agent state that conditions read as variables, e.g. `position.size`
+pub type RuleState = HashMap<String, Value>;
+
+// Tool that computes a condition function, e.g. `price("SOL")` calling `get_price` with
+// `{"mint": "SOL"}`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FunctionBinding {
+    pub tool: String,
+    // Names the positional arguments are passed to the tool as
+    #[serde(default)]
+    pub params: Vec<String>,
+    // Dotted path of the value in a JSON output; the whole output when unset
+    #[serde(default)]
+    pub output_path: Option<String>,
+}
+
+// Action taken when a rule matches
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RuleAction {
+    // Calls a registered tool. Argument strings may embed expressions as `${...}`,
+    // e.g. `"${balance(\"USDC\") * 0.1}"`.
+    Tool {
+        tool: String,
+        #[serde(default)]
+        arguments: Value,
+    },
+    // Hands the decision over to the LLM planner; the reason may embed `${...}` as well
+    Escalate {
+        #[serde(default)]
+        reason: String,
+    },
+}
+
+impl RuleAction {
+    // Part of the action that may hold `${...}` expressions
+    fn template(&self) -> Value {
+        match self {
+            RuleAction::Tool { arguments, .. } => arguments.clone(),
+            RuleAction::Escalate { reason } => Value::String(reason.clone()),
+        }
+    }
+
+    // Same action with its template replaced by the rendered value
+    fn rendered(&self, value: Value) -> RuleAction {
+        match self {
+            RuleAction::Tool { tool, .. } => {
+                RuleAction::Tool { tool: tool.clone(), arguments: value }
+            }
+            RuleAction::Escalate { .. } => RuleAction::Escalate {
+                reason: value.as_str().map(String::from).unwrap_or(value.to_string()),
+            },
+        }
+    }
 }
 
 // Single rule definition
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Rule {
-    condition: String,
-    action: String,
+    pub id: String,
+    pub condition: String,
+    pub action: RuleAction,
+    // Rules are evaluated and fired from the highest priority down
+    #[serde(default)]
+    pub priority: i32,
+    // Minimum time between two firings of the rule
+    #[serde(default)]
+    pub cooldown_secs: u64,
+}
+
+// Error raised when adding a rule
+#[derive(Debug)]
+pub enum RuleError {
+    Duplicate(String),
+    Parse { rule: String, expression: String, error: ParseError },
+}
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuleError::Duplicate(id) => write!(f, "Rule '{}' already exists", id),
+            RuleError::Parse { rule, expression, error } => {
+                write!(f, "Rule '{}': cannot parse '{}': {}", rule, expression, error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RuleError {}
+
+// Result of evaluating one rule
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum RuleStatus {
+    NotMatched,
+    CoolingDown { remaining_secs: u64 },
+    // Matched in a dry run; the action was not taken
+    Matched,
+    Fired { output: Result<String, String> },
+    Escalated { reason: String },
+    Error { message: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RuleOutcome {
+    pub rule_id: String,
+    pub priority: i32,
+    pub status: RuleStatus,
+    // Action with its arguments resolved, for rules that matched
+    pub action: Option<RuleAction>,
+}
+
+// Rule with its parsed condition and argument expressions
+struct CompiledRule {
+    rule: Rule,
+    condition: Expr,
+    placeholders: HashMap<String, Expr>,
+}
+
+// Rule Engine structure
+pub struct RuleEngine {
+    rules: Vec<CompiledRule>,
+    functions: HashMap<String, FunctionBinding>,
+    tool_registry: Arc<ToolRegistry>,
+    // Provider handed to tools; rules run without an LLM unless one is set
+    llm: Arc<dyn LLMProvider>,
+    last_fired: Mutex<HashMap<String, Instant>>,
 }
 
 impl RuleEngine {
-    pub fn new() -> Self {
-        RuleEngine { rules: vec![] }
+    pub fn new(tool_registry: Arc<ToolRegistry>) -> Self {
+        RuleEngine {
+            rules: vec![],
+            functions: HashMap::new(),
+            tool_registry,
+            llm: Arc::new(NoLlmProvider),
+            last_fired: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Sets the LLM provider passed to tools that need one
+    pub fn with_llm(mut self, llm: Arc<dyn LLMProvider>) -> Self {
+        self.llm = llm;
+        self
+    }
+
+    // Binds a condition function to a tool
+    pub fn register_function(&mut self, name: &str, binding: FunctionBinding) {
+        self.functions.insert(name.to_string(), binding);
+    }
+
+    // Adds a rule, parsing its condition and argument expressions up front
+    pub fn add_rule(&mut self, rule: Rule) -> Result<(), RuleError> {
+        if self.rules.iter().any(|r| r.rule.id == rule.id) {
+            return Err(RuleError::Duplicate(rule.id));
+        }
+        let parse = |source: &str| {
+            Expr::parse(source).map_err(|error| RuleError::Parse {
+                rule: rule.id.clone(),
+                expression: source.to_string(),
+                error,
+            })
+        };
+        let condition = parse(&rule.condition)?;
+        let mut placeholders = HashMap::new();
+        for source in template::placeholders(&rule.action.template()) {
+            let expr = parse(&source)?;
+            placeholders.insert(source, expr);
+        }
+        // Stable sort, so rules of equal priority keep the order they were added in
+        let position = self.rules.iter().position(|r| r.rule.priority < rule.priority);
+        let compiled = CompiledRule { rule, condition, placeholders };
+        match position {
+            Some(position) => self.rules.insert(position, compiled),
+            None => self.rules.push(compiled),
+        }
+        Ok(())
+    }
+
+    pub fn remove_rule(&mut self, id: &str) {
+        self.rules.retain(|r| r.rule.id != id);
+    }
+
+    // Rules in evaluation order
+    pub fn rules(&self) -> Vec<&Rule> {
+        self.rules.iter().map(|r| &r.rule).collect()
+    }
+
+    // Evaluates every rule without taking any action or starting cooldowns. Functions bound
+    // to tools that the evaluation reaches are still called to read their values.
+    pub async fn dry_run(&self, state: &RuleState) -> Vec<RuleOutcome> {
+        self.evaluate(state, false).await
+    }
+
+    // Evaluates every rule and fires the matching ones, highest priority first
+    pub async fn run(&self, state: &RuleState) -> Vec<RuleOutcome> {
+        self.evaluate(state, true).await
+    }
+
+    // Fires the matching rule with the highest priority, if any
+    pub async fn run_first(&self, state: &RuleState) -> Option<RuleOutcome> {
+        let outcomes = self.dry_run(state).await;
+        let matched = outcomes.into_iter().find(|o| o.status == RuleStatus::Matched)?;
+        Some(self.fire(matched).await)
+    }
+
+    // Takes the action of a rule that matched in a dry run and starts its cooldown. Outcomes
+    // that did not match are returned unchanged, and a rule another caller fired since the dry
+    // run is reported as cooling down instead.
+    pub async fn fire(&self, outcome: RuleOutcome) -> RuleOutcome {
+        if outcome.status != RuleStatus::Matched || outcome.action.is_none() {
+            return outcome;
+        }
+        if let Err(remaining) = self.start_cooldown(&outcome.rule_id) {
+            let status = RuleStatus::CoolingDown { remaining_secs: remaining.as_secs() };
+            return RuleOutcome { status, ..outcome };
+        }
+        let status = match &outcome.action {
+            Some(RuleAction::Tool { tool, arguments }) => {
+                RuleStatus::Fired { output: self.call_tool(tool, arguments.clone()).await }
+            }
+            Some(RuleAction::Escalate { reason }) => {
+                RuleStatus::Escalated { reason: reason.clone() }
+            }
+            None => return outcome,
+        };
+        RuleOutcome { status, ..outcome }
+    }
+
+    async fn evaluate(&self, state: &RuleState, fire: bool) -> Vec<RuleOutcome> {
+        let mut facts = Facts::new(state, &self.functions);
+        let mut outcomes = vec![];
+        for compiled in &self.rules {
+            let outcome = self.check(compiled, &mut facts).await;
+            if fire {
+                outcomes.push(self.fire(outcome).await);
+            } else {
+                outcomes.push(outcome);
+            }
+        }
+        outcomes
+    }
+
+    // Evaluates a rule's condition and, if it matches, resolves its action
+    async fn check(&self, compiled: &CompiledRule, facts: &mut Facts<'_>) -> RuleOutcome {
+        let rule = &compiled.rule;
+        let outcome = |status| RuleOutcome {
+            rule_id: rule.id.clone(),
+            priority: rule.priority,
+            status,
+            action: None,
+        };
+        let error = |message: String| outcome(RuleStatus::Error { message });
+
+        match self.resolve(&compiled.condition, facts).await {
+            Ok(Value::Bool(true)) => {}
+            Ok(Value::Bool(false)) => return outcome(RuleStatus::NotMatched),
+            Ok(other) => return error(format!("condition is {}, not a boolean", other)),
+            Err(message) => return error(message),
+        }
+        if let Some(remaining) = self.cooldown_remaining(rule) {
+            return outcome(RuleStatus::CoolingDown { remaining_secs: remaining.as_secs() });
+        }
+
+        let mut values = HashMap::new();
+        for (source, expr) in &compiled.placeholders {
+            values.insert(source.as_str(), self.resolve(expr, facts).await);
+        }
+        let resolved = template::render(&rule.action.template(), &|source| {
+            values.get(source).cloned().unwrap_or(Err("unparsed placeholder".to_string()))
+        });
+        match resolved {
+            Ok(value) => RuleOutcome {
+                action: Some(rule.action.rendered(value)),
+                ..outcome(RuleStatus::Matched)
+            },
+            Err(message) => error(message),
+        }
+    }
+
+    fn cooldown_remaining(&self, rule: &Rule) -> Option<Duration> {
+        let last_fired = *self.last_fired.lock().unwrap().get(&rule.id)?;
+        remaining(rule.cooldown_secs, last_fired)
     }
-}
\ No newline at end of file
+
+    // Starts the cooldown of rule `id`, or returns what is left of the running one. Both happen
+    // under one lock, so concurrent callers that matched the same rule fire it only once.
+    fn start_cooldown(&self, id: &str) -> Result<(), Duration> {
+        let compiled = self.rules.iter().find(|c| c.rule.id == id);
+        let cooldown_secs = compiled.map(|c| c.rule.cooldown_secs).unwrap_or_default();
+        let mut last_fired = self.last_fired.lock().unwrap();
+        if let Some(remaining) = last_fired.get(id).and_then(|t| remaining(cooldown_secs, *t)) {
+            return Err(remaining);
+        }
+        last_fired.insert(id.to_string(), Instant::now());
+        Ok(())
+    }
+
+    // Evaluates `expr`, calling the tool behind each bound function only once the evaluation
+    // reaches it, so branches that `&&` and `||` skip call nothing. Values are cached for the
+    // rest of the run.
+    async fn resolve(&self, expr: &Expr, facts: &mut Facts<'_>) -> Result<Value, String> {
+        loop {
+            let result = expr.eval(&*facts);
+            let Some((name, args)) = facts.pending.take() else { return result };
+            let key = Facts::key(&name, &args);
+            let binding = &self.functions[&name];
+            let params = binding.params.iter().cloned().zip(args).collect();
+            let value = self
+                .call_tool(&binding.tool, Value::Object(params))
+                .await
+                .and_then(|output| parse_output(&output, binding.output_path.as_deref()));
+            facts.values.insert(key, value);
+        }
+    }
+
+    async fn call_tool(&self, name: &str, arguments: Value) -> Result<String, String> {
+        let (_, tool) =
+            self.tool_registry.get(name).await.ok_or(format!("Tool '{}' not found", name))?;
+        tool.execute(ToolInput::new(arguments), self.llm.as_ref())
+            .await
+            .map_err(|e| e.to_string())
+    }
+}
+
+// Cooldown of `cooldown_secs` left since a rule last fired at `last_fired`, if any
+fn remaining(cooldown_secs: u64, last_fired: Instant) -> Option<Duration> {
+    Duration::from_secs(cooldown_secs)
+        .checked_sub(last_fired.elapsed())
+        .filter(|remaining| !remaining.is_zero())
+}
+
+// Reads a function value from a tool output, converting numeric strings to numbers
+fn parse_output(output: &str, path: Option<&str>) -> Result<Value, String> {
+    let value = match path {
+        Some(path) => {
+            let json: Value =
+                serde_json::from_str(output).map_err(|_| "tool output is not JSON".to_string())?;
+            template::select(&json, path).ok_or(format!("'{}' not found in tool output", path))?
+        }
+        None => serde_json::from_str(output).unwrap_or(Value::String(output.trim().to_string())),
+    };
+    if let Some(number) = value.as_str().and_then(|s| s.trim().parse::<f64>().ok()) {
+        return Ok(serde_json::json!(number));
+    }
+    Ok(value)
+}
+
+// Scope over the agent state and the function values fetched for one evaluation
+struct Facts<'a> {
+    state: &'a RuleState,
+    functions: &'a HashMap<String, FunctionBinding>,
+    values: HashMap<String, Result<Value, String>>,
+    // Bound function call the last evaluation stopped at, as its value is not fetched yet
+    pending: RefCell<Option<(String, Vec<Value>)>>,
+}
+
+impl<'a> Facts<'a> {
+    fn new(state: &'a RuleState, functions: &'a HashMap<String, FunctionBinding>) -> Self {
+        Facts { state, functions, values: HashMap::new(), pending: RefCell::new(None) }
+    }
+
+    fn key(name: &str, args: &[Value]) -> String {
+        format!("{}{}", name, Value::Array(args.to_vec()))
+    }
+}
+
+impl Scope for Facts<'_> {
+    fn variable(&self, path: &str) -> Option<Value> {
+        if let Some(value) = self.state.get(path) {
+            return Some(value.clone());
+        }
+        let (key, rest) = path.split_once('.')?;
+        template::select(self.state.get(key)?, rest)
+    }
+
+    fn call(&self, name: &str, args: &[Value]) -> Result<Value, String> {
+        if let Some(value) = self.values.get(&Facts::key(name, args)) {
+            return value.clone();
+        }
+        if self.functions.contains_key(name) {
+            *self.pending.borrow_mut() = Some((name.to_string(), args.to_vec()));
+            return Err(format!("'{}' has not been fetched yet", name));
+        }
+        let numbers = || args.iter().map(as_number).collect::<Result<Vec<f64>, String>>();
+        let number = |n: f64| Ok(serde_json::json!(n));
+        match name {
+            "min" => number(numbers()?.into_iter().fold(f64::INFINITY, f64::min)),
+            "max" => number(numbers()?.into_iter().fold(f64::NEG_INFINITY, f64::max)),
+            "abs" => number(numbers()?.first().ok_or("abs takes one argument")?.abs()),
+            _ => Err(format!("unknown function '{}'", name)),
+        }
+    }
+}
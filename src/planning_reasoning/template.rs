@@ -0,0 +1,173 @@
+//! Argument templates with `${...}` placeholders, e.g. referencing the outputs of earlier steps
+//! as `${steps.<id>.output}` or of upstream workflow nodes as `${nodes.<id>.output}`
+
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+// Ids referenced by `${<scope>.<id>.output...}` placeholders anywhere in `value`
+pub fn referenced(value: &Value, scope: &str) -> Vec<String> {
+    let mut ids = vec![];
+    visit_strings(value, &mut |s| {
+        for reference in references(s) {
+            if let Some(id) = output_id(reference, scope) {
+                if !ids.iter().any(|i| i == id) {
+                    ids.push(id.to_string());
+                }
+            }
+        }
+    });
+    ids
+}
+
+// True if the value holds any placeholder, so it can only be type-checked once resolved
+pub fn is_templated(value: &Value) -> bool {
+    let mut templated = false;
+    visit_strings(value, &mut |s| templated |= !references(s).is_empty());
+    templated
+}
+
+// Replaces `${<scope>.<id>.output}` with the output of `id`, and `${<scope>.<id>.output.<path>}`
+// with a field of its JSON output
+pub fn resolve(
+    value: &Value,
+    scope: &str,
+    outputs: &HashMap<String, String>,
+) -> Result<Value, String> {
+    render(value, &|reference| lookup(reference, scope, outputs))
+}
+
+// Replaces every `${...}` placeholder with the value `lookup` gives for its contents. A string
+// that is a single placeholder takes the value as-is, so numbers and objects keep their type.
+pub fn render(
+    value: &Value,
+    lookup: &dyn Fn(&str) -> Result<Value, String>,
+) -> Result<Value, String> {
+    match value {
+        Value::String(s) => render_string(s, lookup),
+        Value::Array(items) => {
+            items.iter().map(|v| render(v, lookup)).collect::<Result<_, _>>().map(Value::Array)
+        }
+        Value::Object(map) => map
+            .iter()
+            .map(|(k, v)| Ok((k.clone(), render(v, lookup)?)))
+            .collect::<Result<_, String>>()
+            .map(Value::Object),
+        other => Ok(other.clone()),
+    }
+}
+
+fn render_string(
+    s: &str,
+    lookup: &dyn Fn(&str) -> Result<Value, String>,
+) -> Result<Value, String> {
+    let references = references(s);
+    if references.len() == 1 && s.trim() == format!("${{{}}}", references[0]) {
+        return lookup(references[0]);
+    }
+    let mut out = s.to_string();
+    for reference in references {
+        let value = match lookup(reference)? {
+            Value::String(text) => text,
+            other => other.to_string(),
+        };
+        out = out.replace(&format!("${{{}}}", reference), &value);
+    }
+    Ok(Value::String(out))
+}
+
+// Contents of every `${...}` placeholder anywhere in `value`
+pub fn placeholders(value: &Value) -> Vec<String> {
+    let mut found = vec![];
+    visit_strings(value, &mut |s| found.extend(references(s).into_iter().map(String::from)));
+    found
+}
+
+// Contents of every `${...}` in `s`
+fn references(s: &str) -> Vec<&str> {
+    let mut found = vec![];
+    let mut rest = s;
+    while let Some(start) = rest.find("${") {
+        let Some(end) = rest[start..].find('}') else { break };
+        found.push(rest[start + 2..start + end].trim());
+        rest = &rest[start + end + 1..];
+    }
+    found
+}
+
+fn output_id<'a>(reference: &'a str, scope: &str) -> Option<&'a str> {
+    let mut parts = reference.split('.');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(s), Some(id), Some("output")) if s == scope => Some(id),
+        _ => None,
+    }
+}
+
+// Value of a `<scope>.<id>.output[.<path>]` reference
+pub fn lookup(
+    reference: &str,
+    scope: &str,
+    outputs: &HashMap<String, String>,
+) -> Result<Value, String> {
+    let id = output_id(reference, scope)
+        .ok_or(format!("'{}' is not of the form {}.<id>.output[.<path>]", reference, scope))?;
+    let output = outputs.get(id).ok_or(format!("'{}.{}' has no output", scope, id))?;
+    let path: Vec<&str> = reference.split('.').skip(3).collect();
+    if path.is_empty() {
+        return Ok(Value::String(output.clone()));
+    }
+    let json: Value = serde_json::from_str(output)
+        .map_err(|_| format!("output of '{}.{}' is not JSON", scope, id))?;
+    select(&json, &path.join("."))
+        .ok_or(format!("'{}' not found in output of '{}.{}'", path.join("."), scope, id))
+}
+
+// Value at a dotted path such as `routes.0.label`, indexing arrays by number
+pub fn select(value: &Value, path: &str) -> Option<Value> {
+    let mut current = value;
+    for segment in path.split('.') {
+        current = match (current, segment.parse::<usize>()) {
+            (Value::Array(items), Ok(index)) => items.get(index)?,
+            (Value::Object(map), _) => map.get(segment)?,
+            _ => return None,
+        };
+    }
+    Some(current.clone())
+}
+
+fn visit_strings(value: &Value, f: &mut dyn FnMut(&str)) {
+    match value {
+        Value::String(s) => f(s),
+        Value::Array(items) => items.iter().for_each(|v| visit_strings(v, f)),
+        Value::Object(map) => map.values().for_each(|v| visit_strings(v, f)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_resolve_keeps_types_and_interpolates() {
+        let outputs = HashMap::from([
+            ("quote".to_string(), r#"{"out_amount": 42.5, "routes": ["orca"]}"#.to_string()),
+            ("wallet".to_string(), "abc123".to_string()),
+        ]);
+        let args = json!({
+            "amount": "${steps.quote.output.out_amount}",
+            "memo": "route ${steps.quote.output.routes.0} for ${steps.wallet.output}",
+        });
+        let resolved = resolve(&args, "steps", &outputs).unwrap();
+        assert_eq!(resolved, json!({"amount": 42.5, "memo": "route orca for abc123"}));
+        assert_eq!(referenced(&args, "steps"), vec!["quote", "wallet"]);
+        assert!(referenced(&args, "nodes").is_empty());
+    }
+
+    #[test]
+    fn test_resolve_missing_output() {
+        let err = resolve(&json!("${steps.swap.output}"), "steps", &HashMap::new()).unwrap_err();
+        assert_eq!(err, "'steps.swap' has no output");
+    }
+}
@@ -1,6 +1,21 @@
 // Attribute-Based Access Control (ABAC)
 
-use casbin::Enforcer;
+use casbin::{CoreApi, DefaultModel, Enforcer, MemoryAdapter};
+
+// Requests are allowed when the subject owns the object
+const ABAC_MODEL: &str = "
+[request_definition]
+r = sub, obj, act
+
+[policy_definition]
+p = sub, obj, act
+
+[policy_effect]
+e = some(where (p.eft == allow))
+
+[matchers]
+m = r.sub == r.obj.owner
+";
 
 // ABAC configuration structure
 pub struct ABAC {
@@ -8,9 +23,13 @@ pub struct ABAC {
 }
 
 impl ABAC {
-    pub fn new() -> Self {
-        ABAC {
-            enforcer: Enforcer::new("abac_model.conf", "abac_policy.csv").unwrap(),
-        }
+    pub async fn new() -> casbin::Result<Self> {
+        let model = DefaultModel::from_str(ABAC_MODEL).await?;
+        let enforcer = Enforcer::new(model, MemoryAdapter::default()).await?;
+        Ok(ABAC { enforcer })
+    }
+
+    pub fn enforcer(&self) -> &Enforcer {
+        &self.enforcer
     }
 }
\ No newline at end of file
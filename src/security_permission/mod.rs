@@ -1,2 +1,5 @@
 pub mod rbac;
-pub mod abac;
\ No newline at end of file
+pub mod abac;
+
+pub use abac::ABAC;
+pub use rbac::RBAC;
\ No newline at end of file
@@ -1,6 +1,24 @@
 // Role-Based Access Control (RBAC) using Casbin
 
-use casbin::Enforcer;
+use casbin::{CoreApi, DefaultModel, Enforcer, MemoryAdapter};
+
+// Subjects get the permissions of the roles they are assigned with `g`
+const RBAC_MODEL: &str = "
+[request_definition]
+r = sub, obj, act
+
+[policy_definition]
+p = sub, obj, act
+
+[role_definition]
+g = _, _
+
+[policy_effect]
+e = some(where (p.eft == allow))
+
+[matchers]
+m = g(r.sub, p.sub) && r.obj == p.obj && r.act == p.act
+";
 
 // RBAC configuration structure
 pub struct RBAC {
@@ -8,9 +26,14 @@ pub struct RBAC {
 }
 
 impl RBAC {
-    pub fn new() -> Self {
-        RBAC {
-            enforcer: Enforcer::new("rbac_model.conf", "rbac_policy.csv").unwrap(),
-        }
+    // Starts with no policies; they are added through the enforcer
+    pub async fn new() -> casbin::Result<Self> {
+        let model = DefaultModel::from_str(RBAC_MODEL).await?;
+        let enforcer = Enforcer::new(model, MemoryAdapter::default()).await?;
+        Ok(RBAC { enforcer })
+    }
+
+    pub fn enforcer(&self) -> &Enforcer {
+        &self.enforcer
     }
 }
\ No newline at end of file
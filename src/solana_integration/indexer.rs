@@ -4,6 +4,12 @@ pub struct IndexerClient {
     api_key: String,
 }
 
+impl Default for IndexerClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl IndexerClient {
     pub fn new() -> Self {
         IndexerClient {
@@ -11,4 +17,14 @@ impl IndexerClient {
             api_key: String::new(),
         }
     }
+
+    pub fn with_api_key(mut self, api_key: &str) -> Self {
+        self.api_key = api_key.to_string();
+        self
+    }
+
+    // URL of an indexer API path, authenticated with the API key
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}?api-key={}", self.endpoint, path, self.api_key)
+    }
 }
\ No newline at end of file
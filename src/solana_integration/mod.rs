@@ -1,2 +1,5 @@
 pub mod rpc;
-pub mod indexer;
\ No newline at end of file
+pub mod indexer;
+
+pub use indexer::IndexerClient;
+pub use rpc::SolanaRPC;
\ No newline at end of file
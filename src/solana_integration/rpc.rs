@@ -5,10 +5,36 @@ pub struct SolanaRPC {
     cluster: Vec<RpcClient>,
 }
 
+impl Default for SolanaRPC {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl SolanaRPC {
     pub fn new() -> Self {
         SolanaRPC {
             cluster: vec![RpcClient::new("https://api.devnet.solana.com".to_string())],
         }
     }
+
+    // RPC clients of the cluster's nodes
+    pub fn clients(&self) -> &[RpcClient] {
+        &self.cluster
+    }
+}
+
+// Cluster name for an RPC URL, used to tell the agent which network it operates on
+pub fn cluster_name(rpc_url: &str) -> &str {
+    if rpc_url.contains("devnet") {
+        "devnet"
+    } else if rpc_url.contains("testnet") {
+        "testnet"
+    } else if rpc_url.contains("mainnet") {
+        "mainnet-beta"
+    } else if rpc_url.contains("localhost") || rpc_url.contains("127.0.0.1") {
+        "localnet"
+    } else {
+        "custom"
+    }
 }
\ No newline at end of file
@@ -0,0 +1,2 @@
+pub mod stake_with_jup;
+pub mod trade;
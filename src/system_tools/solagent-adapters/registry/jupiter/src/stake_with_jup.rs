@@ -0,0 +1,62 @@
+use serde::Deserialize;
+use serde_json::json;
+use solagent::{
+    llm_integration::LLMProvider,
+    tool_system::{SolanaTool, ToolInput, ToolMetadata},
+};
+use solagent_core::SolAgent;
+use solagent_plugin_jupiter::stake_with_jup_reporting;
+use std::sync::Arc;
+
+#[derive(Deserialize)]
+pub struct StakeWithJupArgs {
+    pub amount: f64,
+}
+
+pub struct StakeWithJup {
+    agent: Arc<SolAgent>,
+}
+
+impl StakeWithJup {
+    pub fn new(agent: Arc<SolAgent>) -> Self {
+        StakeWithJup { agent }
+    }
+
+    /// Registry metadata of the tool, offered to `llm_type` providers.
+    pub fn metadata(llm_type: &str) -> ToolMetadata {
+        ToolMetadata {
+            name: "stake_with_jup".to_string(),
+            aliases: vec![],
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            llm_type: llm_type.to_string(),
+            schema: json!({
+                "name": "stake_with_jup",
+                "description": "Stake SOL with the Jupiter validator for jupSOL",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "amount": {"type": "number", "description": "Amount of SOL to stake"}
+                    },
+                    "required": ["amount"]
+                }
+            }),
+            sends_transactions: true,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl SolanaTool for StakeWithJup {
+    async fn execute(
+        &self,
+        input: ToolInput,
+        _llm: &dyn LLMProvider,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let args: StakeWithJupArgs = serde_json::from_value(input.params.clone())?;
+        let signature = stake_with_jup_reporting(&self.agent, args.amount, |signature, blockhash| {
+            input.submitted(signature, blockhash)
+        })
+        .await?;
+        Ok(json!({ "signature": signature }).to_string())
+    }
+}
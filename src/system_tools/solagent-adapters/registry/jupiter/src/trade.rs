@@ -0,0 +1,73 @@
+use serde::Deserialize;
+use serde_json::json;
+use solagent::{
+    llm_integration::LLMProvider,
+    tool_system::{SolanaTool, ToolInput, ToolMetadata},
+};
+use solagent_core::SolAgent;
+use solagent_plugin_jupiter::trade_reporting;
+use std::sync::Arc;
+
+#[derive(Deserialize)]
+pub struct TradeArgs {
+    pub output_mint: String,
+    pub input_amount: f64,
+    pub input_mint: Option<String>,
+    pub slippage_bps: Option<u32>,
+}
+
+pub struct Trade {
+    agent: Arc<SolAgent>,
+}
+
+impl Trade {
+    pub fn new(agent: Arc<SolAgent>) -> Self {
+        Trade { agent }
+    }
+
+    /// Registry metadata of the tool, offered to `llm_type` providers.
+    pub fn metadata(llm_type: &str) -> ToolMetadata {
+        ToolMetadata {
+            name: "trade".to_string(),
+            aliases: vec!["swap".to_string()],
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            llm_type: llm_type.to_string(),
+            schema: json!({
+                "name": "trade",
+                "description": "Swap tokens using Jupiter Exchange",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "output_mint": {"type": "string", "description": "Target token mint"},
+                        "input_amount": {"type": "number", "description": "Amount to swap"},
+                        "input_mint": {"type": "string", "description": "Defaults to SOL"},
+                        "slippage_bps": {"type": "integer", "description": "Defaults to 300"}
+                    },
+                    "required": ["output_mint", "input_amount"]
+                }
+            }),
+            sends_transactions: true,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl SolanaTool for Trade {
+    async fn execute(
+        &self,
+        input: ToolInput,
+        _llm: &dyn LLMProvider,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let args: TradeArgs = serde_json::from_value(input.params.clone())?;
+        let signature = trade_reporting(
+            &self.agent,
+            &args.output_mint,
+            args.input_amount,
+            args.input_mint,
+            args.slippage_bps,
+            |signature, blockhash| input.submitted(signature, blockhash),
+        )
+        .await?;
+        Ok(json!({ "signature": signature }).to_string())
+    }
+}
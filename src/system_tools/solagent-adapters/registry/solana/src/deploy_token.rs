@@ -0,0 +1,76 @@
+use serde::Deserialize;
+use serde_json::json;
+use solagent::{
+    llm_integration::LLMProvider,
+    tool_system::{SolanaTool, ToolInput, ToolMetadata},
+};
+use solagent_core::SolAgent;
+use solagent_plugin_solana::deploy_token_reporting;
+use std::sync::Arc;
+
+#[derive(Deserialize)]
+pub struct DeployTokenArgs {
+    pub name: String,
+    pub uri: String,
+    pub symbol: String,
+    pub decimals: Option<u8>,
+    pub initial_supply: Option<u64>,
+}
+
+pub struct DeployToken {
+    agent: Arc<SolAgent>,
+}
+
+impl DeployToken {
+    pub fn new(agent: Arc<SolAgent>) -> Self {
+        DeployToken { agent }
+    }
+
+    /// Registry metadata of the tool, offered to `llm_type` providers.
+    pub fn metadata(llm_type: &str) -> ToolMetadata {
+        ToolMetadata {
+            name: "deploy_token".to_string(),
+            aliases: vec![],
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            llm_type: llm_type.to_string(),
+            schema: json!({
+                "name": "deploy_token",
+                "description": "Deploy a new SPL token, minting its initial supply to the wallet",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "name": {"type": "string"},
+                        "uri": {"type": "string", "description": "URI of the token metadata"},
+                        "symbol": {"type": "string"},
+                        "decimals": {"type": "integer", "description": "Defaults to 9"},
+                        "initial_supply": {"type": "integer"}
+                    },
+                    "required": ["name", "uri", "symbol"]
+                }
+            }),
+            sends_transactions: true,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl SolanaTool for DeployToken {
+    async fn execute(
+        &self,
+        input: ToolInput,
+        _llm: &dyn LLMProvider,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let args: DeployTokenArgs = serde_json::from_value(input.params.clone())?;
+        let deployed = deploy_token_reporting(
+            &self.agent,
+            args.name,
+            args.uri,
+            args.symbol,
+            args.decimals.unwrap_or(9),
+            args.initial_supply,
+            |tx, blockhash| input.submitted(tx, blockhash),
+        )
+        .await?;
+        Ok(serde_json::to_string(&deployed)?)
+    }
+}
@@ -0,0 +1,2 @@
+pub mod deploy_token;
+pub mod transfer;
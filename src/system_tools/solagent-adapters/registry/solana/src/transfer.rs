@@ -0,0 +1,66 @@
+use serde::Deserialize;
+use serde_json::json;
+use solagent::{
+    llm_integration::LLMProvider,
+    tool_system::{SolanaTool, ToolInput, ToolMetadata},
+};
+use solagent_core::SolAgent;
+use solagent_plugin_solana::transfer_reporting;
+use std::sync::Arc;
+
+#[derive(Deserialize)]
+pub struct TransferArgs {
+    pub to: String,
+    pub amount: u64,
+    pub mint: Option<String>,
+}
+
+pub struct Transfer {
+    agent: Arc<SolAgent>,
+}
+
+impl Transfer {
+    pub fn new(agent: Arc<SolAgent>) -> Self {
+        Transfer { agent }
+    }
+
+    /// Registry metadata of the tool, offered to `llm_type` providers.
+    pub fn metadata(llm_type: &str) -> ToolMetadata {
+        ToolMetadata {
+            name: "transfer".to_string(),
+            aliases: vec![],
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            llm_type: llm_type.to_string(),
+            schema: json!({
+                "name": "transfer",
+                "description": "Transfer SOL, or SPL tokens of `mint`, to another address",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "to": {"type": "string", "description": "Recipient's address"},
+                        "amount": {"type": "integer", "description": "Amount to transfer"},
+                        "mint": {"type": "string", "description": "Mint of the SPL token"}
+                    },
+                    "required": ["to", "amount"]
+                }
+            }),
+            sends_transactions: true,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl SolanaTool for Transfer {
+    async fn execute(
+        &self,
+        input: ToolInput,
+        _llm: &dyn LLMProvider,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let args: TransferArgs = serde_json::from_value(input.params.clone())?;
+        let tx = transfer_reporting(&self.agent, &args.to, args.amount, args.mint, |tx, blockhash| {
+            input.submitted(tx, blockhash)
+        })
+        .await?;
+        Ok(json!({ "tx": tx }).to_string())
+    }
+}
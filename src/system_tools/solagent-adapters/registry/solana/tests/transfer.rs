@@ -0,0 +1,104 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpListener,
+    sync::Arc,
+};
+
+use serde_json::{json, Value};
+use solagent::{
+    llm_integration::mock::MockProvider,
+    memory_system::{actions::ActionLog, long_term::Namespace, LongTermMemory},
+    tool_system::{ToolInput, ToolRegistry},
+};
+use solagent_core::SolAgent;
+use solagent_registry_solana::transfer::Transfer;
+use solagent_wallet_solana::SolAgentWallet;
+use solana_sdk::hash::Hash;
+
+// JSON-RPC node handing out a blockhash and rejecting every transaction sent to it
+fn rejecting_rpc() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut reader = BufReader::new(stream.unwrap());
+            // Requests on a kept-alive connection are answered in turn
+            while let Some(request) = read_request(&mut reader) {
+                let body = match request["method"].as_str() {
+                    Some("getLatestBlockhash") => json!({
+                        "jsonrpc": "2.0",
+                        "id": request["id"],
+                        "result": {
+                            "context": {"slot": 1},
+                            "value": {
+                                "blockhash": Hash::new_unique().to_string(),
+                                "lastValidBlockHeight": 100
+                            }
+                        }
+                    }),
+                    _ => json!({
+                        "jsonrpc": "2.0",
+                        "id": request["id"],
+                        "error": {"code": -32002, "message": "Transaction simulation failed"}
+                    }),
+                }
+                .to_string();
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                reader.get_mut().write_all(response.as_bytes()).unwrap();
+            }
+        }
+    });
+    url
+}
+
+// Body of the next HTTP request, or None once the client closes the connection
+fn read_request(reader: &mut impl BufRead) -> Option<Value> {
+    let mut length = 0;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        let line = line.trim_end().to_ascii_lowercase();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(value) = line.strip_prefix("content-length:") {
+            length = value.trim().parse().ok()?;
+        }
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).ok()?;
+    serde_json::from_slice(&body).ok()
+}
+
+// The blocking RPC client of the plugin needs a multi-threaded runtime
+#[tokio::test(flavor = "multi_thread")]
+async fn test_transfers_are_recorded_in_the_action_log() {
+    let rpc_url = rejecting_rpc();
+    let agent = Arc::new(SolAgent::new(SolAgentWallet::new(&rpc_url), None));
+    let action_log = Arc::new(ActionLog::new(
+        Arc::new(LongTermMemory::in_memory().unwrap()),
+        Namespace::agent("trader"),
+        &rpc_url,
+    ));
+    let registry = ToolRegistry::new().with_action_log(action_log.clone());
+    registry.register(Transfer::metadata("mock"), Arc::new(Transfer::new(agent))).await;
+    let (_, transfer) = registry.get("transfer").await.unwrap();
+
+    let arguments = json!({ "to": "8x2dR8Mpzuz2YqyZyZjUbYWKSWesBo5jMx2Q9Y86udVk", "amount": 1 });
+    let result = transfer.execute(ToolInput::new(arguments.clone()), &MockProvider::new()).await;
+    assert!(result.is_err());
+
+    // The transaction was signed before the node rejected it, so it may still land
+    let actions = action_log.recent(10).await.unwrap();
+    assert_eq!(actions.len(), 1);
+    assert_eq!(actions[0].tool, "transfer");
+    assert_eq!(actions[0].arguments, arguments);
+    assert_eq!(actions[0].signatures.len(), 1);
+    assert!(!actions[0].succeeded());
+}
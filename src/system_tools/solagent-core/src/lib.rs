@@ -4,10 +4,14 @@ pub mod config;
 
 use {
     anyhow::Result, model::SolAgentModel, rig::tool::{Tool, ToolSet}, solagent_wallet_solana::SolAgentWallet,
+    rig::streaming::StreamingResult, serde::de::DeserializeOwned, schemars::JsonSchema,
+    serde_json::Value, crate::structured::StructuredOutputError,
     solana_client::rpc_client::RpcClient, crate::config::SolAgentConfig,
 };
 pub use solana_client;
 pub use tool::SolAgentTool;
+pub use solagent_structured as structured;
+pub use structured::{extract_json, OutputSchema};
 
 pub struct SolAgent {
     pub wallet: SolAgentWallet,
@@ -47,4 +51,98 @@ impl SolAgent {
 
         Ok(response)
     }
+
+    /// Dynamically creates an `Agent` based on the provided model and streams its response.
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - The model to use for creating the agent (OpenAI or Gemini).
+    /// * `tools` - A list of tools to be used by the agent.
+    /// * `prompt` - The input prompt to process.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<StreamingResult>` - A stream of text and tool-call chunks.
+    pub async fn stream_prompt(
+        &self,
+        model: SolAgentModel,
+        tools: SolAgentTool,
+        prompt: &str,
+    ) -> Result<StreamingResult> {
+        let agent = model.create_agent(tools)?;
+        agent.stream_prompt(prompt).await
+    }
+
+    /// Prompts the model for a JSON answer that conforms to `schema`.
+    ///
+    /// The answer is validated, and the model is re-asked with the validation
+    /// errors up to `schema.max_retries` times.
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - The model to use for creating the agent.
+    /// * `tools` - A list of tools to be used by the agent.
+    /// * `prompt` - The input prompt to process.
+    /// * `schema` - The schema the answer must conform to.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Value>` - The validated JSON answer.
+    pub async fn prompt_json(
+        &self,
+        model: SolAgentModel,
+        tools: SolAgentTool,
+        prompt: &str,
+        schema: &OutputSchema,
+    ) -> Result<Value> {
+        let agent = model.create_json_agent(tools, schema)?;
+
+        let instructed = format!("{}\n\n{}", prompt, schema.instructions());
+        let mut request = instructed.clone();
+        let mut errors = vec![];
+        let mut last_output = String::new();
+        for _ in 0..=schema.max_retries {
+            last_output = agent.prompt(&request).await?;
+            match schema.parse(&last_output) {
+                Ok(value) => return Ok(value),
+                Err(violations) => {
+                    request = format!(
+                        "{}\n\nYour previous reply was:\n{}\n\n{}",
+                        instructed,
+                        last_output,
+                        schema.repair_prompt(&violations)
+                    );
+                    errors = violations;
+                }
+            }
+        }
+        Err(StructuredOutputError {
+            schema: schema.name.clone(),
+            attempts: schema.max_retries + 1,
+            errors,
+            last_output,
+        }
+        .into())
+    }
+
+    /// Prompts the model for an answer of type `T`, using the schema generated from `T`.
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - The model to use for creating the agent.
+    /// * `tools` - A list of tools to be used by the agent.
+    /// * `prompt` - The input prompt to process.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<T>` - The validated, deserialized answer.
+    pub async fn prompt_typed<T: JsonSchema + DeserializeOwned>(
+        &self,
+        model: SolAgentModel,
+        tools: SolAgentTool,
+        prompt: &str,
+    ) -> Result<T> {
+        let value = self.prompt_json(model, tools, prompt, &OutputSchema::for_type::<T>()).await?;
+        Ok(serde_json::from_value(value)?)
+    }
 }
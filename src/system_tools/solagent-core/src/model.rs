@@ -1,11 +1,13 @@
 use anyhow::Result;
 use rig::agent::Agent;
 use rig::completion::Prompt;
+use rig::streaming::{StreamingPrompt, StreamingResult};
 use rig::providers::gemini::completion::CompletionModel;
 use rig::providers::{ollama, anthropic, cohere, gemini, openai, perplexity};
 use rig::tool::ToolSet;
+use serde_json::{json, Value};
 
-use crate::tool::SolAgentTool;
+use crate::{structured::OutputSchema, tool::SolAgentTool};
 
 /// Represents the model types supported by SolAgentCompletionModel.
 /// The `String` field specifies the model name, such as "gpt-4" or "gemini-1.0".
@@ -49,17 +51,64 @@ impl SolAgentModel {
     pub fn create_agent(
         &self,
         tools: SolAgentTool,
+    ) -> Result<SolAgentCompletionModel> {
+        self.build_agent(tools, None)
+    }
+
+    /// Creates an `Agent` whose replies are constrained to JSON matching `schema`.
+    ///
+    /// The provider's native structured-output mode is used where available
+    /// (OpenAI and Ollama); other models rely on the instructions in the prompt.
+    ///
+    /// # Arguments
+    ///
+    /// * `tools` - A list of tools to be used by the agent.
+    /// * `schema` - The schema the replies must conform to.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<SolAgentCompletionModel>` - The dynamically created agent wrapped in the `SolAgentCompletionModel` enum.
+    pub fn create_json_agent(
+        &self,
+        tools: SolAgentTool,
+        schema: &OutputSchema,
+    ) -> Result<SolAgentCompletionModel> {
+        let params = match self {
+            SolAgentModel::OpenAI(_) => Some(json!({
+                "response_format": {
+                    "type": "json_schema",
+                    "json_schema": { "name": schema.api_name(), "schema": schema.schema },
+                }
+            })),
+            SolAgentModel::Ollama(_) => Some(json!({ "format": schema.schema })),
+            _ => None,
+        };
+        self.build_agent(tools, params)
+    }
+
+    fn build_agent(
+        &self,
+        tools: SolAgentTool,
+        params: Option<Value>,
     ) -> Result<SolAgentCompletionModel> {
         match self {
             SolAgentModel::Ollama(model_name) => {
                 let client = ollama::Client::new();
-                let mut agent = client.agent(model_name).tool_names(tools.tool_names).build();
+                let mut agent = client.agent(model_name).tool_names(tools.tool_names);
+                if let Some(params) = params {
+                    agent = agent.additional_params(params);
+                }
+                let mut agent = agent.build();
                 agent.tools = tools.toolset;
                 Ok(SolAgentCompletionModel::Ollama(agent))
             },
             SolAgentModel::OpenAI(model_name) => {
                 let client = openai::Client::from_env();
-                let mut agent = client.agent(model_name).tool_names(tools.tool_names).build();
+                let mut agent = client.agent(model_name).tool_names(tools.tool_names);
+                if let Some(params) = params {
+                    agent = agent.additional_params(params);
+                }
+                let mut agent = agent.build();
                 agent.tools = tools.toolset;
                 Ok(SolAgentCompletionModel::OpenAI(agent))
             }
@@ -130,4 +179,23 @@ impl SolAgentCompletionModel {
             }
         }
     }
+
+    /// Streams the response to the given prompt as it is generated.
+    ///
+    /// Streaming is currently supported for OpenAI and Gemini models.
+    ///
+    /// # Arguments
+    ///
+    /// * `prompt` - The input prompt to process.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<StreamingResult>` - A stream of text and tool-call chunks.
+    pub async fn stream_prompt(&self, prompt: &str) -> Result<StreamingResult> {
+        match self {
+            SolAgentCompletionModel::OpenAI(agent) => Ok(agent.stream_prompt(prompt).await?),
+            SolAgentCompletionModel::Gemini(agent) => Ok(agent.stream_prompt(prompt).await?),
+            _ => Err(anyhow::anyhow!("Streaming is not supported for this model")),
+        }
+    }
 }
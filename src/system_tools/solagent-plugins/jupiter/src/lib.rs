@@ -16,13 +16,13 @@ mod get_token_data_by_address;
 pub use get_token_data_by_address::get_token_data_by_address;
 
 mod trade;
-pub use trade::trade;
+pub use trade::{trade, trade_reporting};
 
 mod fetch_price;
 pub use fetch_price::fetch_price;
 
 mod stake_with_jup;
-pub use stake_with_jup::stake_with_jup;
+pub use stake_with_jup::{stake_with_jup, stake_with_jup_reporting};
 
 /// Jupiter API URL
 pub const JUP_API: &str = "https://quote-api.jup.ag/v6";
@@ -32,6 +32,16 @@ use solagent_core::{
 pub async fn stake_with_jup(
     agent: &SolAgent,
     amount: f64,
+) -> Result<String, Box<dyn std::error::Error>> {
+    stake_with_jup_reporting(agent, amount, |_, _| {}).await
+}
+
+/// Same as [`stake_with_jup`], calling `on_submitted` with the signature and recent blockhash of
+/// the stake transaction once it is signed and before it is sent.
+pub async fn stake_with_jup_reporting(
+    agent: &SolAgent,
+    amount: f64,
+    on_submitted: impl Fn(&str, &str),
 ) -> Result<String, Box<dyn std::error::Error>> {
     // Convert SOL amount to lamports
     let amount_lamports = (amount * 1e9) as u64;
@@ -64,6 +74,7 @@ pub async fn stake_with_jup(
     // Sign and send transaction
     let signed_transaction =
         VersionedTransaction::try_new(versioned_transaction.message, &[&agent.wallet.keypair])?;
+    on_submitted(&signed_transaction.signatures[0].to_string(), &blockhash.to_string());
 
     let signature = agent.connection.send_transaction(&signed_transaction)?;
 
@@ -72,6 +72,19 @@ pub async fn trade(
     input_amount: f64,
     input_mint: Option<String>,
     slippage_bps: Option<u32>,
+) -> Result<String, Box<dyn std::error::Error>> {
+    trade_reporting(agent, output_mint, input_amount, input_mint, slippage_bps, |_, _| {}).await
+}
+
+/// Same as [`trade`], calling `on_submitted` with the signature and recent blockhash of the swap
+/// transaction once it is signed and before it is sent.
+pub async fn trade_reporting(
+    agent: &SolAgent,
+    output_mint: &str,
+    input_amount: f64,
+    input_mint: Option<String>,
+    slippage_bps: Option<u32>,
+    on_submitted: impl Fn(&str, &str),
 ) -> Result<String, Box<dyn std::error::Error>> {
     // Convert strings to Pubkeys
     let output_mint = Pubkey::from_str(output_mint)?;
@@ -135,6 +148,10 @@ pub async fn trade(
 
     let signed_transaction =
         VersionedTransaction::try_new(versioned_transaction.message, &[&agent.wallet.keypair])?;
+    on_submitted(
+        &signed_transaction.signatures[0].to_string(),
+        &signed_transaction.message.recent_blockhash().to_string(),
+    );
 
     let signature = agent.connection.send_transaction(&signed_transaction)?;
 
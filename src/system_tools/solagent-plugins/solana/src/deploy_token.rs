@@ -53,6 +53,20 @@ pub async fn deploy_token(
     symbol: String,
     decimals: u8,
     initial_supply: Option<u64>,
+) -> Result<DeployedData, ClientError> {
+    deploy_token_reporting(agent, name, uri, symbol, decimals, initial_supply, |_, _| {}).await
+}
+
+/// Same as [`deploy_token`], calling `on_submitted` with the signature and recent blockhash of
+/// the transaction once it is signed and before it is sent.
+pub async fn deploy_token_reporting(
+    agent: &SolAgent,
+    name: String,
+    uri: String,
+    symbol: String,
+    decimals: u8,
+    initial_supply: Option<u64>,
+    on_submitted: impl Fn(&str, &str),
 ) -> Result<DeployedData, ClientError> {
     let mint = Keypair::new();
     let mint_pubkey = mint.pubkey();
@@ -152,6 +166,7 @@ pub async fn deploy_token(
         &[&agent.wallet.keypair, &mint],
         recent_blockhash,
     );
+    on_submitted(&transaction.signatures[0].to_string(), &recent_blockhash.to_string());
 
     let signature = agent
         .connection
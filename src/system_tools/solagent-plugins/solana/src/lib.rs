@@ -24,11 +24,11 @@ pub use close_empty_token_accounts::{close_empty_token_accounts, CloseEmptyToken
 mod get_tps;
 pub use get_tps::get_tps;
 
-// mod transfer;
-// pub use transfer::transfer;
+mod transfer;
+pub use transfer::{transfer, transfer_reporting};
 
-// mod deploy_token;
-// pub use deploy_token::deploy_token;
+mod deploy_token;
+pub use deploy_token::{deploy_token, deploy_token_reporting};
 
 // mod deploy_collection;
 // pub use deploy_collection::deploy_collection;
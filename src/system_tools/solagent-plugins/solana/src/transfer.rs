@@ -36,7 +36,19 @@ pub async fn transfer(
     amount: u64,
     mint: Option<String>,
 ) -> Result<String, ClientError> {
-    match mint {
+    transfer_reporting(agent, to, amount, mint, |_, _| {}).await
+}
+
+/// Same as [`transfer`], calling `on_submitted` with the signature and recent blockhash of the
+/// transaction once it is signed and before it is sent.
+pub async fn transfer_reporting(
+    agent: &SolAgent,
+    to: &str,
+    amount: u64,
+    mint: Option<String>,
+    on_submitted: impl Fn(&str, &str),
+) -> Result<String, ClientError> {
+    let transfer_instruction = match mint {
         Some(mint) => {
             // Transfer SPL Token
             let mint = Pubkey::from_str_const(&mint);
@@ -45,12 +57,12 @@ pub async fn transfer(
             let from_ata = get_associated_token_address(&mint, &agent.wallet.pubkey);
             let to_ata = get_associated_token_address(&mint, &to);
 
-            let account_info = &agent.connection.get_account(&mint).expect("get_account");
+            let account_info = &agent.connection.get_account(&mint)?;
             let mint_info = Mint::unpack_from_slice(&account_info.data).expect("unpack_from_slice");
 
             let adjusted_amount = amount * 10u64.pow(mint_info.decimals as u32);
 
-            let transfer_instruction = transfer_instruct(
+            transfer_instruct(
                 &spl_token::id(),
                 &from_ata,
                 &to_ata,
@@ -58,45 +70,24 @@ pub async fn transfer(
                 &[&agent.wallet.pubkey],
                 adjusted_amount,
             )
-            .expect("transfer_instruct");
-
-            let transaction = Transaction::new_signed_with_payer(
-                &[transfer_instruction],
-                Some(&agent.wallet.pubkey),
-                &[&agent.wallet.keypair],
-                agent
-                    .connection
-                    .get_latest_blockhash()
-                    .expect("new_signed_with_payer"),
-            );
-
-            let signature = agent
-                .connection
-                .send_and_confirm_transaction(&transaction)
-                .expect("send_and_confirm_transaction");
-            Ok(signature.to_string())
+            .expect("transfer_instruct")
         }
-        None => {
-            let transfer_instruction = system_instruction::transfer(
-                &agent.wallet.pubkey,
-                &Pubkey::from_str_const(to),
-                amount,
-            );
-            let transaction = Transaction::new_signed_with_payer(
-                &[transfer_instruction],
-                Some(&agent.wallet.pubkey),
-                &[&agent.wallet.keypair],
-                agent
-                    .connection
-                    .get_latest_blockhash()
-                    .expect("get_latest_blockhash"),
-            );
+        None => system_instruction::transfer(
+            &agent.wallet.pubkey,
+            &Pubkey::from_str_const(to),
+            amount,
+        ),
+    };
 
-            let signature = agent
-                .connection
-                .send_and_confirm_transaction(&transaction)
-                .expect("send_and_confirm_transaction");
-            Ok(signature.to_string())
-        }
-    }
+    let recent_blockhash = agent.connection.get_latest_blockhash()?;
+    let transaction = Transaction::new_signed_with_payer(
+        &[transfer_instruction],
+        Some(&agent.wallet.pubkey),
+        &[&agent.wallet.keypair],
+        recent_blockhash,
+    );
+    on_submitted(&transaction.signatures[0].to_string(), &recent_blockhash.to_string());
+
+    let signature = agent.connection.send_and_confirm_transaction(&transaction)?;
+    Ok(signature.to_string())
 }
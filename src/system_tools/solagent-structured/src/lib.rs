@@ -0,0 +1,143 @@
+//! JSON answers from LLMs: schemas, extraction from replies and validation. Shared by the
+//! solagent crate's LLM client and solagent-core, so both parse model answers the same way.
+
+use std::fmt;
+
+use schemars::JsonSchema;
+use serde_json::Value;
+
+// JSON Schema an LLM answer must satisfy
+#[derive(Clone, Debug)]
+pub struct OutputSchema {
+    pub name: String,
+    pub schema: Value,
+    // Times the model is re-asked after an invalid answer
+    pub max_retries: u32,
+}
+
+impl OutputSchema {
+    pub fn new(name: &str, schema: Value) -> Self {
+        OutputSchema { name: name.to_string(), schema, max_retries: 2 }
+    }
+
+    // Schema generated from a serde type
+    pub fn for_type<T: JsonSchema>() -> Self {
+        let schema = serde_json::to_value(schemars::schema_for!(T)).unwrap_or_default();
+        Self::new(&T::schema_name(), schema)
+    }
+
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    // Name usable in provider APIs, which only allow `[a-zA-Z0-9_-]`
+    pub fn api_name(&self) -> String {
+        self.name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+            .collect()
+    }
+
+    // System instructions asking for a bare JSON answer
+    pub fn instructions(&self) -> String {
+        format!(
+            "Reply with a single JSON value that conforms to this JSON Schema, without any \
+             other text:\n{}",
+            self.schema
+        )
+    }
+
+    // Validates `value` against the schema, returning every violation
+    pub fn validate(&self, value: &Value) -> Result<(), Vec<String>> {
+        let compiled = jsonschema::JSONSchema::compile(&self.schema)
+            .map_err(|e| vec![format!("invalid schema '{}': {}", self.name, e)])?;
+        compiled.validate(value).map_err(|errors| {
+            errors
+                .map(|e| match e.instance_path.to_string() {
+                    path if path.is_empty() => e.to_string(),
+                    path => format!("{}: {}", path, e),
+                })
+                .collect()
+        })
+    }
+
+    // Extracts JSON from a model reply and validates it. Replies may hold several JSON values,
+    // e.g. an example before the answer, so the first one that conforms is returned; when none
+    // does, the violations of the first are.
+    pub fn parse(&self, text: &str) -> Result<Value, Vec<String>> {
+        let mut candidates = json_candidates(text);
+        if candidates.is_empty() {
+            candidates.push(text.trim());
+        }
+        let mut first_errors = None;
+        for candidate in candidates {
+            let errors = match serde_json::from_str(candidate) {
+                Ok(value) => match self.validate(&value) {
+                    Ok(()) => return Ok(value),
+                    Err(errors) => errors,
+                },
+                Err(e) => vec![format!("reply is not valid JSON: {}", e)],
+            };
+            first_errors.get_or_insert(errors);
+        }
+        Err(first_errors.unwrap_or_default())
+    }
+
+    // Follow-up message asking the model to fix an invalid answer
+    pub fn repair_prompt(&self, errors: &[String]) -> String {
+        format!(
+            "Your reply did not match the schema:\n- {}\nReply again with only the corrected \
+             JSON.",
+            errors.join("\n- ")
+        )
+    }
+}
+
+// Strips Markdown code fences and any prose around the JSON value in a reply; the whole reply
+// is returned when it holds no JSON object or array
+pub fn extract_json(text: &str) -> &str {
+    json_candidates(text).first().copied().unwrap_or(text.trim())
+}
+
+// JSON objects and arrays in a reply, in order. Each `{` or `[` is tried in turn, so braces in
+// the prose are skipped; values nested in an earlier candidate are not candidates themselves.
+pub fn json_candidates(text: &str) -> Vec<&str> {
+    let text = text.trim();
+    let mut candidates = vec![];
+    let mut end = 0;
+    for (start, _) in text.match_indices(['{', '[']) {
+        if start < end {
+            continue;
+        }
+        let mut values = serde_json::Deserializer::from_str(&text[start..]).into_iter::<Value>();
+        if let Some(Ok(_)) = values.next() {
+            end = start + values.byte_offset();
+            candidates.push(&text[start..end]);
+        }
+    }
+    candidates
+}
+
+// Returned when the model never produced a valid answer
+#[derive(Debug)]
+pub struct StructuredOutputError {
+    pub schema: String,
+    pub attempts: u32,
+    pub errors: Vec<String>,
+    pub last_output: String,
+}
+
+impl fmt::Display for StructuredOutputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "No valid '{}' output after {} attempts: {}",
+            self.schema,
+            self.attempts,
+            self.errors.join("; ")
+        )
+    }
+}
+
+impl std::error::Error for StructuredOutputError {}
@@ -1,30 +1,67 @@
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
-use crate::llm_integration::LLMProvider;
-
-pub mod account;
-pub mod defi;
-pub mod misc;
-pub mod nft;
-pub mod staking;
-pub mod transaction;
+use crate::{llm_integration::LLMProvider, memory_system::actions::ActionLog};
 
 // Tool metadata for versioning, dependencies, and LLM compatibility
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct ToolMetadata {
     pub name: String,
     pub aliases: Vec<String>,
     pub version: String,
     pub llm_type: String,
     pub schema: serde_json::Value,
+    // Whether calls may send transactions; those that report one through
+    // `ToolInput::submitted` are recorded in the registry's action log
+    #[serde(default)]
+    pub sends_transactions: bool,
+}
+
+impl ToolMetadata {
+    // JSON Schema of the tool's arguments, from a function declaration or an OpenAI tool
+    pub fn parameters(&self) -> Option<&serde_json::Value> {
+        [&self.schema["parameters"], &self.schema["function"]["parameters"]]
+            .into_iter()
+            .find(|p| p.is_object())
+    }
+
+    pub fn description(&self) -> &str {
+        self.schema["description"]
+            .as_str()
+            .or(self.schema["function"]["description"].as_str())
+            .unwrap_or_default()
+    }
 }
 
+// Called with the signature and recent blockhash of each transaction a tool sends
+pub type SubmittedHook = Arc<dyn Fn(&str, &str) + Send + Sync>;
+
 // Tool input structure
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Default)]
 pub struct ToolInput {
     pub params: serde_json::Value,
+    #[serde(skip)]
+    pub on_submitted: Option<SubmittedHook>,
+}
+
+impl ToolInput {
+    pub fn new(params: serde_json::Value) -> Self {
+        ToolInput { params, on_submitted: None }
+    }
+
+    pub fn with_on_submitted(mut self, hook: SubmittedHook) -> Self {
+        self.on_submitted = Some(hook);
+        self
+    }
+
+    // Tools sending transactions call this once a transaction is signed and before it is sent,
+    // so a workflow interrupted before the tool returns knows the transaction may land
+    pub fn submitted(&self, signature: &str, blockhash: &str) {
+        if let Some(hook) = &self.on_submitted {
+            hook(signature, blockhash);
+        }
+    }
 }
 
 // Solana and LLM tool trait
@@ -37,20 +74,39 @@ pub trait SolanaTool: Send + Sync {
     ) -> Result<String, Box<dyn std::error::Error>>;
 }
 
+// Registered tool with its metadata
+type ToolEntry = (ToolMetadata, Arc<dyn SolanaTool>);
+
 // Tool registry for managing tools
 pub struct ToolRegistry {
-    tools: tokio::sync::RwLock<
-        std::collections::HashMap<String, (ToolMetadata, Arc<dyn SolanaTool>)>,
-    >,
+    tools: tokio::sync::RwLock<std::collections::HashMap<String, ToolEntry>>,
+    action_log: Option<Arc<ActionLog>>,
+}
+
+impl Default for ToolRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ToolRegistry {
     pub fn new() -> Self {
         ToolRegistry {
             tools: tokio::sync::RwLock::new(std::collections::HashMap::new()),
+            action_log: None,
         }
     }
 
+    // Records the on-chain actions taken through the registry's tools in `action_log`
+    pub fn with_action_log(mut self, action_log: Arc<ActionLog>) -> Self {
+        self.action_log = Some(action_log);
+        self
+    }
+
+    pub fn action_log(&self) -> Option<&Arc<ActionLog>> {
+        self.action_log.as_ref()
+    }
+
     // Registers a tool with metadata
     pub async fn register(
         &self,
@@ -67,21 +123,74 @@ impl ToolRegistry {
         name: &str,
     ) -> Option<(ToolMetadata, Arc<dyn SolanaTool>)> {
         let tools = self.tools.read().await;
-        tools
+        let (meta, tool) = tools
             .iter()
-            .find(|(key, (meta, _))| key == name || meta.aliases.contains(&name.to_string()))
-            .map(|(_, v)| v.clone())
+            .find(|(key, (meta, _))| {
+                key.as_str() == name || meta.aliases.contains(&name.to_string())
+            })
+            .map(|(_, v)| v.clone())?;
+        match &self.action_log {
+            Some(action_log) if meta.sends_transactions => {
+                let tool = RecordedTool {
+                    name: meta.name.clone(),
+                    tool,
+                    action_log: action_log.clone(),
+                };
+                Some((meta, Arc::new(tool)))
+            }
+            _ => Some((meta, tool)),
+        }
     }
 
-    // Registers specified tools
-    pub async fn register_tools(&self, tool_names: &[String], rpc_url: &str) {
+    // Lists metadata of all registered tools
+    pub async fn list(&self) -> Vec<ToolMetadata> {
+        let tools = self.tools.read().await;
+        tools.values().map(|(meta, _)| meta.clone()).collect()
+    }
+
+    // Checks that the specified tools are registered; built-in tools are not bundled yet, so
+    // tools are registered by the application with `register`
+    pub async fn register_tools(&self, tool_names: &[String], _rpc_url: &str) {
         for name in tool_names {
-            match name.as_str() {
-                "get_balance" => account::register_account_tools(self, rpc_url).await,
-                "stake_sol" => staking::register_staking_tools(self, rpc_url).await,
-                // Add other tools (defi, nft, transaction, misc)
-                _ => println!("Warning: Tool '{}' not found", name),
+            if self.get(name).await.is_none() {
+                println!("Warning: Tool '{}' not found", name);
+            }
+        }
+    }
+}
+
+// Tool whose calls are recorded in an action log
+struct RecordedTool {
+    name: String,
+    tool: Arc<dyn SolanaTool>,
+    action_log: Arc<ActionLog>,
+}
+
+#[async_trait]
+impl SolanaTool for RecordedTool {
+    async fn execute(
+        &self,
+        input: ToolInput,
+        llm: &dyn LLMProvider,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let arguments = input.params.clone();
+        // Transactions the tool reports are collected, and still passed on to the caller
+        let signatures = Arc::new(Mutex::new(vec![]));
+        let reported = signatures.clone();
+        let caller = input.on_submitted.clone();
+        let input = input.with_on_submitted(Arc::new(move |signature, blockhash| {
+            reported.lock().unwrap().push(signature.to_string());
+            if let Some(caller) = &caller {
+                caller(signature, blockhash);
             }
+        }));
+        // The error becomes text at once, as it cannot be held while recording
+        let result = self.tool.execute(input, llm).await.map_err(|e| e.to_string());
+        let outcome = result.as_deref().map_err(String::as_str);
+        let signatures = signatures.lock().unwrap().clone();
+        if let Err(e) = self.action_log.record(&self.name, &arguments, signatures, outcome).await {
+            tracing::warn!(tool = %self.name, "Could not record action: {}", e);
         }
+        result.map_err(|e| e.into())
     }
 }
\ No newline at end of file
@@ -1,21 +1,163 @@
-use actix_web::App;
+use std::{collections::HashMap, sync::Arc};
+
+use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::agent_controller::{session::SessionManager, transcript::TaskTranscript, AgentController};
+use crate::util::constant_time_eq;
 
 // API server configuration
 pub struct ApiServer {
+    host: String,
     port: u16,
-    app: App<()>,
+    // Caller names by bearer token; the server refuses to start without at least one
+    tokens: HashMap<String, String>,
+    controller: Arc<AgentController>,
 }
 
+// Body of `POST /tasks`; a new session is started when no session id is given
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TaskRequest {
+    #[serde(default)]
+    pub session_id: Option<String>,
+    pub task: String,
+    #[serde(default)]
+    pub input: Value,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TaskResponse {
+    pub session_id: String,
+    pub transcript: TaskTranscript,
+}
+
+// Bearer tokens accepted by the routes, mapped to the caller they identify
+struct Callers(HashMap<String, String>);
+
 impl ApiServer {
-    pub fn new() -> Self {
+    pub fn new(controller: Arc<AgentController>) -> Self {
         ApiServer {
+            host: "127.0.0.1".to_string(),
             port: 8080,
-            app: App::new(),
+            tokens: HashMap::new(),
+            controller,
+        }
+    }
+
+    // Address to listen on; only local clients can connect by default
+    pub fn with_host(mut self, host: &str) -> Self {
+        self.host = host.to_string();
+        self
+    }
+
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    // Accepts `Authorization: Bearer <token>` as `caller`. Each caller only sees its own
+    // sessions. Empty tokens are ignored.
+    pub fn with_token(mut self, caller: &str, token: &str) -> Self {
+        if !token.is_empty() {
+            self.tokens.insert(token.to_string(), caller.to_string());
+        }
+        self
+    }
+
+    // Registers the routes and their state, for serving them from an existing `App`:
+    // - `POST /tasks` runs a task in a session
+    // - `GET /sessions/{id}/history` returns a live session's history
+    // - `DELETE /sessions/{id}` ends a session
+    pub fn configure(&self) -> impl Fn(&mut web::ServiceConfig) + Clone + Send + 'static {
+        let controller = web::Data::from(self.controller.clone());
+        let callers = web::Data::new(Callers(self.tokens.clone()));
+        move |cfg| {
+            cfg.app_data(controller.clone())
+                .app_data(callers.clone())
+                .route("/tasks", web::post().to(run_task))
+                .route("/sessions/{id}/history", web::get().to(session_history))
+                .route("/sessions/{id}", web::delete().to(close_session));
         }
     }
 
-    // Starts the API server
+    // Starts the API server; fails when no bearer token is configured
     pub async fn run(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.tokens.is_empty() {
+            return Err("The API server needs a bearer token; add one with `with_token`".into());
+        }
+        let configure = self.configure();
+        HttpServer::new(move || App::new().configure(configure.clone()))
+            .bind((self.host.as_str(), self.port))?
+            .run()
+            .await?;
         Ok(())
     }
+}
+
+// Caller identified by the request's bearer token. Every token is compared so the time
+// taken does not depend on which one matched.
+fn caller(request: &HttpRequest, callers: &Callers) -> Option<String> {
+    let header = request.headers().get("Authorization")?.to_str().ok()?;
+    let token = header.strip_prefix("Bearer ")?.as_bytes();
+    callers.0.iter().fold(None, |found, (expected, caller)| {
+        if constant_time_eq(token, expected.as_bytes()) {
+            Some(caller.clone())
+        } else {
+            found
+        }
+    })
+}
+
+// Session ids are namespaced by caller so one caller cannot reach another's sessions
+fn scoped(caller: &str, session_id: &str) -> String {
+    format!("{}/{}", caller, session_id)
+}
+
+fn unauthorized() -> HttpResponse {
+    HttpResponse::Unauthorized()
+        .insert_header(("WWW-Authenticate", "Bearer"))
+        .json(json!({ "error": "Missing or invalid bearer token" }))
+}
+
+async fn run_task(
+    http: HttpRequest,
+    request: web::Json<TaskRequest>,
+    controller: web::Data<AgentController>,
+    callers: web::Data<Callers>,
+) -> HttpResponse {
+    let Some(caller) = caller(&http, &callers) else { return unauthorized() };
+    let TaskRequest { session_id, task, input } = request.into_inner();
+    let session_id = session_id.unwrap_or_else(SessionManager::new_id);
+    match controller.execute_task(&scoped(&caller, &session_id), &task, input).await {
+        Ok(transcript) => HttpResponse::Ok().json(TaskResponse { session_id, transcript }),
+        Err(e) => HttpResponse::InternalServerError().json(json!({ "error": e.to_string() })),
+    }
+}
+
+async fn session_history(
+    http: HttpRequest,
+    id: web::Path<String>,
+    controller: web::Data<AgentController>,
+    callers: web::Data<Callers>,
+) -> HttpResponse {
+    let Some(caller) = caller(&http, &callers) else { return unauthorized() };
+    match controller.sessions().get(&scoped(&caller, &id)) {
+        Some(session) => HttpResponse::Ok().json(session.history().await),
+        None => HttpResponse::NotFound().finish(),
+    }
+}
+
+async fn close_session(
+    http: HttpRequest,
+    id: web::Path<String>,
+    controller: web::Data<AgentController>,
+    callers: web::Data<Callers>,
+) -> HttpResponse {
+    let Some(caller) = caller(&http, &callers) else { return unauthorized() };
+    match controller.sessions().close(&scoped(&caller, &id)).await {
+        Ok(true) => HttpResponse::NoContent().finish(),
+        Ok(false) => HttpResponse::NotFound().finish(),
+        Err(e) => HttpResponse::InternalServerError().json(json!({ "error": e.to_string() })),
+    }
 }
\ No newline at end of file
@@ -2,9 +2,14 @@ use async_trait::async_trait;
 use clap::{Parser, Subcommand};
 use serde_json::json;
 
+use crate::agent_controller::AgentController;
+
 // CLI configuration structure
 #[derive(Parser)]
 pub struct CliConfig {
+    // Conversation session the command runs in; reusing an id continues its conversation
+    #[clap(long, global = true, default_value = "cli")]
+    session: String,
     #[clap(subcommand)]
     commands: SolAgentCommands,
 }
@@ -18,7 +23,16 @@ pub enum SolAgentCommands {
 
 #[async_trait]
 pub trait CliHandler {
-    async fn handle_args(&self) -> Result<(), Box<dyn std::error::Error>>;
+    async fn handle_args(
+        &self,
+        controller: &AgentController,
+    ) -> Result<(), Box<dyn std::error::Error>>;
+}
+
+impl Default for CliConfig {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl CliConfig {
@@ -26,12 +40,26 @@ impl CliConfig {
         CliConfig::parse()
     }
 
-    // Handles CLI arguments
-    #[async_trait]
-    impl CliHandler for CliConfig {
-        async fn handle_args(&self) -> Result<(), Box<dyn std::error::Error>> {
-            // Implementation placeholder
-            Ok(())
-        }
+    pub fn session(&self) -> &str {
+        &self.session
+    }
+}
+
+// Handles CLI arguments
+#[async_trait]
+impl CliHandler for CliConfig {
+    async fn handle_args(
+        &self,
+        controller: &AgentController,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let (task, input) = match &self.commands {
+            SolAgentCommands::GetBalance { pubkey } => ("get_balance", json!({ "pubkey": pubkey })),
+            SolAgentCommands::StakeSol { amount, validator } => {
+                ("stake_sol", json!({ "amount": amount, "validator": validator }))
+            }
+        };
+        let transcript = controller.execute_task(&self.session, task, input).await?;
+        println!("{}", transcript.final_answer.unwrap_or_default());
+        Ok(())
     }
 }
\ No newline at end of file
@@ -1,3 +1,7 @@
 pub mod cli;
 pub mod web;
-pub mod api;
\ No newline at end of file
+pub mod api;
+
+pub use api::ApiServer;
+pub use cli::CliConfig;
+pub use web::WebConfig;
\ No newline at end of file
@@ -3,8 +3,18 @@ pub struct WebConfig {
     port: u16,
 }
 
+impl Default for WebConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl WebConfig {
     pub fn new() -> Self {
         WebConfig { port: 3000 }
     }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
 }
\ No newline at end of file
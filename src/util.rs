@@ -0,0 +1,36 @@
+//! Helpers shared across modules
+
+use std::{
+    sync::{Mutex, MutexGuard},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use rusqlite::{ffi, Connection};
+
+// Current Unix time in milliseconds, the unit of every timestamp the crate stores
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or_default()
+}
+
+// FNV-1a hash, which unlike `DefaultHasher` stays the same across Rust versions and platforms
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+// Locks a SQLite connection, failing instead of panicking if a thread panicked while holding it
+pub fn lock_db(db: &Mutex<Connection>) -> rusqlite::Result<MutexGuard<'_, Connection>> {
+    db.lock().map_err(|_| {
+        rusqlite::Error::SqliteFailure(
+            ffi::Error::new(ffi::SQLITE_MISUSE),
+            Some("database lock poisoned".to_string()),
+        )
+    })
+}
+
+// Compares secrets in time independent of where they differ, so a caller cannot guess them byte
+// by byte
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
\ No newline at end of file
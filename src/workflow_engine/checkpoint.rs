@@ -0,0 +1,413 @@
+//! Durable workflow checkpoints stored in SQLite
+
+use std::{
+    collections::HashMap,
+    fmt,
+    str::FromStr,
+    sync::Mutex,
+};
+
+use async_trait::async_trait;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{commitment_config::CommitmentConfig, hash::Hash, signature::Signature};
+
+use crate::util::{lock_db, now};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckpointStatus {
+    Pending,
+    Running,
+    Succeeded,
+    Failed,
+    Skipped,
+    // The node timed out, so whether its tool sent a transaction is unknown
+    Unknown,
+}
+
+impl CheckpointStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CheckpointStatus::Pending => "pending",
+            CheckpointStatus::Running => "running",
+            CheckpointStatus::Succeeded => "succeeded",
+            CheckpointStatus::Failed => "failed",
+            CheckpointStatus::Skipped => "skipped",
+            CheckpointStatus::Unknown => "unknown",
+        }
+    }
+}
+
+impl FromStr for CheckpointStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(CheckpointStatus::Pending),
+            "running" => Ok(CheckpointStatus::Running),
+            "succeeded" => Ok(CheckpointStatus::Succeeded),
+            "failed" => Ok(CheckpointStatus::Failed),
+            "skipped" => Ok(CheckpointStatus::Skipped),
+            "unknown" => Ok(CheckpointStatus::Unknown),
+            other => Err(format!("Unknown checkpoint status '{}'", other)),
+        }
+    }
+}
+
+impl fmt::Display for CheckpointStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// Last known state of one node
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct NodeCheckpoint {
+    pub node_id: String,
+    pub status: CheckpointStatus,
+    // Inputs with their placeholders resolved, once the node started
+    pub inputs: Option<Value>,
+    pub output: Option<String>,
+    pub error: Option<String>,
+    // Transaction signatures the tool reported sending or found in its output or error
+    pub signatures: Vec<String>,
+    // Recent blockhash of each transaction the tool reported sending, by signature
+    #[serde(default)]
+    pub blockhashes: HashMap<String, String>,
+    // Unix time of the last update, in milliseconds
+    pub updated_at: u64,
+}
+
+impl NodeCheckpoint {
+    pub fn new(node_id: &str, status: CheckpointStatus) -> Self {
+        NodeCheckpoint {
+            node_id: node_id.to_string(),
+            status,
+            inputs: None,
+            output: None,
+            error: None,
+            signatures: vec![],
+            blockhashes: HashMap::new(),
+            updated_at: now(),
+        }
+    }
+}
+
+// Workflow state for checkpointing
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct WorkflowState {
+    pub workflow_id: String,
+    pub status: CheckpointStatus,
+    // Inputs the run was started with
+    pub inputs: Value,
+    pub nodes: HashMap<String, NodeCheckpoint>,
+}
+
+// SQLite store of workflow checkpoints
+pub struct CheckpointStore {
+    db: Mutex<Connection>,
+}
+
+impl CheckpointStore {
+    pub fn open(path: &str) -> rusqlite::Result<Self> {
+        Self::init(Connection::open(path)?)
+    }
+
+    pub fn in_memory() -> rusqlite::Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(db: Connection) -> rusqlite::Result<Self> {
+        db.execute_batch(
+            "CREATE TABLE IF NOT EXISTS workflows (
+                id TEXT PRIMARY KEY,
+                status TEXT NOT NULL,
+                updated_at INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS workflow_nodes (
+                workflow_id TEXT NOT NULL,
+                node_id TEXT NOT NULL,
+                status TEXT NOT NULL,
+                inputs TEXT,
+                output TEXT,
+                error TEXT,
+                signatures TEXT NOT NULL,
+                updated_at INTEGER NOT NULL,
+                PRIMARY KEY (workflow_id, node_id)
+            );
+            CREATE TABLE IF NOT EXISTS workflow_inputs (
+                workflow_id TEXT PRIMARY KEY,
+                inputs TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS workflow_transactions (
+                workflow_id TEXT NOT NULL,
+                node_id TEXT NOT NULL,
+                signature TEXT NOT NULL,
+                blockhash TEXT NOT NULL,
+                PRIMARY KEY (workflow_id, signature)
+            );",
+        )?;
+        Ok(CheckpointStore { db: Mutex::new(db) })
+    }
+
+    pub fn save_workflow(
+        &self,
+        workflow_id: &str,
+        status: CheckpointStatus,
+    ) -> rusqlite::Result<()> {
+        lock_db(&self.db)?.execute(
+            "INSERT INTO workflows (id, status, updated_at) VALUES (?1, ?2, ?3)
+             ON CONFLICT(id) DO UPDATE SET status = ?2, updated_at = ?3",
+            params![workflow_id, status.as_str(), now()],
+        )?;
+        Ok(())
+    }
+
+    pub fn save_inputs(&self, workflow_id: &str, inputs: &Value) -> rusqlite::Result<()> {
+        lock_db(&self.db)?.execute(
+            "INSERT OR REPLACE INTO workflow_inputs (workflow_id, inputs) VALUES (?1, ?2)",
+            params![workflow_id, inputs.to_string()],
+        )?;
+        Ok(())
+    }
+
+    pub fn save_node(&self, workflow_id: &str, node: &NodeCheckpoint) -> rusqlite::Result<()> {
+        let db = lock_db(&self.db)?;
+        for (signature, blockhash) in &node.blockhashes {
+            insert_transaction(&db, workflow_id, &node.node_id, signature, blockhash)?;
+        }
+        db.execute(
+            "INSERT OR REPLACE INTO workflow_nodes
+             (workflow_id, node_id, status, inputs, output, error, signatures, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                workflow_id,
+                node.node_id,
+                node.status.as_str(),
+                node.inputs.as_ref().map(|inputs| inputs.to_string()),
+                node.output,
+                node.error,
+                serde_json::to_string(&node.signatures).unwrap_or_default(),
+                node.updated_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    // Adds a transaction to a node that is still running, as soon as its tool reports sending it
+    pub fn save_transaction(
+        &self,
+        workflow_id: &str,
+        node_id: &str,
+        signature: &str,
+        blockhash: &str,
+    ) -> rusqlite::Result<()> {
+        let db = lock_db(&self.db)?;
+        insert_transaction(&db, workflow_id, node_id, signature, blockhash)?;
+        let signatures: Option<String> = db
+            .query_row(
+                "SELECT signatures FROM workflow_nodes WHERE workflow_id = ?1 AND node_id = ?2",
+                [workflow_id, node_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let mut signatures: Vec<String> =
+            signatures.and_then(|s| serde_json::from_str(&s).ok()).unwrap_or_default();
+        if signatures.iter().any(|s| s == signature) {
+            return Ok(());
+        }
+        signatures.push(signature.to_string());
+        db.execute(
+            "UPDATE workflow_nodes SET signatures = ?3, updated_at = ?4
+             WHERE workflow_id = ?1 AND node_id = ?2",
+            params![
+                workflow_id,
+                node_id,
+                serde_json::to_string(&signatures).unwrap_or_default(),
+                now()
+            ],
+        )?;
+        Ok(())
+    }
+
+    // Latest checkpoint of a workflow, if it was ever started
+    pub fn load(&self, workflow_id: &str) -> rusqlite::Result<Option<WorkflowState>> {
+        let db = lock_db(&self.db)?;
+        let status: Option<String> = db
+            .query_row("SELECT status FROM workflows WHERE id = ?1", [workflow_id], |row| {
+                row.get(0)
+            })
+            .optional()?;
+        let Some(status) = status else { return Ok(None) };
+        let inputs: Option<String> = db
+            .query_row(
+                "SELECT inputs FROM workflow_inputs WHERE workflow_id = ?1",
+                [workflow_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        let mut statement = db.prepare(
+            "SELECT node_id, status, inputs, output, error, signatures, updated_at
+             FROM workflow_nodes WHERE workflow_id = ?1",
+        )?;
+        let rows = statement.query_map([workflow_id], |row| {
+            let status: String = row.get(1)?;
+            let inputs: Option<String> = row.get(2)?;
+            let signatures: String = row.get(5)?;
+            Ok(NodeCheckpoint {
+                node_id: row.get(0)?,
+                status: parse_status(&status, 1)?,
+                inputs: inputs.and_then(|inputs| serde_json::from_str(&inputs).ok()),
+                output: row.get(3)?,
+                error: row.get(4)?,
+                signatures: serde_json::from_str(&signatures).unwrap_or_default(),
+                blockhashes: HashMap::new(),
+                updated_at: row.get(6)?,
+            })
+        })?;
+        let mut nodes: HashMap<String, NodeCheckpoint> = rows
+            .map(|node| node.map(|node| (node.node_id.clone(), node)))
+            .collect::<rusqlite::Result<_>>()?;
+        let mut statement = db.prepare(
+            "SELECT node_id, signature, blockhash FROM workflow_transactions
+             WHERE workflow_id = ?1",
+        )?;
+        let transactions = statement.query_map([workflow_id], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?))
+        })?;
+        for transaction in transactions {
+            let (node_id, signature, blockhash) = transaction?;
+            if let Some(node) = nodes.get_mut(&node_id) {
+                node.blockhashes.insert(signature, blockhash);
+            }
+        }
+        Ok(Some(WorkflowState {
+            workflow_id: workflow_id.to_string(),
+            status: parse_status(&status, 0)?,
+            inputs: inputs.and_then(|i| serde_json::from_str(&i).ok()).unwrap_or_default(),
+            nodes,
+        }))
+    }
+
+    pub fn delete(&self, workflow_id: &str) -> rusqlite::Result<()> {
+        let db = lock_db(&self.db)?;
+        db.execute("DELETE FROM workflow_nodes WHERE workflow_id = ?1", [workflow_id])?;
+        db.execute("DELETE FROM workflow_inputs WHERE workflow_id = ?1", [workflow_id])?;
+        db.execute("DELETE FROM workflow_transactions WHERE workflow_id = ?1", [workflow_id])?;
+        db.execute("DELETE FROM workflows WHERE id = ?1", [workflow_id])?;
+        Ok(())
+    }
+}
+
+fn insert_transaction(
+    db: &Connection,
+    workflow_id: &str,
+    node_id: &str,
+    signature: &str,
+    blockhash: &str,
+) -> rusqlite::Result<()> {
+    db.execute(
+        "INSERT OR IGNORE INTO workflow_transactions (workflow_id, node_id, signature, blockhash)
+         VALUES (?1, ?2, ?3, ?4)",
+        params![workflow_id, node_id, signature, blockhash],
+    )?;
+    Ok(())
+}
+
+fn parse_status(status: &str, column: usize) -> rusqlite::Result<CheckpointStatus> {
+    status.parse().map_err(|e: String| {
+        rusqlite::Error::FromSqlConversionFailure(column, rusqlite::types::Type::Text, e.into())
+    })
+}
+
+// Confirms whether submitted transactions landed, so resumed workflows don't send them again
+#[async_trait]
+pub trait SignatureChecker: Send + Sync {
+    async fn is_confirmed(&self, signature: &str) -> Result<bool, Box<dyn std::error::Error>>;
+    // Whether transactions built on `blockhash` can still land; an unconfirmed transaction is
+    // only safe to send again once this is false
+    async fn is_blockhash_valid(
+        &self,
+        blockhash: &str,
+    ) -> Result<bool, Box<dyn std::error::Error>>;
+}
+
+// Signature checker querying a Solana RPC node
+pub struct RpcSignatureChecker {
+    client: RpcClient,
+}
+
+impl RpcSignatureChecker {
+    pub fn new(rpc_url: &str) -> Self {
+        RpcSignatureChecker { client: RpcClient::new(rpc_url.to_string()) }
+    }
+}
+
+#[async_trait]
+impl SignatureChecker for RpcSignatureChecker {
+    // Confirmed means the transaction landed without an error at `confirmed` commitment
+    async fn is_confirmed(&self, signature: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        let signature = Signature::from_str(signature)?;
+        let statuses = self.client.get_signature_statuses(&[signature]).await?.value;
+        Ok(statuses.into_iter().flatten().any(|status| {
+            status.err.is_none() && status.satisfies_commitment(CommitmentConfig::confirmed())
+        }))
+    }
+
+    async fn is_blockhash_valid(
+        &self,
+        blockhash: &str,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let blockhash = Hash::from_str(blockhash)?;
+        Ok(self.client.is_blockhash_valid(&blockhash, CommitmentConfig::processed()).await?)
+    }
+}
+
+// Transaction signatures mentioned in a tool's output or error
+pub fn find_signatures(text: &str) -> Vec<String> {
+    let mut signatures: Vec<String> = vec![];
+    for word in text.split(|c: char| !c.is_ascii_alphanumeric()) {
+        let candidate = (86..=88).contains(&word.len()) && Signature::from_str(word).is_ok();
+        if candidate && !signatures.iter().any(|s| s == word) {
+            signatures.push(word.to_string());
+        }
+    }
+    signatures
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_round_trip() {
+        let store = CheckpointStore::in_memory().unwrap();
+        let signature = Signature::new_unique().to_string();
+        let mut node = NodeCheckpoint::new("swap", CheckpointStatus::Failed);
+        node.error = Some(format!("Transaction {} was not confirmed in time", signature));
+        node.signatures = find_signatures(node.error.as_ref().unwrap());
+        store.save_workflow("wf", CheckpointStatus::Running).unwrap();
+        store.save_node("wf", &node).unwrap();
+
+        let state = store.load("wf").unwrap().unwrap();
+        assert_eq!(state.status, CheckpointStatus::Running);
+        assert_eq!(state.nodes["swap"], node);
+        assert_eq!(node.signatures, vec![signature]);
+        assert!(store.load("other").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_poisoned_store_returns_errors() {
+        let store = CheckpointStore::in_memory().unwrap();
+        let _ = std::panic::catch_unwind(|| {
+            let _db = store.db.lock().unwrap();
+            panic!("writer crashed");
+        });
+        assert!(store.save_workflow("wf", CheckpointStatus::Running).is_err());
+        assert!(store.load("wf").is_err());
+    }
+}
@@ -1,30 +1,1070 @@
-///! Directed Acyclic Graph (DAG) for workflow orchestration
+//! Directed Acyclic Graph (DAG) for workflow orchestration
 
 
-use petgraph::Graph;
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use futures::{
+    stream::{self, FuturesUnordered},
+    StreamExt,
+};
+use petgraph::{
+    algo::has_path_connecting, graph::NodeIndex, visit::EdgeRef, Direction, Graph,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::Semaphore;
+
+use crate::{
+    llm_integration::{LLMProvider, NoLlmProvider},
+    planning_reasoning::{
+        expression::{Expr, Scope},
+        template,
+    },
+    tool_system::{SubmittedHook, ToolInput, ToolRegistry},
+    workflow_engine::checkpoint::{
+        find_signatures, CheckpointStatus, CheckpointStore, NodeCheckpoint, SignatureChecker,
+        WorkflowState,
+    },
+};
+
+// Placeholder scope of upstream outputs in node inputs, e.g. `${nodes.quote.output.out_amount}`
+pub const NODES: &str = "nodes";
+
+// Placeholder scope of the inputs a run was started with, e.g. `${inputs.payload.mint}`
+pub const INPUTS: &str = "inputs";
 
 // Workflow Engine structure
 pub struct WorkflowEngine {
-    dag: Graph<WorkflowNode, ()>,
-    checkpoints: std::collections::HashMap<String, WorkflowState>,
+    dag: Graph<WorkflowNode, WorkflowEdge>,
+    index: HashMap<String, NodeIndex>,
+    // Store recording every node's progress, so interrupted runs can be resumed
+    checkpoints: Option<Arc<CheckpointStore>>,
+    // Confirms transactions of interrupted nodes before a resumed run would send them again
+    signature_checker: Option<Arc<dyn SignatureChecker>>,
+    tool_registry: Arc<ToolRegistry>,
+    // Provider handed to tools; workflows run without an LLM unless one is set
+    llm: Arc<dyn LLMProvider>,
+    // Maximum number of nodes running at once
+    max_concurrency: usize,
+    // Permits for tool calls, `max_concurrency` of them, taken by every call of every node so
+    // list elements of concurrent nodes stay within the limit as a whole
+    tool_calls: Semaphore,
 }
 
+// Delay before the first retry of a failed node, doubled for every further retry
+const RETRY_BACKOFF_MS: u64 = 500;
+
 // Workflow node structure
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct WorkflowNode {
-    tool_name: String,
-    inputs: serde_json::Value,
+    pub id: String,
+    pub tool_name: String,
+    // Tool arguments; strings may embed outputs of upstream nodes as `${nodes.<id>.output}`
+    pub inputs: serde_json::Value,
+    // Expression over upstream outputs, e.g. `nodes.quote.output.out_amount > 100`; the node
+    // is skipped when it is false
+    #[serde(default)]
+    pub condition: Option<String>,
+    // Extra attempts after a failure. Failures after the tool reported sending a transaction,
+    // or mentioning a transaction signature, are never retried, so a sent transaction is not
+    // sent twice; neither are timeouts unless the node is idempotent.
+    #[serde(default)]
+    pub retries: u32,
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+    // Runs the tool once per element of this list, e.g. `"${nodes.tokens.output.mints}"`.
+    // Inputs read the element as `${item}` or `${item.<path>}` and its position as `${index}`.
+    #[serde(default)]
+    pub for_each: Option<Value>,
+    #[serde(default)]
+    pub repeat: Option<RepeatUntil>,
+    // Safe to run again after an interruption, e.g. a read-only query. A node that was
+    // interrupted while running is otherwise not run again on resume, as it may have sent a
+    // transaction without reporting it.
+    #[serde(default)]
+    pub idempotent: bool,
+}
+
+// Repeats a node until a condition on its latest output holds
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RepeatUntil {
+    // Expression over the latest `output[.<path>]`, the `pass` number and upstream outputs,
+    // e.g. `output.filled == true`
+    pub until: String,
+    // The node fails if the condition still does not hold after this many passes
+    pub max_passes: u32,
+    #[serde(default)]
+    pub delay_secs: u64,
 }
 
-// Workflow state for checkpointing
-pub struct WorkflowState {
-    status: String,
+// Edge between two nodes
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct WorkflowEdge {
+    // Expression over upstream outputs, e.g. `nodes.rugcheck.output.score < 500`; the
+    // downstream node is skipped when it is false
+    #[serde(default)]
+    pub condition: Option<String>,
+}
+
+impl WorkflowNode {
+    pub fn new(id: &str, tool_name: &str, inputs: Value) -> Self {
+        WorkflowNode {
+            id: id.to_string(),
+            tool_name: tool_name.to_string(),
+            inputs,
+            condition: None,
+            retries: 0,
+            timeout_secs: None,
+            for_each: None,
+            repeat: None,
+            idempotent: false,
+        }
+    }
+
+    pub fn with_condition(mut self, condition: &str) -> Self {
+        self.condition = Some(condition.to_string());
+        self
+    }
+
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout_secs = Some(timeout.as_secs().max(1));
+        self
+    }
+
+    pub fn with_for_each(mut self, list: Value) -> Self {
+        self.for_each = Some(list);
+        self
+    }
+
+    pub fn with_repeat(mut self, repeat: RepeatUntil) -> Self {
+        self.repeat = Some(repeat);
+        self
+    }
+
+    pub fn with_idempotent(mut self, idempotent: bool) -> Self {
+        self.idempotent = idempotent;
+        self
+    }
+
+    fn condition_text(&self) -> &str {
+        self.condition.as_deref().unwrap_or_default()
+    }
+}
+
+// Error raised while building or validating a workflow
+#[derive(Debug, Clone, PartialEq)]
+pub enum WorkflowError {
+    DuplicateNode(String),
+    UnknownNode(String),
+    // The edge would close a cycle
+    Cycle { from: String, to: String },
+    // Inputs use the output of a node that is not directly upstream
+    InvalidReference { node: String, referenced: String },
+    InvalidCondition { node: String, error: String },
+    // No checkpoint of the workflow to resume
+    NotFound(String),
+    Checkpoint(String),
+}
+
+impl fmt::Display for WorkflowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WorkflowError::DuplicateNode(id) => write!(f, "Node '{}' already exists", id),
+            WorkflowError::UnknownNode(id) => write!(f, "Node '{}' not found", id),
+            WorkflowError::Cycle { from, to } => {
+                write!(f, "Edge '{}' -> '{}' would create a cycle", from, to)
+            }
+            WorkflowError::InvalidReference { node, referenced } => write!(
+                f,
+                "Node '{}' uses the output of '{}', which is not directly upstream",
+                node, referenced
+            ),
+            WorkflowError::InvalidCondition { node, error } => {
+                write!(f, "Node '{}' has an invalid condition: {}", node, error)
+            }
+            WorkflowError::NotFound(id) => write!(f, "No checkpoint of workflow '{}'", id),
+            WorkflowError::Checkpoint(error) => write!(f, "Checkpoint failed: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for WorkflowError {}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum NodeStatus {
+    Succeeded { output: String },
+    Failed { error: String },
+    // Not run because an upstream node did not succeed or the node's condition was false
+    Skipped { reason: String },
+}
+
+// Result of one node
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NodeReport {
+    pub node_id: String,
+    pub tool_name: String,
+    // Inputs with their placeholders resolved, for nodes that ran
+    pub inputs: Option<Value>,
+    pub status: NodeStatus,
+    pub duration_ms: u64,
+    // Every call of the tool, for nodes that run for each element of a list or repeat
+    #[serde(default)]
+    pub iterations: Vec<Iteration>,
+    // Taken from the checkpoint of an earlier run instead of running again
+    #[serde(default)]
+    pub resumed: bool,
+    // Transactions the tool reported sending
+    #[serde(default)]
+    pub signatures: Vec<String>,
+    // A call of the tool timed out, so it may have sent transactions it never reported
+    #[serde(default)]
+    pub timed_out: bool,
+}
+
+impl NodeReport {
+    fn new(node: &WorkflowNode, inputs: Option<Value>, status: NodeStatus, took: Duration) -> Self {
+        NodeReport {
+            node_id: node.id.clone(),
+            tool_name: node.tool_name.clone(),
+            inputs,
+            status,
+            duration_ms: took.as_millis() as u64,
+            iterations: vec![],
+            resumed: false,
+            signatures: vec![],
+            timed_out: false,
+        }
+    }
+
+    fn resumed(node: &WorkflowNode, checkpoint: &NodeCheckpoint, status: NodeStatus) -> Self {
+        let inputs = checkpoint.inputs.clone();
+        NodeReport {
+            resumed: true,
+            signatures: checkpoint.signatures.clone(),
+            ..NodeReport::new(node, inputs, status, Duration::ZERO)
+        }
+    }
+
+    fn checkpoint(&self) -> NodeCheckpoint {
+        let (status, output, error) = match &self.status {
+            NodeStatus::Succeeded { output } => {
+                (CheckpointStatus::Succeeded, Some(output.clone()), None)
+            }
+            NodeStatus::Failed { error } if self.timed_out => {
+                (CheckpointStatus::Unknown, None, Some(error.clone()))
+            }
+            NodeStatus::Failed { error } => (CheckpointStatus::Failed, None, Some(error.clone())),
+            NodeStatus::Skipped { reason } => {
+                (CheckpointStatus::Skipped, None, Some(reason.clone()))
+            }
+        };
+        // Transactions sent by every iteration count, not only those of the final output
+        let mut text = output.as_ref().or(error.as_ref()).cloned().unwrap_or_default();
+        for iteration in &self.iterations {
+            text.push('\n');
+            text.push_str(iteration.output.as_ref().unwrap_or_else(|e| e));
+        }
+        let mut signatures = self.signatures.clone();
+        for signature in find_signatures(&text) {
+            if !signatures.contains(&signature) {
+                signatures.push(signature);
+            }
+        }
+        NodeCheckpoint {
+            inputs: self.inputs.clone(),
+            signatures,
+            output,
+            error,
+            ..NodeCheckpoint::new(&self.node_id, status)
+        }
+    }
+}
+
+// One call of a node's tool
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Iteration {
+    // Position of the element, for nodes run for each element of a list
+    pub item: Option<usize>,
+    // Pass of a repeating node, from 1
+    pub pass: u32,
+    pub inputs: Value,
+    pub output: Result<String, String>,
+}
+
+// Result of a workflow run, one report per node in the order the nodes finished
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct WorkflowReport {
+    pub workflow_id: String,
+    pub nodes: Vec<NodeReport>,
+}
+
+impl WorkflowReport {
+    // A branch skipped because its condition was false is a path not taken, not a failure; a
+    // node skipped because an upstream failed always has that failed node in the report too
+    pub fn succeeded(&self) -> bool {
+        !self.nodes.iter().any(|n| matches!(n.status, NodeStatus::Failed { .. }))
+    }
+
+    pub fn node(&self, id: &str) -> Option<&NodeReport> {
+        self.nodes.iter().find(|n| n.node_id == id)
+    }
 }
 
 impl WorkflowEngine {
-    pub fn new() -> Self {
+    pub fn new(tool_registry: Arc<ToolRegistry>) -> Self {
         WorkflowEngine {
             dag: Graph::new(),
-            checkpoints: std::collections::HashMap::new(),
+            index: HashMap::new(),
+            checkpoints: None,
+            signature_checker: None,
+            tool_registry,
+            llm: Arc::new(NoLlmProvider),
+            max_concurrency: 4,
+            tool_calls: Semaphore::new(4),
+        }
+    }
+
+    // Sets the LLM provider passed to tools that need one
+    pub fn with_llm(mut self, llm: Arc<dyn LLMProvider>) -> Self {
+        self.llm = llm;
+        self
+    }
+
+    // Checkpoints every node to `store`
+    pub fn with_checkpoints(mut self, store: Arc<CheckpointStore>) -> Self {
+        self.checkpoints = Some(store);
+        self
+    }
+
+    pub fn with_signature_checker(mut self, checker: Arc<dyn SignatureChecker>) -> Self {
+        self.signature_checker = Some(checker);
+        self
+    }
+
+    pub fn with_max_concurrency(mut self, max_concurrency: usize) -> Self {
+        self.max_concurrency = max_concurrency.max(1);
+        self.tool_calls = Semaphore::new(self.max_concurrency);
+        self
+    }
+
+    pub fn add_node(&mut self, node: WorkflowNode) -> Result<(), WorkflowError> {
+        if self.index.contains_key(&node.id) {
+            return Err(WorkflowError::DuplicateNode(node.id));
+        }
+        let id = node.id.clone();
+        let index = self.dag.add_node(node);
+        self.index.insert(id, index);
+        Ok(())
+    }
+
+    // Makes `to` wait for `from`, rejecting edges that would close a cycle
+    pub fn add_edge(&mut self, from: &str, to: &str) -> Result<(), WorkflowError> {
+        self.connect(from, to, WorkflowEdge::default())
+    }
+
+    // Makes `to` wait for `from`, and only run if `condition` holds once `from` succeeded
+    pub fn add_conditional_edge(
+        &mut self,
+        from: &str,
+        to: &str,
+        condition: &str,
+    ) -> Result<(), WorkflowError> {
+        self.connect(from, to, WorkflowEdge { condition: Some(condition.to_string()) })
+    }
+
+    fn connect(&mut self, from: &str, to: &str, edge: WorkflowEdge) -> Result<(), WorkflowError> {
+        let source = self.node_index(from)?;
+        let target = self.node_index(to)?;
+        if source == target || has_path_connecting(&self.dag, target, source, None) {
+            return Err(WorkflowError::Cycle { from: from.to_string(), to: to.to_string() });
+        }
+        self.dag.update_edge(source, target, edge);
+        Ok(())
+    }
+
+    pub fn node(&self, id: &str) -> Option<&WorkflowNode> {
+        self.index.get(id).map(|index| &self.dag[*index])
+    }
+
+    // Checks that conditions parse and that every node only references the outputs of nodes
+    // directly upstream
+    pub fn validate(&self) -> Result<(), WorkflowError> {
+        for index in self.dag.node_indices() {
+            let node = &self.dag[index];
+            let mut referenced = template::referenced(&node.inputs, NODES);
+            if let Some(for_each) = &node.for_each {
+                referenced.extend(template::referenced(for_each, NODES));
+            }
+            let edges = self.dag.edges_directed(index, Direction::Incoming);
+            let conditions = node
+                .condition
+                .iter()
+                .chain(node.repeat.iter().map(|repeat| &repeat.until))
+                .chain(edges.filter_map(|edge| edge.weight().condition.as_ref()));
+            for condition in conditions {
+                let expr = Expr::parse(condition).map_err(|e| WorkflowError::InvalidCondition {
+                    node: node.id.clone(),
+                    error: format!("'{}': {}", condition, e),
+                })?;
+                referenced.extend(expr.variables().into_iter().filter_map(condition_reference));
+            }
+            for referenced in referenced {
+                let upstream = self.index.get(&referenced).is_some_and(|upstream| {
+                    self.dag.find_edge(*upstream, index).is_some()
+                });
+                if !upstream {
+                    return Err(WorkflowError::InvalidReference {
+                        node: node.id.clone(),
+                        referenced,
+                    });
+                }
+            }
         }
+        Ok(())
+    }
+
+    // Runs every node once all of its upstream nodes have succeeded, running independent
+    // branches concurrently. Nodes downstream of a failure are skipped.
+    pub async fn run(&self) -> Result<WorkflowReport, WorkflowError> {
+        self.run_with_id(&new_workflow_id()).await
     }
-}
\ No newline at end of file
+
+    // Runs the workflow from the start, checkpointing it under `workflow_id`
+    pub async fn run_with_id(&self, workflow_id: &str) -> Result<WorkflowReport, WorkflowError> {
+        self.run_with_inputs(workflow_id, Value::Null).await
+    }
+
+    // Same as `run_with_id`, with `inputs` readable by nodes as `${inputs[.<path>]}`
+    pub async fn run_with_inputs(
+        &self,
+        workflow_id: &str,
+        inputs: Value,
+    ) -> Result<WorkflowReport, WorkflowError> {
+        self.validate()?;
+        if let Some(store) = &self.checkpoints {
+            store.delete(workflow_id).map_err(checkpoint_error)?;
+            store.save_workflow(workflow_id, CheckpointStatus::Running).map_err(checkpoint_error)?;
+            store.save_inputs(workflow_id, &inputs).map_err(checkpoint_error)?;
+            for node in self.dag.node_weights() {
+                let pending = NodeCheckpoint::new(&node.id, CheckpointStatus::Pending);
+                store.save_node(workflow_id, &pending).map_err(checkpoint_error)?;
+            }
+        }
+        self.execute(workflow_id, &inputs, HashMap::new()).await
+    }
+
+    // Continues an interrupted run from its last checkpoint. Succeeded nodes keep their
+    // output, and nodes whose transactions were already confirmed are not run again.
+    pub async fn resume(&self, workflow_id: &str) -> Result<WorkflowReport, WorkflowError> {
+        self.validate()?;
+        let store = self
+            .checkpoints
+            .as_ref()
+            .ok_or(WorkflowError::Checkpoint("no checkpoint store configured".to_string()))?;
+        let state = store
+            .load(workflow_id)
+            .map_err(checkpoint_error)?
+            .ok_or(WorkflowError::NotFound(workflow_id.to_string()))?;
+        store.save_workflow(workflow_id, CheckpointStatus::Running).map_err(checkpoint_error)?;
+        self.execute(workflow_id, &state.inputs, state.nodes).await
+    }
+
+    // Latest checkpoint of a run, or None if it never started or no checkpoint store is set
+    pub fn checkpoint(&self, workflow_id: &str) -> Result<Option<WorkflowState>, WorkflowError> {
+        match &self.checkpoints {
+            Some(store) => store.load(workflow_id).map_err(checkpoint_error),
+            None => Ok(None),
+        }
+    }
+
+    async fn execute(
+        &self,
+        workflow_id: &str,
+        inputs: &Value,
+        previous: HashMap<String, NodeCheckpoint>,
+    ) -> Result<WorkflowReport, WorkflowError> {
+        let store = self.checkpoints.as_deref();
+        let mut schedule = Schedule::new(&self.dag, workflow_id, inputs, store);
+        let mut running = FuturesUnordered::new();
+        loop {
+            while running.len() < self.max_concurrency {
+                let Some(index) = schedule.ready.pop_front() else { break };
+                let node = &self.dag[index];
+                if let Some(report) = self.restore(node, previous.get(&node.id)).await {
+                    schedule.finish(index, report)?;
+                    continue;
+                }
+                let skip = match self.condition_holds(node, &schedule.outputs, inputs) {
+                    Ok(true) => None,
+                    Ok(false) => Some(NodeStatus::Skipped {
+                        reason: format!("condition '{}' is false", node.condition_text()),
+                    }),
+                    Err(error) => Some(NodeStatus::Failed { error }),
+                };
+                if let Some(status) = skip {
+                    schedule.finish(index, NodeReport::new(node, None, status, Duration::ZERO))?;
+                    continue;
+                }
+                match calls(node, &schedule.outputs, inputs) {
+                    Ok(calls) => {
+                        schedule.started(node, &combined_inputs(node, &calls))?;
+                        // Only repeat conditions read upstream outputs while the node runs
+                        let upstream = match node.repeat {
+                            Some(_) => schedule.outputs.clone(),
+                            None => HashMap::new(),
+                        };
+                        running.push(self.run_node(workflow_id, index, calls, upstream, inputs))
+                    }
+                    Err(error) => {
+                        let status = NodeStatus::Failed { error };
+                        schedule.finish(index, NodeReport::new(node, None, status, Duration::ZERO))?
+                    }
+                }
+            }
+            let Some((index, report)) = running.next().await else { break };
+            schedule.finish(index, report)?;
+        }
+
+        let report = schedule.report;
+        if let Some(store) = store {
+            let status = if report.succeeded() {
+                CheckpointStatus::Succeeded
+            } else {
+                CheckpointStatus::Failed
+            };
+            store.save_workflow(workflow_id, status).map_err(checkpoint_error)?;
+        }
+        Ok(report)
+    }
+
+    // Outcome of a node taken from an earlier run, or None if the node has to run (again).
+    // A node that was interrupted, timed out or failed after submitting transactions is only
+    // run again once none of them is confirmed or can still land, and a node interrupted or
+    // timed out before submitting any only if it is idempotent.
+    async fn restore(
+        &self,
+        node: &WorkflowNode,
+        checkpoint: Option<&NodeCheckpoint>,
+    ) -> Option<NodeReport> {
+        let checkpoint = checkpoint?;
+        let status = match checkpoint.status {
+            CheckpointStatus::Succeeded => {
+                NodeStatus::Succeeded { output: checkpoint.output.clone().unwrap_or_default() }
+            }
+            CheckpointStatus::Running | CheckpointStatus::Failed | CheckpointStatus::Unknown
+                if !checkpoint.signatures.is_empty() =>
+            {
+                self.confirm(checkpoint).await?
+            }
+            CheckpointStatus::Running | CheckpointStatus::Unknown if !node.idempotent => {
+                let what = match checkpoint.status {
+                    CheckpointStatus::Running => "Interrupted while running",
+                    _ => "Timed out",
+                };
+                let error = format!(
+                    "{} and not idempotent, so it is not run again; it may have sent a \
+                     transaction",
+                    what
+                );
+                NodeStatus::Failed { error }
+            }
+            _ => return None,
+        };
+        Some(NodeReport::resumed(node, checkpoint, status))
+    }
+
+    // Succeeded if one of the node's transactions is confirmed, failed while one may still
+    // land, or None once none can and the node can run again
+    async fn confirm(&self, checkpoint: &NodeCheckpoint) -> Option<NodeStatus> {
+        let Some(checker) = &self.signature_checker else {
+            let error = format!(
+                "Transaction {} may already have been sent; a signature checker is needed to \
+                 confirm it before running the node again",
+                checkpoint.signatures[0]
+            );
+            return Some(NodeStatus::Failed { error });
+        };
+        for signature in &checkpoint.signatures {
+            let confirmed = match checker.is_confirmed(signature).await {
+                Ok(confirmed) => confirmed,
+                Err(e) => {
+                    let error = format!("Cannot confirm transaction {}: {}", signature, e);
+                    return Some(NodeStatus::Failed { error });
+                }
+            };
+            if confirmed {
+                let output = checkpoint.output.clone().unwrap_or_else(|| {
+                    serde_json::json!({"signatures": checkpoint.signatures}).to_string()
+                });
+                return Some(NodeStatus::Succeeded { output });
+            }
+        }
+        // Unconfirmed is not the same as dropped: a transaction can land until its blockhash
+        // expires
+        for signature in &checkpoint.signatures {
+            let Some(blockhash) = checkpoint.blockhashes.get(signature) else {
+                let error = format!(
+                    "Transaction {} is not confirmed and its blockhash is unknown, so it may \
+                     still land",
+                    signature
+                );
+                return Some(NodeStatus::Failed { error });
+            };
+            let error = match checker.is_blockhash_valid(blockhash).await {
+                Ok(false) => continue,
+                Ok(true) => format!(
+                    "Transaction {} is not confirmed yet but may still land; resume again once \
+                     its blockhash expired",
+                    signature
+                ),
+                Err(e) => format!("Cannot check the blockhash of transaction {}: {}", signature, e),
+            };
+            return Some(NodeStatus::Failed { error });
+        }
+        None
+    }
+
+    fn condition_holds(
+        &self,
+        node: &WorkflowNode,
+        outputs: &HashMap<String, String>,
+        inputs: &Value,
+    ) -> Result<bool, String> {
+        match &node.condition {
+            Some(condition) => holds(condition, &OutputScope { outputs, inputs }),
+            None => Ok(true),
+        }
+    }
+
+    // Runs every call of a node, elements of a list concurrently, and reports the outcome
+    async fn run_node(
+        &self,
+        workflow_id: &str,
+        index: NodeIndex,
+        calls: Vec<Call>,
+        upstream: HashMap<String, String>,
+        workflow_inputs: &Value,
+    ) -> (NodeIndex, NodeReport) {
+        let started = Instant::now();
+        let node = &self.dag[index];
+        let inputs = combined_inputs(node, &calls);
+        let submitted = Submitted::new(self.checkpoints.clone(), workflow_id, &node.id);
+        let runs: Vec<(Vec<Iteration>, Result<String, String>)> = stream::iter(calls)
+            .map(|call| self.run_passes(node, call, &upstream, workflow_inputs, &submitted))
+            .buffered(self.max_concurrency)
+            .collect()
+            .await;
+
+        let mut iterations = vec![];
+        let mut outputs = vec![];
+        let mut error = None;
+        for (item, (passes, output)) in runs.into_iter().enumerate() {
+            iterations.extend(passes);
+            match output {
+                Ok(output) => outputs.push(output),
+                Err(e) if node.for_each.is_some() => {
+                    error.get_or_insert(format!("item {}: {}", item, e));
+                }
+                Err(e) => error = Some(e),
+            }
+        }
+        let status = match error {
+            Some(error) => NodeStatus::Failed { error },
+            // Outputs of all elements are collected into a JSON array
+            None if node.for_each.is_some() => {
+                let outputs: Vec<Value> = outputs.iter().map(|o| parse_output(o)).collect();
+                NodeStatus::Succeeded { output: Value::Array(outputs).to_string() }
+            }
+            None => NodeStatus::Succeeded { output: outputs.pop().unwrap_or_default() },
+        };
+        if node.for_each.is_none() && node.repeat.is_none() {
+            iterations.clear();
+        }
+        let report = NodeReport::new(node, Some(inputs), status, started.elapsed());
+        let signatures = submitted.signatures();
+        let timed_out = submitted.timed_out.load(Ordering::SeqCst);
+        (index, NodeReport { iterations, signatures, timed_out, ..report })
+    }
+
+    // Calls the tool once, or until the node's repeat condition holds
+    async fn run_passes(
+        &self,
+        node: &WorkflowNode,
+        call: Call,
+        upstream: &HashMap<String, String>,
+        workflow_inputs: &Value,
+        submitted: &Submitted,
+    ) -> (Vec<Iteration>, Result<String, String>) {
+        let Call { item, inputs } = call;
+        let mut passes = vec![];
+        let max_passes = node.repeat.as_ref().map(|repeat| repeat.max_passes.max(1)).unwrap_or(1);
+        for pass in 1..=max_passes {
+            if let Some(repeat) = node.repeat.as_ref().filter(|r| pass > 1 && r.delay_secs > 0) {
+                tokio::time::sleep(Duration::from_secs(repeat.delay_secs)).await;
+            }
+            let output = self.call_with_retries(node, &inputs, submitted).await;
+            passes.push(Iteration { item, pass, inputs: inputs.clone(), output: output.clone() });
+            let Some(repeat) = &node.repeat else { return (passes, output) };
+            let output = match output {
+                Ok(output) => output,
+                Err(error) => return (passes, Err(error)),
+            };
+            let nodes = OutputScope { outputs: upstream, inputs: workflow_inputs };
+            let scope = PassScope { output: &output, pass, upstream: nodes };
+            match holds(&repeat.until, &scope) {
+                Ok(true) => return (passes, Ok(output)),
+                Ok(false) => {}
+                Err(error) => return (passes, Err(error)),
+            }
+        }
+        let until = node.repeat.as_ref().map(|repeat| repeat.until.as_str()).unwrap_or_default();
+        (passes, Err(format!("'{}' still false after {} passes", until, max_passes)))
+    }
+
+    async fn call_with_retries(
+        &self,
+        node: &WorkflowNode,
+        inputs: &Value,
+        submitted: &Submitted,
+    ) -> Result<String, String> {
+        let mut attempt = 0;
+        loop {
+            let sent = submitted.signatures().len();
+            let output = self.call_tool(node, inputs, submitted).await;
+            // Only an attempt known to have sent nothing is safe to make again
+            let unsent = sent == submitted.signatures().len()
+                && (node.idempotent || !submitted.timed_out.load(Ordering::SeqCst));
+            match output {
+                Err(error)
+                    if attempt < node.retries && unsent && find_signatures(&error).is_empty() =>
+                {
+                    tracing::warn!(node = %node.id, attempt, "Node failed, retrying: {}", error);
+                    tokio::time::sleep(Duration::from_millis(RETRY_BACKOFF_MS << attempt.min(6)))
+                        .await;
+                    attempt += 1;
+                }
+                output => return output,
+            }
+        }
+    }
+
+    async fn call_tool(
+        &self,
+        node: &WorkflowNode,
+        inputs: &Value,
+        submitted: &Submitted,
+    ) -> Result<String, String> {
+        let (_, tool) = self
+            .tool_registry
+            .get(&node.tool_name)
+            .await
+            .ok_or(format!("Tool '{}' not found", node.tool_name))?;
+        let input = ToolInput::new(inputs.clone()).with_on_submitted(submitted.hook.clone());
+        // Waiting for a permit does not count towards the node's timeout
+        let _permit = self.tool_calls.acquire().await.map_err(|e| e.to_string())?;
+        let call = tool.execute(input, self.llm.as_ref());
+        let output = match node.timeout_secs {
+            Some(secs) => tokio::time::timeout(Duration::from_secs(secs), call).await.map_err(|_| {
+                submitted.timed_out.store(true, Ordering::SeqCst);
+                format!("Timed out after {}s; the outcome is unknown", secs)
+            })?,
+            None => call.await,
+        };
+        output.map_err(|e| e.to_string())
+    }
+
+    fn node_index(&self, id: &str) -> Result<NodeIndex, WorkflowError> {
+        self.index.get(id).copied().ok_or(WorkflowError::UnknownNode(id.to_string()))
+    }
+}
+
+// Transactions a node's tool reports sending. Each is checkpointed as soon as it is reported,
+// so a run interrupted before the tool returns still knows it may have landed.
+struct Submitted {
+    signatures: Arc<Mutex<Vec<String>>>,
+    hook: SubmittedHook,
+    // A call timed out, so the tool may have sent transactions it never reported
+    timed_out: AtomicBool,
+}
+
+impl Submitted {
+    fn new(store: Option<Arc<CheckpointStore>>, workflow_id: &str, node_id: &str) -> Self {
+        let signatures = Arc::new(Mutex::new(vec![]));
+        let reported = signatures.clone();
+        let (workflow_id, node_id) = (workflow_id.to_string(), node_id.to_string());
+        let hook: SubmittedHook = Arc::new(move |signature: &str, blockhash: &str| {
+            reported.lock().unwrap().push(signature.to_string());
+            let Some(store) = &store else { return };
+            if let Err(e) = store.save_transaction(&workflow_id, &node_id, signature, blockhash) {
+                tracing::warn!(node = %node_id, "Could not checkpoint transaction: {}", e);
+            }
+        });
+        Submitted { signatures, hook, timed_out: AtomicBool::new(false) }
+    }
+
+    fn signatures(&self) -> Vec<String> {
+        self.signatures.lock().unwrap().clone()
+    }
+}
+
+// Single call of a node's tool: the element of the list it runs for, if any, and its inputs
+struct Call {
+    item: Option<usize>,
+    inputs: Value,
+}
+
+// Resolves the inputs of every call of a node
+fn calls(
+    node: &WorkflowNode,
+    outputs: &HashMap<String, String>,
+    workflow_inputs: &Value,
+) -> Result<Vec<Call>, String> {
+    let resolve = |reference: &str| lookup(reference, outputs, workflow_inputs);
+    let Some(for_each) = &node.for_each else {
+        let inputs = template::render(&node.inputs, &resolve)?;
+        return Ok(vec![Call { item: None, inputs }]);
+    };
+    let items = match template::render(for_each, &resolve)? {
+        Value::String(text) => serde_json::from_str(&text).unwrap_or(Value::String(text)),
+        other => other,
+    };
+    let Value::Array(items) = items else {
+        return Err(format!("for_each is not a list: {}", items));
+    };
+    items
+        .iter()
+        .enumerate()
+        .map(|(index, item)| {
+            let inputs = template::render(&node.inputs, &|reference| match reference {
+                "index" => Ok(Value::from(index)),
+                "item" => Ok(item.clone()),
+                _ => match reference.strip_prefix("item.") {
+                    Some(path) => template::select(item, path)
+                        .ok_or(format!("'{}' not found in item {}", path, index)),
+                    None => resolve(reference),
+                },
+            })?;
+            Ok(Call { item: Some(index), inputs })
+        })
+        .collect()
+}
+
+// Value of an `${inputs[.<path>]}` or `${nodes.<id>.output[.<path>]}` placeholder
+fn lookup(
+    reference: &str,
+    outputs: &HashMap<String, String>,
+    workflow_inputs: &Value,
+) -> Result<Value, String> {
+    if reference == INPUTS {
+        return Ok(workflow_inputs.clone());
+    }
+    match reference.strip_prefix("inputs.") {
+        Some(path) => template::select(workflow_inputs, path)
+            .ok_or(format!("'{}' not found in the workflow inputs", path)),
+        None => template::lookup(reference, NODES, outputs),
+    }
+}
+
+// Inputs recorded for a node: those of its only call, or the list of inputs of every element
+fn combined_inputs(node: &WorkflowNode, calls: &[Call]) -> Value {
+    match (&node.for_each, calls) {
+        (None, [call]) => call.inputs.clone(),
+        _ => Value::Array(calls.iter().map(|call| call.inputs.clone()).collect()),
+    }
+}
+
+fn parse_output(output: &str) -> Value {
+    serde_json::from_str(output).unwrap_or(Value::String(output.to_string()))
+}
+
+fn holds(condition: &str, scope: &dyn Scope) -> Result<bool, String> {
+    let expr = Expr::parse(condition).map_err(|e| e.to_string())?;
+    let value = expr.eval(scope)?;
+    value.as_bool().ok_or(format!("condition '{}' is {}, not a boolean", condition, value))
+}
+
+// Node whose output a condition variable such as `nodes.quote.output.out_amount` reads
+pub fn condition_reference(variable: &str) -> Option<String> {
+    let mut parts = variable.split('.');
+    match (parts.next(), parts.next()) {
+        (Some(NODES), Some(id)) => Some(id.to_string()),
+        _ => None,
+    }
+}
+
+// Scope of node conditions: `nodes.<id>.output[.<path>]` reads the output of an upstream node,
+// parsed as JSON when possible, and `inputs[.<path>]` the inputs of the run
+struct OutputScope<'a> {
+    outputs: &'a HashMap<String, String>,
+    inputs: &'a Value,
+}
+
+impl Scope for OutputScope<'_> {
+    fn variable(&self, path: &str) -> Option<Value> {
+        if path == INPUTS {
+            return Some(self.inputs.clone());
+        }
+        if let Some(field) = path.strip_prefix("inputs.") {
+            return template::select(self.inputs, field);
+        }
+        let id = condition_reference(path)?;
+        let value = parse_output(self.outputs.get(&id)?);
+        let rest = path.strip_prefix(&format!("{}.{}.output", NODES, id))?;
+        match rest.strip_prefix('.') {
+            Some(field) => template::select(&value, field),
+            None if rest.is_empty() => Some(value),
+            None => None,
+        }
+    }
+
+    fn call(&self, name: &str, _args: &[Value]) -> Result<Value, String> {
+        Err(format!("unknown function '{}'", name))
+    }
+}
+
+// Scope of repeat conditions: the pass's `output[.<path>]` and `pass` number, then upstream
+// outputs
+struct PassScope<'a> {
+    output: &'a str,
+    pass: u32,
+    upstream: OutputScope<'a>,
+}
+
+impl Scope for PassScope<'_> {
+    fn variable(&self, path: &str) -> Option<Value> {
+        match path {
+            "pass" => Some(Value::from(self.pass)),
+            "output" => Some(parse_output(self.output)),
+            _ => match path.strip_prefix("output.") {
+                Some(field) => template::select(&parse_output(self.output), field),
+                None => self.upstream.variable(path),
+            },
+        }
+    }
+
+    fn call(&self, name: &str, args: &[Value]) -> Result<Value, String> {
+        self.upstream.call(name, args)
+    }
+}
+
+fn checkpoint_error(error: rusqlite::Error) -> WorkflowError {
+    WorkflowError::Checkpoint(error.to_string())
+}
+
+// Unique id for a run, e.g. `wf-1767225600000000000-0`
+fn new_workflow_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0);
+    format!("wf-{}-{}", nanos, COUNTER.fetch_add(1, Ordering::Relaxed))
+}
+
+// Progress of a run: nodes whose upstream has settled are queued once every upstream node
+// has finished
+struct Schedule<'a> {
+    dag: &'a Graph<WorkflowNode, WorkflowEdge>,
+    workflow_id: &'a str,
+    store: Option<&'a CheckpointStore>,
+    // Number of upstream nodes each node is still waiting for
+    waiting: HashMap<NodeIndex, usize>,
+    ready: VecDeque<NodeIndex>,
+    outputs: HashMap<String, String>,
+    inputs: &'a Value,
+    report: WorkflowReport,
+}
+
+impl<'a> Schedule<'a> {
+    fn new(
+        dag: &'a Graph<WorkflowNode, WorkflowEdge>,
+        workflow_id: &'a str,
+        inputs: &'a Value,
+        store: Option<&'a CheckpointStore>,
+    ) -> Self {
+        let waiting: HashMap<NodeIndex, usize> = dag
+            .node_indices()
+            .map(|index| (index, dag.neighbors_directed(index, Direction::Incoming).count()))
+            .collect();
+        let ready = dag.node_indices().filter(|index| waiting[index] == 0).collect();
+        let report = WorkflowReport { workflow_id: workflow_id.to_string(), nodes: vec![] };
+        let outputs = HashMap::new();
+        Schedule { dag, workflow_id, store, waiting, ready, outputs, inputs, report }
+    }
+
+    fn started(&self, node: &WorkflowNode, inputs: &Value) -> Result<(), WorkflowError> {
+        let Some(store) = self.store else { return Ok(()) };
+        let checkpoint = NodeCheckpoint {
+            inputs: Some(inputs.clone()),
+            ..NodeCheckpoint::new(&node.id, CheckpointStatus::Running)
+        };
+        store.save_node(self.workflow_id, &checkpoint).map_err(checkpoint_error)
+    }
+
+    // Records a finished node, then queues or skips the downstream nodes it was holding up
+    fn finish(&mut self, index: NodeIndex, report: NodeReport) -> Result<(), WorkflowError> {
+        if let Some(store) = self.store {
+            store.save_node(self.workflow_id, &report.checkpoint()).map_err(checkpoint_error)?;
+        }
+        if let NodeStatus::Succeeded { output } = &report.status {
+            self.outputs.insert(report.node_id.clone(), output.clone());
+        }
+        self.report.nodes.push(report);
+
+        let dag = self.dag;
+        for next in dag.neighbors_directed(index, Direction::Outgoing) {
+            let waiting = self.waiting.get_mut(&next).expect("every node is counted");
+            *waiting -= 1;
+            if *waiting > 0 {
+                continue;
+            }
+            match self.blocked(next) {
+                Some(status) => {
+                    self.finish(next, NodeReport::new(&dag[next], None, status, Duration::ZERO))?
+                }
+                None => self.ready.push_back(next),
+            }
+        }
+        Ok(())
+    }
+
+    // Why a node whose upstream has settled cannot run: an upstream node did not succeed or a
+    // condition on an incoming edge does not hold
+    fn blocked(&self, index: NodeIndex) -> Option<NodeStatus> {
+        let edges: Vec<(&String, &WorkflowEdge)> = self
+            .dag
+            .edges_directed(index, Direction::Incoming)
+            .map(|edge| (&self.dag[edge.source()].id, edge.weight()))
+            .collect();
+        if let Some((upstream, _)) = edges.iter().find(|(id, _)| !self.outputs.contains_key(*id)) {
+            let reason = format!("upstream '{}' did not succeed", upstream);
+            return Some(NodeStatus::Skipped { reason });
+        }
+        let scope = OutputScope { outputs: &self.outputs, inputs: self.inputs };
+        edges.iter().find_map(|(upstream, edge)| {
+            let condition = edge.condition.as_ref()?;
+            match holds(condition, &scope) {
+                Ok(true) => None,
+                Ok(false) => {
+                    let reason = format!(
+                        "condition '{}' on the edge from '{}' is false",
+                        condition, upstream
+                    );
+                    Some(NodeStatus::Skipped { reason })
+                }
+                Err(error) => Some(NodeStatus::Failed { error }),
+            }
+        })
+    }
+}
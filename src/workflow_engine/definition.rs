@@ -0,0 +1,359 @@
+//! Declarative workflow definitions loaded from YAML or TOML
+
+use std::{fmt, path::Path, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    planning_reasoning::{expression::Expr, template},
+    tool_system::ToolRegistry,
+    workflow_engine::dag::{
+        condition_reference, RepeatUntil, WorkflowEngine, WorkflowError, WorkflowNode, NODES,
+    },
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DefinitionFormat {
+    Yaml,
+    Toml,
+}
+
+impl DefinitionFormat {
+    // Format of a definition file, from its extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "yaml" | "yml" => Some(DefinitionFormat::Yaml),
+            "toml" => Some(DefinitionFormat::Toml),
+            _ => None,
+        }
+    }
+}
+
+// Workflow as written by hand, e.g.
+//
+// name: swap-and-stake
+// nodes:
+//   - id: swap
+//     tool: trade
+//     inputs: { output_mint: So11111111111111111111111111111111111111112, amount: 10 }
+//     retries: 2
+//   - id: stake
+//     tool: stake_with_jup
+//     inputs: { amount: "${nodes.swap.output.out_amount}" }
+//     depends_on: [swap]
+//     condition: nodes.swap.output.out_amount > 0.1
+//     timeout_secs: 60
+//   - id: reports
+//     tool: token_report
+//     inputs: { mint: "${item}" }
+//     for_each: [So11111111111111111111111111111111111111112]
+//     depends_on: [{ node: stake, condition: "nodes.stake.output.staked == true" }]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct WorkflowDefinition {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub max_concurrency: Option<usize>,
+    pub nodes: Vec<NodeDefinition>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct NodeDefinition {
+    pub id: String,
+    pub tool: String,
+    #[serde(default = "empty_inputs")]
+    pub inputs: Value,
+    #[serde(default)]
+    pub depends_on: Vec<Dependency>,
+    #[serde(default)]
+    pub condition: Option<String>,
+    #[serde(default)]
+    pub retries: u32,
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+    #[serde(default)]
+    pub for_each: Option<Value>,
+    #[serde(default)]
+    pub repeat: Option<RepeatUntil>,
+    #[serde(default)]
+    pub idempotent: bool,
+}
+
+// Upstream node, written as its id or with a condition on the edge
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum Dependency {
+    Node(String),
+    Conditional { node: String, condition: String },
+}
+
+impl Dependency {
+    pub fn node(&self) -> &str {
+        match self {
+            Dependency::Node(node) | Dependency::Conditional { node, .. } => node,
+        }
+    }
+
+    pub fn condition(&self) -> Option<&str> {
+        match self {
+            Dependency::Node(_) => None,
+            Dependency::Conditional { condition, .. } => Some(condition),
+        }
+    }
+}
+
+fn empty_inputs() -> Value {
+    Value::Object(Default::default())
+}
+
+// Problem found in a definition, with the line it is on when it can be located
+#[derive(Clone, Debug, PartialEq)]
+pub struct DefinitionIssue {
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for DefinitionIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+// Returned when a definition fails to parse or validate
+#[derive(Debug)]
+pub struct InvalidDefinition {
+    pub issues: Vec<DefinitionIssue>,
+}
+
+impl fmt::Display for InvalidDefinition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let issues: Vec<String> = self.issues.iter().map(|i| i.to_string()).collect();
+        write!(f, "Invalid workflow definition: {}", issues.join("; "))
+    }
+}
+
+impl std::error::Error for InvalidDefinition {}
+
+impl WorkflowDefinition {
+    pub fn parse(source: &str, format: DefinitionFormat) -> Result<Self, InvalidDefinition> {
+        let parsed = match format {
+            DefinitionFormat::Yaml => serde_yaml::from_str(source).map_err(|e| {
+                // The location is reported separately
+                let message = e.to_string();
+                let message = message.split(" at line ").next().unwrap_or_default().to_string();
+                DefinitionIssue { line: e.location().map(|l| l.line()), message }
+            }),
+            DefinitionFormat::Toml => toml::from_str(source).map_err(|e| DefinitionIssue {
+                line: e.span().map(|span| source[..span.start].matches('\n').count() + 1),
+                message: e.message().to_string(),
+            }),
+        };
+        parsed.map_err(|issue| InvalidDefinition { issues: vec![issue] })
+    }
+
+    // Checks tools against the registry and every dependency, reference and condition,
+    // locating issues in `source`
+    pub async fn validate(
+        &self,
+        source: &str,
+        tool_registry: &Arc<ToolRegistry>,
+    ) -> Result<(), InvalidDefinition> {
+        let lines = SourceLines::new(source);
+        let node_lines = lines.nodes(&self.nodes);
+        let mut issues = vec![];
+        let mut issue = |line: Option<usize>, message: String| {
+            issues.push(DefinitionIssue { line, message })
+        };
+        if self.nodes.is_empty() {
+            issue(None, "workflow has no nodes".to_string());
+        }
+
+        for (i, node) in self.nodes.iter().enumerate() {
+            let line = node_lines[i];
+            if self.nodes[..i].iter().any(|n| n.id == node.id) {
+                issue(line, format!("duplicate node id '{}'", node.id));
+            }
+            if tool_registry.get(&node.tool).await.is_none() {
+                let line = lines.find(line, Some("tool"), &node.tool).or(line);
+                issue(line, format!("node '{}' uses unknown tool '{}'", node.id, node.tool));
+            }
+            for dependency in node.depends_on.iter().map(Dependency::node) {
+                if dependency == node.id || !self.nodes.iter().any(|n| n.id == dependency) {
+                    let line = lines.find(line, Some("depends_on"), dependency).or(line);
+                    let message =
+                        format!("node '{}' depends on unknown node '{}'", node.id, dependency);
+                    issue(line, message);
+                }
+            }
+
+            let mut referenced = template::referenced(&node.inputs, NODES);
+            if let Some(for_each) = &node.for_each {
+                referenced.extend(template::referenced(for_each, NODES));
+            }
+            let conditions = node
+                .condition
+                .iter()
+                .map(|condition| ("condition", condition.as_str()))
+                .chain(node.repeat.iter().map(|repeat| ("until", repeat.until.as_str())))
+                .chain(node.depends_on.iter().filter_map(|d| Some(("condition", d.condition()?))));
+            for (key, condition) in conditions {
+                match Expr::parse(condition) {
+                    Ok(expr) => referenced
+                        .extend(expr.variables().into_iter().filter_map(condition_reference)),
+                    Err(e) => {
+                        let line = lines.find(line, Some(key), condition).or(line);
+                        issue(line, format!("node '{}' has an invalid {}: {}", node.id, key, e));
+                    }
+                }
+            }
+            for id in referenced {
+                if !node.depends_on.iter().any(|dependency| dependency.node() == id) {
+                    let reference = format!("{}.{}.output", NODES, id);
+                    let line = lines.find(line, None, &reference).or(line);
+                    let message = format!(
+                        "node '{}' uses the output of '{}' without depending on it",
+                        node.id, id
+                    );
+                    issue(line, message);
+                }
+            }
+        }
+
+        if issues.is_empty() {
+            if let Err(WorkflowError::Cycle { from, to }) = self.build(tool_registry.clone()) {
+                let i = self.nodes.iter().position(|n| n.id == to).unwrap_or_default();
+                let line = lines.find(node_lines[i], Some("depends_on"), &from).or(node_lines[i]);
+                let message = format!("depending on '{}' makes '{}' part of a cycle", from, to);
+                issues.push(DefinitionIssue { line, message });
+            }
+        }
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(InvalidDefinition { issues })
+        }
+    }
+
+    // Builds the engine without checking tools; see `WorkflowEngine::load` for validated loading
+    pub fn build(&self, tool_registry: Arc<ToolRegistry>) -> Result<WorkflowEngine, WorkflowError> {
+        let mut engine = WorkflowEngine::new(tool_registry);
+        if let Some(max_concurrency) = self.max_concurrency {
+            engine = engine.with_max_concurrency(max_concurrency);
+        }
+        for node in &self.nodes {
+            engine.add_node(WorkflowNode {
+                condition: node.condition.clone(),
+                retries: node.retries,
+                timeout_secs: node.timeout_secs,
+                for_each: node.for_each.clone(),
+                repeat: node.repeat.clone(),
+                idempotent: node.idempotent,
+                ..WorkflowNode::new(&node.id, &node.tool, node.inputs.clone())
+            })?;
+        }
+        for node in &self.nodes {
+            for dependency in &node.depends_on {
+                match dependency.condition() {
+                    Some(condition) => {
+                        engine.add_conditional_edge(dependency.node(), &node.id, condition)?
+                    }
+                    None => engine.add_edge(dependency.node(), &node.id)?,
+                }
+            }
+        }
+        engine.validate()?;
+        Ok(engine)
+    }
+}
+
+impl WorkflowEngine {
+    // Loads and validates a workflow definition
+    pub async fn load(
+        source: &str,
+        format: DefinitionFormat,
+        tool_registry: Arc<ToolRegistry>,
+    ) -> Result<Self, InvalidDefinition> {
+        let definition = WorkflowDefinition::parse(source, format)?;
+        definition.validate(source, &tool_registry).await?;
+        definition.build(tool_registry).map_err(|e| InvalidDefinition {
+            issues: vec![DefinitionIssue { line: None, message: e.to_string() }],
+        })
+    }
+
+    // Loads a `.yaml`, `.yml` or `.toml` workflow definition file
+    pub async fn load_file(
+        path: &Path,
+        tool_registry: Arc<ToolRegistry>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let format = DefinitionFormat::from_path(path)
+            .ok_or(format!("{}: expected a .yaml, .yml or .toml file", path.display()))?;
+        let source = std::fs::read_to_string(path)?;
+        Ok(Self::load(&source, format, tool_registry).await?)
+    }
+}
+
+// Finds the lines definition issues are on. Definitions are parsed into plain values, so
+// lines are located by searching the source for the node ids and values in question.
+struct SourceLines<'a> {
+    lines: Vec<&'a str>,
+}
+
+impl<'a> SourceLines<'a> {
+    fn new(source: &'a str) -> Self {
+        SourceLines { lines: source.lines().collect() }
+    }
+
+    // Line declaring the id of each node, in order
+    fn nodes(&self, nodes: &[NodeDefinition]) -> Vec<Option<usize>> {
+        let mut from = Some(1);
+        nodes
+            .iter()
+            .map(|node| {
+                let line = self.find(from, Some("id"), &node.id);
+                from = line.map(|line| line + 1).or(from);
+                line
+            })
+            .collect()
+    }
+
+    // First line from `from` on that mentions `word`, on the line of `key` if there is one
+    fn find(&self, from: Option<usize>, key: Option<&str>, word: &str) -> Option<usize> {
+        let from = from?;
+        let keyed = key.and_then(|key| self.search(from, word, |line| has_key(line, key)));
+        keyed.or_else(|| self.search(from + 1, word, |_| true))
+    }
+
+    fn search(&self, from: usize, word: &str, accept: impl Fn(&str) -> bool) -> Option<usize> {
+        self.lines
+            .iter()
+            .enumerate()
+            .skip(from.saturating_sub(1))
+            .find(|(_, line)| accept(line) && mentions(line, word))
+            .map(|(i, _)| i + 1)
+    }
+}
+
+// True if the line sets `key`, as `key: ...` in YAML or `key = ...` in TOML
+fn has_key(line: &str, key: &str) -> bool {
+    let line = line.trim_start().trim_start_matches('-').trim_start();
+    line.strip_prefix(key)
+        .map(str::trim_start)
+        .is_some_and(|rest| rest.starts_with(':') || rest.starts_with('='))
+}
+
+// True if `word` appears in the line, not as part of a longer name
+fn mentions(line: &str, word: &str) -> bool {
+    let is_name = |c: char| c.is_alphanumeric() || c == '_' || c == '-';
+    line.match_indices(word).any(|(start, _)| {
+        let before = line[..start].chars().next_back();
+        let after = line[start + word.len()..].chars().next();
+        !before.is_some_and(is_name) && !after.is_some_and(is_name)
+    })
+}
@@ -0,0 +1,666 @@
+//! Workflows started by on-chain events: token transfers, Helius webhooks and Pyth prices
+
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+use async_trait::async_trait;
+use futures::StreamExt;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    nonblocking::{pubsub_client::PubsubClient, rpc_client::RpcClient},
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, RpcFilterType},
+    rpc_request::TokenAccountsFilter,
+};
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey};
+use tokio::{
+    sync::mpsc::{self, UnboundedSender},
+    task::JoinHandle,
+    time::Instant,
+};
+
+use crate::{
+    util::{constant_time_eq, lock_db, now},
+    workflow_engine::{checkpoint::CheckpointStatus, dag::WorkflowEngine},
+};
+
+// Something that happened on chain
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ChainEvent {
+    // Identifies the event for deduplication, e.g. a transaction signature
+    pub key: String,
+    pub payload: Value,
+}
+
+// Subscription to a stream of chain events
+#[async_trait]
+pub trait EventSource: Send + Sync {
+    // Sends events until `events` is closed, returning early if the subscription fails.
+    // Failed sources are started again after a delay.
+    async fn run(&self, events: UnboundedSender<ChainEvent>) -> Result<(), String>;
+}
+
+const SPL_TOKEN_PROGRAM: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+// Size of an SPL token account, and offset of its owner
+const TOKEN_ACCOUNT_LEN: u64 = 165;
+const TOKEN_OWNER_OFFSET: usize = 32;
+
+// Token transfers received by a wallet, seen as balance increases of its SPL token accounts
+pub struct TokenTransferSource {
+    rpc_url: String,
+    ws_url: String,
+    wallet: Pubkey,
+}
+
+impl TokenTransferSource {
+    pub fn new(rpc_url: &str, ws_url: &str, wallet: &str) -> Result<Self, String> {
+        let wallet = Pubkey::from_str(wallet).map_err(|e| format!("Invalid wallet: {}", e))?;
+        Ok(TokenTransferSource { rpc_url: rpc_url.to_string(), ws_url: ws_url.to_string(), wallet })
+    }
+
+    // Current raw balance of each of the wallet's token accounts
+    async fn balances(&self, program: &Pubkey) -> Result<HashMap<String, u64>, String> {
+        let client = RpcClient::new(self.rpc_url.clone());
+        let accounts = client
+            .get_token_accounts_by_owner(&self.wallet, TokenAccountsFilter::ProgramId(*program))
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(accounts
+            .into_iter()
+            .filter_map(|keyed| {
+                let account = serde_json::to_value(&keyed.account).ok()?;
+                Some((keyed.pubkey, token_amount(&account)?))
+            })
+            .collect())
+    }
+}
+
+#[async_trait]
+impl EventSource for TokenTransferSource {
+    async fn run(&self, events: UnboundedSender<ChainEvent>) -> Result<(), String> {
+        let program = Pubkey::from_str(SPL_TOKEN_PROGRAM).map_err(|e| e.to_string())?;
+        let client = PubsubClient::new(&self.ws_url).await.map_err(|e| e.to_string())?;
+        let config = RpcProgramAccountsConfig {
+            filters: Some(vec![
+                RpcFilterType::DataSize(TOKEN_ACCOUNT_LEN),
+                RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                    TOKEN_OWNER_OFFSET,
+                    &self.wallet.to_bytes(),
+                )),
+            ]),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::JsonParsed),
+                commitment: Some(CommitmentConfig::confirmed()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let (mut updates, unsubscribe) =
+            client.program_subscribe(&program, Some(config)).await.map_err(|e| e.to_string())?;
+        // Subscribe first, so no transfer lands between reading balances and watching them
+        let mut balances = self.balances(&program).await?;
+
+        while let Some(update) = updates.next().await {
+            let account = serde_json::to_value(&update.value.account).unwrap_or_default();
+            let Some(amount) = token_amount(&account) else { continue };
+            let token_account = update.value.pubkey;
+            let previous = balances.insert(token_account.clone(), amount).unwrap_or_default();
+            if amount <= previous {
+                continue;
+            }
+            let info = &account["data"]["parsed"]["info"];
+            let event = ChainEvent {
+                key: format!("{}:{}:{}", token_account, update.context.slot, amount),
+                payload: json!({
+                    "wallet": self.wallet.to_string(),
+                    "token_account": token_account,
+                    "mint": info["mint"],
+                    "decimals": info["tokenAmount"]["decimals"],
+                    "received": (amount - previous).to_string(),
+                    "balance": amount.to_string(),
+                    "slot": update.context.slot,
+                }),
+            };
+            if events.send(event).is_err() {
+                break;
+            }
+        }
+        unsubscribe().await;
+        Ok(())
+    }
+}
+
+// Raw amount of a `jsonParsed` token account
+fn token_amount(account: &Value) -> Option<u64> {
+    account["data"]["parsed"]["info"]["tokenAmount"]["amount"].as_str()?.parse().ok()
+}
+
+// Helius webhook deliveries, received over HTTP. Helius posts a list of enhanced transactions;
+// each transaction touching one of `accounts`, or any if none are given, is an event.
+pub struct HeliusWebhookSource {
+    bind: String,
+    path: String,
+    // Expected `Authorization` header, as set on the webhook in Helius
+    auth_header: String,
+    accounts: Vec<String>,
+}
+
+impl HeliusWebhookSource {
+    // Deliveries without `auth_header` as their `Authorization` header are rejected; anyone who
+    // can reach `bind` could start workflows otherwise, so the header cannot be empty
+    pub fn new(bind: &str, path: &str, auth_header: &str) -> Result<Self, String> {
+        if auth_header.trim().is_empty() {
+            return Err("Helius webhooks need an auth header".to_string());
+        }
+        Ok(HeliusWebhookSource {
+            bind: bind.to_string(),
+            path: path.to_string(),
+            auth_header: auth_header.to_string(),
+            accounts: vec![],
+        })
+    }
+
+    pub fn with_accounts(mut self, accounts: &[&str]) -> Self {
+        self.accounts = accounts.iter().map(|account| account.to_string()).collect();
+        self
+    }
+}
+
+// Events in a Helius webhook body, keyed by transaction signature
+pub fn helius_events(body: &Value, accounts: &[String]) -> Vec<ChainEvent> {
+    let transactions = match body {
+        Value::Array(transactions) => transactions.clone(),
+        other => vec![other.clone()],
+    };
+    transactions
+        .into_iter()
+        .filter(|transaction| {
+            let text = transaction.to_string();
+            accounts.is_empty() || accounts.iter().any(|account| text.contains(account.as_str()))
+        })
+        .filter_map(|transaction| {
+            let key = transaction["signature"].as_str()?.to_string();
+            Some(ChainEvent { key, payload: transaction })
+        })
+        .collect()
+}
+
+struct Webhook {
+    events: UnboundedSender<ChainEvent>,
+    auth_header: String,
+    accounts: Vec<String>,
+}
+
+async fn receive_webhook(
+    request: HttpRequest,
+    body: web::Json<Value>,
+    webhook: web::Data<Webhook>,
+) -> HttpResponse {
+    let given = request.headers().get("Authorization").map(|v| v.as_bytes()).unwrap_or_default();
+    if !constant_time_eq(given, webhook.auth_header.as_bytes()) {
+        return HttpResponse::Unauthorized().finish();
+    }
+    for event in helius_events(&body, &webhook.accounts) {
+        if webhook.events.send(event).is_err() {
+            return HttpResponse::ServiceUnavailable().finish();
+        }
+    }
+    HttpResponse::Ok().finish()
+}
+
+#[async_trait]
+impl EventSource for HeliusWebhookSource {
+    async fn run(&self, events: UnboundedSender<ChainEvent>) -> Result<(), String> {
+        let webhook = web::Data::new(Webhook {
+            events: events.clone(),
+            auth_header: self.auth_header.clone(),
+            accounts: self.accounts.clone(),
+        });
+        let path = self.path.clone();
+        let server = HttpServer::new(move || {
+            App::new().app_data(webhook.clone()).route(&path, web::post().to(receive_webhook))
+        })
+        .workers(1)
+        .bind(&self.bind)
+        .map_err(|e| format!("Could not listen on {}: {}", self.bind, e))?
+        .run();
+        let handle = server.handle();
+        tokio::select! {
+            served = server => served.map_err(|e| e.to_string()),
+            _ = events.closed() => {
+                handle.stop(true).await;
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Crossing {
+    Above,
+    Below,
+}
+
+// Pyth price feed crossing a threshold, streamed from a Hermes server
+pub struct PythPriceSource {
+    hermes_url: String,
+    feed_id: String,
+    threshold: f64,
+    crossing: Crossing,
+}
+
+impl PythPriceSource {
+    // `feed_id` is the hex id of the price feed, e.g. that of SOL/USD
+    pub fn new(hermes_url: &str, feed_id: &str, threshold: f64, crossing: Crossing) -> Self {
+        PythPriceSource {
+            hermes_url: hermes_url.trim_end_matches('/').to_string(),
+            feed_id: feed_id.trim_start_matches("0x").to_string(),
+            threshold,
+            crossing,
+        }
+    }
+
+    // True if the price moved from one side of the threshold to the other, in the direction
+    // watched
+    pub fn crossed(&self, previous: f64, price: f64) -> bool {
+        match self.crossing {
+            Crossing::Above => previous < self.threshold && price >= self.threshold,
+            Crossing::Below => previous > self.threshold && price <= self.threshold,
+        }
+    }
+}
+
+#[async_trait]
+impl EventSource for PythPriceSource {
+    async fn run(&self, events: UnboundedSender<ChainEvent>) -> Result<(), String> {
+        let url = format!(
+            "{}/v2/updates/price/stream?ids[]={}&parsed=true",
+            self.hermes_url, self.feed_id
+        );
+        let response = reqwest::get(&url).await.map_err(|e| e.to_string())?;
+        let response = response.error_for_status().map_err(|e| e.to_string())?;
+        let mut chunks = response.bytes_stream();
+        let mut buffer = String::new();
+        let mut previous: Option<f64> = None;
+
+        // Server-sent events, one `data:` line per update
+        while let Some(chunk) = chunks.next().await {
+            let chunk = chunk.map_err(|e| e.to_string())?;
+            buffer.push_str(&String::from_utf8_lossy(&chunk));
+            while let Some(end) = buffer.find('\n') {
+                let line: String = buffer.drain(..=end).collect();
+                let Some(data) = line.trim().strip_prefix("data:") else { continue };
+                let Ok(update) = serde_json::from_str::<Value>(data.trim()) else { continue };
+                for parsed in update["parsed"].as_array().into_iter().flatten() {
+                    let Some(price) = pyth_price(&parsed["price"]) else { continue };
+                    let crossed = previous.is_some_and(|previous| self.crossed(previous, price));
+                    if crossed {
+                        let publish_time = &parsed["price"]["publish_time"];
+                        let event = ChainEvent {
+                            key: format!("{}:{}", self.feed_id, publish_time),
+                            payload: json!({
+                                "feed_id": self.feed_id,
+                                "price": price,
+                                "previous": previous,
+                                "threshold": self.threshold,
+                                "crossing": self.crossing,
+                                "publish_time": publish_time,
+                            }),
+                        };
+                        if events.send(event).is_err() {
+                            return Ok(());
+                        }
+                    }
+                    previous = Some(price);
+                }
+            }
+        }
+        Err("Price stream ended".to_string())
+    }
+}
+
+// Price of a Hermes price object, `price * 10^expo`
+fn pyth_price(price: &Value) -> Option<f64> {
+    let mantissa: f64 = price["price"].as_str()?.parse().ok()?;
+    let expo = price["expo"].as_i64()?;
+    Some(mantissa * 10f64.powi(expo as i32))
+}
+
+// Delay before restarting a source that failed or ended
+const RESTART_DELAY: Duration = Duration::from_secs(5);
+
+// Starts a workflow for the events of a source. Each run gets the inputs
+// `{"trigger": <id>, "key": <event key>, "payload": <event payload>, "events": <count>}`,
+// read by nodes as e.g. `${inputs.payload.mint}`.
+pub struct EventTrigger {
+    pub id: String,
+    pub source: Arc<dyn EventSource>,
+    pub workflow: Arc<WorkflowEngine>,
+    // Events closer together than this start a single run, with the latest event
+    pub debounce: Duration,
+    // Events with a key seen within this window are dropped
+    pub dedupe_window: Duration,
+}
+
+impl EventTrigger {
+    pub fn new(id: &str, source: Arc<dyn EventSource>, workflow: Arc<WorkflowEngine>) -> Self {
+        EventTrigger {
+            id: id.to_string(),
+            source,
+            workflow,
+            debounce: Duration::ZERO,
+            dedupe_window: Duration::from_secs(600),
+        }
+    }
+
+    pub fn with_debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    pub fn with_dedupe_window(mut self, dedupe_window: Duration) -> Self {
+        self.dedupe_window = dedupe_window;
+        self
+    }
+}
+
+// Counters of a trigger
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TriggerStatus {
+    pub id: String,
+    pub received: u64,
+    pub duplicates: u64,
+    pub runs: u64,
+    pub last_key: Option<String>,
+    pub last_error: Option<String>,
+}
+
+// SQLite store of the event keys each trigger has seen
+pub struct EventStore {
+    db: Mutex<Connection>,
+}
+
+impl EventStore {
+    pub fn open(path: &str) -> rusqlite::Result<Self> {
+        Self::init(Connection::open(path)?)
+    }
+
+    pub fn in_memory() -> rusqlite::Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(db: Connection) -> rusqlite::Result<Self> {
+        db.execute_batch(
+            "CREATE TABLE IF NOT EXISTS trigger_events (
+                trigger_id TEXT NOT NULL,
+                key TEXT NOT NULL,
+                seen_at INTEGER NOT NULL,
+                PRIMARY KEY (trigger_id, key)
+            );",
+        )?;
+        Ok(EventStore { db: Mutex::new(db) })
+    }
+
+    // Records that a trigger saw a key, forgetting keys older than `window`. Returns false if
+    // the key was already seen within the window.
+    pub fn record(&self, trigger_id: &str, key: &str, window: Duration) -> rusqlite::Result<bool> {
+        let db = lock_db(&self.db)?;
+        let now = now();
+        db.execute(
+            "DELETE FROM trigger_events WHERE trigger_id = ?1 AND seen_at < ?2",
+            params![trigger_id, now.saturating_sub(window.as_millis() as u64)],
+        )?;
+        let inserted = db.execute(
+            "INSERT OR IGNORE INTO trigger_events (trigger_id, key, seen_at) VALUES (?1, ?2, ?3)",
+            params![trigger_id, key, now],
+        )?;
+        Ok(inserted > 0)
+    }
+}
+
+// Trigger's listener and the task running it
+type Running = (Arc<Listener>, JoinHandle<()>);
+
+// Runs event triggers in the background
+pub struct EventTriggers {
+    store: Option<Arc<EventStore>>,
+    triggers: Mutex<HashMap<String, Running>>,
+}
+
+impl EventTriggers {
+    pub fn new() -> Self {
+        EventTriggers { store: None, triggers: Mutex::new(HashMap::new()) }
+    }
+
+    // Keeps the event keys seen, so events a source delivers again after a restart are still
+    // dropped as duplicates within the dedupe window
+    pub fn with_store(mut self, store: Arc<EventStore>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    // Starts listening for a trigger's events; needs a Tokio runtime
+    pub fn add(&self, trigger: EventTrigger) -> Result<(), String> {
+        let mut triggers = self.triggers.lock().unwrap();
+        if triggers.contains_key(&trigger.id) {
+            return Err(format!("Trigger '{}' already exists", trigger.id));
+        }
+        let listener = Arc::new(Listener {
+            trigger,
+            store: self.store.clone(),
+            active: Mutex::new(HashSet::new()),
+            received: AtomicU64::new(0),
+            duplicates: AtomicU64::new(0),
+            runs: AtomicU64::new(0),
+            last_key: Mutex::new(None),
+            last_error: Mutex::new(None),
+        });
+        let handle = tokio::spawn(listener.clone().listen());
+        triggers.insert(listener.trigger.id.clone(), (listener, handle));
+        Ok(())
+    }
+
+    // Stops a trigger; runs already started are left to finish
+    pub fn remove(&self, id: &str) -> bool {
+        match self.triggers.lock().unwrap().remove(id) {
+            Some((_, handle)) => {
+                handle.abort();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn status(&self, id: &str) -> Option<TriggerStatus> {
+        self.triggers.lock().unwrap().get(id).map(|(listener, _)| listener.status())
+    }
+
+    pub fn shutdown(&self) {
+        for (_, (_, handle)) in self.triggers.lock().unwrap().drain() {
+            handle.abort();
+        }
+    }
+}
+
+impl Default for EventTriggers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for EventTriggers {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+struct Listener {
+    trigger: EventTrigger,
+    store: Option<Arc<EventStore>>,
+    // Workflow ids of the runs in progress
+    active: Mutex<HashSet<String>>,
+    received: AtomicU64,
+    duplicates: AtomicU64,
+    runs: AtomicU64,
+    last_key: Mutex<Option<String>>,
+    last_error: Mutex<Option<String>>,
+}
+
+impl Listener {
+    async fn listen(self: Arc<Self>) {
+        let (sender, mut events) = mpsc::unbounded_channel();
+        let feed = feed(self.trigger.id.clone(), self.trigger.source.clone(), sender);
+        tokio::pin!(feed);
+        let mut seen: HashMap<String, Instant> = HashMap::new();
+        // Latest event waiting out the debounce, how many events it stands for and when it is due
+        let mut pending: Option<(ChainEvent, u64, Instant)> = None;
+
+        loop {
+            let due = pending.as_ref().map(|(_, _, due)| *due);
+            let debounced = tokio::time::sleep_until(due.unwrap_or_else(Instant::now));
+            tokio::select! {
+                event = events.recv() => {
+                    let Some(event) = event else { break };
+                    self.received.fetch_add(1, Ordering::SeqCst);
+                    let now = Instant::now();
+                    seen.retain(|_, at| now.duration_since(*at) < self.trigger.dedupe_window);
+                    let repeated = seen.insert(event.key.clone(), now).is_some();
+                    if repeated || !self.record(&event.key) {
+                        self.duplicates.fetch_add(1, Ordering::SeqCst);
+                        continue;
+                    }
+                    if self.trigger.debounce.is_zero() {
+                        self.start(event, 1);
+                        continue;
+                    }
+                    let count = pending.take().map(|(_, count, _)| count).unwrap_or_default();
+                    pending = Some((event, count + 1, now + self.trigger.debounce));
+                }
+                _ = debounced, if due.is_some() => {
+                    if let Some((event, count, _)) = pending.take() {
+                        self.start(event, count);
+                    }
+                }
+                _ = &mut feed => break,
+            }
+        }
+    }
+
+    // Records a key in the store, returning false if it was seen before. Keys are taken as new
+    // when the store fails, since the in-memory check already passed.
+    fn record(&self, key: &str) -> bool {
+        let Some(store) = &self.store else { return true };
+        match store.record(&self.trigger.id, key, self.trigger.dedupe_window) {
+            Ok(new) => new,
+            Err(error) => {
+                tracing::warn!(trigger = %self.trigger.id, "Could not record event: {}", error);
+                true
+            }
+        }
+    }
+
+    // Starts a run for an event, under a workflow id derived from its key. With a checkpoint
+    // store, an event delivered again once its run succeeded starts nothing, and one whose run
+    // was interrupted or failed resumes it instead of running every node again.
+    fn start(self: &Arc<Self>, event: ChainEvent, count: u64) {
+        let id = &self.trigger.id;
+        let workflow_id = format!("{}-{}", id, event.key);
+        if !self.active.lock().unwrap().insert(workflow_id.clone()) {
+            tracing::info!(trigger = %id, "Run {} is still going, dropping event", workflow_id);
+            self.duplicates.fetch_add(1, Ordering::SeqCst);
+            return;
+        }
+        let listener = self.clone();
+        tokio::spawn(async move {
+            let id = &listener.trigger.id;
+            let workflow = &listener.trigger.workflow;
+            let inputs = json!({
+                "trigger": id,
+                "key": event.key,
+                "payload": event.payload,
+                "events": count,
+            });
+            *listener.last_key.lock().unwrap() = Some(event.key);
+            let result = match workflow.checkpoint(&workflow_id).map(|s| s.map(|s| s.status)) {
+                Ok(Some(CheckpointStatus::Succeeded)) => {
+                    tracing::info!(trigger = %id, "Run {} already succeeded", workflow_id);
+                    listener.duplicates.fetch_add(1, Ordering::SeqCst);
+                    listener.active.lock().unwrap().remove(&workflow_id);
+                    return;
+                }
+                Ok(Some(CheckpointStatus::Running | CheckpointStatus::Failed)) => {
+                    workflow.resume(&workflow_id).await
+                }
+                Ok(_) => workflow.run_with_inputs(&workflow_id, inputs).await,
+                Err(error) => Err(error),
+            };
+            listener.active.lock().unwrap().remove(&workflow_id);
+            let error = match result {
+                Ok(report) if report.succeeded() => None,
+                Ok(_) => Some(format!("Workflow {} did not succeed", workflow_id)),
+                Err(error) => Some(error.to_string()),
+            };
+            if let Some(error) = &error {
+                tracing::warn!(trigger = %id, "Triggered run failed: {}", error);
+            }
+            *listener.last_error.lock().unwrap() = error;
+            listener.runs.fetch_add(1, Ordering::SeqCst);
+        });
+    }
+
+    fn status(&self) -> TriggerStatus {
+        TriggerStatus {
+            id: self.trigger.id.clone(),
+            received: self.received.load(Ordering::SeqCst),
+            duplicates: self.duplicates.load(Ordering::SeqCst),
+            runs: self.runs.load(Ordering::SeqCst),
+            last_key: self.last_key.lock().unwrap().clone(),
+            last_error: self.last_error.lock().unwrap().clone(),
+        }
+    }
+}
+
+// Keeps a source running, restarting it whenever it fails or ends
+async fn feed(id: String, source: Arc<dyn EventSource>, events: UnboundedSender<ChainEvent>) {
+    while !events.is_closed() {
+        if let Err(error) = source.run(events.clone()).await {
+            tracing::warn!(trigger = %id, "Event source failed: {}", error);
+        }
+        tokio::time::sleep(RESTART_DELAY).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_helius_events_filter_accounts() {
+        let body = json!([
+            {"signature": "a", "accountData": [{"account": "Wallet111"}]},
+            {"signature": "b", "accountData": [{"account": "Other111"}]},
+        ]);
+        let events = helius_events(&body, &["Wallet111".to_string()]);
+        let keys: Vec<&str> = events.iter().map(|e| e.key.as_str()).collect();
+        assert_eq!(keys, vec!["a"]);
+        assert_eq!(helius_events(&body, &[]).len(), 2);
+    }
+
+    #[test]
+    fn test_helius_webhooks_need_an_auth_header() {
+        assert!(HeliusWebhookSource::new("127.0.0.1:0", "/helius", " ").is_err());
+        assert!(HeliusWebhookSource::new("127.0.0.1:0", "/helius", "Bearer secret").is_ok());
+    }
+}
@@ -1 +1,7 @@
-pub mod dag;
\ No newline at end of file
+pub mod dag;
+pub mod checkpoint;
+pub mod definition;
+pub mod scheduler;
+pub mod events;
+
+pub use dag::WorkflowEngine;
\ No newline at end of file
@@ -0,0 +1,566 @@
+//! Runs workflows and agent tasks on cron expressions or fixed intervals
+
+use std::{
+    collections::HashMap,
+    fmt,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::task::JoinHandle;
+
+use crate::{agent_controller::AgentController, util::lock_db, workflow_engine::dag::WorkflowEngine};
+
+// When a schedule fires
+#[derive(Clone, Debug)]
+pub enum Trigger {
+    // Fires at the earliest next match of any of the schedules
+    Cron(Vec<cron::Schedule>),
+    Interval(Duration),
+}
+
+impl Trigger {
+    // Parses a cron expression. The usual five Unix fields starting with the minute, e.g.
+    // `*/5 * * * *`, have Unix meaning: weekdays are 0-6 from Sunday (7 is Sunday too), and
+    // when both the day of month and the weekday are restricted either one matching is
+    // enough. Six or seven fields starting with the second follow the `cron` crate instead,
+    // with weekdays 1-7 from Sunday and both day fields having to match.
+    pub fn cron(expression: &str) -> Result<Self, SchedulerError> {
+        let invalid = |error: String| SchedulerError::InvalidCron {
+            expression: expression.to_string(),
+            error,
+        };
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let expressions = match fields[..] {
+            [minute, hour, day, month, weekday] => {
+                let weekdays = unix_weekdays(weekday).map_err(invalid)?;
+                if is_any(day) || is_any(weekday) {
+                    vec![format!("0 {} {} {} {} {}", minute, hour, day, month, weekdays)]
+                } else {
+                    vec![
+                        format!("0 {} {} {} {} *", minute, hour, day, month),
+                        format!("0 {} {} * {} {}", minute, hour, month, weekdays),
+                    ]
+                }
+            }
+            _ => vec![expression.to_string()],
+        };
+        expressions
+            .iter()
+            .map(|e| cron::Schedule::from_str(e).map_err(|e| invalid(e.to_string())))
+            .collect::<Result<_, _>>()
+            .map(Trigger::Cron)
+    }
+
+    pub fn every(interval: Duration) -> Self {
+        Trigger::Interval(interval.max(Duration::from_millis(1)))
+    }
+
+    // Next fire time after the last run; schedules that never ran start right away on an
+    // interval and at the next match of a cron expression
+    fn next_after(&self, last_run: Option<SystemTime>) -> Option<SystemTime> {
+        match (self, last_run) {
+            (Trigger::Interval(interval), Some(last_run)) => Some(last_run + *interval),
+            (Trigger::Interval(_), None) => Some(SystemTime::now()),
+            (Trigger::Cron(schedules), last_run) => {
+                let after: DateTime<Utc> = last_run.unwrap_or_else(SystemTime::now).into();
+                schedules.iter().filter_map(|s| s.after(&after).next()).min().map(SystemTime::from)
+            }
+        }
+    }
+}
+
+const WEEKDAYS: [&str; 7] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+
+fn is_any(field: &str) -> bool {
+    field == "*" || field == "?"
+}
+
+// Translates a Unix weekday field, e.g. `1-5` or `0,6` or `*/2`, to the `cron` crate's
+// numbering by listing the weekdays it matches
+fn unix_weekdays(field: &str) -> Result<String, String> {
+    if is_any(field) {
+        return Ok(field.to_string());
+    }
+    let weekday = |value: &str| -> Result<usize, String> {
+        let day = match value.parse::<usize>() {
+            Ok(day) => day,
+            Err(_) => WEEKDAYS
+                .iter()
+                .position(|name| name.eq_ignore_ascii_case(value))
+                .ok_or_else(|| format!("Invalid weekday '{}'", value))?,
+        };
+        if day > 7 {
+            return Err(format!("Weekday {} is out of range 0-7", day));
+        }
+        Ok(day)
+    };
+    let mut matched = [false; 7];
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step = step.parse::<usize>().ok().filter(|step| *step > 0);
+                (range, step.ok_or_else(|| format!("Invalid step in '{}'", part))?)
+            }
+            None => (part, 1),
+        };
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (0, 6),
+            Some((start, end)) => (weekday(start)?, weekday(end)?),
+            None if step > 1 => (weekday(range)?, 6),
+            None => (weekday(range)?, weekday(range)?),
+        };
+        if start > end {
+            return Err(format!("Invalid weekday range '{}'", range));
+        }
+        for day in (start..=end).step_by(step) {
+            matched[day % 7] = true;
+        }
+    }
+    let days: Vec<&str> = (0..7).filter(|day| matched[*day]).map(|day| WEEKDAYS[day]).collect();
+    Ok(days.join(","))
+}
+
+// Work run each time a schedule fires
+#[async_trait]
+pub trait ScheduledJob: Send + Sync {
+    // Runs once, returning a short summary of the outcome
+    async fn run(&self) -> Result<String, String>;
+}
+
+#[async_trait]
+impl ScheduledJob for WorkflowEngine {
+    async fn run(&self) -> Result<String, String> {
+        let report = WorkflowEngine::run(self).await.map_err(|e| e.to_string())?;
+        if report.succeeded() {
+            Ok(format!("Workflow {} succeeded", report.workflow_id))
+        } else {
+            Err(format!("Workflow {} did not succeed", report.workflow_id))
+        }
+    }
+}
+
+// Agent task run with the same input every time, in the same session
+pub struct TaskJob {
+    controller: Arc<AgentController>,
+    session_id: String,
+    task: String,
+    input: Value,
+}
+
+impl TaskJob {
+    pub fn new(controller: Arc<AgentController>, task: &str, input: Value) -> Self {
+        TaskJob { controller, session_id: "scheduler".to_string(), task: task.to_string(), input }
+    }
+
+    pub fn with_session(mut self, session_id: &str) -> Self {
+        self.session_id = session_id.to_string();
+        self
+    }
+}
+
+#[async_trait]
+impl ScheduledJob for TaskJob {
+    async fn run(&self) -> Result<String, String> {
+        let transcript = self
+            .controller
+            .execute_task(&self.session_id, &self.task, self.input.clone())
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(transcript.final_answer.unwrap_or_default())
+    }
+}
+
+// Job with its trigger, e.g.
+// `Schedule::new("poll_pools", Trigger::every(Duration::from_secs(60)), job)`
+pub struct Schedule {
+    pub id: String,
+    pub trigger: Trigger,
+    // Each run starts up to this much later than due, so schedules don't all fire at once
+    pub jitter: Duration,
+    pub job: Arc<dyn ScheduledJob>,
+}
+
+impl Schedule {
+    pub fn new(id: &str, trigger: Trigger, job: Arc<dyn ScheduledJob>) -> Self {
+        Schedule { id: id.to_string(), trigger, jitter: Duration::ZERO, job }
+    }
+
+    pub fn with_jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SchedulerError {
+    InvalidCron { expression: String, error: String },
+    Duplicate(String),
+    NotFound(String),
+    Store(String),
+}
+
+impl fmt::Display for SchedulerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchedulerError::InvalidCron { expression, error } => {
+                write!(f, "Invalid cron expression '{}': {}", expression, error)
+            }
+            SchedulerError::Duplicate(id) => write!(f, "Schedule '{}' already exists", id),
+            SchedulerError::NotFound(id) => write!(f, "Schedule '{}' not found", id),
+            SchedulerError::Store(error) => write!(f, "Schedule store error: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for SchedulerError {}
+
+// Current state of a schedule; times are Unix milliseconds
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ScheduleStatus {
+    pub id: String,
+    pub paused: bool,
+    pub running: bool,
+    pub last_run: Option<u64>,
+    pub next_run: Option<u64>,
+    pub runs: u64,
+    // Times the schedule fired while the previous run was still going, and was skipped
+    pub overlaps: u64,
+    pub last_error: Option<String>,
+}
+
+// What is kept of a schedule across restarts
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ScheduleRecord {
+    pub last_run: Option<u64>,
+    pub paused: bool,
+}
+
+// SQLite store of schedule records
+pub struct ScheduleStore {
+    db: Mutex<Connection>,
+}
+
+impl ScheduleStore {
+    pub fn open(path: &str) -> rusqlite::Result<Self> {
+        Self::init(Connection::open(path)?)
+    }
+
+    pub fn in_memory() -> rusqlite::Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(db: Connection) -> rusqlite::Result<Self> {
+        db.execute_batch(
+            "CREATE TABLE IF NOT EXISTS schedules (
+                id TEXT PRIMARY KEY,
+                last_run INTEGER,
+                paused INTEGER NOT NULL
+            );",
+        )?;
+        Ok(ScheduleStore { db: Mutex::new(db) })
+    }
+
+    pub fn load(&self, id: &str) -> rusqlite::Result<Option<ScheduleRecord>> {
+        lock_db(&self.db)?
+            .query_row("SELECT last_run, paused FROM schedules WHERE id = ?1", [id], |row| {
+                Ok(ScheduleRecord { last_run: row.get(0)?, paused: row.get(1)? })
+            })
+            .optional()
+    }
+
+    pub fn save(&self, id: &str, record: &ScheduleRecord) -> rusqlite::Result<()> {
+        lock_db(&self.db)?.execute(
+            "INSERT OR REPLACE INTO schedules (id, last_run, paused) VALUES (?1, ?2, ?3)",
+            params![id, record.last_run, record.paused],
+        )?;
+        Ok(())
+    }
+}
+
+// Schedule's state and the task running it
+type Running = (Arc<Entry>, JoinHandle<()>);
+
+// Runs schedules in the background. A schedule that fires while its previous run is still
+// going skips that run, and paused schedules keep their place but don't run.
+pub struct Scheduler {
+    store: Option<Arc<ScheduleStore>>,
+    schedules: Mutex<HashMap<String, Running>>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Scheduler { store: None, schedules: Mutex::new(HashMap::new()) }
+    }
+
+    // Persists last-run times and paused flags, and picks them up for schedules with the same
+    // id, so a restarted scheduler neither repeats nor forgets runs
+    pub fn with_store(mut self, store: Arc<ScheduleStore>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    // Starts running a schedule; needs a Tokio runtime
+    pub fn add(&self, schedule: Schedule) -> Result<(), SchedulerError> {
+        let mut schedules = self.schedules.lock().unwrap();
+        if schedules.contains_key(&schedule.id) {
+            return Err(SchedulerError::Duplicate(schedule.id));
+        }
+        let record = match &self.store {
+            Some(store) => store.load(&schedule.id).map_err(store_error)?.unwrap_or_default(),
+            None => ScheduleRecord::default(),
+        };
+        let entry = Arc::new(Entry {
+            paused: AtomicBool::new(record.paused),
+            running: AtomicBool::new(false),
+            last_run: AtomicU64::new(record.last_run.unwrap_or(NEVER)),
+            next_run: AtomicU64::new(NEVER),
+            runs: AtomicU64::new(0),
+            overlaps: AtomicU64::new(0),
+            last_error: Mutex::new(None),
+            store: self.store.clone(),
+            schedule,
+        });
+        let handle = tokio::spawn(entry.clone().drive());
+        schedules.insert(entry.schedule.id.clone(), (entry, handle));
+        Ok(())
+    }
+
+    // Stops a schedule; a run in progress is left to finish
+    pub fn remove(&self, id: &str) -> bool {
+        match self.schedules.lock().unwrap().remove(id) {
+            Some((_, handle)) => {
+                handle.abort();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn pause(&self, id: &str) -> Result<(), SchedulerError> {
+        self.entry(id)?.set_paused(true)
+    }
+
+    pub fn resume(&self, id: &str) -> Result<(), SchedulerError> {
+        self.entry(id)?.set_paused(false)
+    }
+
+    pub fn status(&self, id: &str) -> Option<ScheduleStatus> {
+        self.entry(id).ok().map(|entry| entry.status())
+    }
+
+    pub fn statuses(&self) -> Vec<ScheduleStatus> {
+        let schedules = self.schedules.lock().unwrap();
+        let mut statuses: Vec<ScheduleStatus> =
+            schedules.values().map(|(entry, _)| entry.status()).collect();
+        statuses.sort_by(|a, b| a.id.cmp(&b.id));
+        statuses
+    }
+
+    // Stops every schedule
+    pub fn shutdown(&self) {
+        for (_, (_, handle)) in self.schedules.lock().unwrap().drain() {
+            handle.abort();
+        }
+    }
+
+    fn entry(&self, id: &str) -> Result<Arc<Entry>, SchedulerError> {
+        let schedules = self.schedules.lock().unwrap();
+        let (entry, _) = schedules.get(id).ok_or(SchedulerError::NotFound(id.to_string()))?;
+        Ok(entry.clone())
+    }
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Scheduler {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+// Stands in for a time that is not set
+const NEVER: u64 = u64::MAX;
+
+struct Entry {
+    schedule: Schedule,
+    store: Option<Arc<ScheduleStore>>,
+    paused: AtomicBool,
+    running: AtomicBool,
+    last_run: AtomicU64,
+    next_run: AtomicU64,
+    runs: AtomicU64,
+    overlaps: AtomicU64,
+    last_error: Mutex<Option<String>>,
+}
+
+impl Entry {
+    // Fires the schedule until it has no fire times left or is removed
+    async fn drive(self: Arc<Self>) {
+        let id = &self.schedule.id;
+        // Skipped and paused fire times move the cursor without counting as runs
+        let mut cursor = time(self.last_run.load(Ordering::SeqCst));
+        loop {
+            let Some(due) = self.schedule.trigger.next_after(cursor) else {
+                tracing::info!(schedule = %id, "Schedule has no more fire times");
+                self.next_run.store(NEVER, Ordering::SeqCst);
+                break;
+            };
+            let due = due + jitter(self.schedule.jitter);
+            self.next_run.store(millis(due), Ordering::SeqCst);
+            if let Ok(wait) = due.duration_since(SystemTime::now()) {
+                tokio::time::sleep(wait).await;
+            }
+            let fired = SystemTime::now();
+            cursor = Some(fired);
+
+            if self.paused.load(Ordering::SeqCst) {
+                continue;
+            }
+            if self.running.swap(true, Ordering::SeqCst) {
+                tracing::warn!(schedule = %id, "Previous run still going, skipping this one");
+                self.overlaps.fetch_add(1, Ordering::SeqCst);
+                continue;
+            }
+            self.last_run.store(millis(fired), Ordering::SeqCst);
+            self.save();
+
+            let entry = self.clone();
+            tokio::spawn(async move {
+                let result = entry.schedule.job.run().await;
+                if let Err(error) = &result {
+                    let id = &entry.schedule.id;
+                    tracing::warn!(schedule = %id, "Scheduled run failed: {}", error);
+                }
+                *entry.last_error.lock().unwrap() = result.err();
+                entry.runs.fetch_add(1, Ordering::SeqCst);
+                entry.running.store(false, Ordering::SeqCst);
+            });
+        }
+    }
+
+    fn set_paused(&self, paused: bool) -> Result<(), SchedulerError> {
+        self.paused.store(paused, Ordering::SeqCst);
+        match &self.store {
+            Some(store) => store.save(&self.schedule.id, &self.record()).map_err(store_error),
+            None => Ok(()),
+        }
+    }
+
+    fn save(&self) {
+        let Some(store) = &self.store else { return };
+        if let Err(error) = store.save(&self.schedule.id, &self.record()) {
+            tracing::warn!(schedule = %self.schedule.id, "Could not save schedule: {}", error);
+        }
+    }
+
+    fn record(&self) -> ScheduleRecord {
+        ScheduleRecord {
+            last_run: set(self.last_run.load(Ordering::SeqCst)),
+            paused: self.paused.load(Ordering::SeqCst),
+        }
+    }
+
+    fn status(&self) -> ScheduleStatus {
+        ScheduleStatus {
+            id: self.schedule.id.clone(),
+            paused: self.paused.load(Ordering::SeqCst),
+            running: self.running.load(Ordering::SeqCst),
+            last_run: set(self.last_run.load(Ordering::SeqCst)),
+            next_run: set(self.next_run.load(Ordering::SeqCst)),
+            runs: self.runs.load(Ordering::SeqCst),
+            overlaps: self.overlaps.load(Ordering::SeqCst),
+            last_error: self.last_error.lock().unwrap().clone(),
+        }
+    }
+}
+
+fn jitter(max: Duration) -> Duration {
+    if max.is_zero() {
+        return Duration::ZERO;
+    }
+    Duration::from_millis(rand::thread_rng().gen_range(0..=max.as_millis() as u64))
+}
+
+fn millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or_default()
+}
+
+fn set(millis: u64) -> Option<u64> {
+    (millis != NEVER).then_some(millis)
+}
+
+fn time(millis: u64) -> Option<SystemTime> {
+    set(millis).map(|millis| UNIX_EPOCH + Duration::from_millis(millis))
+}
+
+fn store_error(error: rusqlite::Error) -> SchedulerError {
+    SchedulerError::Store(error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cron_accepts_five_fields() {
+        let next = next_after("*/5 * * * *", "2024-01-01T00:01:30Z");
+        assert_eq!(next, "2024-01-01T00:05:00+00:00");
+        assert!(Trigger::cron("every minute").is_err());
+    }
+
+    // Next fire time of a cron trigger after `after`, in RFC 3339
+    fn next_after(expression: &str, after: &str) -> String {
+        let after: DateTime<Utc> = after.parse().unwrap();
+        let next = Trigger::cron(expression).unwrap().next_after(Some(after.into())).unwrap();
+        DateTime::<Utc>::from(next).to_rfc3339()
+    }
+
+    #[test]
+    fn test_cron_weekdays_count_from_sunday_as_zero() {
+        // 2024-01-01 is a Monday
+        let monday = "2024-01-01T00:00:30Z";
+        assert_eq!(next_after("0 9 * * 1", monday), "2024-01-01T09:00:00+00:00");
+        assert_eq!(next_after("0 9 * * 0", monday), "2024-01-07T09:00:00+00:00");
+        assert_eq!(next_after("0 9 * * 7", monday), "2024-01-07T09:00:00+00:00");
+        assert_eq!(next_after("0 9 * * 6", monday), "2024-01-06T09:00:00+00:00");
+        assert_eq!(next_after("0 9 * * sat,sun", monday), "2024-01-06T09:00:00+00:00");
+        let friday = "2024-01-05T10:00:00Z";
+        assert_eq!(next_after("0 9 * * 1-5", friday), "2024-01-08T09:00:00+00:00");
+        assert_eq!(next_after("0 9 * * 5-7", friday), "2024-01-06T09:00:00+00:00");
+        assert!(Trigger::cron("0 9 * * 8").is_err());
+        assert!(Trigger::cron("0 9 * * 5-1").is_err());
+    }
+
+    #[test]
+    fn test_cron_matches_either_day_field() {
+        // The 15th, or any Monday; 2024-01-08 is a Monday
+        let expression = "0 9 15 * 1";
+        assert_eq!(next_after(expression, "2024-01-02T00:00:00Z"), "2024-01-08T09:00:00+00:00");
+        assert_eq!(next_after(expression, "2024-01-10T00:00:00Z"), "2024-01-15T09:00:00+00:00");
+        assert_eq!(next_after(expression, "2024-01-16T00:00:00Z"), "2024-01-22T09:00:00+00:00");
+        // With only one day field set, that one has to match
+        assert_eq!(next_after("0 9 15 * *", "2024-01-02T00:00:00Z"), "2024-01-15T09:00:00+00:00");
+    }
+
+    #[test]
+    fn test_cron_seconds_field_keeps_crate_weekdays() {
+        // Six fields follow the `cron` crate, where 1 is Sunday
+        let next = next_after("0 0 9 * * 1", "2024-01-01T00:00:30Z");
+        assert_eq!(next, "2024-01-07T09:00:00+00:00");
+    }
+}
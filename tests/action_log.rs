@@ -0,0 +1,137 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use serde_json::json;
+use solagent::{
+    llm_integration::{mock::MockProvider, LLMProvider},
+    memory_system::{
+        actions::{ActionLog, ActionOutcome},
+        long_term::Namespace,
+        LongTermMemory,
+    },
+    tool_system::{SolanaTool, ToolInput, ToolMetadata, ToolRegistry},
+};
+use solana_sdk::signature::Signature;
+
+mod common;
+use common::metadata;
+
+// Tool answering like a plugin function: a signature for swaps, plain text for quotes. Swaps
+// report the transaction before it is sent.
+struct SwapTool;
+
+#[async_trait::async_trait]
+impl SolanaTool for SwapTool {
+    async fn execute(
+        &self,
+        input: ToolInput,
+        _llm: &dyn LLMProvider,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let signature = Signature::new_unique();
+        // Quotes mention a signature but send nothing
+        if input.params["mode"] == "quote" {
+            return Ok(format!("1 SOL = 150 USDC, last swap {}", signature));
+        }
+        input.submitted(&signature.to_string(), "blockhash");
+        match input.params["mode"].as_str() {
+            Some("timeout") => Err(format!("Transaction {} was not confirmed", signature).into()),
+            _ => Ok(json!({ "signature": signature.to_string() }).to_string()),
+        }
+    }
+}
+
+fn sending(name: &str) -> ToolMetadata {
+    ToolMetadata { sends_transactions: true, ..metadata(name) }
+}
+
+#[tokio::test]
+async fn test_tool_calls_sending_transactions_are_recorded() {
+    let memory = Arc::new(LongTermMemory::in_memory().unwrap());
+    let namespace = Namespace::agent("trader");
+    let action_log = Arc::new(ActionLog::new(
+        memory.clone(),
+        namespace.clone(),
+        "https://api.devnet.solana.com",
+    ));
+    let registry = ToolRegistry::new().with_action_log(action_log.clone());
+    registry.register(sending("trade"), Arc::new(SwapTool)).await;
+    registry.register(metadata("read_only"), Arc::new(SwapTool)).await;
+    let (_, trade) = registry.get("trade").await.unwrap();
+    let llm = MockProvider::new();
+
+    let swap = json!({ "mode": "swap", "amount": 1.0 });
+    let output = trade.execute(ToolInput::new(swap.clone()), &llm).await.unwrap();
+    trade.execute(ToolInput::new(json!({ "mode": "quote" })), &llm).await.unwrap();
+    let error = trade
+        .execute(ToolInput::new(json!({ "mode": "timeout" })), &llm)
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("was not confirmed"));
+
+    // Tools not marked as sending transactions are never recorded
+    let (_, read_only) = registry.get("read_only").await.unwrap();
+    read_only.execute(ToolInput::new(swap.clone()), &llm).await.unwrap();
+
+    // The quote sent no transaction, so only the swap and the timed out call are actions
+    let actions = action_log.recent(10).await.unwrap();
+    assert_eq!(actions.len(), 2);
+    assert_eq!(actions[0].tool, "trade");
+    assert_eq!(actions[0].arguments, swap);
+    assert_eq!(actions[0].cluster, "devnet");
+    assert_eq!(actions[0].outcome, ActionOutcome::Succeeded { output: output.clone() });
+    assert!(output.contains(&actions[0].signatures[0]));
+    assert!(!actions[1].succeeded());
+    assert!(actions[0].timestamp <= actions[1].timestamp);
+
+    assert_eq!(action_log.recent(1).await.unwrap(), vec![actions[1].clone()]);
+
+    let from = actions[0].timestamp;
+    assert!(action_log.between(from, from).await.unwrap().contains(&actions[0]));
+    assert!(action_log.between(0, from - 1).await.unwrap().is_empty());
+    assert_eq!(memory.list(&namespace, "action:").await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_submitted_transactions_still_reach_the_caller() {
+    let action_log = Arc::new(ActionLog::new(
+        Arc::new(LongTermMemory::in_memory().unwrap()),
+        Namespace::agent("trader"),
+        "https://api.devnet.solana.com",
+    ));
+    let registry = ToolRegistry::new().with_action_log(action_log.clone());
+    registry.register(sending("trade"), Arc::new(SwapTool)).await;
+    let (_, trade) = registry.get("trade").await.unwrap();
+    let reported = Arc::new(std::sync::Mutex::new(vec![]));
+    let seen = reported.clone();
+    let input = ToolInput::new(json!({ "mode": "swap" })).with_on_submitted(Arc::new(
+        move |signature: &str, _: &str| seen.lock().unwrap().push(signature.to_string()),
+    ));
+
+    trade.execute(input, &MockProvider::new()).await.unwrap();
+
+    let actions = action_log.recent(10).await.unwrap();
+    assert_eq!(actions[0].signatures, *reported.lock().unwrap());
+}
+
+#[tokio::test]
+async fn test_slow_balance_reads_do_not_hold_up_the_tool() {
+    // Accepts connections but never answers, like an overloaded RPC node
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let rpc_url = format!("http://{}", listener.local_addr().unwrap());
+    let action_log = ActionLog::new(
+        Arc::new(LongTermMemory::in_memory().unwrap()),
+        Namespace::agent("trader"),
+        &rpc_url,
+    )
+    .with_wallet(&solana_sdk::pubkey::Pubkey::new_unique().to_string())
+    .unwrap();
+    let signatures = vec![Signature::new_unique().to_string()];
+
+    let started = Instant::now();
+    let record = action_log.record("trade", &json!({}), signatures, Ok("done")).await.unwrap();
+
+    assert!(started.elapsed() < Duration::from_secs(10));
+    assert_eq!(record.unwrap().balances, None);
+}
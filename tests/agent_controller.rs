@@ -0,0 +1,209 @@
+use std::sync::Arc;
+
+use serde_json::json;
+use solagent::{
+    agent_controller::{
+        controller::AgentController,
+        session::SessionManager,
+        transcript::{ExecutionBudget, StopReason, TaskError, TranscriptStep},
+    },
+    llm_integration::{
+        mock::MockProvider,
+        prompt::{PromptConfig, PromptTemplate, TASK_TEMPLATE},
+        response::{FinishReason, LlmResponse, Usage},
+        retry::RetryPolicy,
+        usage::UsageBudget,
+        LLMClient, LLMProvider,
+    },
+    memory_system::LongTermMemory,
+    tool_system::{SolanaTool, ToolInput, ToolMetadata, ToolRegistry},
+};
+
+mod common;
+use common::{metadata, FailingProvider};
+
+// Tool returning a fixed balance
+struct BalanceTool;
+
+#[async_trait::async_trait]
+impl SolanaTool for BalanceTool {
+    async fn execute(
+        &self,
+        input: ToolInput,
+        _llm: &dyn LLMProvider,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let pubkey = input.params["pubkey"].as_str().ok_or("Missing pubkey")?;
+        Ok(format!("{} holds 12.5 SOL", pubkey))
+    }
+}
+
+fn balance_metadata() -> ToolMetadata {
+    ToolMetadata { aliases: vec!["balance".to_string()], ..metadata("get_balance") }
+}
+
+async fn controller(mock: Arc<MockProvider>) -> AgentController {
+    let registry = Arc::new(ToolRegistry::new());
+    registry.register(balance_metadata(), Arc::new(BalanceTool)).await;
+    let llm_client = Arc::new(LLMClient::new());
+    llm_client.register_provider("mock", mock).await;
+    AgentController::new(
+        registry,
+        Arc::new(SessionManager::new()),
+        Arc::new(LongTermMemory::in_memory().unwrap()),
+        llm_client,
+    )
+}
+
+#[tokio::test]
+async fn test_tool_result_is_fed_back_to_the_model() {
+    let mock = Arc::new(
+        MockProvider::new()
+            .tool_call("get_balance", json!({"pubkey": "abc123"}))
+            .text("Your balance is 12.5 SOL"),
+    );
+    let controller = controller(mock.clone()).await;
+
+    let transcript =
+        controller.execute_task("alice", "get_balance", json!({"pubkey": "abc123"})).await.unwrap();
+
+    assert_eq!(transcript.stop_reason, StopReason::FinalAnswer);
+    assert_eq!(transcript.final_answer.as_deref(), Some("Your balance is 12.5 SOL"));
+    assert_eq!(transcript.tool_calls(), 1);
+    let requests = mock.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].tool_names(), vec!["get_balance"]);
+    assert_eq!(requests[1].prompt(), "abc123 holds 12.5 SOL");
+}
+
+#[tokio::test]
+async fn test_unknown_tool_error_is_reported_to_the_model() {
+    let mock = Arc::new(
+        MockProvider::new()
+            .tool_call("swap", json!({}))
+            .when("not found", MockProvider::text_response("Cannot swap")),
+    );
+    let controller = controller(mock.clone()).await;
+
+    let transcript = controller.execute_task("alice", "swap tokens", json!({})).await.unwrap();
+
+    assert_eq!(transcript.final_answer.as_deref(), Some("Cannot swap"));
+    assert_eq!(mock.prompts()[1], "Error: Tool 'swap' not found");
+}
+
+#[tokio::test]
+async fn test_sessions_stay_on_one_prompt_variant() {
+    let mut prompts = PromptConfig::new();
+    for version in ["a", "b"] {
+        prompts.register(PromptTemplate {
+            name: TASK_TEMPLATE.to_string(),
+            version: version.to_string(),
+            system: String::new(),
+            examples: vec![],
+            user: "{{task}}".to_string(),
+        });
+    }
+    prompts.set_variants(TASK_TEMPLATE, vec![("a", 1), ("b", 1)]);
+    let mock = Arc::new(MockProvider::new().fallback_text("Done"));
+    let controller = controller(mock).await.with_prompts(Arc::new(prompts));
+
+    let mut versions = vec![];
+    for task in ["check balance", "swap", "stake", "unstake", "claim", "report"] {
+        let transcript = controller.execute_task("alice", task, json!({})).await.unwrap();
+        versions.push(transcript.prompt_version.unwrap());
+    }
+    versions.dedup();
+    assert_eq!(versions.len(), 1);
+}
+
+// Text answer that used `total_tokens` tokens
+fn answer_using(total_tokens: u64) -> LlmResponse {
+    let usage = Usage { prompt_tokens: total_tokens, completion_tokens: 0, total_tokens };
+    LlmResponse::new(Some("Done".to_string()), vec![], FinishReason::Stop, usage)
+}
+
+#[tokio::test]
+async fn test_session_budget_spans_tasks() {
+    let mock = Arc::new(MockProvider::new().fallback_text("Done").when("task", answer_using(500)));
+    let llm_client = Arc::new(LLMClient::new());
+    llm_client.register_provider("mock", mock.clone()).await;
+    let budget = UsageBudget { max_total_tokens: Some(500), max_cost_usd: None };
+    let controller = AgentController::new(
+        Arc::new(ToolRegistry::new()),
+        Arc::new(SessionManager::new().with_usage_budget(budget)),
+        Arc::new(LongTermMemory::in_memory().unwrap()),
+        llm_client,
+    );
+
+    let first = controller.execute_task("alice", "first task", json!({})).await.unwrap();
+    assert_eq!(first.stop_reason, StopReason::FinalAnswer);
+    assert_eq!(first.usage.total_tokens, 500);
+    // The completion was capped at what the session had left after the prompt
+    let max_tokens = mock.requests()[0].max_tokens.unwrap();
+    assert!(max_tokens > 0 && max_tokens < 500);
+
+    let second = controller.execute_task("alice", "second task", json!({})).await.unwrap();
+    assert_eq!(second.stop_reason, StopReason::TokenBudget);
+    assert!(second.steps.is_empty());
+    assert_eq!(mock.call_count(), 1);
+
+    let other = controller.execute_task("bob", "first task", json!({})).await.unwrap();
+    assert_eq!(other.stop_reason, StopReason::FinalAnswer);
+}
+
+#[tokio::test]
+async fn test_calls_are_refused_when_the_prompt_alone_is_over_budget() {
+    let mock = Arc::new(MockProvider::new().fallback_text("Done"));
+    let budget = ExecutionBudget { max_total_tokens: Some(5), ..ExecutionBudget::default() };
+    let controller = controller(mock.clone()).await.with_budget(budget);
+
+    let transcript = controller.execute_task("alice", "check balance", json!({})).await.unwrap();
+
+    assert_eq!(transcript.stop_reason, StopReason::TokenBudget);
+    assert_eq!(mock.call_count(), 0);
+}
+
+#[tokio::test]
+async fn test_failed_tasks_return_their_partial_transcript() {
+    // The script runs out after the tool call, so the second model call fails
+    let mock = Arc::new(MockProvider::new().tool_call("get_balance", json!({"pubkey": "abc123"})));
+    let controller = controller(mock).await;
+
+    let error = controller.execute_task("alice", "get_balance", json!({})).await.unwrap_err();
+
+    let error = error.downcast::<TaskError>().unwrap();
+    assert!(error.error.contains("script exhausted"));
+    assert_eq!(error.transcript.tool_calls(), 1);
+    assert!(matches!(
+        &error.transcript.steps[1],
+        TranscriptStep::Tool { output: Ok(output), .. } if output == "abc123 holds 12.5 SOL"
+    ));
+}
+
+#[tokio::test]
+async fn test_transcript_names_the_fallback_provider_that_answered() {
+    let backup = Arc::new(
+        MockProvider::new()
+            .tool_call("get_balance", json!({"pubkey": "abc123"}))
+            .text("Your balance is 12.5 SOL"),
+    );
+    let registry = Arc::new(ToolRegistry::new());
+    let backup_tool = ToolMetadata { llm_type: "backup".to_string(), ..balance_metadata() };
+    registry.register(backup_tool, Arc::new(BalanceTool)).await;
+    let llm_client = Arc::new(LLMClient::new());
+    llm_client.register_provider("primary", FailingProvider::new(503)).await;
+    llm_client.register_provider("backup", backup.clone()).await;
+    llm_client.set_fallback_chain(vec!["backup".to_string()]).await;
+    llm_client.set_retry_policy(RetryPolicy::none()).await;
+    let controller = AgentController::new(
+        registry,
+        Arc::new(SessionManager::new()),
+        Arc::new(LongTermMemory::in_memory().unwrap()),
+        llm_client,
+    );
+
+    let transcript = controller.execute_task("alice", "check my balance", json!({})).await.unwrap();
+
+    assert_eq!(transcript.provider, "backup");
+    assert_eq!(transcript.final_answer.as_deref(), Some("Your balance is 12.5 SOL"));
+    assert_eq!(backup.requests()[0].tool_names(), vec!["get_balance"]);
+}
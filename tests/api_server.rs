@@ -0,0 +1,93 @@
+use std::sync::Arc;
+
+use actix_web::{http::StatusCode, test, App};
+use serde_json::json;
+use solagent::{
+    agent_controller::{controller::AgentController, session::SessionManager},
+    llm_integration::{mock::MockProvider, LLMClient},
+    memory_system::LongTermMemory,
+    tool_system::ToolRegistry,
+    user_interface::{api::TaskResponse, ApiServer},
+};
+
+async fn controller() -> Arc<AgentController> {
+    let mock = Arc::new(MockProvider::new().fallback_text("Noted"));
+    let llm_client = Arc::new(LLMClient::new());
+    llm_client.register_provider("mock", mock).await;
+    let controller = AgentController::new(
+        Arc::new(ToolRegistry::new()),
+        Arc::new(SessionManager::new()),
+        Arc::new(LongTermMemory::in_memory().unwrap()),
+        llm_client,
+    );
+    Arc::new(controller)
+}
+
+async fn server() -> ApiServer {
+    ApiServer::new(controller().await)
+        .with_token("alice", "alice-token")
+        .with_token("bob", "bob-token")
+}
+
+#[actix_web::test]
+async fn test_requests_need_a_valid_bearer_token() {
+    let app = test::init_service(App::new().configure(server().await.configure())).await;
+
+    let missing = test::TestRequest::post()
+        .uri("/tasks")
+        .set_json(json!({ "task": "hello" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, missing).await.status(), StatusCode::UNAUTHORIZED);
+
+    let wrong = test::TestRequest::post()
+        .uri("/tasks")
+        .insert_header(("Authorization", "Bearer alice-tokem"))
+        .set_json(json!({ "task": "hello" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, wrong).await.status(), StatusCode::UNAUTHORIZED);
+
+    let valid = test::TestRequest::post()
+        .uri("/tasks")
+        .insert_header(("Authorization", "Bearer alice-token"))
+        .set_json(json!({ "task": "hello" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, valid).await.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn test_callers_only_see_their_own_sessions() {
+    let app = test::init_service(App::new().configure(server().await.configure())).await;
+
+    let request = test::TestRequest::post()
+        .uri("/tasks")
+        .insert_header(("Authorization", "Bearer alice-token"))
+        .set_json(json!({ "session_id": "shared", "task": "hello" }))
+        .to_request();
+    let response: TaskResponse = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(response.session_id, "shared");
+
+    let history = |token: &str| {
+        test::TestRequest::get()
+            .uri("/sessions/shared/history")
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .to_request()
+    };
+    assert_eq!(test::call_service(&app, history("alice-token")).await.status(), StatusCode::OK);
+    assert_eq!(
+        test::call_service(&app, history("bob-token")).await.status(),
+        StatusCode::NOT_FOUND
+    );
+
+    let close = test::TestRequest::delete()
+        .uri("/sessions/shared")
+        .insert_header(("Authorization", "Bearer bob-token"))
+        .to_request();
+    assert_eq!(test::call_service(&app, close).await.status(), StatusCode::NOT_FOUND);
+    assert_eq!(test::call_service(&app, history("alice-token")).await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_server_does_not_start_without_a_token() {
+    let api = ApiServer::new(controller().await).with_port(0).with_token("alice", "");
+    assert!(api.run().await.is_err());
+}
@@ -0,0 +1,63 @@
+//! Fixtures shared by the integration tests; each test crate uses only some of them.
+#![allow(dead_code)]
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use serde_json::json;
+use solagent::{
+    llm_integration::{
+        message::ChatMessage, response::LlmResponse, retry::HttpStatusError, LLMProvider,
+    },
+    tool_system::ToolMetadata,
+};
+
+// Fresh SQLite file in the temp directory, unique to this test process
+pub fn database_path(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("solagent-{}-{}.db", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path.to_string_lossy().to_string()
+}
+
+// Metadata for a mock-provider tool taking an object of parameters
+pub fn metadata(name: &str) -> ToolMetadata {
+    ToolMetadata {
+        name: name.to_string(),
+        aliases: vec![],
+        version: "1.0".to_string(),
+        llm_type: "mock".to_string(),
+        schema: json!({"name": name, "parameters": {"type": "object"}}),
+        sends_transactions: false,
+    }
+}
+
+// Provider failing every call with an HTTP status
+pub struct FailingProvider {
+    status: u16,
+    calls: AtomicUsize,
+}
+
+impl FailingProvider {
+    pub fn new(status: u16) -> Arc<Self> {
+        Arc::new(FailingProvider { status, calls: AtomicUsize::new(0) })
+    }
+
+    pub fn call_count(&self) -> usize {
+        self.calls.load(Ordering::SeqCst)
+    }
+}
+
+#[async_trait::async_trait]
+impl LLMProvider for FailingProvider {
+    async fn chat(
+        &self,
+        _messages: &[ChatMessage],
+        _tools: Vec<ToolMetadata>,
+    ) -> Result<LlmResponse, Box<dyn std::error::Error>> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        let body = "unavailable".to_string();
+        Err(Box::new(HttpStatusError { status: self.status, retry_after: None, body }))
+    }
+}
@@ -0,0 +1,120 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde_json::{json, Value};
+use solagent::{
+    llm_integration::LLMProvider,
+    tool_system::{SolanaTool, ToolInput, ToolRegistry},
+    workflow_engine::{
+        checkpoint::CheckpointStore,
+        dag::{WorkflowEngine, WorkflowNode},
+        events::{ChainEvent, EventSource, EventStore, EventTrigger, EventTriggers},
+    },
+};
+use tokio::sync::mpsc::UnboundedSender;
+
+mod common;
+use common::metadata;
+
+// Source sending a fixed list of events, each after a delay in milliseconds
+struct ScriptSource {
+    events: Vec<(u64, ChainEvent)>,
+}
+
+#[async_trait::async_trait]
+impl EventSource for ScriptSource {
+    async fn run(&self, events: UnboundedSender<ChainEvent>) -> Result<(), String> {
+        for (delay, event) in &self.events {
+            tokio::time::sleep(Duration::from_millis(*delay)).await;
+            events.send(event.clone()).map_err(|e| e.to_string())?;
+        }
+        futures::future::pending().await
+    }
+}
+
+// Tool recording the arguments of every call
+#[derive(Default)]
+struct RecordTool {
+    calls: Mutex<Vec<Value>>,
+}
+
+#[async_trait::async_trait]
+impl SolanaTool for RecordTool {
+    async fn execute(
+        &self,
+        input: ToolInput,
+        _llm: &dyn LLMProvider,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        self.calls.lock().unwrap().push(input.params);
+        Ok("recorded".to_string())
+    }
+}
+
+fn event(key: &str, mint: &str) -> ChainEvent {
+    ChainEvent { key: key.to_string(), payload: json!({"mint": mint}) }
+}
+
+#[tokio::test]
+async fn test_events_are_deduplicated_and_debounced() {
+    let registry = Arc::new(ToolRegistry::new());
+    let record = Arc::new(RecordTool::default());
+    registry.register(metadata("record"), record.clone()).await;
+    let mut workflow = WorkflowEngine::new(registry);
+    let inputs = json!({"mint": "${inputs.payload.mint}", "events": "${inputs.events}"});
+    workflow.add_node(WorkflowNode::new("record", "record", inputs)).unwrap();
+
+    let source = ScriptSource {
+        events: vec![
+            (0, event("a", "MintA")),
+            (10, event("a", "MintA")),
+            (10, event("b", "MintB")),
+            (200, event("c", "MintC")),
+        ],
+    };
+    let triggers = EventTriggers::new();
+    let trigger = EventTrigger::new("transfers", Arc::new(source), Arc::new(workflow))
+        .with_debounce(Duration::from_millis(50));
+    triggers.add(trigger).unwrap();
+
+    tokio::time::sleep(Duration::from_millis(400)).await;
+
+    let status = triggers.status("transfers").unwrap();
+    assert_eq!((status.received, status.duplicates, status.runs), (4, 1, 2));
+    assert_eq!(status.last_key, Some("c".to_string()));
+    assert_eq!(
+        *record.calls.lock().unwrap(),
+        vec![json!({"mint": "MintB", "events": 2}), json!({"mint": "MintC", "events": 1})]
+    );
+}
+
+#[tokio::test]
+async fn test_redelivered_events_do_not_run_again() {
+    let registry = Arc::new(ToolRegistry::new());
+    let record = Arc::new(RecordTool::default());
+    registry.register(metadata("record"), record.clone()).await;
+    let mut workflow = WorkflowEngine::new(registry)
+        .with_checkpoints(Arc::new(CheckpointStore::in_memory().unwrap()));
+    let inputs = json!({"mint": "${inputs.payload.mint}"});
+    workflow.add_node(WorkflowNode::new("record", "record", inputs)).unwrap();
+    let workflow = Arc::new(workflow);
+    let store = Arc::new(EventStore::in_memory().unwrap());
+
+    // The source delivers the same event after every restart, first with the key store...
+    for _ in 0..2 {
+        let triggers = EventTriggers::new().with_store(store.clone());
+        let source = ScriptSource { events: vec![(0, event("a", "MintA"))] };
+        triggers.add(EventTrigger::new("transfers", Arc::new(source), workflow.clone())).unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    // ...then without it, when only the checkpoint of the run that succeeded is left
+    let triggers = EventTriggers::new();
+    let source = ScriptSource { events: vec![(0, event("a", "MintA"))] };
+    triggers.add(EventTrigger::new("transfers", Arc::new(source), workflow.clone())).unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let status = triggers.status("transfers").unwrap();
+    assert_eq!((status.received, status.duplicates, status.runs), (1, 1, 0));
+    assert_eq!(*record.calls.lock().unwrap(), vec![json!({"mint": "MintA"})]);
+}
@@ -0,0 +1,150 @@
+use std::sync::Arc;
+
+use serde_json::json;
+use solagent::{
+    llm_integration::{mock::MockProvider, LLMClient, LLMProvider},
+    planning_reasoning::{
+        hybrid::{DecisionOutcome, DecisionSource, HybridPlanner},
+        llm_planner::{LLMPlanner, PlanStatus},
+        rule_engine::{Rule, RuleAction, RuleEngine, RuleState, RuleStatus},
+    },
+    tool_system::{SolanaTool, ToolInput, ToolMetadata, ToolRegistry},
+};
+
+mod common;
+
+// Tool echoing the swap it would send
+struct SwapTool;
+
+#[async_trait::async_trait]
+impl SolanaTool for SwapTool {
+    async fn execute(
+        &self,
+        input: ToolInput,
+        _llm: &dyn LLMProvider,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let amount = input.params["amount"].as_f64().ok_or("Missing amount")?;
+        Ok(format!("swapped {}", amount))
+    }
+}
+
+// Hybrid planner over the buy and outlier rules, plus `extra` rules
+async fn hybrid(mock: Arc<MockProvider>, extra: Vec<Rule>) -> HybridPlanner {
+    let registry = Arc::new(ToolRegistry::new());
+    let metadata = ToolMetadata {
+        schema: json!({
+            "name": "swap",
+            "parameters": {
+                "type": "object",
+                "properties": {"amount": {"type": "number"}},
+                "required": ["amount"],
+            },
+        }),
+        ..common::metadata("swap")
+    };
+    registry.register(metadata, Arc::new(SwapTool)).await;
+
+    let mut rules = RuleEngine::new(registry.clone());
+    let buy = Rule {
+        id: "buy_dip".to_string(),
+        condition: "price < 100".to_string(),
+        action: RuleAction::Tool { tool: "swap".to_string(), arguments: json!({"amount": 1}) },
+        priority: 0,
+        cooldown_secs: 0,
+    };
+    rules.add_rule(buy).unwrap();
+    let outlier = Rule {
+        id: "price_outlier".to_string(),
+        condition: "price > 1000".to_string(),
+        action: RuleAction::Escalate { reason: "SOL quoted at ${price}".to_string() },
+        priority: 10,
+        cooldown_secs: 0,
+    };
+    rules.add_rule(outlier).unwrap();
+    for rule in extra {
+        rules.add_rule(rule).unwrap();
+    }
+
+    let llm_client = Arc::new(LLMClient::new());
+    llm_client.register_provider("mock", mock).await;
+    let planner = LLMPlanner::new(llm_client, registry);
+    HybridPlanner::new(Arc::new(rules), Arc::new(planner))
+}
+
+fn state(price: f64) -> RuleState {
+    RuleState::from([("price".to_string(), json!(price))])
+}
+
+fn plan() -> String {
+    json!({"steps": [{"id": "s", "tool": "swap", "arguments": {"amount": 2}}]}).to_string()
+}
+
+#[tokio::test]
+async fn test_matching_rule_decides_without_llm() {
+    let mock = Arc::new(MockProvider::new());
+    let hybrid = hybrid(mock.clone(), vec![]).await;
+
+    let decision = hybrid.decide("Trade SOL", &state(90.0)).await.unwrap();
+
+    assert_eq!(decision.source, DecisionSource::Rule { rule_id: "buy_dip".to_string() });
+    let DecisionOutcome::Action(outcome) = decision.outcome else {
+        panic!("expected a rule action");
+    };
+    assert_eq!(outcome.status, RuleStatus::Fired { output: Ok("swapped 1".to_string()) });
+    assert!(mock.prompts().is_empty());
+}
+
+#[tokio::test]
+async fn test_llm_decides_when_no_rule_matches_or_a_rule_escalates() {
+    let mock = Arc::new(MockProvider::new().text(&plan()).text(&plan()));
+    let hybrid = hybrid(mock.clone(), vec![]).await;
+
+    let decision = hybrid.decide("Trade SOL", &state(200.0)).await.unwrap();
+    assert_eq!(decision.source, DecisionSource::Llm { escalated_by: None });
+    let DecisionOutcome::Plan(execution) = decision.outcome else {
+        panic!("expected a plan");
+    };
+    assert_eq!(execution.status, PlanStatus::Completed);
+
+    let decision = hybrid.decide("Trade SOL", &state(5000.0)).await.unwrap();
+    let escalated_by = Some("price_outlier".to_string());
+    assert_eq!(decision.source, DecisionSource::Llm { escalated_by });
+    let reason = "SOL quoted at 5000.0".to_string();
+    assert_eq!(decision.rules[0].status, RuleStatus::Escalated { reason });
+    assert!(mock.prompts()[1].contains("Escalated by rule 'price_outlier': SOL quoted at 5000"));
+}
+
+#[tokio::test]
+async fn test_higher_rule_in_error_or_cooldown_stops_the_decision() {
+    let mock = Arc::new(MockProvider::new().text(&plan()));
+    let stop_loss = Rule {
+        id: "stop_loss".to_string(),
+        condition: "drawdown > 0.2".to_string(),
+        action: RuleAction::Tool { tool: "swap".to_string(), arguments: json!({"amount": -1}) },
+        priority: 20,
+        cooldown_secs: 3600,
+    };
+    let hybrid = hybrid(mock.clone(), vec![stop_loss]).await;
+
+    // Without the drawdown the stop loss cannot be evaluated, so the dip is not bought
+    let decision = hybrid.decide("Trade SOL", &state(90.0)).await.unwrap();
+    assert_eq!(decision.source, DecisionSource::Rule { rule_id: "stop_loss".to_string() });
+    let DecisionOutcome::Stopped { reason } = decision.outcome else {
+        panic!("expected the decision to stop");
+    };
+    assert!(reason.starts_with("rule 'stop_loss' could not be evaluated"));
+    assert!(decision.rules.iter().all(|o| !matches!(o.status, RuleStatus::Fired { .. })));
+
+    let mut state = state(90.0);
+    state.insert("drawdown".to_string(), json!(0.3));
+    let decision = hybrid.decide("Trade SOL", &state).await.unwrap();
+    assert!(matches!(decision.outcome, DecisionOutcome::Action(_)));
+
+    // While the stop loss cools down, neither the dip rule nor the planner acts
+    let decision = hybrid.decide("Trade SOL", &state).await.unwrap();
+    let DecisionOutcome::Stopped { reason } = decision.outcome else {
+        panic!("expected the decision to stop");
+    };
+    assert!(reason.contains("cooling down"));
+    assert!(mock.prompts().is_empty());
+}
@@ -0,0 +1,140 @@
+use std::sync::Arc;
+
+use serde_json::json;
+use solagent::{
+    llm_integration::{
+        message::ChatMessage,
+        mock::MockProvider,
+        openai_compatible::OpenAICompatibleConfig,
+        retry::RetryPolicy,
+        LLMClient, ProviderConfig,
+    },
+    tool_system::ToolMetadata,
+};
+
+mod common;
+use common::{metadata, FailingProvider};
+
+// Client trying `primary`, then `backup`, retrying each failure twice without waiting
+async fn client(primary: Arc<FailingProvider>, backup: Arc<MockProvider>) -> LLMClient {
+    let client = LLMClient::new();
+    client.register_provider("primary", primary).await;
+    client.register_provider("backup", backup).await;
+    client.set_fallback_chain(vec!["backup".to_string()]).await;
+    let policy = RetryPolicy { max_retries: 2, initial_backoff_ms: 0, max_backoff_ms: 0 };
+    client.set_retry_policy(policy).await;
+    client
+}
+
+fn tool(name: &str, llm_type: &str) -> ToolMetadata {
+    ToolMetadata { llm_type: llm_type.to_string(), ..metadata(name) }
+}
+
+#[tokio::test]
+async fn test_transient_failures_fall_back_with_the_fallbacks_tools() {
+    let primary = FailingProvider::new(503);
+    let backup = Arc::new(MockProvider::new().fallback_text("Done"));
+    let client = client(primary.clone(), backup.clone()).await;
+    let tools = vec![tool("get_balance", "primary"), tool("get_price", "backup")];
+
+    let (response, answered) = client
+        .chat_metered("primary", &[ChatMessage::user("price?")], tools, None)
+        .await
+        .unwrap();
+
+    assert_eq!(response.text(), Some("Done"));
+    assert_eq!(answered, "backup");
+    assert_eq!(primary.call_count(), 3);
+    assert_eq!(backup.requests()[0].tool_names(), vec!["get_price"]);
+}
+
+#[tokio::test]
+async fn test_requested_provider_is_offered_every_tool() {
+    let backup = Arc::new(MockProvider::new().fallback_text("Done"));
+    let client = client(FailingProvider::new(503), backup.clone()).await;
+    let tools = vec![tool("get_balance", "primary"), tool("get_price", "openai")];
+
+    client.chat("backup", &[ChatMessage::user("price?")], tools).await.unwrap();
+
+    assert_eq!(backup.requests()[0].tool_names(), vec!["get_balance", "get_price"]);
+}
+
+#[tokio::test]
+async fn test_rejected_requests_do_not_fall_back() {
+    let primary = FailingProvider::new(400);
+    let backup = Arc::new(MockProvider::new().fallback_text("Done"));
+    let client = client(primary.clone(), backup.clone()).await;
+
+    let err = client.chat("primary", &[ChatMessage::user("price?")], vec![]).await.unwrap_err();
+
+    assert!(err.to_string().contains("HTTP 400"));
+    assert_eq!(primary.call_count(), 1);
+    assert_eq!(backup.call_count(), 0);
+}
+
+fn openai_config() -> ProviderConfig {
+    let config = OpenAICompatibleConfig::new("https://openrouter.ai/api/v1", "gpt-4o")
+        .with_api_key("sk-secret".to_string())
+        .with_header("Authorization", "Bearer header-secret")
+        .with_header("HTTP-Referer", "https://example.com")
+        .with_max_tokens(512)
+        .with_timeout_secs(30);
+    ProviderConfig::OpenAICompatible { name: "openrouter".to_string(), config }
+}
+
+fn gemini_config() -> ProviderConfig {
+    ProviderConfig::Gemini {
+        name: "gemini".to_string(),
+        model: "gemini-1.5-pro".to_string(),
+        api_key: "gemini-secret".to_string(),
+        timeout_secs: Some(60),
+    }
+}
+
+#[test]
+fn test_provider_configs_round_trip() {
+    // Compared as values, as headers serialize in no particular order
+    for config in [openai_config(), gemini_config()] {
+        let value = serde_json::to_value(&config).unwrap();
+        let json = serde_json::to_string(&config).unwrap();
+        let decoded: ProviderConfig = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_value(&decoded).unwrap(), value);
+        let toml = toml::to_string(&config).unwrap();
+        let decoded: ProviderConfig = toml::from_str(&toml).unwrap();
+        assert_eq!(serde_json::to_value(&decoded).unwrap(), value);
+    }
+}
+
+#[test]
+fn test_base_url_is_trimmed_when_read() {
+    let config: OpenAICompatibleConfig = serde_json::from_value(json!({
+        "base_url": " http://localhost:11434/v1/ ",
+        "model": "llama3",
+    }))
+    .unwrap();
+    assert_eq!(config.base_url, "http://localhost:11434/v1");
+}
+
+#[test]
+fn test_debug_output_hides_credentials() {
+    let debug = format!("{:?}", openai_config());
+    assert!(!debug.contains("sk-secret"));
+    assert!(!debug.contains("header-secret"));
+    assert!(debug.contains("https://example.com"));
+    assert!(debug.contains("gpt-4o"));
+
+    assert!(!format!("{:?}", gemini_config()).contains("gemini-secret"));
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_deprecated_providers_build_openai_compatible_ones() {
+    use solagent::llm_integration::llm_client::{Grok3Provider, OpenAIProvider};
+
+    let grok3 = Grok3Provider::new("xai-key".to_string());
+    assert_eq!(grok3.config().base_url, "https://api.x.ai/v1");
+    assert_eq!(grok3.config().model, "grok-3");
+    assert_eq!(grok3.config().api_key.as_deref(), Some("xai-key"));
+    let openai = OpenAIProvider::new("sk-key".to_string());
+    assert_eq!(openai.config().base_url, "https://api.openai.com/v1");
+}
@@ -0,0 +1,128 @@
+use std::sync::Arc;
+
+use serde_json::json;
+use solagent::{
+    llm_integration::{mock::MockProvider, LLMClient, LLMProvider},
+    planning_reasoning::llm_planner::{LLMPlanner, Plan, PlanStatus},
+    tool_system::{SolanaTool, ToolInput, ToolMetadata, ToolRegistry},
+};
+
+mod common;
+
+// Tool returning a fixed quote
+struct QuoteTool;
+
+#[async_trait::async_trait]
+impl SolanaTool for QuoteTool {
+    async fn execute(
+        &self,
+        input: ToolInput,
+        _llm: &dyn LLMProvider,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let amount = input.params["amount"].as_f64().ok_or("Missing amount")?;
+        Ok(json!({"out_amount": amount * 150.0}).to_string())
+    }
+}
+
+// Tool echoing the swap it would send, failing on large swaps
+struct SwapTool;
+
+#[async_trait::async_trait]
+impl SolanaTool for SwapTool {
+    async fn execute(
+        &self,
+        input: ToolInput,
+        _llm: &dyn LLMProvider,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let min_out = input.params["min_out"].as_f64().ok_or("Missing min_out")?;
+        if min_out > 1000.0 {
+            return Err("Slippage tolerance exceeded".into());
+        }
+        Ok(format!("swapped with min_out {}", min_out))
+    }
+}
+
+fn metadata(name: &str, properties: serde_json::Value) -> ToolMetadata {
+    let required: Vec<String> = properties.as_object().unwrap().keys().cloned().collect();
+    ToolMetadata {
+        schema: json!({
+            "name": name,
+            "parameters": {
+                "type": "object",
+                "properties": properties,
+                "required": required,
+            },
+        }),
+        ..common::metadata(name)
+    }
+}
+
+async fn planner(mock: Arc<MockProvider>) -> LLMPlanner {
+    let registry = Arc::new(ToolRegistry::new());
+    let quote = metadata("quote", json!({"amount": {"type": "number"}}));
+    registry.register(quote, Arc::new(QuoteTool)).await;
+    let swap = metadata("swap", json!({"min_out": {"type": "number"}}));
+    registry.register(swap, Arc::new(SwapTool)).await;
+    let llm_client = Arc::new(LLMClient::new());
+    llm_client.register_provider("mock", mock).await;
+    LLMPlanner::new(llm_client, registry)
+}
+
+fn swap_plan(quote_amount: serde_json::Value) -> String {
+    json!({
+        "steps": [
+            {"id": "q", "tool": "quote", "arguments": {"amount": quote_amount}},
+            {
+                "id": "s",
+                "tool": "swap",
+                "arguments": {"min_out": "${steps.q.output.out_amount}"},
+                "depends_on": ["q"],
+                "expected_outcome": "swap sent",
+            },
+        ],
+    })
+    .to_string()
+}
+
+#[tokio::test]
+async fn test_invalid_plan_is_re_asked_then_executed() {
+    let mock =
+        Arc::new(MockProvider::new().text(&swap_plan(json!("ten"))).text(&swap_plan(json!(2))));
+    let planner = planner(mock.clone()).await;
+
+    let plan = planner.plan("Swap 2 SOL to USDC").await.unwrap();
+    assert_eq!(plan.goal, "Swap 2 SOL to USDC");
+    assert!(mock.prompts()[1].contains("step 'q': argument 'amount'"));
+
+    let execution = planner.execute(&plan).await.unwrap();
+    assert_eq!(execution.status, PlanStatus::Completed);
+    assert_eq!(execution.results[1].arguments, json!({"min_out": 300.0}));
+    assert_eq!(execution.results[1].output, Ok("swapped with min_out 300".to_string()));
+}
+
+#[tokio::test]
+async fn test_failed_step_is_replanned() {
+    let revision = json!({
+        "reason": "Slippage on the full quote; accept a lower minimum output",
+        "steps": [{
+            "id": "s2",
+            "tool": "swap",
+            "arguments": {"min_out": 900},
+            "depends_on": ["q"],
+        }],
+    });
+    let mock = Arc::new(MockProvider::new().text(&revision.to_string()));
+    let planner = planner(mock.clone()).await.with_max_replans(1);
+    let plan = Plan::from_json(&swap_plan(json!(10))).unwrap();
+
+    let execution = planner.execute(&plan).await.unwrap();
+
+    assert_eq!(execution.status, PlanStatus::Completed);
+    assert_eq!(execution.replans.len(), 1);
+    assert_eq!(execution.replans[0].failed_step, "s");
+    assert_eq!(execution.replans[0].error, "Slippage tolerance exceeded");
+    assert!(execution.replans[0].reason.starts_with("Slippage"));
+    let ids: Vec<&str> = execution.plan.steps.iter().map(|s| s.id.as_str()).collect();
+    assert_eq!(ids, vec!["q", "s2"]);
+    assert!(mock.prompts()[0].contains("Slippage tolerance exceeded"));
+}
@@ -0,0 +1,62 @@
+use std::time::Duration;
+
+use solagent::memory_system::long_term::{LongTermMemory, MemoryError, Namespace};
+
+mod common;
+use common::database_path;
+
+#[tokio::test]
+async fn test_namespaces_prefixes_and_reopening() {
+    let path = database_path("memory");
+    let memory = LongTermMemory::open(&path).unwrap();
+    let alice = Namespace::user("trader", "alice");
+    let bob = Namespace::user("trader", "bob");
+
+    memory.store(&alice, "swap:1", "10 SOL -> USDC").await.unwrap();
+    memory.store(&alice, "swap:2", "5 SOL -> BONK").await.unwrap();
+    memory.store(&alice, "stake:1", "20 SOL").await.unwrap();
+    memory.store(&bob, "swap:1", "1 SOL -> USDC").await.unwrap();
+
+    let swaps = memory.list(&alice, "swap:").await.unwrap();
+    let keys: Vec<&str> = swaps.iter().map(|record| record.key.as_str()).collect();
+    assert_eq!(keys, vec!["swap:1", "swap:2"]);
+    assert_eq!(memory.retrieve(&bob, "swap:1").await.unwrap().as_deref(), Some("1 SOL -> USDC"));
+    assert_eq!(memory.retrieve(&bob, "swap:2").await.unwrap(), None);
+
+    memory.store(&alice, "swap:1", "12 SOL -> USDC").await.unwrap();
+    let record = memory.record(&alice, "swap:1").await.unwrap().unwrap();
+    assert_eq!(record.value, "12 SOL -> USDC");
+    assert!(record.updated_at >= record.created_at);
+
+    assert_eq!(memory.delete_prefix(&alice, "swap:").await.unwrap(), 2);
+    assert!(memory.delete(&alice, "stake:1").await.unwrap());
+    assert!(memory.list(&alice, "").await.unwrap().is_empty());
+    drop(memory);
+
+    // Reopening keeps the data and does not run the migrations again
+    let memory = LongTermMemory::open(&path).unwrap();
+    assert_eq!(memory.list(&bob, "").await.unwrap().len(), 1);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn test_expired_memories_are_forgotten() {
+    let memory = LongTermMemory::in_memory().unwrap();
+    let agent = Namespace::agent("trader");
+    let ttl = Duration::from_millis(50);
+    memory.store_with_ttl(&agent, "quote", "1 SOL = 150 USDC", ttl).await.unwrap();
+    memory.store(&agent, "wallet", "abc123").await.unwrap();
+    assert!(memory.retrieve(&agent, "quote").await.unwrap().is_some());
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(memory.retrieve(&agent, "quote").await.unwrap(), None);
+    assert_eq!(memory.list(&agent, "").await.unwrap().len(), 1);
+    assert_eq!(memory.purge_expired().await.unwrap(), 1);
+}
+
+#[test]
+fn test_open_failure_is_an_error() {
+    let path = std::env::temp_dir().join("solagent-missing-dir").join("nested").join("memory.db");
+    let result = LongTermMemory::open(&path.to_string_lossy());
+    assert!(matches!(result, Err(MemoryError::Open { .. })));
+}
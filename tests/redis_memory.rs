@@ -0,0 +1,106 @@
+// Runs against the Redis server at `SOLAGENT_TEST_REDIS_URL`, e.g. a local `redis-server` at
+// `redis://127.0.0.1:6379/`, with `cargo test --test redis_memory -- --ignored`.
+
+use std::{sync::Arc, time::Duration};
+
+use deadpool_redis::Pool;
+use solagent::{
+    agent_controller::session::SessionManager,
+    memory_system::{
+        long_term::{LongTermMemory, Namespace},
+        redis_backend::{redis_pool, RedisBackend},
+        short_term::ShortTermMemory,
+    },
+};
+
+fn pool() -> Pool {
+    let url = std::env::var("SOLAGENT_TEST_REDIS_URL")
+        .expect("SOLAGENT_TEST_REDIS_URL must point at a Redis server");
+    redis_pool(&url, 4).unwrap()
+}
+
+fn prefix(name: &str) -> String {
+    format!("solagent-test-{}-{}", name, std::process::id())
+}
+
+fn long_term(pool: Pool, prefix: &str) -> LongTermMemory {
+    LongTermMemory::with_backend(Box::new(RedisBackend::new(pool).with_prefix(prefix)))
+}
+
+#[tokio::test]
+#[ignore = "needs a Redis server at SOLAGENT_TEST_REDIS_URL"]
+async fn test_replicas_share_long_term_memory() {
+    let pool = pool();
+    let prefix = prefix("long-term");
+    let first = long_term(pool.clone(), &prefix);
+    let second = long_term(pool, &prefix);
+    let alice = Namespace::user("trader", "alice");
+
+    first.store(&alice, "swap:1", "10 SOL -> USDC").await.unwrap();
+    first.store(&alice, "swap:2", "5 SOL -> BONK").await.unwrap();
+    first.store(&alice, "stake:1", "20 SOL").await.unwrap();
+    let ttl = Duration::from_millis(50);
+    first.store_with_ttl(&alice, "swap:3", "quote", ttl).await.unwrap();
+
+    let keys: Vec<String> =
+        second.list(&alice, "swap:").await.unwrap().into_iter().map(|r| r.key).collect();
+    assert_eq!(keys, vec!["swap:1", "swap:2", "swap:3"]);
+    let other = Namespace::user("trader", "bob");
+    assert_eq!(second.retrieve(&other, "swap:1").await.unwrap(), None);
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(second.retrieve(&alice, "swap:3").await.unwrap(), None);
+    assert_eq!(second.list(&alice, "swap:").await.unwrap().len(), 2);
+
+    assert_eq!(second.delete_prefix(&alice, "swap:").await.unwrap(), 2);
+    assert!(first.delete(&alice, "stake:1").await.unwrap());
+    assert!(first.list(&alice, "").await.unwrap().is_empty());
+}
+
+#[tokio::test]
+#[ignore = "needs a Redis server at SOLAGENT_TEST_REDIS_URL"]
+async fn test_replicas_share_short_term_memory() {
+    let pool = pool();
+    let prefix = prefix("short-term");
+    let memory = |pool: Pool| {
+        let ttl = Some(Duration::from_secs(60));
+        ShortTermMemory::redis(pool, &Namespace::agent("trader"), ttl).with_prefix(&prefix)
+    };
+    let (first, second) = (memory(pool.clone()), memory(pool));
+
+    first.insert("get_balance", "12.5 SOL").await.unwrap();
+    first.insert("get_price", "150 USDC").await.unwrap();
+    assert_eq!(
+        second.entries().await.unwrap(),
+        vec![
+            ("get_balance".to_string(), "12.5 SOL".to_string()),
+            ("get_price".to_string(), "150 USDC".to_string()),
+        ]
+    );
+    assert!(second.remove("get_price").await.unwrap());
+    assert_eq!(first.get("get_price").await.unwrap(), None);
+    first.clear().await.unwrap();
+    assert!(second.entries().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+#[ignore = "needs a Redis server at SOLAGENT_TEST_REDIS_URL"]
+async fn test_replicas_share_session_memory() {
+    let pool = pool();
+    let replica = || {
+        let pool = pool.clone();
+        SessionManager::new().with_memory(Arc::new(move |id| {
+            ShortTermMemory::redis(pool.clone(), &Namespace::session(id), None)
+        }))
+    };
+    let (first, second) = (replica(), replica());
+    let id = prefix("session");
+
+    first.open(&id).await.unwrap().remember("get_balance", "12.5 SOL").await.unwrap();
+    let session = second.open(&id).await.unwrap();
+    assert_eq!(session.memory().get("get_balance").await.unwrap().as_deref(), Some("12.5 SOL"));
+
+    // Closing the session on one replica clears the memory the other one sees
+    assert!(first.close(&id).await.unwrap());
+    assert!(session.memory().entries().await.unwrap().is_empty());
+}
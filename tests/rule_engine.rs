@@ -0,0 +1,214 @@
+use std::sync::{Arc, Mutex};
+
+use serde_json::{json, Value};
+use solagent::{
+    llm_integration::LLMProvider,
+    planning_reasoning::rule_engine::{
+        FunctionBinding, Rule, RuleAction, RuleEngine, RuleError, RuleState, RuleStatus,
+    },
+    tool_system::{SolanaTool, ToolInput, ToolRegistry},
+};
+
+mod common;
+use common::metadata;
+
+// Tool answering with a fixed value per mint
+struct PriceTool;
+
+#[async_trait::async_trait]
+impl SolanaTool for PriceTool {
+    async fn execute(
+        &self,
+        input: ToolInput,
+        _llm: &dyn LLMProvider,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        match input.params["mint"].as_str() {
+            Some("SOL") => Ok(json!({"price": 110}).to_string()),
+            Some("USDC") => Ok("1000".to_string()),
+            _ => Err("Unknown mint".into()),
+        }
+    }
+}
+
+// Tool recording the arguments of every call
+struct RecordingTool(Arc<Mutex<Vec<Value>>>);
+
+#[async_trait::async_trait]
+impl SolanaTool for RecordingTool {
+    async fn execute(
+        &self,
+        input: ToolInput,
+        _llm: &dyn LLMProvider,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        self.0.lock().unwrap().push(input.params);
+        Ok("sent".to_string())
+    }
+}
+
+// Recording tool taking a while to send, so other callers run before it returns
+struct SlowTool(Arc<Mutex<Vec<Value>>>);
+
+#[async_trait::async_trait]
+impl SolanaTool for SlowTool {
+    async fn execute(
+        &self,
+        input: ToolInput,
+        _llm: &dyn LLMProvider,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        self.0.lock().unwrap().push(input.params);
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        Ok("sent".to_string())
+    }
+}
+
+fn rule(id: &str, condition: &str, priority: i32, arguments: Value) -> Rule {
+    Rule {
+        id: id.to_string(),
+        condition: condition.to_string(),
+        action: RuleAction::Tool { tool: "swap".to_string(), arguments },
+        priority,
+        cooldown_secs: 60,
+    }
+}
+
+async fn engine() -> (RuleEngine, Arc<Mutex<Vec<Value>>>) {
+    let registry = Arc::new(ToolRegistry::new());
+    let calls = Arc::new(Mutex::new(vec![]));
+    registry.register(metadata("get_price"), Arc::new(PriceTool)).await;
+    registry.register(metadata("swap"), Arc::new(RecordingTool(calls.clone()))).await;
+
+    let mut engine = RuleEngine::new(registry);
+    let price = FunctionBinding {
+        tool: "get_price".to_string(),
+        params: vec!["mint".to_string()],
+        output_path: Some("price".to_string()),
+    };
+    engine.register_function("price", price);
+    let balance = FunctionBinding {
+        tool: "get_price".to_string(),
+        params: vec!["mint".to_string()],
+        output_path: None,
+    };
+    engine.register_function("balance", balance);
+
+    let buy = rule(
+        "buy_dip",
+        r#"price("SOL") < 120 && balance("USDC") > 500"#,
+        10,
+        json!({"amount": "${balance(\"USDC\") / 10}", "memo": "buy at ${price(\"SOL\")}"}),
+    );
+    engine.add_rule(buy).unwrap();
+    let hedge = rule("hedge", "position.size > 5", 20, json!({"amount": "${position.size}"}));
+    engine.add_rule(hedge).unwrap();
+    let sell = rule("take_profit", r#"price("SOL") > 150"#, 30, json!({}));
+    engine.add_rule(sell).unwrap();
+    (engine, calls)
+}
+
+#[tokio::test]
+async fn test_rules_fire_by_priority_then_cool_down() {
+    let (engine, calls) = engine().await;
+    let state = RuleState::from([("position".to_string(), json!({"size": 8}))]);
+
+    let outcomes = engine.run(&state).await;
+    let ids: Vec<&str> = outcomes.iter().map(|o| o.rule_id.as_str()).collect();
+    assert_eq!(ids, vec!["take_profit", "hedge", "buy_dip"]);
+    assert_eq!(outcomes[0].status, RuleStatus::NotMatched);
+    assert_eq!(outcomes[1].status, RuleStatus::Fired { output: Ok("sent".to_string()) });
+    assert_eq!(
+        *calls.lock().unwrap(),
+        vec![json!({"amount": 8}), json!({"amount": 100.0, "memo": "buy at 110"})]
+    );
+
+    let outcomes = engine.run(&state).await;
+    assert!(matches!(outcomes[1].status, RuleStatus::CoolingDown { .. }));
+    assert!(matches!(outcomes[2].status, RuleStatus::CoolingDown { .. }));
+    assert_eq!(calls.lock().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_dry_run_takes_no_action() {
+    let (mut engine, calls) = engine().await;
+
+    let outcomes = engine.dry_run(&RuleState::new()).await;
+    assert!(matches!(outcomes[1].status, RuleStatus::Error { .. }));
+    assert_eq!(outcomes[2].status, RuleStatus::Matched);
+    let Some(RuleAction::Tool { arguments, .. }) = outcomes[2].action.clone() else {
+        panic!("expected a tool action");
+    };
+    assert_eq!(arguments["amount"], json!(100.0));
+    assert!(calls.lock().unwrap().is_empty());
+
+    let first = engine.run_first(&RuleState::new()).await.unwrap();
+    assert_eq!(first.rule_id, "buy_dip");
+    assert_eq!(calls.lock().unwrap().len(), 1);
+
+    let broken = rule("broken", "price(\"SOL\") <", 0, json!({}));
+    assert!(matches!(engine.add_rule(broken), Err(RuleError::Parse { .. })));
+}
+
+#[tokio::test]
+async fn test_bound_functions_are_only_called_when_reached() {
+    let registry = Arc::new(ToolRegistry::new());
+    let quotes = Arc::new(Mutex::new(vec![]));
+    registry.register(metadata("get_quote"), Arc::new(RecordingTool(quotes.clone()))).await;
+    let mut engine = RuleEngine::new(registry);
+    let quote = FunctionBinding {
+        tool: "get_quote".to_string(),
+        params: vec!["mint".to_string()],
+        output_path: None,
+    };
+    engine.register_function("quote", quote);
+    let small = rule("small", r#"position.size > 100 && quote("SOL") == "sent""#, 0, json!({}));
+    engine.add_rule(small).unwrap();
+    let large = rule("large", r#"position.size > 5 || quote("BONK") == "sent""#, 0, json!({}));
+    engine.add_rule(large).unwrap();
+    let state = RuleState::from([("position".to_string(), json!({"size": 8}))]);
+
+    let outcomes = engine.dry_run(&state).await;
+    assert_eq!(outcomes[0].status, RuleStatus::NotMatched);
+    assert_eq!(outcomes[1].status, RuleStatus::Matched);
+    assert!(quotes.lock().unwrap().is_empty());
+
+    // Reached once the first operand no longer decides the condition
+    let state = RuleState::from([("position".to_string(), json!({"size": 200}))]);
+    let outcomes = engine.dry_run(&state).await;
+    assert_eq!(outcomes[0].status, RuleStatus::Matched);
+    assert_eq!(*quotes.lock().unwrap(), vec![json!({"mint": "SOL"})]);
+}
+
+#[tokio::test]
+async fn test_concurrent_callers_fire_a_rule_once() {
+    let registry = Arc::new(ToolRegistry::new());
+    let calls = Arc::new(Mutex::new(vec![]));
+    registry.register(metadata("get_price"), Arc::new(PriceTool)).await;
+    registry.register(metadata("swap"), Arc::new(SlowTool(calls.clone()))).await;
+    let mut engine = RuleEngine::new(registry);
+    let price = FunctionBinding {
+        tool: "get_price".to_string(),
+        params: vec!["mint".to_string()],
+        output_path: Some("price".to_string()),
+    };
+    engine.register_function("price", price);
+    engine.add_rule(rule("buy_dip", r#"price("SOL") < 120"#, 10, json!({}))).unwrap();
+
+    // The second caller evaluates the rule while the first is still sending its swap
+    let state = RuleState::new();
+    let (first, second) = tokio::join!(engine.run_first(&state), engine.run_first(&state));
+
+    assert_eq!(first.unwrap().status, RuleStatus::Fired { output: Ok("sent".to_string()) });
+    assert!(second.is_none());
+    assert_eq!(calls.lock().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_outcomes_of_earlier_dry_runs_fire_once() {
+    let (engine, calls) = engine().await;
+    let state = RuleState::from([("position".to_string(), json!({"size": 8}))]);
+    let first = engine.dry_run(&state).await.remove(1);
+    let second = engine.dry_run(&state).await.remove(1);
+
+    assert!(matches!(engine.fire(first).await.status, RuleStatus::Fired { .. }));
+    assert!(matches!(engine.fire(second).await.status, RuleStatus::CoolingDown { .. }));
+    assert_eq!(calls.lock().unwrap().len(), 1);
+}
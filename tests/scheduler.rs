@@ -0,0 +1,96 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use solagent::workflow_engine::scheduler::{
+    Schedule, ScheduleRecord, ScheduleStore, ScheduledJob, Scheduler, Trigger,
+};
+
+// Job taking `duration` to run, tracking how many runs overlap
+struct SlowJob {
+    duration: Duration,
+    runs: AtomicUsize,
+    running: AtomicUsize,
+    max_running: AtomicUsize,
+}
+
+impl SlowJob {
+    fn new(duration: Duration) -> Arc<Self> {
+        Arc::new(SlowJob {
+            duration,
+            runs: AtomicUsize::new(0),
+            running: AtomicUsize::new(0),
+            max_running: AtomicUsize::new(0),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl ScheduledJob for SlowJob {
+    async fn run(&self) -> Result<String, String> {
+        let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_running.fetch_max(running, Ordering::SeqCst);
+        tokio::time::sleep(self.duration).await;
+        self.running.fetch_sub(1, Ordering::SeqCst);
+        self.runs.fetch_add(1, Ordering::SeqCst);
+        Ok("done".to_string())
+    }
+}
+
+#[tokio::test]
+async fn test_runs_do_not_overlap() {
+    let job = SlowJob::new(Duration::from_millis(120));
+    let scheduler = Scheduler::new();
+    let trigger = Trigger::every(Duration::from_millis(30));
+    scheduler.add(Schedule::new("poll", trigger, job.clone())).unwrap();
+
+    tokio::time::sleep(Duration::from_millis(400)).await;
+    let status = scheduler.status("poll").unwrap();
+    scheduler.shutdown();
+
+    assert!(status.overlaps > 0);
+    assert!(scheduler.statuses().is_empty());
+    assert_eq!(job.max_running.load(Ordering::SeqCst), 1);
+    assert!(job.runs.load(Ordering::SeqCst) >= 2);
+}
+
+#[tokio::test]
+async fn test_pause_resume_and_persisted_last_run() {
+    let store = Arc::new(ScheduleStore::in_memory().unwrap());
+    let job = SlowJob::new(Duration::ZERO);
+    let scheduler = Scheduler::new().with_store(store.clone());
+    let trigger = Trigger::every(Duration::from_millis(20));
+    scheduler.add(Schedule::new("poll", trigger, job.clone())).unwrap();
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    scheduler.pause("poll").unwrap();
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    let runs = job.runs.load(Ordering::SeqCst);
+    assert!(runs >= 1);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(job.runs.load(Ordering::SeqCst), runs);
+
+    let status = scheduler.status("poll").unwrap();
+    assert!(status.paused);
+    assert_eq!(status.overlaps, 0);
+    let record = store.load("poll").unwrap().unwrap();
+    assert_eq!(record, ScheduleRecord { last_run: status.last_run, paused: true });
+
+    scheduler.resume("poll").unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(job.runs.load(Ordering::SeqCst) > runs);
+    drop(scheduler);
+
+    // A restarted scheduler waits out the interval since the persisted last run
+    let job = SlowJob::new(Duration::ZERO);
+    let scheduler = Scheduler::new().with_store(store);
+    let trigger = Trigger::every(Duration::from_secs(3600));
+    scheduler.add(Schedule::new("poll", trigger, job.clone())).unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(job.runs.load(Ordering::SeqCst), 0);
+    assert!(scheduler.status("poll").unwrap().last_run.is_some());
+}
@@ -0,0 +1,109 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde_json::json;
+use solagent::{
+    agent_controller::{
+        controller::AgentController,
+        session::{EntryRole, SessionEntry, SessionManager, SessionStore},
+    },
+    llm_integration::{mock::MockProvider, LLMClient},
+    memory_system::{LongTermMemory, ShortTermMemory},
+    tool_system::ToolRegistry,
+};
+
+mod common;
+use common::database_path;
+
+#[tokio::test]
+async fn test_sessions_keep_separate_ordered_histories() {
+    let mock = Arc::new(MockProvider::new().fallback_text("Noted"));
+    let llm_client = Arc::new(LLMClient::new());
+    llm_client.register_provider("mock", mock.clone()).await;
+    let sessions = Arc::new(SessionManager::new());
+    let controller = AgentController::new(
+        Arc::new(ToolRegistry::new()),
+        sessions.clone(),
+        Arc::new(LongTermMemory::in_memory().unwrap()),
+        llm_client,
+    );
+
+    controller.execute_task("alice", "remember", json!({"name": "Alice"})).await.unwrap();
+    controller.execute_task("bob", "remember", json!({"name": "Bob"})).await.unwrap();
+    controller.execute_task("alice", "what is my name", json!({})).await.unwrap();
+
+    // Bob's task sees nothing of Alice's session, Alice's second task sees her first one
+    let prompts = mock.prompts();
+    assert!(!prompts[1].contains("Alice"));
+    assert!(prompts[2].contains("- user: remember\nInput: {\"name\":\"Alice\"}"));
+    assert!(prompts[2].contains("- assistant: Noted"));
+    assert!(!prompts[2].contains("Bob"));
+
+    let alice = sessions.get("alice").unwrap();
+    let roles: Vec<EntryRole> = alice.history().await.iter().map(|e| e.role).collect();
+    let exchange = [EntryRole::User, EntryRole::Assistant];
+    assert_eq!(roles, [exchange, exchange].concat());
+    assert_eq!(alice.memory().get("remember").await.unwrap().as_deref(), Some("Noted"));
+    assert_eq!(sessions.ids(), vec!["alice", "bob"]);
+}
+
+#[tokio::test]
+async fn test_sessions_persist_and_expire_when_idle() {
+    let path = database_path("sessions");
+    let store = Arc::new(SessionStore::open(&path).unwrap());
+    let sessions = SessionManager::new().with_store(store.clone()).with_max_history(2);
+    let session = sessions.open("alice").await.unwrap();
+    for message in ["first", "second", "third"] {
+        session.push(SessionEntry::user(message)).await.unwrap();
+    }
+    session.remember("get_balance", "12.5 SOL").await.unwrap();
+    drop(sessions);
+
+    // A restarted agent picks the session up, with only the newest entries kept
+    let store = Arc::new(SessionStore::open(&path).unwrap());
+    let sessions = SessionManager::new()
+        .with_store(store)
+        .with_max_history(2)
+        .with_idle_timeout(Duration::from_millis(100));
+    let session = sessions.open("alice").await.unwrap();
+    let contents: Vec<String> = session.history().await.into_iter().map(|e| e.content).collect();
+    assert_eq!(contents, vec!["second", "third"]);
+    assert_eq!(session.memory().get("get_balance").await.unwrap().as_deref(), Some("12.5 SOL"));
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(sessions.expire_idle().await.unwrap(), 1);
+    assert!(sessions.get("alice").is_none());
+    let session = sessions.open("alice").await.unwrap();
+    assert!(session.history().await.is_empty());
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn test_sessions_get_their_memory_from_the_factory() {
+    let created = Arc::new(Mutex::new(vec![]));
+    let ids = created.clone();
+    let sessions = SessionManager::new().with_memory(Arc::new(move |id| {
+        ids.lock().unwrap().push(id.to_string());
+        ShortTermMemory::new()
+    }));
+    sessions.open("alice").await.unwrap().remember("get_balance", "12.5 SOL").await.unwrap();
+    let session = sessions.open("alice").await.unwrap();
+    assert_eq!(session.memory().get("get_balance").await.unwrap().as_deref(), Some("12.5 SOL"));
+    sessions.open("bob").await.unwrap();
+    assert_eq!(*created.lock().unwrap(), vec!["alice", "bob"]);
+}
+
+#[tokio::test]
+async fn test_idle_sessions_are_swept_until_the_manager_is_dropped() {
+    let sessions = Arc::new(SessionManager::new().with_idle_timeout(Duration::from_millis(50)));
+    let sweeper = sessions.sweep_every(Duration::from_millis(20));
+    sessions.open("alice").await.unwrap();
+
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert!(sessions.get("alice").is_none());
+    drop(sessions);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(sweeper.is_finished());
+}
@@ -0,0 +1,121 @@
+use std::sync::Arc;
+
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::json;
+use solagent::llm_integration::{
+    message::ChatMessage,
+    mock::MockProvider,
+    structured::{extract_json, OutputSchema, StructuredOutputError},
+    LLMClient,
+};
+
+#[derive(Deserialize, JsonSchema, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum Action {
+    Buy,
+    Sell,
+    Hold,
+}
+
+#[derive(Deserialize, JsonSchema, Debug)]
+struct TradeDecision {
+    action: Action,
+    mint: String,
+    amount: f64,
+}
+
+async fn client(mock: Arc<MockProvider>) -> LLMClient {
+    let client = LLMClient::new();
+    client.register_provider("mock", mock).await;
+    client
+}
+
+#[tokio::test]
+async fn test_invalid_answer_is_re_asked_with_errors() {
+    let mock = Arc::new(
+        MockProvider::new()
+            .text(r#"{"action": "short", "mint": "SOL", "amount": 1}"#)
+            .text(r#"```json
+{"action": "sell", "mint": "SOL", "amount": 1.5}
+```"#),
+    );
+    let client = client(mock.clone()).await;
+
+    let decision: TradeDecision =
+        client.chat_typed("mock", &[ChatMessage::user("SOL is at 250")]).await.unwrap();
+
+    assert_eq!(decision.action, Action::Sell);
+    assert_eq!(decision.mint, "SOL");
+    assert_eq!(decision.amount, 1.5);
+    let prompts = mock.prompts();
+    assert_eq!(prompts.len(), 2);
+    assert!(prompts[1].contains("did not match the schema"));
+}
+
+#[tokio::test]
+async fn test_gives_up_after_max_retries() {
+    let mock = Arc::new(MockProvider::new().fallback_text("I think you should buy"));
+    let client = client(mock.clone()).await;
+
+    let err = client
+        .chat_typed::<TradeDecision>("mock", &[ChatMessage::user("SOL is at 250")])
+        .await
+        .unwrap_err();
+
+    let err = err.downcast_ref::<StructuredOutputError>().unwrap();
+    assert_eq!(err.attempts, 3);
+    assert_eq!(mock.call_count(), 3);
+}
+
+fn trade_schema() -> OutputSchema {
+    OutputSchema::new(
+        "trade",
+        json!({
+            "type": "object",
+            "properties": {
+                "action": {"enum": ["buy", "sell", "hold"]},
+                "mint": {"type": "string"},
+                "amount": {"type": "number", "minimum": 0},
+            },
+            "required": ["action", "mint", "amount"],
+        }),
+    )
+}
+
+#[test]
+fn test_parse_fenced_reply() {
+    let reply = r#"Here you go:
+```json
+{"action": "buy", "mint": "SOL", "amount": 1}
+```"#;
+    let value = trade_schema().parse(reply).unwrap();
+    assert_eq!(value["action"], "buy");
+}
+
+#[test]
+fn test_parse_skips_braces_in_prose() {
+    let reply = r#"Filling in {action} and [mint]:
+{"action": "sell", "mint": "BONK", "amount": 5}
+Let me know if you want [more] trades."#;
+    let value = trade_schema().parse(reply).unwrap();
+    assert_eq!(value["mint"], "BONK");
+    assert_eq!(extract_json("no JSON here"), "no JSON here");
+}
+
+#[test]
+fn test_parse_returns_the_first_conforming_value() {
+    let reply = r#"Answers look like {"action": "...", "mint": "...", "amount": 0}, so:
+{"action": "hold", "mint": "SOL", "amount": 0}"#;
+    assert_eq!(trade_schema().parse(reply).unwrap()["action"], "hold");
+
+    // Without a conforming value, the violations of the first one are reported
+    let errors = trade_schema().parse(r#"{"action": "short"} or [1, 2]"#).unwrap_err();
+    assert_eq!(errors.len(), 3);
+}
+
+#[test]
+fn test_parse_reports_violations() {
+    let errors = trade_schema().parse(r#"{"action": "short", "amount": -1}"#).unwrap_err();
+    assert_eq!(errors.len(), 3);
+}
@@ -0,0 +1,79 @@
+use std::sync::Arc;
+
+use serde_json::json;
+use solagent::memory_system::{
+    long_term::Namespace,
+    vector::{Document, HashEmbedding, MetadataFilter, VectorMemory, VectorQuery, VectorRecall},
+};
+
+mod common;
+use common::database_path;
+
+fn transaction(id: &str, text: &str, wallet: &str, block_time: i64) -> Document {
+    Document::new(id, text).with_metadata(json!({ "wallet": wallet, "block_time": block_time }))
+}
+
+async fn history(memory: &VectorMemory, namespace: &Namespace) {
+    let documents = vec![
+        transaction("tx1", "Swapped 10 SOL for USDC on Raydium", "alice", 1_000),
+        transaction("tx2", "Staked 20 SOL with a validator", "alice", 2_000),
+        transaction("tx3", "Swapped 5 SOL for BONK on Jupiter", "alice", 3_000),
+        transaction("tx4", "Swapped 1 SOL for USDC on Orca", "bob", 2_500),
+        Document::new("note", "Alice prefers swapping on Jupiter"),
+    ];
+    memory.add_all(namespace, documents).await.unwrap();
+}
+
+fn ids(results: &[solagent::memory_system::vector::ScoredEntry]) -> Vec<&str> {
+    results.iter().map(|r| r.entry.document.id.as_str()).collect()
+}
+
+#[tokio::test]
+async fn test_similarity_search_with_metadata_filters() {
+    let memory = VectorMemory::in_memory(Arc::new(HashEmbedding::default())).unwrap();
+    let namespace = Namespace::agent("rag");
+    history(&memory, &namespace).await;
+
+    let results = memory.search(&namespace, &VectorQuery::new("staked SOL")).await.unwrap();
+    assert_eq!(results.len(), 5);
+    assert_eq!(results[0].entry.document.id, "tx2");
+    assert!(results.windows(2).all(|pair| pair[0].score >= pair[1].score));
+
+    // Only Alice's swaps from the date range, and documents without the fields never match
+    let query = VectorQuery::new("swapped SOL for USDC")
+        .with_top_k(2)
+        .with_filter(MetadataFilter::equals("wallet", "alice"))
+        .with_filter(MetadataFilter::range("block_time", Some(1_000.0), Some(2_000.0)));
+    let results = memory.search(&namespace, &query).await.unwrap();
+    assert_eq!(ids(&results), vec!["tx1", "tx2"]);
+
+    let query = VectorQuery::new("swapped SOL for USDC").with_top_k(1);
+    let results = memory.search(&Namespace::agent("other"), &query).await.unwrap();
+    assert!(results.is_empty());
+
+    assert!(memory.delete(&namespace, "tx1").await.unwrap());
+    let results = memory.search(&namespace, &query).await.unwrap();
+    assert_eq!(ids(&results), vec!["tx4"]);
+}
+
+#[tokio::test]
+async fn test_recall_follows_task_input_and_survives_reopening() {
+    let path = database_path("vector-memory");
+    let memory = VectorMemory::open(&path, Arc::new(HashEmbedding::default())).unwrap();
+    let namespace = Namespace::agent("rag");
+    history(&memory, &namespace).await;
+    drop(memory);
+
+    let memory = Arc::new(VectorMemory::open(&path, Arc::new(HashEmbedding::default())).unwrap());
+    let recall = VectorRecall::new(memory.clone(), namespace.clone())
+        .with_top_k(1)
+        .with_input_filter("wallet");
+    let recalled = recall.recall("Swap SOL for USDC", &json!({ "wallet": "bob" })).await.unwrap();
+    assert_eq!(ids(&recalled), vec!["tx4"]);
+
+    // Vectors of another embedding model are never compared with the query
+    let other = VectorMemory::open(&path, Arc::new(HashEmbedding::new(64))).unwrap();
+    let query = VectorQuery::new("Swap SOL for USDC");
+    assert!(other.search(&namespace, &query).await.unwrap().is_empty());
+    let _ = std::fs::remove_file(&path);
+}
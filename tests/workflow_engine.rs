@@ -0,0 +1,579 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use serde_json::json;
+use solagent::{
+    llm_integration::LLMProvider,
+    tool_system::{SolanaTool, ToolInput, ToolRegistry},
+    workflow_engine::{
+        checkpoint::{CheckpointStatus, CheckpointStore, SignatureChecker},
+        dag::{NodeStatus, RepeatUntil, WorkflowEngine, WorkflowError, WorkflowNode},
+        definition::DefinitionFormat,
+    },
+};
+use solana_sdk::signature::Signature;
+
+mod common;
+use common::{database_path, metadata};
+
+// Tool returning its `value` argument after a delay, tracking how many calls overlap
+#[derive(Default)]
+struct FetchTool {
+    running: AtomicUsize,
+    max_running: AtomicUsize,
+}
+
+#[async_trait::async_trait]
+impl SolanaTool for FetchTool {
+    async fn execute(
+        &self,
+        input: ToolInput,
+        _llm: &dyn LLMProvider,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_running.fetch_max(running, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        self.running.fetch_sub(1, Ordering::SeqCst);
+        let value = input.params["value"].as_f64().ok_or("Missing value")?;
+        Ok(json!({"value": value}).to_string())
+    }
+}
+
+// Tool adding its arguments
+struct SumTool;
+
+#[async_trait::async_trait]
+impl SolanaTool for SumTool {
+    async fn execute(
+        &self,
+        input: ToolInput,
+        _llm: &dyn LLMProvider,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let a = input.params["a"].as_f64().ok_or("Missing a")?;
+        let b = input.params["b"].as_f64().ok_or("Missing b")?;
+        Ok((a + b).to_string())
+    }
+}
+
+// Three fetches feeding a sum of the first two
+async fn workflow(fetch: Arc<FetchTool>, max_concurrency: usize) -> WorkflowEngine {
+    let registry = Arc::new(ToolRegistry::new());
+    registry.register(metadata("fetch"), fetch).await;
+    registry.register(metadata("sum"), Arc::new(SumTool)).await;
+
+    let mut engine = WorkflowEngine::new(registry).with_max_concurrency(max_concurrency);
+    engine.add_node(WorkflowNode::new("a", "fetch", json!({"value": 1}))).unwrap();
+    engine.add_node(WorkflowNode::new("b", "fetch", json!({"value": 2}))).unwrap();
+    engine.add_node(WorkflowNode::new("c", "fetch", json!({"value": 3}))).unwrap();
+    let inputs = json!({"a": "${nodes.a.output.value}", "b": "${nodes.b.output.value}"});
+    engine.add_node(WorkflowNode::new("sum", "sum", inputs)).unwrap();
+    engine.add_edge("a", "sum").unwrap();
+    engine.add_edge("b", "sum").unwrap();
+    engine
+}
+
+#[tokio::test]
+async fn test_branches_run_concurrently_within_limit() {
+    let fetch = Arc::new(FetchTool::default());
+    let report = workflow(fetch.clone(), 2).await.run().await.unwrap();
+
+    assert!(report.succeeded());
+    assert_eq!(report.nodes.len(), 4);
+    assert_eq!(report.node("sum").unwrap().inputs, Some(json!({"a": 1.0, "b": 2.0})));
+    assert_eq!(report.node("sum").unwrap().status, NodeStatus::Succeeded { output: "3".into() });
+    assert_eq!(fetch.max_running.load(Ordering::SeqCst), 2);
+
+    let fetch = Arc::new(FetchTool::default());
+    workflow(fetch.clone(), 1).await.run().await.unwrap();
+    assert_eq!(fetch.max_running.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_list_elements_of_concurrent_nodes_share_the_limit() {
+    let fetch = Arc::new(FetchTool::default());
+    let registry = Arc::new(ToolRegistry::new());
+    registry.register(metadata("fetch"), fetch.clone()).await;
+    let mut engine = WorkflowEngine::new(registry).with_max_concurrency(2);
+    for id in ["a", "b"] {
+        let node = WorkflowNode::new(id, "fetch", json!({"value": "${item}"}));
+        engine.add_node(WorkflowNode { for_each: Some(json!([1, 2, 3, 4])), ..node }).unwrap();
+    }
+
+    assert!(engine.run().await.unwrap().succeeded());
+    assert_eq!(fetch.max_running.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_failure_skips_downstream_nodes() {
+    let mut engine = workflow(Arc::new(FetchTool::default()), 4).await;
+    engine.add_node(WorkflowNode::new("bad", "fetch", json!({}))).unwrap();
+    engine.add_node(WorkflowNode::new("after", "sum", json!({"a": 1, "b": 2}))).unwrap();
+    engine.add_edge("bad", "after").unwrap();
+
+    let report = engine.run().await.unwrap();
+
+    assert!(!report.succeeded());
+    assert_eq!(
+        report.node("bad").unwrap().status,
+        NodeStatus::Failed { error: "Missing value".to_string() }
+    );
+    let skipped = NodeStatus::Skipped { reason: "upstream 'bad' did not succeed".to_string() };
+    assert_eq!(report.node("after").unwrap().status, skipped);
+    assert!(matches!(report.node("sum").unwrap().status, NodeStatus::Succeeded { .. }));
+}
+
+#[tokio::test]
+async fn test_cycles_and_stray_references_are_rejected() {
+    let mut engine = workflow(Arc::new(FetchTool::default()), 4).await;
+    let cycle = WorkflowError::Cycle { from: "sum".to_string(), to: "a".to_string() };
+    assert_eq!(engine.add_edge("sum", "a"), Err(cycle));
+
+    engine.add_node(WorkflowNode::new("d", "sum", json!({"a": "${nodes.c.output}"}))).unwrap();
+    let invalid = WorkflowError::InvalidReference { node: "d".into(), referenced: "c".into() };
+    assert_eq!(engine.run().await.unwrap_err(), invalid);
+}
+
+// Tool submitting a transaction and returning its signature
+#[derive(Default)]
+struct SwapTool {
+    calls: AtomicUsize,
+}
+
+#[async_trait::async_trait]
+impl SolanaTool for SwapTool {
+    async fn execute(
+        &self,
+        _input: ToolInput,
+        _llm: &dyn LLMProvider,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        Ok(json!({"signature": Signature::new_unique().to_string()}).to_string())
+    }
+}
+
+// Tool failing with `error` on its first call only
+struct StakeTool {
+    calls: AtomicUsize,
+    error: String,
+}
+
+#[async_trait::async_trait]
+impl SolanaTool for StakeTool {
+    async fn execute(
+        &self,
+        _input: ToolInput,
+        _llm: &dyn LLMProvider,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        if self.calls.fetch_add(1, Ordering::SeqCst) == 0 {
+            return Err(self.error.clone().into());
+        }
+        Ok("staked".to_string())
+    }
+}
+
+// Signature checker treating every transaction as confirmed
+struct ConfirmAll;
+
+#[async_trait::async_trait]
+impl SignatureChecker for ConfirmAll {
+    async fn is_confirmed(&self, _signature: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        Ok(true)
+    }
+
+    async fn is_blockhash_valid(
+        &self,
+        _blockhash: &str,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        Ok(true)
+    }
+}
+
+// Swap-and-stake workflow checkpointed to `path`; each call stands for a fresh process
+async fn pipeline(path: &str, swap: Arc<SwapTool>, stake: Arc<StakeTool>) -> WorkflowEngine {
+    let registry = Arc::new(ToolRegistry::new());
+    registry.register(metadata("swap"), swap).await;
+    registry.register(metadata("stake"), stake).await;
+    let store = Arc::new(CheckpointStore::open(path).unwrap());
+
+    let mut engine = WorkflowEngine::new(registry).with_checkpoints(store);
+    engine.add_node(WorkflowNode::new("swap", "swap", json!({}))).unwrap();
+    let inputs = json!({"after": "${nodes.swap.output.signature}"});
+    engine.add_node(WorkflowNode::new("stake", "stake", inputs)).unwrap();
+    engine.add_edge("swap", "stake").unwrap();
+    engine
+}
+
+#[tokio::test]
+async fn test_resume_keeps_succeeded_nodes() {
+    let path = database_path("resume");
+    let swap = Arc::new(SwapTool::default());
+    let stake = Arc::new(StakeTool { calls: AtomicUsize::new(0), error: "RPC down".into() });
+
+    let report = pipeline(&path, swap.clone(), stake.clone()).await.run_with_id("wf").await;
+    assert!(!report.unwrap().succeeded());
+
+    let report = pipeline(&path, swap.clone(), stake.clone()).await.resume("wf").await.unwrap();
+    assert!(report.succeeded());
+    assert!(report.node("swap").unwrap().resumed);
+    assert!(!report.node("stake").unwrap().resumed);
+    assert_eq!(swap.calls.load(Ordering::SeqCst), 1);
+    assert_eq!(stake.calls.load(Ordering::SeqCst), 2);
+
+    let state = CheckpointStore::open(&path).unwrap().load("wf").unwrap().unwrap();
+    assert_eq!(state.status, CheckpointStatus::Succeeded);
+    assert_eq!(state.nodes["swap"].signatures.len(), 1);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn test_resume_does_not_resend_confirmed_transactions() {
+    let path = database_path("confirmed");
+    let error = format!("Transaction {} was not confirmed in time", Signature::new_unique());
+    let stake = Arc::new(StakeTool { calls: AtomicUsize::new(0), error });
+    let swap = Arc::new(SwapTool::default());
+    pipeline(&path, swap.clone(), stake.clone()).await.run_with_id("wf").await.unwrap();
+
+    // Without a way to confirm the transaction, the node is not run again
+    let report = pipeline(&path, swap.clone(), stake.clone()).await.resume("wf").await.unwrap();
+    assert!(matches!(report.node("stake").unwrap().status, NodeStatus::Failed { .. }));
+    assert_eq!(stake.calls.load(Ordering::SeqCst), 1);
+
+    let engine = pipeline(&path, swap.clone(), stake.clone()).await;
+    let report = engine.with_signature_checker(Arc::new(ConfirmAll)).resume("wf").await.unwrap();
+    assert!(report.succeeded());
+    assert!(report.node("stake").unwrap().resumed);
+    assert_eq!(stake.calls.load(Ordering::SeqCst), 1);
+    assert_eq!(swap.calls.load(Ordering::SeqCst), 1);
+    let _ = std::fs::remove_file(&path);
+}
+
+const BLOCKHASH: &str = "4sGjMW1sUnHzSxGspuhpqLDx6wiyjNtZAMdL4VZHirAn";
+
+// Tool hanging on its first call, as if the process stopped there, after reporting a
+// transaction if `reports` is set
+#[derive(Default)]
+struct SendTool {
+    calls: AtomicUsize,
+    reports: bool,
+}
+
+#[async_trait::async_trait]
+impl SolanaTool for SendTool {
+    async fn execute(
+        &self,
+        input: ToolInput,
+        _llm: &dyn LLMProvider,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let first = self.calls.fetch_add(1, Ordering::SeqCst) == 0;
+        let signature = Signature::new_unique().to_string();
+        if self.reports {
+            input.submitted(&signature, BLOCKHASH);
+        }
+        if first {
+            futures::future::pending::<()>().await;
+        }
+        Ok(json!({"signature": signature}).to_string())
+    }
+}
+
+// Signature checker finding no transaction confirmed
+struct ConfirmNone {
+    blockhash_valid: bool,
+}
+
+#[async_trait::async_trait]
+impl SignatureChecker for ConfirmNone {
+    async fn is_confirmed(&self, _signature: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        Ok(false)
+    }
+
+    async fn is_blockhash_valid(
+        &self,
+        _blockhash: &str,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        Ok(self.blockhash_valid)
+    }
+}
+
+fn send_node() -> WorkflowNode {
+    WorkflowNode::new("send", "send", json!({}))
+}
+
+// Workflow of the single `node`, sending with `send` and checkpointed to `path`
+async fn sending(path: &str, send: Arc<SendTool>, node: WorkflowNode) -> WorkflowEngine {
+    let registry = Arc::new(ToolRegistry::new());
+    registry.register(metadata("send"), send).await;
+    let store = Arc::new(CheckpointStore::open(path).unwrap());
+    let mut engine = WorkflowEngine::new(registry).with_checkpoints(store);
+    engine.add_node(node).unwrap();
+    engine
+}
+
+// Runs the workflow until its first node hangs, then drops it as a crash would
+async fn interrupt(engine: WorkflowEngine) {
+    let run = tokio::time::timeout(Duration::from_millis(100), engine.run_with_id("wf")).await;
+    assert!(run.is_err());
+}
+
+#[tokio::test]
+async fn test_interrupted_transactions_are_resent_once_their_blockhash_expired() {
+    let path = database_path("interrupted");
+    let send = Arc::new(SendTool { calls: AtomicUsize::new(0), reports: true });
+    interrupt(sending(&path, send.clone(), send_node()).await).await;
+
+    // The transaction reported before the interruption is checkpointed with its blockhash
+    let state = CheckpointStore::open(&path).unwrap().load("wf").unwrap().unwrap();
+    let node = &state.nodes["send"];
+    assert_eq!(node.status, CheckpointStatus::Running);
+    assert_eq!(node.signatures.len(), 1);
+    assert_eq!(node.blockhashes[&node.signatures[0]], BLOCKHASH);
+
+    let checker = Arc::new(ConfirmNone { blockhash_valid: true });
+    let engine = sending(&path, send.clone(), send_node()).await.with_signature_checker(checker);
+    let report = engine.resume("wf").await.unwrap();
+    let NodeStatus::Failed { error } = &report.node("send").unwrap().status else {
+        panic!("an unconfirmed transaction that may still land is not sent again");
+    };
+    assert!(error.contains("may still land"));
+    assert_eq!(send.calls.load(Ordering::SeqCst), 1);
+
+    let checker = Arc::new(ConfirmNone { blockhash_valid: false });
+    let engine = sending(&path, send.clone(), send_node()).await.with_signature_checker(checker);
+    assert!(engine.resume("wf").await.unwrap().succeeded());
+    assert_eq!(send.calls.load(Ordering::SeqCst), 2);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn test_interrupted_nodes_run_again_only_when_idempotent() {
+    let path = database_path("idempotent");
+    let send = Arc::new(SendTool::default());
+    interrupt(sending(&path, send.clone(), send_node()).await).await;
+
+    let report = sending(&path, send.clone(), send_node()).await.resume("wf").await.unwrap();
+    assert!(matches!(report.node("send").unwrap().status, NodeStatus::Failed { .. }));
+    assert_eq!(send.calls.load(Ordering::SeqCst), 1);
+
+    let engine = sending(&path, send.clone(), send_node().with_idempotent(true)).await;
+    let report = engine.resume("wf").await.unwrap();
+    assert!(report.succeeded());
+    assert_eq!(send.calls.load(Ordering::SeqCst), 2);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn test_timed_out_nodes_are_retried_only_when_idempotent() {
+    let path = database_path("timeout");
+    let timing_out = || send_node().with_timeout(Duration::from_secs(1)).with_retries(2);
+    let send = Arc::new(SendTool::default());
+    let engine = sending(&path, send.clone(), timing_out()).await;
+    let report = engine.run_with_id("wf").await.unwrap();
+    assert!(report.node("send").unwrap().timed_out);
+    assert_eq!(send.calls.load(Ordering::SeqCst), 1);
+    let state = CheckpointStore::open(&path).unwrap().load("wf").unwrap().unwrap();
+    assert_eq!(state.nodes["send"].status, CheckpointStatus::Unknown);
+
+    let report = engine.resume("wf").await.unwrap();
+    let NodeStatus::Failed { error } = &report.node("send").unwrap().status else {
+        panic!("a timed out node is not run again");
+    };
+    assert!(error.starts_with("Timed out and not idempotent"));
+    assert_eq!(send.calls.load(Ordering::SeqCst), 1);
+
+    let send = Arc::new(SendTool::default());
+    let engine = sending(&path, send.clone(), timing_out().with_idempotent(true)).await;
+    assert!(engine.run_with_id("wf").await.unwrap().succeeded());
+    assert_eq!(send.calls.load(Ordering::SeqCst), 2);
+    let _ = std::fs::remove_file(&path);
+}
+
+async fn registry() -> Arc<ToolRegistry> {
+    let registry = Arc::new(ToolRegistry::new());
+    registry.register(metadata("fetch"), Arc::new(FetchTool::default())).await;
+    registry.register(metadata("sum"), Arc::new(SumTool)).await;
+    registry
+}
+
+#[tokio::test]
+async fn test_definition_errors_point_at_lines() {
+    let yaml = r#"name: broken
+nodes:
+  - id: price
+    tool: fetch
+    inputs: { value: 1 }
+  - id: total
+    tool: multiply
+    inputs:
+      a: "${nodes.price.output.value}"
+      b: "${nodes.volume.output.value}"
+    depends_on: [price]
+"#;
+    let err = WorkflowEngine::load(yaml, DefinitionFormat::Yaml, registry().await).await;
+    let issues: Vec<String> = err.err().unwrap().issues.iter().map(|i| i.to_string()).collect();
+    assert_eq!(
+        issues,
+        vec![
+            "line 7: node 'total' uses unknown tool 'multiply'",
+            "line 10: node 'total' uses the output of 'volume' without depending on it",
+        ]
+    );
+
+    let err = WorkflowEngine::load("name = 1", DefinitionFormat::Toml, registry().await).await;
+    assert_eq!(err.err().unwrap().issues[0].line, Some(1));
+}
+
+#[tokio::test]
+async fn test_toml_definition_runs_with_conditions() {
+    let toml = r#"
+name = "conditional"
+
+[[nodes]]
+id = "price"
+tool = "fetch"
+inputs = { value = 90 }
+
+[[nodes]]
+id = "buy"
+tool = "sum"
+inputs = { a = "${nodes.price.output.value}", b = 1 }
+depends_on = ["price"]
+condition = "nodes.price.output.value < 100"
+retries = 1
+
+[[nodes]]
+id = "sell"
+tool = "sum"
+inputs = { a = 0, b = 1 }
+depends_on = ["price"]
+condition = "nodes.price.output.value > 150"
+timeout_secs = 5
+"#;
+    let engine = WorkflowEngine::load(toml, DefinitionFormat::Toml, registry().await).await;
+    let report = engine.unwrap().run().await.unwrap();
+
+    assert_eq!(report.node("buy").unwrap().status, NodeStatus::Succeeded { output: "91".into() });
+    let reason = "condition 'nodes.price.output.value > 150' is false".to_string();
+    assert_eq!(report.node("sell").unwrap().status, NodeStatus::Skipped { reason });
+}
+
+#[tokio::test]
+async fn test_conditional_edges_skip_downstream_and_lists_fan_out() {
+    let yaml = r#"name: quotes
+nodes:
+  - id: price
+    tool: fetch
+    inputs: { value: 90 }
+  - id: buy
+    tool: sum
+    inputs: { a: "${nodes.price.output.value}", b: 1 }
+    depends_on: [{ node: price, condition: "nodes.price.output.value > 150" }]
+  - id: after_buy
+    tool: sum
+    inputs: { a: 1, b: 1 }
+    depends_on: [buy]
+  - id: quotes
+    tool: fetch
+    inputs: { value: "${item}" }
+    for_each: [1, 2, 3]
+  - id: total
+    tool: sum
+    inputs: { a: "${nodes.quotes.output.0.value}", b: "${nodes.quotes.output.2.value}" }
+    depends_on: [quotes]
+"#;
+    let engine = WorkflowEngine::load(yaml, DefinitionFormat::Yaml, registry().await).await;
+    let report = engine.unwrap().run().await.unwrap();
+
+    let reason = "condition 'nodes.price.output.value > 150' on the edge from 'price' is false";
+    let skipped = NodeStatus::Skipped { reason: reason.to_string() };
+    assert_eq!(report.node("buy").unwrap().status, skipped);
+    let reason = "upstream 'buy' did not succeed".to_string();
+    assert_eq!(report.node("after_buy").unwrap().status, NodeStatus::Skipped { reason });
+
+    let quotes = report.node("quotes").unwrap();
+    assert_eq!(quotes.inputs, Some(json!([{"value": 1}, {"value": 2}, {"value": 3}])));
+    let items: Vec<Option<usize>> = quotes.iterations.iter().map(|i| i.item).collect();
+    assert_eq!(items, vec![Some(0), Some(1), Some(2)]);
+    assert_eq!(report.node("total").unwrap().status, NodeStatus::Succeeded { output: "4".into() });
+}
+
+#[tokio::test]
+async fn test_skipped_branch_counts_as_success() {
+    let yaml = r#"name: branch
+nodes:
+  - id: price
+    tool: fetch
+    inputs: { value: 90 }
+  - id: sell
+    tool: sum
+    inputs: { a: 0, b: 1 }
+    depends_on: [price]
+    condition: "nodes.price.output.value > 150"
+  - id: after_sell
+    tool: sum
+    inputs: { a: 1, b: 1 }
+    depends_on: [sell]
+"#;
+    let path = database_path("branch");
+    let store = Arc::new(CheckpointStore::open(&path).unwrap());
+    let engine = WorkflowEngine::load(yaml, DefinitionFormat::Yaml, registry().await).await;
+    let report = engine.unwrap().with_checkpoints(store).run_with_id("wf").await.unwrap();
+
+    assert!(matches!(report.node("sell").unwrap().status, NodeStatus::Skipped { .. }));
+    assert!(matches!(report.node("after_sell").unwrap().status, NodeStatus::Skipped { .. }));
+    assert!(report.succeeded());
+    let state = CheckpointStore::open(&path).unwrap().load("wf").unwrap().unwrap();
+    assert_eq!(state.status, CheckpointStatus::Succeeded);
+    let _ = std::fs::remove_file(&path);
+}
+
+// Tool reporting an order as filled from its third call on
+#[derive(Default)]
+struct FillTool {
+    calls: AtomicUsize,
+}
+
+#[async_trait::async_trait]
+impl SolanaTool for FillTool {
+    async fn execute(
+        &self,
+        _input: ToolInput,
+        _llm: &dyn LLMProvider,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let calls = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+        Ok(json!({"filled": calls >= 3}).to_string())
+    }
+}
+
+#[tokio::test]
+async fn test_repeating_nodes_stop_when_done_or_out_of_passes() {
+    let registry = registry().await;
+    registry.register(metadata("fill"), Arc::new(FillTool::default())).await;
+    let mut engine = WorkflowEngine::new(registry);
+    let until = |until: &str, max_passes| RepeatUntil {
+        until: until.to_string(),
+        max_passes,
+        delay_secs: 0,
+    };
+    let order = WorkflowNode::new("order", "fill", json!({}));
+    engine.add_node(order.with_repeat(until("output.filled", 5))).unwrap();
+    let price = WorkflowNode::new("price", "fetch", json!({"value": 1}));
+    engine.add_node(price.with_repeat(until("output.value > pass", 2))).unwrap();
+
+    let report = engine.run().await.unwrap();
+
+    let order = report.node("order").unwrap();
+    let output = json!({"filled": true}).to_string();
+    assert_eq!(order.status, NodeStatus::Succeeded { output });
+    let passes: Vec<u32> = order.iterations.iter().map(|i| i.pass).collect();
+    assert_eq!(passes, vec![1, 2, 3]);
+
+    let price = report.node("price").unwrap();
+    let error = "'output.value > pass' still false after 2 passes".to_string();
+    assert_eq!(price.status, NodeStatus::Failed { error });
+    assert_eq!(price.iterations.len(), 2);
+}